@@ -0,0 +1,67 @@
+//! Solves synthetic schools of increasing size, so that changes to the solver
+//! which slow it down show up as regressions in `cargo bench`.
+//!
+//! A school of 5,000 students taking 8 of 40 subjects solves in about a
+//! quarter of a second, or in under a second with rooms and teachers to
+//! assign, so the solver's data structures are left as they are. These benches
+//! are here to keep it that way.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use timetabler_gui::timetabler::generate::{self, SchoolConfig};
+use timetabler_gui::timetabler::solve_timetable;
+
+const STUDENT_COUNTS: [usize; 3] = [100, 1_000, 5_000];
+
+/// Every subject is equally popular and nothing but the students' subjects
+/// needs placing.
+fn solve(c: &mut Criterion) {
+    let mut group = c.benchmark_group("solve");
+    group.sample_size(10);
+    for &student_count in &STUDENT_COUNTS {
+        let info = generate::school(&SchoolConfig {
+            students: student_count,
+            subjects: 40,
+            subjects_per_student: 8..=8,
+            popularity_skew: 0.0,
+            max_groups: 30,
+            ..SchoolConfig::default()
+        });
+        group.bench_with_input(
+            BenchmarkId::from_parameter(student_count),
+            &info,
+            |b, info| b.iter(|| solve_timetable(info)),
+        );
+    }
+    group.finish();
+}
+
+/// Some subjects are much more popular than others, and groups are given
+/// rooms, including labs, and teachers, some of whom work part time. There
+/// are two teachers for each subject, and the number of rooms grows with the
+/// school.
+fn solve_with_rooms_and_teachers(c: &mut Criterion) {
+    let mut group = c.benchmark_group("solve_with_rooms_and_teachers");
+    group.sample_size(10);
+    for &student_count in &STUDENT_COUNTS {
+        let info = generate::school(&SchoolConfig {
+            students: student_count,
+            subjects: 40,
+            subjects_per_student: 8..=8,
+            popularity_skew: 0.5,
+            max_groups: 30,
+            daily_lesson_capacity: 6,
+            rooms: (student_count / 80).max(20),
+            teachers: 80,
+            ..SchoolConfig::default()
+        });
+        group.bench_with_input(
+            BenchmarkId::from_parameter(student_count),
+            &info,
+            |b, info| b.iter(|| solve_timetable(info)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, solve, solve_with_rooms_and_teachers);
+criterion_main!(benches);
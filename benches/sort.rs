@@ -0,0 +1,109 @@
+//! Compares sorting `try_make_room`'s move candidates by attendance with
+//! `sort_by_cached_key` against the recursive quicksort it replaced. The
+//! candidates come already sorted, as they often do, which is the quicksort's
+//! worst case.
+
+use std::collections::BTreeMap;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+/// A subject and group index, as the solver's candidates are.
+type Candidate = (usize, usize);
+
+/// The students in each group of each subject.
+type Groups = BTreeMap<usize, Vec<Vec<usize>>>;
+
+fn attendance(candidate: Candidate, groups_by_subject: &Groups) -> usize {
+    groups_by_subject[&candidate.0]
+        .get(candidate.1)
+        .map(Vec::len)
+        .unwrap_or_default()
+}
+
+/// The sort `try_make_room` used before, kept as it was apart from the types.
+fn sort_by_ascending_attendance(
+    candidates: &mut [Candidate],
+    groups_by_subject: &Groups,
+    start: i32,
+    end: i32,
+) {
+    if start >= end {
+        return;
+    }
+
+    let pivot = attendance(candidates[start as usize], groups_by_subject);
+    let mut low_mark = start + 1;
+    let mut high_mark = end;
+
+    loop {
+        while low_mark <= high_mark
+            && attendance(candidates[low_mark as usize], groups_by_subject) <= pivot
+        {
+            low_mark += 1;
+        }
+        while low_mark <= high_mark
+            && attendance(candidates[high_mark as usize], groups_by_subject) >= pivot
+        {
+            high_mark -= 1;
+        }
+        if low_mark < high_mark {
+            candidates.swap(low_mark as usize, high_mark as usize);
+        } else {
+            break;
+        }
+    }
+
+    candidates.swap(start as usize, high_mark as usize);
+    sort_by_ascending_attendance(candidates, groups_by_subject, start, high_mark - 1);
+    sort_by_ascending_attendance(candidates, groups_by_subject, high_mark + 1, end);
+}
+
+/// `count` groups spread over 40 subjects, each a student bigger than the
+/// last, and a candidate for each in order of size.
+fn candidates(count: usize) -> (Vec<Candidate>, Groups) {
+    let mut groups_by_subject = Groups::new();
+    let mut candidates = Vec::new();
+    for idx in 0..count {
+        let subject = idx % 40;
+        let groups = groups_by_subject.entry(subject).or_default();
+        candidates.push((subject, groups.len()));
+        groups.push(vec![0; idx]);
+    }
+    (candidates, groups_by_subject)
+}
+
+fn sort(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sort candidates");
+    group.sample_size(10);
+    for &count in &[1_000, 10_000] {
+        let (candidates, groups_by_subject) = candidates(count);
+        group.bench_with_input(
+            BenchmarkId::new("quicksort", count),
+            &candidates,
+            |b, candidates| {
+                b.iter(|| {
+                    let mut candidates = candidates.clone();
+                    let end = candidates.len() as i32 - 1;
+                    sort_by_ascending_attendance(&mut candidates, &groups_by_subject, 0, end);
+                    candidates
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("sort_by_cached_key", count),
+            &candidates,
+            |b, candidates| {
+                b.iter(|| {
+                    let mut candidates = candidates.clone();
+                    candidates
+                        .sort_by_cached_key(|&candidate| attendance(candidate, &groups_by_subject));
+                    candidates
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, sort);
+criterion_main!(benches);
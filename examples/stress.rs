@@ -0,0 +1,37 @@
+//! Solves random schools and verifies every timetable, printing the smallest
+//! failing school found for each failure.
+//!
+//! `cargo run --release --example stress --features stress -- [instances] [seed]`
+
+use timetabler_gui::timetabler::stress::{self, StressConfig};
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut config = StressConfig {
+        exhaustive: true,
+        ..StressConfig::default()
+    };
+    if let Some(instances) = args.next() {
+        config.instances = instances.parse().expect("instances should be a number");
+    }
+    if let Some(seed) = args.next() {
+        config.seed = seed.parse().expect("seed should be a number");
+    }
+
+    // Panics are reported with the failures, rather than as they happen.
+    std::panic::set_hook(Box::new(|_| {}));
+    let failures = stress::run(&config);
+    let _ = std::panic::take_hook();
+
+    for failed in &failures {
+        println!("{:#?}\n{:#?}\n", failed.instance, failed.failure);
+    }
+    println!(
+        "{} of {} instances failed",
+        failures.len(),
+        config.instances
+    );
+    if !failures.is_empty() {
+        std::process::exit(1);
+    }
+}
@@ -1,11 +1,22 @@
-use std::collections::{hash_map::Entry, HashMap};
+use std::collections::{BTreeMap, BTreeSet};
+use std::mem;
+use std::ops::RangeInclusive;
+use std::sync::{mpsc, Arc, Mutex};
 
 use crate::timetabler;
 use eframe::{egui, epi};
+use thiserror::Error;
 
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
 enum AppState {
     GeneralConfig,
     StudentConfig(bool),
+    SubjectConfig,
+    /// What was submitted is summed up, with any problems found in it,
+    /// before it is solved.
+    Review,
+    /// The timetable is being solved, as `TimetablerApp::solving` follows.
+    Solving,
     Submitted,
 }
 
@@ -15,343 +26,3812 @@ impl Default for AppState {
     }
 }
 
-const WEEK_DAYS: [&str; 5] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday"];
+/// The steps shown across the top of the window, in the order they are
+/// gone through.
+const WIZARD_STEPS: [&str; 4] = ["General", "Students", "Review", "Result"];
+
+impl AppState {
+    /// The index in `WIZARD_STEPS` of the step this screen is part of.
+    fn wizard_step(&self) -> usize {
+        match self {
+            AppState::GeneralConfig => 0,
+            AppState::StudentConfig(_) | AppState::SubjectConfig => 1,
+            AppState::Review => 2,
+            AppState::Solving | AppState::Submitted => 3,
+        }
+    }
+}
+
+const WEEK_DAYS: [&str; timetabler::DAYS_PER_WEEK] =
+    ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday"];
+
+/// The heading for a day of the cycle, naming the week as well when the
+/// timetable is fortnightly.
+fn day_label(day_of_cycle: usize, fortnightly: bool) -> String {
+    let week_day = WEEK_DAYS[day_of_cycle % WEEK_DAYS.len()];
+    if fortnightly {
+        let week = if day_of_cycle < WEEK_DAYS.len() {
+            'A'
+        } else {
+            'B'
+        };
+        format!("Week {} {}", week, week_day)
+    } else {
+        week_day.to_string()
+    }
+}
+
+/// Describes a slot for the user, e.g. "Week A Monday slot 2".
+fn slot_label(slot: usize, daily_lesson_capacity: u8, fortnightly: bool) -> String {
+    let cycle = if fortnightly {
+        timetabler::WeekCycle::Fortnightly
+    } else {
+        timetabler::WeekCycle::Weekly
+    };
+    let position = timetabler::SlotPosition::of(slot, daily_lesson_capacity, cycle);
+    format!(
+        "{} slot {}",
+        day_label(position.week * WEEK_DAYS.len() + position.day, fortnightly),
+        position.period + 1
+    )
+}
+
+/// An error message as a sentence for the user. Errors start in lowercase
+/// so they can follow other text, e.g. "Unable to solve: ...".
+fn sentence(error: &impl std::fmt::Display) -> String {
+    let message = error.to_string();
+    let mut chars = message.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => message,
+    }
+}
+
+/// Why something typed into the app can't be used.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+enum InputError {
+    /// A field which needs a whole number from 0 to 255 was given something
+    /// else.
+    #[error("{field} must be a whole number from 0 to 255, not \"{txt}\"")]
+    NotANumber { field: &'static str, txt: String },
+    /// A field which needs at least 1 was given 0.
+    #[error("{field} must be at least 1")]
+    Zero { field: &'static str },
+    #[error("the student needs an ID")]
+    NoStudentId,
+    #[error("there is already a student with the ID {0}")]
+    DuplicateStudentId(timetabler::StudentId),
+    #[error("the student needs at least one subject")]
+    NoSubjects,
+    #[error("the subject needs a name")]
+    NoSubjectName,
+    #[error("there is already a subject called {0}")]
+    DuplicateSubject(String),
+    /// Deleting the subject would leave the student without any.
+    #[error("{subject} is the only subject {student_id} takes, so it can't be deleted")]
+    OnlySubject {
+        student_id: timetabler::StudentId,
+        subject: String,
+    },
+    /// The curricula or subject aliases couldn't be read.
+    #[error(transparent)]
+    Parse(timetabler::ParseError),
+}
+
+fn parse_number(field: &'static str, txt: &str) -> Result<u8, InputError> {
+    txt.trim().parse().map_err(|_| InputError::NotANumber {
+        field,
+        txt: txt.to_string(),
+    })
+}
+
+/// Reads a number which must be at least 1.
+fn parse_positive(field: &'static str, txt: &str) -> Result<u8, InputError> {
+    match parse_number(field, txt)? {
+        0 => Err(InputError::Zero { field }),
+        number => Ok(number),
+    }
+}
+
+/// Reads max groups at once, which may be left blank for no limit.
+fn parse_max_groups_per_slot(txt: &str) -> Result<Option<u8>, InputError> {
+    match txt.trim() {
+        "" => Ok(None),
+        txt => Ok(Some(parse_positive("Max groups at once", txt)?)),
+    }
+}
+
+/// The values max groups can be set to.
+const MAX_GROUPS_RANGE: RangeInclusive<u8> = 1..=100;
+
+/// The values daily lesson capacity can be set to.
+const DAILY_LESSON_CAPACITY_RANGE: RangeInclusive<u8> = 1..=20;
+
+/// The values the number of terms can be set to.
+const TERMS_RANGE: RangeInclusive<u8> = 1..=6;
+
+/// Reads comma separated subjects, leaving out blank and repeated ones.
+fn parse_subjects(txt: &str) -> Result<Vec<String>, InputError> {
+    let mut subjects = Vec::new();
+    for subject in txt.split(',').map(str::trim) {
+        if !subject.is_empty() && !subjects.iter().any(|taken| taken == subject) {
+            subjects.push(subject.to_string());
+        }
+    }
+    if subjects.is_empty() {
+        return Err(InputError::NoSubjects);
+    }
+    Ok(subjects)
+}
+
+/// The most students the "Suggest" button for max groups aims to put in a
+/// group.
+const TARGET_GROUP_SIZE: usize = 25;
+
+/// The students entered so far, in the form the timetabler takes them, with
+/// their curricula expanded and their subjects registered in `subjects`.
+fn student_infos(
+    subjects_by_student_id: &BTreeMap<timetabler::StudentId, Vec<timetabler::SubjectId>>,
+    curricula_txt: &str,
+    subjects: &mut timetabler::SubjectRegistry,
+) -> Vec<timetabler::StudentInfo> {
+    // Mistakes in the curricula are reported when the timetable is submitted.
+    let curricula = timetabler::Curricula::parse(curricula_txt).unwrap_or_default();
+    subjects_by_student_id
+        .iter()
+        .map(|(student_id, taken)| {
+            let taken: Vec<String> = taken
+                .iter()
+                .map(|&subject| subjects.name(subject).to_string())
+                .collect();
+            let taken = curricula
+                .expand(&taken)
+                .iter()
+                .map(|subject| subjects.register(subject))
+                .collect();
+            timetabler::StudentInfo::new(student_id.clone(), taken)
+        })
+        .collect()
+}
+
+/// The timetable described by the settings and students entered, with at
+/// most `max_groups_per_slot` groups at once. Anything the app has no screens
+/// for, such as rooms and teachers, comes from the last file imported, as do
+/// the cohorts of students still in it.
+fn entered_info(
+    imported_info: Option<&timetabler::TimetableInfo>,
+    inputs: &Inputs,
+    max_groups_per_slot: Option<u8>,
+) -> timetabler::TimetableInfo {
+    let mut info = match imported_info {
+        Some(imported_info) => imported_info.clone(),
+        None => timetabler::TimetableInfo::new(inputs.max_groups, inputs.daily_lesson_capacity),
+    };
+    let subjects = &inputs.subjects;
+    // Subjects are matched to the imported ones by name, so the teachers
+    // still teach the right ones, and take the details set in the app.
+    // Subjects nobody takes, such as deleted ones, are removed afterwards.
+    let mut ids = BTreeMap::new();
+    let registry = &mut info.subjects;
+    info.students = inputs
+        .subjects_by_student_id
+        .iter()
+        .map(|(student_id, taken)| {
+            let taken = taken
+                .iter()
+                .map(|&subject| {
+                    *ids.entry(subject)
+                        .or_insert_with(|| registry.insert(subjects[subject].clone()))
+                })
+                .collect();
+            let student = timetabler::StudentInfo::new(student_id.clone(), taken);
+            let cohort = imported_info.and_then(|imported_info| {
+                imported_info
+                    .students
+                    .iter()
+                    .find(|imported| imported.id() == student_id)
+                    .and_then(|imported| imported.cohort())
+            });
+            match cohort {
+                Some(cohort) => student.with_cohort(cohort),
+                None => student,
+            }
+        })
+        .collect();
+    let taken: BTreeSet<timetabler::SubjectId> = ids.into_values().collect();
+    info.retain_subjects(|subject| taken.contains(&subject));
+    info.max_groups = inputs.max_groups;
+    info.daily_lesson_capacity = inputs.daily_lesson_capacity;
+    info.cycle = if inputs.fortnightly {
+        timetabler::WeekCycle::Fortnightly
+    } else {
+        timetabler::WeekCycle::Weekly
+    };
+    info.max_groups_per_slot = max_groups_per_slot;
+    info.algorithm = if inputs.exhaustive {
+        timetabler::Algorithm::Exhaustive
+    } else {
+        timetabler::Algorithm::Greedy
+    };
+    info.terms = inputs.terms.max(1);
+    info
+}
+
+/// Every subject on the subject list or taken by a student.
+fn known_subjects(
+    subject_list: &BTreeSet<timetabler::SubjectId>,
+    subjects_by_student_id: &BTreeMap<timetabler::StudentId, Vec<timetabler::SubjectId>>,
+) -> BTreeSet<timetabler::SubjectId> {
+    let mut subjects = subject_list.clone();
+    for taken in subjects_by_student_id.values() {
+        subjects.extend(taken.iter().copied());
+    }
+    subjects
+}
+
+/// The names of `subjects`, in the same order.
+fn subject_names<'a>(
+    registry: &'a timetabler::SubjectRegistry,
+    subjects: &[timetabler::SubjectId],
+) -> Vec<&'a str> {
+    subjects
+        .iter()
+        .map(|&subject| registry.name(subject))
+        .collect()
+}
+
+/// The edits which give every student taking `from` the subject `into`
+/// instead. A student who already takes `into` keeps it only once.
+fn merge_subject(
+    subjects_by_student_id: &BTreeMap<timetabler::StudentId, Vec<timetabler::SubjectId>>,
+    from: timetabler::SubjectId,
+    into: timetabler::SubjectId,
+) -> Vec<Edit> {
+    subjects_by_student_id
+        .iter()
+        .filter_map(|(student_id, subjects)| {
+            let idx = subjects.iter().position(|&subject| subject == from)?;
+            let mut subjects = subjects.clone();
+            if subjects.contains(&into) {
+                subjects.remove(idx);
+            } else {
+                subjects[idx] = into;
+            }
+            Some(Edit::Student(student_id.clone(), Some(subjects)))
+        })
+        .collect()
+}
+
+/// The names of the known subjects starting with what is being typed after
+/// the last comma of `subjects_txt`, in any case, to offer as completions.
+fn subject_completions<'a>(
+    registry: &'a timetabler::SubjectRegistry,
+    known: &BTreeSet<timetabler::SubjectId>,
+    subjects_txt: &str,
+) -> Vec<&'a str> {
+    let typed = match subjects_txt.rsplit(',').next() {
+        Some(typed) if !typed.trim().is_empty() => typed.trim().to_lowercase(),
+        _ => return Vec::new(),
+    };
+    let mut completions: Vec<&str> = known
+        .iter()
+        .map(|&subject| registry.name(subject))
+        .filter(|name| {
+            let name = name.to_lowercase();
+            name.starts_with(&typed) && name != typed
+        })
+        .collect();
+    completions.sort_unstable();
+    completions.truncate(5);
+    completions
+}
+
+/// The IDs of `subjects`, each matched to a known subject of the same name
+/// ignoring case, so "physics" and "Physics" don't become two subjects. New
+/// subjects are registered.
+fn match_known_subjects(
+    subjects: Vec<String>,
+    known: &BTreeSet<timetabler::SubjectId>,
+    registry: &mut timetabler::SubjectRegistry,
+) -> Vec<timetabler::SubjectId> {
+    let mut matched = Vec::new();
+    for subject in subjects {
+        let id = known
+            .iter()
+            .copied()
+            .find(|&known| registry.name(known).to_lowercase() == subject.to_lowercase())
+            .unwrap_or_else(|| registry.register(&subject));
+        if !matched.contains(&id) {
+            matched.push(id);
+        }
+    }
+    matched
+}
+
+/// Whether a student is shown in the list when searching for `filter`, which
+/// may be part of their ID or of one of their subjects, in any case.
+fn matches_filter(
+    student_id: &timetabler::StudentId,
+    subjects: &[timetabler::SubjectId],
+    registry: &timetabler::SubjectRegistry,
+    filter: &str,
+) -> bool {
+    let filter = filter.trim().to_lowercase();
+    student_id.as_str().to_lowercase().contains(&filter)
+        || subjects
+            .iter()
+            .any(|&subject| registry.name(subject).to_lowercase().contains(&filter))
+}
+
+/// The widest a column of a timetable grid gets before its text wraps.
+const MAX_SLOT_COLUMN_WIDTH: f32 = 200.0;
+
+/// The slots of a timetable split up by day.
+fn split_days<T: Clone>(slots: &[T], daily_lesson_capacity: u8) -> Vec<Vec<T>> {
+    slots
+        .chunks(daily_lesson_capacity.into())
+        .map(<[T]>::to_vec)
+        .collect()
+}
+
+/// The part of `slots`, which cover every term of the year one after another,
+/// which falls in `term`.
+fn term_slots<T>(slots: &[T], terms: u8, term: usize) -> &[T] {
+    let per_term = slots.len() / usize::from(terms.max(1));
+    let start = (per_term * term).min(slots.len());
+    &slots[start..(start + per_term).min(slots.len())]
+}
+
+/// The subjects taught in each slot of the whole school's timetable, by
+/// name.
+fn slot_names(
+    subjects: &timetabler::SubjectRegistry,
+    slots: &[Vec<timetabler::SubjectId>],
+) -> Vec<String> {
+    slots
+        .iter()
+        .map(|slot| {
+            let names: Vec<&str> = slot.iter().map(|&subject| subjects.name(subject)).collect();
+            names.join(", ")
+        })
+        .collect()
+}
+
+/// What each slot of each day holds in the whole school's timetable.
+fn global_days(
+    subjects: &timetabler::SubjectRegistry,
+    slots: &[Vec<timetabler::SubjectId>],
+    daily_lesson_capacity: u8,
+) -> Vec<Vec<String>> {
+    split_days(&slot_names(subjects, slots), daily_lesson_capacity)
+}
+
+/// What each slot of each day holds in a student's timetable. Students see
+/// which group they are in. Teachers' timetables take the same form, showing
+/// the group they teach.
+fn student_days(
+    slots: &[Option<(timetabler::SubjectId, usize)>],
+    group_names: &BTreeMap<timetabler::SubjectId, Vec<String>>,
+    daily_lesson_capacity: u8,
+) -> Vec<Vec<String>> {
+    split_days(slots, daily_lesson_capacity)
+        .iter()
+        .map(|day| {
+            day.iter()
+                .map(|slot| match slot {
+                    Some((subject, group_idx)) => group_names[subject][*group_idx].clone(),
+                    None => String::new(),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// What breaks the rules in each slot of the year, for highlighting the
+/// slots in a timetable.
+fn conflicts_by_slot(violations: &[timetabler::Violation]) -> BTreeMap<usize, Vec<String>> {
+    let mut conflicts: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+    for violation in violations {
+        if let Some(slot) = violation.slot() {
+            conflicts
+                .entry(slot)
+                .or_default()
+                .push(violation.to_string());
+        }
+    }
+    conflicts
+}
+
+/// Lays out a timetable with a grid for each day, putting the days of a week
+/// side by side. `cell` draws what is in a slot, given the slot of the year.
+fn timetable_grid(
+    ui: &mut egui::Ui,
+    id_source: &str,
+    days: usize,
+    daily_lesson_capacity: u8,
+    fortnightly: bool,
+    mut cell: impl FnMut(&mut egui::Ui, usize),
+) {
+    let daily_lesson_capacity = usize::from(daily_lesson_capacity);
+    for week_start in (0..days).step_by(WEEK_DAYS.len()) {
+        ui.horizontal_top(|ui| {
+            for day in week_start..days.min(week_start + WEEK_DAYS.len()) {
+                ui.vertical(|ui| {
+                    let label = day_label(day, fortnightly);
+                    ui.label(&label);
+                    // We need to create a new grid for each day as we cannot
+                    // approach this in a 3D manner.
+                    egui::Grid::new((id_source, &label))
+                        .striped(true)
+                        .max_col_width(MAX_SLOT_COLUMN_WIDTH)
+                        .show(ui, |ui| {
+                            for period in 0..daily_lesson_capacity {
+                                ui.label(format!("Slot {}", period + 1));
+                                cell(ui, day * daily_lesson_capacity + period);
+                                ui.end_row();
+                            }
+                        });
+                });
+            }
+        });
+    }
+}
+
+/// The group each subject is taught to in each slot of the year. Only one
+/// group of a subject is taught at a time.
+fn groups_by_slot(
+    slots_by_student_id: &BTreeMap<
+        timetabler::StudentId,
+        Vec<Option<(timetabler::SubjectId, usize)>>,
+    >,
+) -> BTreeMap<(usize, timetabler::SubjectId), usize> {
+    let mut groups = BTreeMap::new();
+    for slots in slots_by_student_id.values() {
+        for (slot, entry) in slots.iter().enumerate() {
+            if let Some((subject, group_idx)) = *entry {
+                groups.insert((slot, subject), group_idx);
+            }
+        }
+    }
+    groups
+}
+
+/// Draws the subjects taught in a slot as links, in red if the slot breaks
+/// the rules, returning the one clicked.
+fn subject_links(
+    ui: &mut egui::Ui,
+    registry: &timetabler::SubjectRegistry,
+    subjects: &[timetabler::SubjectId],
+    has_problems: bool,
+) -> Option<timetabler::SubjectId> {
+    let mut clicked = None;
+    for &subject in subjects {
+        let mut text = egui::RichText::new(registry.name(subject));
+        if has_problems {
+            text = text.color(egui::Color32::RED);
+        }
+        if ui
+            .add(egui::Label::new(text).sense(egui::Sense::click()))
+            .clicked()
+        {
+            clicked = Some(subject);
+        }
+    }
+    clicked
+}
+
+/// A timetable as tab separated values, with the days as columns and the
+/// slots as rows, to paste into a spreadsheet or an email.
+fn timetable_tsv(days: &[Vec<String>], fortnightly: bool) -> String {
+    // Tabs and line breaks in a cell would break up the table.
+    let cell = |text: &str| text.replace(&['\t', '\n'][..], " ");
+    let mut tsv = String::new();
+    for day in 0..days.len() {
+        tsv.push('\t');
+        tsv.push_str(&day_label(day, fortnightly));
+    }
+    tsv.push('\n');
+    let slots = days.iter().map(Vec::len).max().unwrap_or(0);
+    for slot in 0..slots {
+        tsv.push_str(&format!("Slot {}", slot + 1));
+        for day in days {
+            tsv.push('\t');
+            tsv.push_str(&cell(day.get(slot).map_or("", String::as_str)));
+        }
+        tsv.push('\n');
+    }
+    tsv
+}
+
+/// Which timetable the print view shows.
+#[derive(Clone, PartialEq, Eq)]
+enum PrintTarget {
+    Global,
+    Student(timetabler::StudentId),
+    Teacher(String),
+}
+
+/// An A4 page in landscape, in points at 96 dots per inch.
+const A4_LANDSCAPE: egui::Vec2 = egui::Vec2 {
+    x: 1123.0,
+    y: 794.0,
+};
+
+const PRINT_MARGIN: f32 = 40.0;
+
+const PRINT_TEXT_SIZE: f32 = 18.0;
+
+fn print_text(text: impl Into<String>) -> egui::RichText {
+    egui::RichText::new(text)
+        .size(PRINT_TEXT_SIZE)
+        .color(egui::Color32::BLACK)
+}
+
+/// Lays out a timetable on a white A4 page, with the days as columns and the
+/// slots as rows, for printing or putting on a noticeboard.
+fn print_page(ui: &mut egui::Ui, title: &str, days: &[Vec<String>], fortnightly: bool) {
+    egui::Frame::none()
+        .fill(egui::Color32::WHITE)
+        .margin(egui::vec2(PRINT_MARGIN, PRINT_MARGIN))
+        .show(ui, |ui| {
+            ui.set_min_size(A4_LANDSCAPE - egui::vec2(PRINT_MARGIN, PRINT_MARGIN) * 2.0);
+            ui.label(print_text(title).size(PRINT_TEXT_SIZE * 1.5));
+            // Every column, including the slot numbers, gets an equal share of
+            // the page.
+            let col_width = (A4_LANDSCAPE.x - PRINT_MARGIN * 2.0) / (days.len() + 1) as f32
+                - PRINT_MARGIN / 2.0;
+            egui::Grid::new("Print view")
+                .min_col_width(col_width)
+                .max_col_width(col_width)
+                .spacing(egui::vec2(PRINT_MARGIN / 2.0, PRINT_MARGIN / 4.0))
+                .show(ui, |ui| {
+                    ui.label("");
+                    for day in 0..days.len() {
+                        ui.label(print_text(day_label(day, fortnightly)).strong());
+                    }
+                    ui.end_row();
+                    let slots = days.iter().map(Vec::len).max().unwrap_or(0);
+                    for slot in 0..slots {
+                        ui.label(print_text(format!("Slot {}", slot + 1)).strong());
+                        for day in days {
+                            let text = day.get(slot).map_or("", String::as_str);
+                            ui.add(egui::Label::new(print_text(text)).wrap(true));
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+}
+
+/// Sums up what is about to be solved, so mistakes in it can be caught
+/// before waiting for the solver.
+fn review_summary(ui: &mut egui::Ui, info: &timetabler::TimetableInfo, exam_mode: bool) {
+    let rows = [
+        ("Students", info.students.len().to_string()),
+        ("Subjects", info.subjects.len().to_string()),
+        ("Teachers", info.teachers.len().to_string()),
+        ("Rooms", info.rooms.len().to_string()),
+        ("Lessons a day", info.daily_lesson_capacity.to_string()),
+        ("Slots a year", info.slots_per_year().to_string()),
+        ("Most groups of a subject", info.max_groups.to_string()),
+        (
+            "Most groups at once",
+            info.max_groups_per_slot
+                .map_or("Any".to_string(), |max| max.to_string()),
+        ),
+        (
+            "Solving",
+            if exam_mode {
+                "Exams only"
+            } else if info.algorithm == timetabler::Algorithm::Exhaustive {
+                "Exhaustive search"
+            } else {
+                "Greedy"
+            }
+            .to_string(),
+        ),
+    ];
+    egui::Grid::new("Review").striped(true).show(ui, |ui| {
+        for (name, value) in rows {
+            ui.label(name);
+            ui.label(value);
+            ui.end_row();
+        }
+    });
+}
+
+/// Summarises a solved timetable: the groups of each subject and their sizes,
+/// how many slots are used and how many gaps students have.
+fn statistics(
+    ui: &mut egui::Ui,
+    registry: &timetabler::SubjectRegistry,
+    result: &timetabler::TimetableResult,
+) {
+    let (subjects, metrics) = match result {
+        timetabler::TimetableResult::Solved {
+            subjects, metrics, ..
+        } => (subjects, metrics),
+        timetabler::TimetableResult::Unsolved(_) => return,
+    };
+
+    egui::Grid::new("Statistics").striped(true).show(ui, |ui| {
+        ui.label("Subject");
+        ui.label("Groups");
+        ui.label("Smallest");
+        ui.label("Average");
+        ui.label("Largest");
+        ui.end_row();
+        for (subject, groups) in result.rosters() {
+            let sizes = groups.iter().map(Vec::len);
+            let total: usize = sizes.clone().sum();
+            ui.label(registry.name(subject));
+            ui.label(groups.len().to_string());
+            ui.label(sizes.clone().min().unwrap_or(0).to_string());
+            ui.label(format!("{:.1}", total as f64 / groups.len().max(1) as f64));
+            ui.label(sizes.max().unwrap_or(0).to_string());
+            ui.end_row();
+        }
+    });
+
+    let used_slots = subjects.iter().filter(|slot| !slot.is_empty()).count();
+    ui.label(format!(
+        "{} of {} slots have lessons ({:.0}%)",
+        used_slots,
+        subjects.len(),
+        100.0 * used_slots as f64 / subjects.len().max(1) as f64
+    ));
+    ui.label(format!(
+        "{} gaps between students' lessons",
+        group_digits(metrics.student_gaps_after)
+    ));
+}
+
+/// Charts how many groups there are of each size, and how many lessons are
+/// taught in each slot, so uneven groups and crowded slots stand out.
+fn statistics_charts(
+    ui: &mut egui::Ui,
+    result: &timetabler::TimetableResult,
+    daily_lesson_capacity: u8,
+    fortnightly: bool,
+) {
+    let subjects = match result {
+        timetabler::TimetableResult::Solved { subjects, .. } => subjects,
+        timetabler::TimetableResult::Unsolved(_) => return,
+    };
+
+    let mut groups_by_size: BTreeMap<usize, usize> = BTreeMap::new();
+    for groups in result.rosters().values() {
+        for student_ids in groups {
+            *groups_by_size.entry(student_ids.len()).or_default() += 1;
+        }
+    }
+    ui.label("Groups by size");
+    let bars = groups_by_size
+        .into_iter()
+        .map(|(size, groups)| {
+            egui::plot::Bar::new(size as f64, groups as f64)
+                .name(format!("{} students", size))
+                .width(0.9)
+        })
+        .collect();
+    egui::plot::Plot::new("Groups by size")
+        .height(150.0)
+        .allow_zoom(false)
+        .allow_drag(false)
+        .include_y(0.0)
+        .show(ui, |plot_ui| {
+            plot_ui.bar_chart(egui::plot::BarChart::new(bars))
+        });
+
+    ui.label("Lessons by slot");
+    let bars = subjects
+        .iter()
+        .enumerate()
+        .map(|(slot, slot_subjects)| {
+            egui::plot::Bar::new((slot + 1) as f64, slot_subjects.len() as f64)
+                .name(slot_label(slot, daily_lesson_capacity, fortnightly))
+                .width(0.9)
+        })
+        .collect();
+    egui::plot::Plot::new("Lessons by slot")
+        .height(150.0)
+        .allow_zoom(false)
+        .allow_drag(false)
+        .include_y(0.0)
+        .show(ui, |plot_ui| {
+            plot_ui.bar_chart(egui::plot::BarChart::new(bars))
+        });
+}
+
+/// Compares the result solved before with the current one: how each measure
+/// changed, how many groups and students moved, and the earlier timetable
+/// with the slots which differ picked out. Returns whether the user chose to
+/// go back to the earlier result.
+fn comparison(
+    ui: &mut egui::Ui,
+    previous_info: &timetabler::TimetableInfo,
+    previous: &timetabler::TimetableResult,
+    current_info: &timetabler::TimetableInfo,
+    current: &timetabler::TimetableResult,
+) -> bool {
+    let (previous_subjects, previous_metrics, current_subjects, current_metrics) =
+        match (previous, current) {
+            (
+                timetabler::TimetableResult::Solved {
+                    subjects: previous_subjects,
+                    metrics: previous_metrics,
+                    ..
+                },
+                timetabler::TimetableResult::Solved {
+                    subjects: current_subjects,
+                    metrics: current_metrics,
+                    ..
+                },
+            ) => (
+                previous_subjects,
+                previous_metrics,
+                current_subjects,
+                current_metrics,
+            ),
+            _ => return false,
+        };
+
+    // Both are scored with the current weights so that the scores compare.
+    let weights = &current_info.weights;
+    let rows = [
+        (
+            "Groups",
+            previous.group_count() as u64,
+            current.group_count() as u64,
+        ),
+        (
+            "Gaps",
+            previous_metrics.student_gaps_after as u64,
+            current_metrics.student_gaps_after as u64,
+        ),
+        (
+            "Group size spread",
+            previous_metrics.group_size_spread_after as u64,
+            current_metrics.group_size_spread_after as u64,
+        ),
+        (
+            "Slot preference penalty",
+            u64::from(previous_metrics.slot_preference_penalty),
+            u64::from(current_metrics.slot_preference_penalty),
+        ),
+        (
+            "Daily lesson penalty",
+            previous_metrics.daily_lesson_penalty as u64,
+            current_metrics.daily_lesson_penalty as u64,
+        ),
+        (
+            "Teacher preference penalty",
+            u64::from(previous_metrics.teacher_preference_penalty),
+            u64::from(current_metrics.teacher_preference_penalty),
+        ),
+        (
+            "Score (lower is better)",
+            previous_metrics.score(weights),
+            current_metrics.score(weights),
+        ),
+    ];
+    egui::Grid::new("Comparison").striped(true).show(ui, |ui| {
+        ui.label("");
+        ui.label("Previous");
+        ui.label("Current");
+        ui.label("Change");
+        ui.end_row();
+        for (name, previous_value, current_value) in rows {
+            ui.label(name);
+            ui.label(previous_value.to_string());
+            ui.label(current_value.to_string());
+            ui.label(format!(
+                "{:+}",
+                current_value as i64 - previous_value as i64
+            ));
+            ui.end_row();
+        }
+    });
+
+    let timetable_diff = timetabler::diff(previous, current);
+    ui.label(format!(
+        "{} groups moved, {} are new and {} are gone. {} students' timetables changed.",
+        timetable_diff.moved_groups.len(),
+        timetable_diff.new_groups.len(),
+        timetable_diff.removed_groups.len(),
+        timetable_diff.changed_students.len()
+    ));
+
+    ui.label("Previous timetable, with the slots which changed in yellow");
+    let daily_lesson_capacity = previous_info.daily_lesson_capacity;
+    let capacity = usize::from(daily_lesson_capacity);
+    let previous_names = slot_names(&previous_info.subjects, previous_subjects);
+    let current_names = slot_names(&current_info.subjects, current_subjects);
+    let days = split_days(&previous_names, daily_lesson_capacity);
+    timetable_grid(
+        ui,
+        "Previous timetable",
+        days.len(),
+        daily_lesson_capacity,
+        previous_info.cycle == timetabler::WeekCycle::Fortnightly,
+        |ui, slot| {
+            let text = days
+                .get(slot / capacity)
+                .and_then(|day| day.get(slot % capacity))
+                .map_or("", String::as_str);
+            let mut text = egui::RichText::new(text);
+            if previous_names.get(slot) != current_names.get(slot) {
+                text = text.color(egui::Color32::YELLOW);
+            }
+            ui.add(egui::Label::new(text).wrap(true));
+        },
+    );
+
+    // The result screen lays out the timetable from the current settings, so
+    // results with a different week can't be swapped in.
+    if previous_info.daily_lesson_capacity == current_info.daily_lesson_capacity
+        && previous_info.cycle == current_info.cycle
+    {
+        ui.button("Keep the previous result").clicked()
+    } else {
+        ui.label("The previous result has a different week, so it can't be kept.");
+        false
+    }
+}
+
+/// Describes what the solver is doing for the user.
+fn phase_label(phase: timetabler::Phase) -> &'static str {
+    match phase {
+        timetabler::Phase::PlacingStudents => "Placing students",
+        timetabler::Phase::BalancingGroupSizes => "Balancing group sizes",
+        timetabler::Phase::MinimizingGaps => "Closing gaps",
+        timetabler::Phase::LocalSearch => "Improving the timetable",
+        timetabler::Phase::BalancingDailyWorkload => "Balancing daily workloads",
+        timetabler::Phase::ExhaustiveSearch => "Searching for the best timetable",
+        timetabler::Phase::AssigningRooms => "Assigning rooms",
+        timetabler::Phase::AssigningTeachers => "Assigning teachers",
+    }
+}
+
+/// Writes a number with commas between groups of three digits, e.g. "2,340".
+fn group_digits(number: usize) -> String {
+    let digits = number.to_string();
+    let mut grouped = String::new();
+    for (idx, digit) in digits.chars().enumerate() {
+        if idx > 0 && (digits.len() - idx) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// How much space is left around and between widgets.
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Density {
+    Compact,
+    Normal,
+    Roomy,
+}
+
+impl Density {
+    /// How much egui's default spacing is scaled by.
+    fn factor(self) -> f32 {
+        match self {
+            Density::Compact => 0.5,
+            Density::Normal => 1.0,
+            Density::Roomy => 1.5,
+        }
+    }
+}
+
+/// How the app looks, chosen in the settings window.
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+struct ThemeSettings {
+    light_mode: bool,
+    /// The colour of selected widgets and links.
+    accent_color: [u8; 3],
+    density: Density,
+}
+
+impl Default for ThemeSettings {
+    /// egui's own look.
+    fn default() -> Self {
+        ThemeSettings {
+            light_mode: false,
+            accent_color: [0, 92, 128],
+            density: Density::Normal,
+        }
+    }
+}
+
+impl ThemeSettings {
+    fn apply(&self, ctx: &egui::Context) {
+        let mut style = egui::Style {
+            visuals: if self.light_mode {
+                egui::Visuals::light()
+            } else {
+                egui::Visuals::dark()
+            },
+            ..Default::default()
+        };
+        let [r, g, b] = self.accent_color;
+        style.visuals.selection.bg_fill = egui::Color32::from_rgb(r, g, b);
+        style.visuals.hyperlink_color = egui::Color32::from_rgb(r, g, b);
+        let factor = self.density.factor();
+        style.spacing.item_spacing *= factor;
+        style.spacing.button_padding *= factor;
+        ctx.set_style(style);
+    }
+}
+
+/// How long a toast stays on screen, in seconds.
+const TOAST_DURATION: f64 = 3.0;
+
+/// A short message acknowledging something the user did.
+struct Toast {
+    message: String,
+    is_error: bool,
+    /// When the toast was first drawn, in egui's time.
+    shown_at: Option<f64>,
+}
+
+/// Toasts shown in the bottom right corner for a few seconds each.
+#[derive(Default)]
+struct Toasts {
+    toasts: Vec<Toast>,
+}
+
+impl Toasts {
+    fn info(&mut self, message: impl Into<String>) {
+        self.toasts.push(Toast {
+            message: message.into(),
+            is_error: false,
+            shown_at: None,
+        });
+    }
+
+    fn error(&mut self, message: impl Into<String>) {
+        self.toasts.push(Toast {
+            message: message.into(),
+            is_error: true,
+            shown_at: None,
+        });
+    }
+
+    /// Draws the toasts, dropping those which have been up long enough.
+    fn show(&mut self, ctx: &egui::Context) {
+        let now = ctx.input().time;
+        self.toasts.retain(|toast| {
+            toast
+                .shown_at
+                .map_or(true, |shown_at| now - shown_at < TOAST_DURATION)
+        });
+        if self.toasts.is_empty() {
+            return;
+        }
+
+        egui::Area::new("toasts")
+            .anchor(egui::Align2::RIGHT_BOTTOM, [-10.0, -10.0])
+            .show(ctx, |ui| {
+                for toast in &mut self.toasts {
+                    toast.shown_at.get_or_insert(now);
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        if toast.is_error {
+                            ui.colored_label(egui::Color32::RED, toast.message.as_str());
+                        } else {
+                            ui.label(toast.message.as_str());
+                        }
+                    });
+                }
+            });
+        // Nothing else may cause a repaint by the time the toasts are due to
+        // go.
+        ctx.request_repaint();
+    }
+}
+
+/// Edits to the same setting made within this many seconds of each other are
+/// undone together, so typing a word doesn't take an undo for every letter.
+const UNDO_MERGE_SECONDS: f64 = 1.0;
+
+/// The most edits which can be undone.
+const MAX_UNDO_STEPS: usize = 100;
+
+/// Everything the user enters, which undo and redo step through. Only
+/// `History::apply` changes them, apart from registering subjects and
+/// dropping the imported details of subjects once the import isn't used,
+/// neither of which changes what was entered.
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "persistence", serde(default))]
+#[derive(Clone)]
+struct Inputs {
+    max_groups: u8,
+    daily_lesson_capacity: u8,
+    max_groups_per_slot_txt: String,
+    fortnightly: bool,
+    /// The number of terms the year is split into.
+    terms: u8,
+    exhaustive: bool,
+    fewest_groups: bool,
+    exam_mode: bool,
+    subjects_by_student_id: BTreeMap<timetabler::StudentId, Vec<timetabler::SubjectId>>,
+    /// Every subject entered or imported, which students and the subject
+    /// list refer to by ID, with the term and max groups set for each.
+    subjects: timetabler::SubjectRegistry,
+    /// Subjects made on the subjects screen, which may not be taken by any
+    /// student yet.
+    subject_list: BTreeSet<timetabler::SubjectId>,
+    /// Other names for subjects, written as `alias=subject` separated by
+    /// commas.
+    subject_aliases_txt: String,
+    /// Bundles of subjects students can be given by name, written as
+    /// `name=subject,subject` separated by semicolons.
+    curricula_txt: String,
+}
+
+impl Default for Inputs {
+    /// The smallest school the settings allow, with nobody in it yet.
+    fn default() -> Self {
+        Inputs {
+            max_groups: *MAX_GROUPS_RANGE.start(),
+            daily_lesson_capacity: *DAILY_LESSON_CAPACITY_RANGE.start(),
+            max_groups_per_slot_txt: String::new(),
+            fortnightly: false,
+            terms: *TERMS_RANGE.start(),
+            exhaustive: false,
+            fewest_groups: false,
+            exam_mode: false,
+            subjects_by_student_id: BTreeMap::new(),
+            subjects: timetabler::SubjectRegistry::new(),
+            subject_list: BTreeSet::new(),
+            subject_aliases_txt: String::new(),
+            curricula_txt: String::new(),
+        }
+    }
+}
+
+/// A change to the inputs. Making an edit gives back the edit which reverses
+/// it, which is what undo and redo keep.
+#[derive(Clone)]
+enum Edit {
+    MaxGroups(u8),
+    DailyLessonCapacity(u8),
+    MaxGroupsPerSlotTxt(String),
+    Fortnightly(bool),
+    Terms(u8),
+    Exhaustive(bool),
+    FewestGroups(bool),
+    ExamMode(bool),
+    SubjectAliasesTxt(String),
+    CurriculaTxt(String),
+    /// Gives a student these subjects, adding them if they are new, or
+    /// deletes them with `None`.
+    Student(timetabler::StudentId, Option<Vec<timetabler::SubjectId>>),
+    /// Puts a subject on the subject list, or takes it off with `false`.
+    ListSubject(timetabler::SubjectId, bool),
+    /// Renames a subject, unless another subject has the name already.
+    RenameSubject(timetabler::SubjectId, String),
+    SubjectTerm(timetabler::SubjectId, Option<u8>),
+    SubjectMaxGroups(timetabler::SubjectId, Option<u8>),
+    /// Edits which are made, and undone, together.
+    Batch(Vec<Edit>),
+    /// Replaces all the inputs, as importing a file does.
+    All(Box<Inputs>),
+}
+
+impl Edit {
+    /// Whether this and `other` change the same setting, so that one made
+    /// soon after the other can be undone with it.
+    fn same_setting(&self, other: &Edit) -> bool {
+        matches!(
+            (self, other),
+            (Edit::MaxGroups(_), Edit::MaxGroups(_))
+                | (Edit::DailyLessonCapacity(_), Edit::DailyLessonCapacity(_))
+                | (Edit::MaxGroupsPerSlotTxt(_), Edit::MaxGroupsPerSlotTxt(_))
+                | (Edit::Terms(_), Edit::Terms(_))
+                | (Edit::SubjectAliasesTxt(_), Edit::SubjectAliasesTxt(_))
+                | (Edit::CurriculaTxt(_), Edit::CurriculaTxt(_))
+        )
+    }
+}
+
+impl Inputs {
+    /// Makes `edit`, returning the edit which reverses it.
+    fn apply(&mut self, edit: Edit) -> Edit {
+        match edit {
+            Edit::MaxGroups(value) => Edit::MaxGroups(mem::replace(&mut self.max_groups, value)),
+            Edit::DailyLessonCapacity(value) => {
+                Edit::DailyLessonCapacity(mem::replace(&mut self.daily_lesson_capacity, value))
+            }
+            Edit::MaxGroupsPerSlotTxt(txt) => {
+                Edit::MaxGroupsPerSlotTxt(mem::replace(&mut self.max_groups_per_slot_txt, txt))
+            }
+            Edit::Fortnightly(value) => {
+                Edit::Fortnightly(mem::replace(&mut self.fortnightly, value))
+            }
+            Edit::Terms(value) => Edit::Terms(mem::replace(&mut self.terms, value)),
+            Edit::Exhaustive(value) => Edit::Exhaustive(mem::replace(&mut self.exhaustive, value)),
+            Edit::FewestGroups(value) => {
+                Edit::FewestGroups(mem::replace(&mut self.fewest_groups, value))
+            }
+            Edit::ExamMode(value) => Edit::ExamMode(mem::replace(&mut self.exam_mode, value)),
+            Edit::SubjectAliasesTxt(txt) => {
+                Edit::SubjectAliasesTxt(mem::replace(&mut self.subject_aliases_txt, txt))
+            }
+            Edit::CurriculaTxt(txt) => {
+                Edit::CurriculaTxt(mem::replace(&mut self.curricula_txt, txt))
+            }
+            Edit::Student(student_id, Some(subjects)) => {
+                let old = self
+                    .subjects_by_student_id
+                    .insert(student_id.clone(), subjects);
+                Edit::Student(student_id, old)
+            }
+            Edit::Student(student_id, None) => {
+                let old = self.subjects_by_student_id.remove(&student_id);
+                Edit::Student(student_id, old)
+            }
+            Edit::ListSubject(subject, true) => {
+                Edit::ListSubject(subject, !self.subject_list.insert(subject))
+            }
+            Edit::ListSubject(subject, false) => {
+                Edit::ListSubject(subject, self.subject_list.remove(&subject))
+            }
+            Edit::RenameSubject(subject, name) => {
+                let old = self.subjects.name(subject).to_string();
+                match self.subjects.rename(subject, &name) {
+                    Ok(()) => Edit::RenameSubject(subject, old),
+                    Err(_) => Edit::Batch(Vec::new()),
+                }
+            }
+            Edit::SubjectTerm(subject, term) => Edit::SubjectTerm(
+                subject,
+                mem::replace(&mut self.subjects[subject].term, term),
+            ),
+            Edit::SubjectMaxGroups(subject, groups) => Edit::SubjectMaxGroups(
+                subject,
+                mem::replace(&mut self.subjects[subject].max_groups, groups),
+            ),
+            Edit::Batch(edits) => {
+                let mut reverse: Vec<Edit> =
+                    edits.into_iter().map(|edit| self.apply(edit)).collect();
+                reverse.reverse();
+                Edit::Batch(reverse)
+            }
+            Edit::All(inputs) => Edit::All(Box::new(mem::replace(self, *inputs))),
+        }
+    }
+}
+
+/// The edits which reverse each edit which can be undone, and each edit
+/// which has been undone.
+#[derive(Default)]
+struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    /// When the last edit was made, in egui's time.
+    last_edit: Option<f64>,
+}
+
+impl History {
+    /// Makes `edit` to `inputs` so that it can be undone.
+    fn apply(&mut self, inputs: &mut Inputs, edit: Edit, time: f64) {
+        let reverse = inputs.apply(edit);
+        let merged = matches!(self.last_edit, Some(last_edit) if time - last_edit < UNDO_MERGE_SECONDS)
+            && matches!(self.undo.last(), Some(last) if last.same_setting(&reverse));
+        // The earlier edit already goes back to before them both.
+        if !merged {
+            self.undo.push(reverse);
+            if self.undo.len() > MAX_UNDO_STEPS {
+                self.undo.remove(0);
+            }
+        }
+        self.redo.clear();
+        self.last_edit = Some(time);
+    }
+
+    /// Reverses the last edit, returning whether there was one.
+    fn undo(&mut self, inputs: &mut Inputs) -> bool {
+        match self.undo.pop() {
+            Some(reverse) => {
+                self.redo.push(inputs.apply(reverse));
+                self.last_edit = None;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Makes the last edit undone again, returning whether there was one.
+    fn redo(&mut self, inputs: &mut Inputs) -> bool {
+        match self.redo.pop() {
+            Some(edit) => {
+                self.undo.push(inputs.apply(edit));
+                self.last_edit = None;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// The keyboard shortcuts pressed this frame, read in one place so each
+/// screen only has to check the ones it handles.
+struct Shortcuts {
+    undo: bool,
+    redo: bool,
+    /// Ctrl+N, to add a student.
+    new_student: bool,
+    /// Ctrl+S, to save the project.
+    #[cfg(all(feature = "project", not(target_arch = "wasm32")))]
+    save: bool,
+    /// Ctrl+Enter, to submit the students for review and then solve them.
+    solve: bool,
+    /// Enter, to confirm the open dialog.
+    confirm: bool,
+    /// Escape, to cancel the open dialog.
+    cancel: bool,
+}
+
+impl Shortcuts {
+    fn read(ctx: &egui::Context) -> Shortcuts {
+        // Text boxes undo their own typing while they have focus.
+        let typing = ctx.wants_keyboard_input();
+        let input = ctx.input();
+        let command = input.modifiers.command;
+        let shift = input.modifiers.shift;
+        Shortcuts {
+            undo: !typing && command && !shift && input.key_pressed(egui::Key::Z),
+            redo: !typing
+                && command
+                && (input.key_pressed(egui::Key::Y) || shift && input.key_pressed(egui::Key::Z)),
+            new_student: command && input.key_pressed(egui::Key::N),
+            #[cfg(all(feature = "project", not(target_arch = "wasm32")))]
+            save: command && input.key_pressed(egui::Key::S),
+            solve: command && input.key_pressed(egui::Key::Enter),
+            confirm: !command && input.key_pressed(egui::Key::Enter),
+            cancel: input.key_pressed(egui::Key::Escape),
+        }
+    }
+}
 
 /// We derive Deserialize/Serialize so we can persist app state on shutdown.
 #[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
 #[cfg_attr(feature = "persistence", serde(default))] // if we add new fields, give them default values when deserializing old state
 #[derive(Default)]
 pub struct TimetablerApp {
-    max_groups_txt: String,
-    max_groups: Option<u8>,
-    daily_lesson_capacity_txt: String,
-    daily_lesson_capacity: Option<u8>,
+    /// What the user has entered, which is changed through `history`.
+    inputs: Inputs,
+    /// The max groups at once last read from `max_groups_per_slot_txt`.
+    max_groups_per_slot: Option<u8>,
     state: AppState,
-    subjects_by_student_id: HashMap<String, Vec<String>>,
     new_student_id_txt: String,
     new_student_subjects_txt: String,
-    selected_student_id: String,
+    /// The student whose subjects are being changed in the create window,
+    /// if it's open for an existing student rather than a new one.
+    editing_student_id: Option<timetabler::StudentId>,
+    rename_student_id_txt: String,
+    selected_subject: Option<timetabler::SubjectId>,
+    new_subject_txt: String,
+    rename_subject_txt: String,
+    selected_student_id: timetabler::StudentId,
+    /// The teacher whose timetable is shown on the result screen.
+    selected_teacher_id: String,
+    /// The term whose timetables are shown on the result screen.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    shown_term: usize,
+    /// The students ticked in the list, which bulk actions apply to.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    checked_student_ids: BTreeSet<timetabler::StudentId>,
+    /// Whether the selected student is waiting for the user to confirm
+    /// deleting them.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    confirming_delete: bool,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    student_filter_txt: String,
     result: Option<timetabler::TimetableResult>,
+    /// The result solved before the current one, with what it was solved
+    /// from, to compare them.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    previous_result: Option<(timetabler::TimetableInfo, timetabler::TimetableResult)>,
+    /// The seed students were last shuffled with to re-solve, which the next
+    /// re-solve goes on from.
+    solve_seed: u64,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    solving: Option<Solving>,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    exams: Option<Result<timetabler::ExamSchedule, timetabler::ExamError>>,
+    /// Who supervises the exams, when there are teachers to do so.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    invigilation: Option<Result<timetabler::InvigilationRota, timetabler::ExamError>>,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    validation_issues: Vec<timetabler::ValidationIssue>,
+    /// The subject and group index of the group whose details are shown.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    selected_group: Option<(timetabler::SubjectId, usize)>,
+    /// The rules the result breaks, as found by the verifier.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    violations: Vec<timetabler::Violation>,
+    record_trace: bool,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    trace: Vec<timetabler::TraceEvent>,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    relaxations: Vec<timetabler::Relaxation>,
+    xhstt_path: String,
+    #[cfg(feature = "json")]
+    config_path: String,
+    /// A CSV file of students and their subjects to add in bulk.
+    roster_path: String,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    roster_error: Option<String>,
+    /// The last XHSTT or JSON file imported, for the subjects, rooms,
+    /// teachers and other settings the app has no screens for.
+    imported_info: Option<timetabler::TimetableInfo>,
+    /// What was last submitted on the students screen, to review before
+    /// solving it.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    reviewed_info: Option<timetabler::TimetableInfo>,
+    /// What the result was solved from, which exports need.
+    submitted_info: Option<timetabler::TimetableInfo>,
+    /// The folder exported timetables are written to.
+    export_dir: String,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    import_error: Option<timetabler::ImportError>,
+    /// Why the last thing entered on the current screen wasn't accepted.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    input_error: Option<InputError>,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    toasts: Toasts,
+    theme: ThemeSettings,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    show_settings: bool,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    history: History,
+    /// Whether the user is being asked before everything is cleared for a
+    /// new timetable.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    confirming_reset: bool,
+    /// The project file last opened or saved, which "Save project" writes
+    /// to.
+    #[cfg(all(feature = "project", not(target_arch = "wasm32")))]
+    project_path: Option<std::path::PathBuf>,
+    /// The project files opened or saved lately, the latest first.
+    #[cfg(all(feature = "project", not(target_arch = "wasm32")))]
+    recent_projects: Vec<std::path::PathBuf>,
+    #[cfg(all(feature = "project", not(target_arch = "wasm32")))]
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    project_action: Option<ProjectAction>,
+    /// The timetable shown ready for printing, in place of the result screen.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    print_target: Option<PrintTarget>,
+    #[cfg(feature = "scripting")]
+    constraint_script_path: String,
+    #[cfg(feature = "scripting")]
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    script_error: Option<String>,
     // // this how you opt-out of serialization of a member
     // #[cfg_attr(feature = "persistence", serde(skip))]
     // value: f32
 }
 
-impl epi::App for TimetablerApp {
-    fn name(&self) -> &str {
-        "Timetabler"
+/// What to do with a project file, which happens once the frame is drawn so
+/// that the whole app can be written out or replaced.
+#[cfg(all(feature = "project", not(target_arch = "wasm32")))]
+#[derive(Clone, PartialEq, Eq)]
+enum ProjectAction {
+    Open,
+    /// Open a project from the recent projects list, without asking where.
+    OpenRecent(std::path::PathBuf),
+    Save,
+    SaveAs,
+}
+
+/// The most projects kept in the recent projects list.
+#[cfg(all(feature = "project", not(target_arch = "wasm32")))]
+const MAX_RECENT_PROJECTS: usize = 8;
+
+/// How far the solver has got, as it last said.
+#[derive(Default)]
+struct SolveProgress {
+    phase: Option<timetabler::Phase>,
+    placed: usize,
+    total: usize,
+}
+
+/// Passes the solver's progress on to the window, recording its decisions
+/// too when a trace was asked for.
+struct ProgressObserver {
+    progress: Arc<Mutex<SolveProgress>>,
+    trace: Option<Vec<timetabler::TraceEvent>>,
+}
+
+impl timetabler::SolveObserver for ProgressObserver {
+    fn phase_started(&mut self, phase: timetabler::Phase) {
+        self.progress.lock().unwrap().phase = Some(phase);
     }
 
-    /// Called once before the first frame.
-    fn setup(
-        &mut self,
-        _ctx: &egui::Context,
-        _frame: &epi::Frame,
-        _storage: Option<&dyn epi::Storage>,
-    ) {
-        // Load previous app state (if any).
-        // Note that you must enable the `persistence` feature for this to work.
-        #[cfg(feature = "persistence")]
-        if let Some(storage) = _storage {
-            *self = epi::get_value(storage, epi::APP_KEY).unwrap_or_default()
+    fn student_placed(&mut self, _student_id: &timetabler::StudentId, placed: usize, total: usize) {
+        let mut progress = self.progress.lock().unwrap();
+        progress.placed = placed;
+        progress.total = total;
+    }
+
+    fn decision(&mut self, event: timetabler::TraceEvent) {
+        if let Some(trace) = &mut self.trace {
+            trace.push(event);
         }
     }
+}
 
-    /// Called by the frame work to save state before shutdown.
+/// Everything a finished solve gives the result screen.
+struct SolveOutcome {
+    info: timetabler::TimetableInfo,
+    result: timetabler::TimetableResult,
+    trace: Vec<timetabler::TraceEvent>,
+    relaxations: Vec<timetabler::Relaxation>,
+    violations: Vec<timetabler::Violation>,
+}
+
+/// A timetable being solved. Natively the solver runs on a thread of its own
+/// so that the window keeps responding. The web has no threads, so there it
+/// solves straight away.
+struct Solving {
+    progress: Arc<Mutex<SolveProgress>>,
+    receiver: mpsc::Receiver<SolveOutcome>,
+    /// When solving started, in seconds as `egui::InputState::time` gives.
+    started: f64,
+    /// The seed students were shuffled with, when re-solving a timetable
+    /// which was already solved. Failing then leaves the current timetable.
+    reseeded: Option<u64>,
+    cancellation: timetabler::CancellationToken,
+}
+
+impl Solving {
+    fn start(
+        info: timetabler::TimetableInfo,
+        record_trace: bool,
+        fewest_groups: bool,
+        started: f64,
+    ) -> Solving {
+        let progress = Arc::new(Mutex::new(SolveProgress {
+            total: info.students.len(),
+            ..Default::default()
+        }));
+        let (sender, receiver) = mpsc::channel();
+        let cancellation = timetabler::CancellationToken::new();
+        let solver_cancellation = cancellation.clone();
+        let mut observer = ProgressObserver {
+            progress: Arc::clone(&progress),
+            trace: record_trace.then(Vec::new),
+        };
+        let solve = move || {
+            // Solving for the fewest groups solves many times over, so it
+            // doesn't report its progress.
+            let result = if fewest_groups && !record_trace {
+                timetabler::solve_with_fewest_groups_cancellable(&info, &solver_cancellation)
+            } else {
+                timetabler::solve_timetable_cancellable(&info, &mut observer, &solver_cancellation)
+            };
+            // When solving fails, we look for what would fix it so the user
+            // has somewhere to start. The solver should keep to the rules,
+            // but anything it gets wrong is shown on the grid.
+            let (relaxations, violations) = match &result {
+                timetabler::TimetableResult::Unsolved(timetabler::TimetableError::Cancelled) => {
+                    (Vec::new(), Vec::new())
+                }
+                timetabler::TimetableResult::Unsolved(error) => (
+                    timetabler::suggest_relaxations(&info, error, &solver_cancellation),
+                    Vec::new(),
+                ),
+                timetabler::TimetableResult::Solved { .. } => {
+                    (Vec::new(), timetabler::verify(&info, &result))
+                }
+            };
+            // The window may have been closed in the meantime.
+            let _ = sender.send(SolveOutcome {
+                info,
+                result,
+                trace: observer.trace.unwrap_or_default(),
+                relaxations,
+                violations,
+            });
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        std::thread::spawn(solve);
+        #[cfg(target_arch = "wasm32")]
+        solve();
+        Solving {
+            progress,
+            receiver,
+            started,
+            reseeded: None,
+            cancellation,
+        }
+    }
+
+    /// What the solve ended with, once it has.
+    fn finished(&self) -> Option<SolveOutcome> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+impl TimetablerApp {
+    /// Forgets the students an edit undone or redone took away.
+    fn forget_missing_students(&mut self) {
+        let subjects_by_student_id = &self.inputs.subjects_by_student_id;
+        if !subjects_by_student_id.contains_key(&self.selected_student_id) {
+            self.selected_student_id = subjects_by_student_id
+                .keys()
+                .next()
+                .cloned()
+                .unwrap_or_default();
+        }
+        self.checked_student_ids
+            .retain(|student_id| subjects_by_student_id.contains_key(student_id));
+    }
+
+    /// Clears the inputs and result for a new timetable, keeping only how the
+    /// app looks and the recent projects.
+    fn reset(&mut self) {
+        let mut fresh = TimetablerApp {
+            theme: std::mem::take(&mut self.theme),
+            toasts: std::mem::take(&mut self.toasts),
+            ..Default::default()
+        };
+        #[cfg(all(feature = "project", not(target_arch = "wasm32")))]
+        {
+            fresh.recent_projects = std::mem::take(&mut self.recent_projects);
+        }
+        fresh.toasts.info("Started a new timetable");
+        *self = fresh;
+    }
+
+    /// Goes back to the result solved before the current one, which is kept
+    /// to compare with in turn.
+    fn keep_previous_result(&mut self) {
+        if let Some((info, result)) = self.previous_result.take() {
+            self.violations = timetabler::verify(&info, &result);
+            self.previous_result = self
+                .submitted_info
+                .replace(info)
+                .zip(self.result.replace(result));
+            self.trace.clear();
+            self.selected_group = None;
+            self.toasts.info("Went back to the previous result");
+        }
+    }
+
+    /// Solves the timetable again with the students shuffled by the next
+    /// seed. A timetable found replaces the current one, which is kept to
+    /// compare with.
+    fn resolve_with_new_seed(&mut self, started: f64) {
+        let mut info = match &self.submitted_info {
+            Some(info) => info.clone(),
+            None => return,
+        };
+        self.solve_seed += 1;
+        info.student_ordering = timetabler::StudentOrdering::Shuffled(self.solve_seed);
+        let mut solving = Solving::start(info, false, self.inputs.fewest_groups, started);
+        solving.reseeded = Some(self.solve_seed);
+        self.solving = Some(solving);
+        self.state = AppState::Solving;
+    }
+
+    /// Stops solving, going back to where solving was started from with
+    /// everything as it was.
+    fn cancel_solve(&mut self) {
+        let solving = match self.solving.take() {
+            Some(solving) => solving,
+            None => return,
+        };
+        solving.cancellation.cancel();
+        if solving.reseeded.is_some() {
+            self.state = AppState::Submitted;
+        } else {
+            // Solving put the last result aside to compare the new one with,
+            // so it is brought back.
+            if let Some((info, result)) = self.previous_result.take() {
+                self.submitted_info = Some(info);
+                self.result = Some(result);
+            }
+            self.state = AppState::Review;
+        }
+        self.toasts.info("Solving cancelled");
+    }
+
+    /// Shows what a solve ended with.
+    fn finish_solve(&mut self, reseeded: Option<u64>, outcome: SolveOutcome) {
+        self.state = AppState::Submitted;
+        let solved = matches!(outcome.result, timetabler::TimetableResult::Solved { .. });
+        match reseeded {
+            Some(seed) if !solved => {
+                self.toasts
+                    .error(format!("No timetable was found with seed {}", seed));
+                return;
+            }
+            Some(seed) => {
+                self.previous_result = self
+                    .submitted_info
+                    .replace(outcome.info)
+                    .zip(self.result.take());
+                self.selected_group = None;
+                self.toasts.info(format!("Solved again with seed {}", seed));
+            }
+            None if solved => self.toasts.info("Timetable solved"),
+            None => self.toasts.error("Solve failed"),
+        }
+        self.result = Some(outcome.result);
+        self.trace = outcome.trace;
+        self.relaxations = outcome.relaxations;
+        self.violations = outcome.violations;
+    }
+
+    fn undo(&mut self) {
+        if self.history.undo(&mut self.inputs) {
+            self.forget_missing_students();
+        }
+    }
+
+    fn redo(&mut self) {
+        if self.history.redo(&mut self.inputs) {
+            self.forget_missing_students();
+        }
+    }
+}
+
+#[cfg(all(feature = "project", not(target_arch = "wasm32")))]
+impl TimetablerApp {
+    fn project_dialog() -> rfd::FileDialog {
+        rfd::FileDialog::new().add_filter("Timetable project", &["json"])
+    }
+
+    fn read_project(path: &std::path::Path) -> Result<TimetablerApp, String> {
+        let json = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+        serde_json::from_str(&json).map_err(|error| error.to_string())
+    }
+
+    fn write_project(&self, path: &std::path::Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|error| error.to_string())?;
+        std::fs::write(path, json).map_err(|error| error.to_string())
+    }
+
+    fn run_project_action(&mut self, action: ProjectAction) {
+        let is_open = matches!(action, ProjectAction::Open | ProjectAction::OpenRecent(_));
+        let path = match (action, &self.project_path) {
+            (ProjectAction::Open, _) => Self::project_dialog().pick_file(),
+            (ProjectAction::OpenRecent(path), _) => Some(path),
+            (ProjectAction::Save, Some(path)) => Some(path.clone()),
+            (ProjectAction::Save | ProjectAction::SaveAs, _) => Self::project_dialog()
+                .set_file_name("timetable.json")
+                .save_file(),
+        };
+        // The dialog was cancelled.
+        let path = match path {
+            Some(path) => path,
+            None => return,
+        };
+        self.recent_projects.retain(|recent| *recent != path);
+        if is_open {
+            match Self::read_project(&path) {
+                Ok(mut project) => {
+                    // How the app looks and what was opened lately aren't
+                    // part of the project.
+                    project.theme = std::mem::take(&mut self.theme);
+                    project.recent_projects = std::mem::take(&mut self.recent_projects);
+                    project.recent_projects.insert(0, path.clone());
+                    project.recent_projects.truncate(MAX_RECENT_PROJECTS);
+                    project.toasts.info(format!("Opened {}", path.display()));
+                    project.project_path = Some(path);
+                    *self = project;
+                }
+                // A project which can't be opened is left off the recent
+                // projects list, as it has most likely been moved.
+                Err(error) => {
+                    self.toasts
+                        .error(format!("Couldn't open {}: {}", path.display(), error))
+                }
+            }
+        } else {
+            match self.write_project(&path) {
+                Ok(()) => {
+                    self.toasts.info(format!("Saved {}", path.display()));
+                    self.recent_projects.insert(0, path.clone());
+                    self.recent_projects.truncate(MAX_RECENT_PROJECTS);
+                    self.project_path = Some(path);
+                }
+                Err(error) => {
+                    self.toasts
+                        .error(format!("Couldn't save {}: {}", path.display(), error))
+                }
+            }
+        }
+    }
+}
+
+impl epi::App for TimetablerApp {
+    fn name(&self) -> &str {
+        "Timetabler"
+    }
+
+    /// Called once before the first frame.
+    fn setup(
+        &mut self,
+        _ctx: &egui::Context,
+        _frame: &epi::Frame,
+        _storage: Option<&dyn epi::Storage>,
+    ) {
+        // Load previous app state (if any).
+        // Note that you must enable the `persistence` feature for this to work.
+        #[cfg(feature = "persistence")]
+        if let Some(storage) = _storage {
+            *self = epi::get_value(storage, epi::APP_KEY).unwrap_or_default()
+        }
+    }
+
+    /// Called by the frame work to save state before shutdown.
     /// Note that you must enable the `persistence` feature for this to work.
     #[cfg(feature = "persistence")]
     fn save(&mut self, storage: &mut dyn epi::Storage) {
         epi::set_value(storage, epi::APP_KEY, self);
     }
 
-    /// Called each time the UI needs repainting, which may be many times per second.
-    /// Put your widgets into a `SidePanel`, `TopPanel`, `CentralPanel`, `Window` or `Area`.
-    fn update(&mut self, ctx: &egui::Context, _: &epi::Frame) {
-        let Self {
-            max_groups_txt,
-            max_groups,
-            daily_lesson_capacity_txt,
-            daily_lesson_capacity,
-            state,
-            subjects_by_student_id,
-            new_student_id_txt,
-            new_student_subjects_txt,
-            selected_student_id,
-            result,
-        } = self;
+    /// Called each time the UI needs repainting, which may be many times per second.
+    /// Put your widgets into a `SidePanel`, `TopPanel`, `CentralPanel`, `Window` or `Area`.
+    fn update(&mut self, ctx: &egui::Context, _: &epi::Frame) {
+        if let Some(solving) = &self.solving {
+            if let Some(outcome) = solving.finished() {
+                let reseeded = solving.reseeded;
+                self.solving = None;
+                self.finish_solve(reseeded, outcome);
+            }
+        }
+        let shortcuts = Shortcuts::read(ctx);
+        // Nothing may change while the timetable is being solved.
+        if self.solving.is_none() {
+            if shortcuts.undo {
+                self.undo();
+            } else if shortcuts.redo {
+                self.redo();
+            }
+        }
+        // Edits are timed so that those made quickly one after another are
+        // undone together.
+        let now = ctx.input().time;
+        let mut undo_clicked = false;
+        let mut redo_clicked = false;
+        let mut reset_confirmed = false;
+        let mut keep_previous = false;
+        let mut resolve_clicked = false;
+        let mut cancel_clicked = false;
+        #[cfg(all(feature = "project", not(target_arch = "wasm32")))]
+        if shortcuts.save {
+            self.project_action = Some(ProjectAction::Save);
+        }
+
+        let Self {
+            inputs,
+            max_groups_per_slot,
+            state,
+            new_student_id_txt,
+            new_student_subjects_txt,
+            editing_student_id,
+            rename_student_id_txt,
+            selected_subject,
+            new_subject_txt,
+            rename_subject_txt,
+            selected_student_id,
+            selected_teacher_id,
+            shown_term,
+            checked_student_ids,
+            confirming_delete,
+            student_filter_txt,
+            result,
+            previous_result,
+            solve_seed: _,
+            solving,
+            exams,
+            invigilation,
+            validation_issues,
+            violations,
+            selected_group,
+            record_trace,
+            trace,
+            relaxations,
+            xhstt_path,
+            #[cfg(feature = "json")]
+            config_path,
+            roster_path,
+            roster_error,
+            imported_info,
+            reviewed_info,
+            submitted_info,
+            export_dir,
+            import_error,
+            input_error,
+            toasts,
+            theme,
+            show_settings,
+            history,
+            confirming_reset,
+            #[cfg(all(feature = "project", not(target_arch = "wasm32")))]
+                project_path: _,
+            #[cfg(all(feature = "project", not(target_arch = "wasm32")))]
+            recent_projects,
+            #[cfg(all(feature = "project", not(target_arch = "wasm32")))]
+            project_action,
+            print_target,
+            #[cfg(feature = "scripting")]
+            constraint_script_path,
+            #[cfg(feature = "scripting")]
+            script_error,
+        } = self;
+
+        theme.apply(ctx);
+
+        // The print view has nothing but the page.
+        if print_target.is_none() {
+            egui::TopBottomPanel::top("menu").show(ctx, |ui| {
+                // Nothing may change while the timetable is being solved.
+                ui.set_enabled(solving.is_none());
+                ui.horizontal(|ui| {
+                    #[cfg(all(feature = "project", not(target_arch = "wasm32")))]
+                    {
+                        if ui.button("Open project").clicked() {
+                            *project_action = Some(ProjectAction::Open);
+                        }
+                        if ui.button("Save project").clicked() {
+                            *project_action = Some(ProjectAction::Save);
+                        }
+                        if ui.button("Save project as").clicked() {
+                            *project_action = Some(ProjectAction::SaveAs);
+                        }
+                    }
+                    if ui
+                        .add_enabled(!history.undo.is_empty(), egui::Button::new("Undo"))
+                        .clicked()
+                    {
+                        undo_clicked = true;
+                    }
+                    if ui
+                        .add_enabled(!history.redo.is_empty(), egui::Button::new("Redo"))
+                        .clicked()
+                    {
+                        redo_clicked = true;
+                    }
+                    if ui.button("Settings").clicked() {
+                        *show_settings = !*show_settings;
+                    }
+                });
+
+                // Steps already done can be gone back to.
+                let current_step = state.wizard_step();
+                ui.horizontal(|ui| {
+                    for (step, name) in WIZARD_STEPS.iter().enumerate() {
+                        if step > 0 {
+                            ui.label("→");
+                        }
+                        let label = egui::SelectableLabel::new(
+                            step == current_step,
+                            format!("{}. {}", step + 1, name),
+                        );
+                        if ui.add_enabled(step <= current_step, label).clicked()
+                            && step < current_step
+                        {
+                            *input_error = None;
+                            *state = match step {
+                                0 => AppState::GeneralConfig,
+                                1 => AppState::StudentConfig(false),
+                                _ => AppState::Review,
+                            };
+                        }
+                    }
+                });
+            });
+        }
+
+        egui::Window::new("Settings")
+            .open(show_settings)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut theme.light_mode, false, "Dark");
+                    ui.radio_value(&mut theme.light_mode, true, "Light");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Accent colour: ");
+                    ui.color_edit_button_srgb(&mut theme.accent_color);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Density: ");
+                    ui.radio_value(&mut theme.density, Density::Compact, "Compact");
+                    ui.radio_value(&mut theme.density, Density::Normal, "Normal");
+                    ui.radio_value(&mut theme.density, Density::Roomy, "Roomy");
+                });
+                if ui.button("Reset").clicked() {
+                    *theme = ThemeSettings::default();
+                }
+            });
+
+        *state = match &*state {
+            AppState::GeneralConfig => {
+                let mut new_state = AppState::GeneralConfig;
+
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    #[cfg(all(feature = "project", not(target_arch = "wasm32")))]
+                    if !recent_projects.is_empty() {
+                        ui.heading("Recent projects");
+                        for path in recent_projects.iter() {
+                            // egui has no links yet, so a label which
+                            // senses clicks stands in for one.
+                            let link = egui::Label::new(path.display().to_string())
+                                .sense(egui::Sense::click());
+                            if ui.add(link).clicked() {
+                                *project_action = Some(ProjectAction::OpenRecent(path.clone()));
+                            }
+                        }
+                        ui.separator();
+                    }
+
+                    ui.heading("General Configuration");
+
+                    ui.horizontal(|ui| {
+                        ui.label("Max groups: ");
+                        let mut value = inputs.max_groups;
+                        if ui
+                            .add(egui::DragValue::new(&mut value).clamp_range(MAX_GROUPS_RANGE))
+                            .changed()
+                        {
+                            history.apply(inputs, Edit::MaxGroups(value), now);
+                        }
+                        // Suggestions need students, which may have been
+                        // imported or kept from last time.
+                        if ui
+                            .add_enabled(
+                                !inputs.subjects_by_student_id.is_empty(),
+                                egui::Button::new("Suggest"),
+                            )
+                            .clicked()
+                        {
+                            // Curricula may bring in subjects nobody typed,
+                            // which are registered as their limits are set.
+                            let mut expanded = inputs.subjects.clone();
+                            let suggested = timetabler::suggest_max_groups(
+                                &student_infos(
+                                    &inputs.subjects_by_student_id,
+                                    &inputs.curricula_txt,
+                                    &mut expanded,
+                                ),
+                                TARGET_GROUP_SIZE,
+                            );
+                            let mut limits: BTreeMap<_, _> = inputs
+                                .subjects
+                                .ids()
+                                .map(|subject| (subject, None))
+                                .collect();
+                            for (subject, groups) in suggested {
+                                let subject = inputs.subjects.register(expanded.name(subject));
+                                let groups =
+                                    groups.clamp(*MAX_GROUPS_RANGE.start(), *MAX_GROUPS_RANGE.end());
+                                limits.insert(subject, Some(groups));
+                            }
+                            let mut edits: Vec<Edit> = limits
+                                .iter()
+                                .map(|(&subject, &groups)| Edit::SubjectMaxGroups(subject, groups))
+                                .collect();
+                            if let Some(most) = limits.values().flatten().max() {
+                                edits.push(Edit::MaxGroups(*most));
+                            }
+                            history.apply(inputs, Edit::Batch(edits), now);
+                        }
+                    });
+                    let limits: Vec<String> = inputs
+                        .subjects
+                        .iter()
+                        .filter_map(|subject| {
+                            let groups = subject.max_groups?;
+                            Some(format!("{} {}", subject.name(), groups))
+                        })
+                        .collect();
+                    if !limits.is_empty() {
+                        ui.horizontal(|ui| {
+                            ui.label(format!(
+                                "Max groups for up to {} students each: {}",
+                                TARGET_GROUP_SIZE,
+                                limits.join(", ")
+                            ));
+                            if ui.button("Clear").clicked() {
+                                let edits = inputs
+                                    .subjects
+                                    .ids()
+                                    .map(|subject| Edit::SubjectMaxGroups(subject, None))
+                                    .collect();
+                                history.apply(inputs, Edit::Batch(edits), now);
+                            }
+                        });
+                    }
+
+                    ui.horizontal(|ui| {
+                        ui.label("Daily lesson capacity: ");
+                        let mut value = inputs.daily_lesson_capacity;
+                        if ui
+                            .add(
+                                egui::DragValue::new(&mut value)
+                                    .clamp_range(DAILY_LESSON_CAPACITY_RANGE),
+                            )
+                            .changed()
+                        {
+                            history.apply(inputs, Edit::DailyLessonCapacity(value), now);
+                        }
+                        if ui
+                            .add_enabled(
+                                !inputs.subjects_by_student_id.is_empty(),
+                                egui::Button::new("Suggest"),
+                            )
+                            .clicked()
+                        {
+                            // Subjects which weren't imported have one lesson a
+                            // week.
+                            let mut expanded = inputs.subjects.clone();
+                            let cycle = if inputs.fortnightly {
+                                timetabler::WeekCycle::Fortnightly
+                            } else {
+                                timetabler::WeekCycle::Weekly
+                            };
+                            let suggested = timetabler::suggest_daily_lesson_capacity(
+                                &student_infos(
+                                    &inputs.subjects_by_student_id,
+                                    &inputs.curricula_txt,
+                                    &mut expanded,
+                                ),
+                                &expanded,
+                                cycle,
+                            )
+                            .clamp(
+                                *DAILY_LESSON_CAPACITY_RANGE.start(),
+                                *DAILY_LESSON_CAPACITY_RANGE.end(),
+                            );
+                            history.apply(inputs, Edit::DailyLessonCapacity(suggested), now);
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Enter max groups at once (optional): ");
+                        let mut txt = inputs.max_groups_per_slot_txt.clone();
+                        if ui.text_edit_singleline(&mut txt).changed() {
+                            history.apply(inputs, Edit::MaxGroupsPerSlotTxt(txt), now);
+                        }
+                    });
+
+                    let mut fortnightly = inputs.fortnightly;
+                    if ui
+                        .checkbox(&mut fortnightly, "Fortnightly (week A and week B)")
+                        .changed()
+                    {
+                        history.apply(inputs, Edit::Fortnightly(fortnightly), now);
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Terms: ");
+                        let mut value = inputs.terms;
+                        if ui
+                            .add(egui::DragValue::new(&mut value).clamp_range(TERMS_RANGE))
+                            .changed()
+                        {
+                            history.apply(inputs, Edit::Terms(value), now);
+                        }
+                    });
+                    let mut exhaustive = inputs.exhaustive;
+                    if ui
+                        .checkbox(
+                            &mut exhaustive,
+                            "Search exhaustively for the best timetable (up to around 10 students)",
+                        )
+                        .changed()
+                    {
+                        history.apply(inputs, Edit::Exhaustive(exhaustive), now);
+                    }
+                    let mut fewest_groups = inputs.fewest_groups;
+                    if ui
+                        .checkbox(
+                            &mut fewest_groups,
+                            "Use as few groups as possible (solves many times over)",
+                        )
+                        .changed()
+                    {
+                        history.apply(inputs, Edit::FewestGroups(fewest_groups), now);
+                    }
+                    let mut exam_mode = inputs.exam_mode;
+                    if ui
+                        .checkbox(
+                            &mut exam_mode,
+                            "Schedule one exam per subject instead of lessons",
+                        )
+                        .changed()
+                    {
+                        history.apply(inputs, Edit::ExamMode(exam_mode), now);
+                    }
+                    ui.checkbox(record_trace, "Record the solver's decisions");
+
+                    ui.horizontal(|ui| {
+                        ui.label("Subject aliases, e.g. Math=Maths (optional): ");
+                        let mut txt = inputs.subject_aliases_txt.clone();
+                        if ui.text_edit_singleline(&mut txt).changed() {
+                            history.apply(inputs, Edit::SubjectAliasesTxt(txt), now);
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label(
+                            "Curricula, e.g. Year 10 core=English,Maths,Science,PE (optional): ",
+                        );
+                        let mut txt = inputs.curricula_txt.clone();
+                        if ui.text_edit_singleline(&mut txt).changed() {
+                            history.apply(inputs, Edit::CurriculaTxt(txt), now);
+                        }
+                    });
+
+                    #[cfg(feature = "scripting")]
+                    ui.horizontal(|ui| {
+                        ui.label("Constraint script (optional): ");
+                        ui.text_edit_singleline(constraint_script_path);
+                    });
+
+                    // Both kinds of file are loaded the same way once read.
+                    let mut loaded = None;
+                    ui.horizontal(|ui| {
+                        ui.label("XHSTT file to import (optional): ");
+                        ui.text_edit_singleline(xhstt_path);
+                        if ui.button("Import").clicked() {
+                            loaded = Some(
+                                timetabler::from_xhstt_file(xhstt_path.trim())
+                                    .map_err(timetabler::ImportError::from),
+                            );
+                        }
+                    });
+                    #[cfg(feature = "json")]
+                    ui.horizontal(|ui| {
+                        ui.label("JSON configuration file: ");
+                        ui.text_edit_singleline(config_path);
+                        if ui.button("Import").clicked() {
+                            loaded = Some(
+                                timetabler::from_json_file(config_path.trim())
+                                    .map_err(timetabler::ImportError::from),
+                            );
+                        }
+                        // Students are entered on the next screen, so this
+                        // exports what was entered before coming back here.
+                        if ui.button("Export").clicked() {
+                            let parsed = parse_max_groups_per_slot(&inputs.max_groups_per_slot_txt)
+                                .and_then(|new_max_groups_per_slot| {
+                                    let curricula =
+                                        timetabler::Curricula::parse(&inputs.curricula_txt)
+                                            .map_err(InputError::Parse)?;
+                                    let aliases = timetabler::SubjectAliases::parse(
+                                        &inputs.subject_aliases_txt,
+                                    )
+                                    .map_err(InputError::Parse)?;
+                                    Ok((new_max_groups_per_slot, curricula, aliases))
+                                });
+                            match parsed {
+                                Ok((new_max_groups_per_slot, curricula, aliases)) => {
+                                    let mut info = entered_info(
+                                        imported_info.as_ref(),
+                                        inputs,
+                                        new_max_groups_per_slot,
+                                    );
+                                    curricula.apply(&mut info);
+                                    aliases.apply(&mut info);
+                                    match timetabler::to_json_file(&info, config_path.trim()) {
+                                        Ok(()) => {
+                                            toasts.info(format!("Exported {}", config_path.trim()));
+                                            *import_error = None;
+                                        }
+                                        Err(error) => {
+                                            toasts.error("Export failed");
+                                            *import_error = Some(error.into());
+                                        }
+                                    }
+                                    *input_error = None;
+                                }
+                                Err(error) => *input_error = Some(error),
+                            }
+                        }
+                    });
+                    match loaded {
+                        Some(Ok(info)) => {
+                            // The imported subjects replace those entered,
+                            // keeping any made on the subjects screen.
+                            let mut subjects = info.subjects.clone();
+                            let subject_list = inputs
+                                .subject_list
+                                .iter()
+                                .map(|&subject| subjects.register(inputs.subjects.name(subject)))
+                                .collect();
+                            let imported = Inputs {
+                                max_groups: info.max_groups,
+                                daily_lesson_capacity: info.daily_lesson_capacity,
+                                max_groups_per_slot_txt: info
+                                    .max_groups_per_slot
+                                    .map(|max| max.to_string())
+                                    .unwrap_or_default(),
+                                fortnightly: info.cycle == timetabler::WeekCycle::Fortnightly,
+                                terms: info.terms,
+                                exhaustive: info.algorithm == timetabler::Algorithm::Exhaustive,
+                                fewest_groups: inputs.fewest_groups,
+                                exam_mode: inputs.exam_mode,
+                                subjects_by_student_id: info
+                                    .students
+                                    .iter()
+                                    .map(|student| {
+                                        (student.id().clone(), student.subjects().to_vec())
+                                    })
+                                    .collect(),
+                                subjects,
+                                subject_list,
+                                subject_aliases_txt: inputs.subject_aliases_txt.clone(),
+                                curricula_txt: inputs.curricula_txt.clone(),
+                            };
+                            history.apply(inputs, Edit::All(Box::new(imported)), now);
+                            *max_groups_per_slot = info.max_groups_per_slot;
+                            *selected_subject = None;
+                            *selected_student_id = inputs
+                                .subjects_by_student_id
+                                .keys()
+                                .next()
+                                .cloned()
+                                .unwrap_or_default();
+                            toasts.info(format!(
+                                "Imported {} students",
+                                inputs.subjects_by_student_id.len()
+                            ));
+                            *imported_info = Some(info);
+                            *import_error = None;
+                        }
+                        Some(Err(error)) => {
+                            toasts.error("Import failed");
+                            *import_error = Some(error);
+                        }
+                        None => {}
+                    }
+                    if let Some(error) = import_error {
+                        ui.colored_label(egui::Color32::RED, sentence(error));
+                    }
+                    // The import is kept between runs, so it is always shown
+                    // while it is being used.
+                    let mut clear_import = false;
+                    if let Some(info) = imported_info {
+                        ui.horizontal(|ui| {
+                            ui.label(format!(
+                                "Using the {} subjects, {} rooms, {} teachers and other settings imported",
+                                info.subjects.len(),
+                                info.rooms.len(),
+                                info.teachers.len()
+                            ));
+                            clear_import = ui
+                                .button("Stop using")
+                                .on_hover_text("Students and settings on these screens are kept")
+                                .clicked();
+                        });
+                    }
+                    if clear_import {
+                        *imported_info = None;
+                        // Subjects keep only what can be set in the app.
+                        for subject in inputs.subjects.iter_mut() {
+                            let mut kept = timetabler::SubjectInfo::new(subject.name());
+                            kept.term = subject.term;
+                            kept.max_groups = subject.max_groups;
+                            *subject = kept;
+                        }
+                        toasts.info("Stopped using the import");
+                    }
+
+                    if ui.button("Next").clicked() {
+                        match parse_max_groups_per_slot(&inputs.max_groups_per_slot_txt) {
+                            Ok(new_max_groups_per_slot) => {
+                                *max_groups_per_slot = new_max_groups_per_slot;
+                                *input_error = None;
+                                new_state = AppState::StudentConfig(false);
+                            }
+                            Err(error) => *input_error = Some(error),
+                        }
+                    }
+                    if let Some(error) = input_error {
+                        ui.colored_label(egui::Color32::RED, sentence(error));
+                    }
+                });
+
+                new_state
+            }
+            AppState::StudentConfig(is_creating) => {
+                let mut new_state = AppState::StudentConfig(*is_creating);
+
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    ui.heading("Student Configuration");
+
+                    // The entered students are kept, so going back and
+                    // forward again doesn't lose them.
+                    if ui.button("Back").clicked() {
+                        *input_error = None;
+                        new_state = AppState::GeneralConfig;
+                    }
+
+                    if ui.button("Manage subjects").clicked() && !*is_creating {
+                        *input_error = None;
+                        new_state = AppState::SubjectConfig;
+                    }
+
+                    // The last result is kept while the students are changed,
+                    // so it can be looked at again without solving.
+                    if (result.is_some() || exams.is_some())
+                        && ui.button("View last result").clicked()
+                        && !*is_creating
+                    {
+                        *input_error = None;
+                        new_state = AppState::Submitted;
+                    }
+
+                    ui.horizontal(|ui| {
+                        ui.label("Search by ID or subject: ");
+                        ui.text_edit_singleline(student_filter_txt);
+                    });
+
+                    // Ticking students picks them for bulk actions, while
+                    // clicking a student's ID selects them on their own.
+                    egui::ScrollArea::vertical()
+                        .max_height(200.0)
+                        .show(ui, |ui| {
+                            for (student_id, taken) in &inputs.subjects_by_student_id {
+                                if !matches_filter(
+                                    student_id,
+                                    taken,
+                                    &inputs.subjects,
+                                    student_filter_txt,
+                                ) {
+                                    continue;
+                                }
+                                ui.horizontal(|ui| {
+                                    let mut checked = checked_student_ids.contains(student_id);
+                                    if ui.checkbox(&mut checked, "").changed() {
+                                        if checked {
+                                            checked_student_ids.insert(student_id.clone());
+                                        } else {
+                                            checked_student_ids.remove(student_id);
+                                        }
+                                    }
+                                    ui.selectable_value(
+                                        selected_student_id,
+                                        student_id.clone(),
+                                        student_id.as_str(),
+                                    );
+                                });
+                            }
+                        });
+
+                    ui.horizontal(|ui| {
+                        if ui.button("Tick all shown").clicked() {
+                            for (student_id, taken) in &inputs.subjects_by_student_id {
+                                if matches_filter(
+                                    student_id,
+                                    taken,
+                                    &inputs.subjects,
+                                    student_filter_txt,
+                                ) {
+                                    checked_student_ids.insert(student_id.clone());
+                                }
+                            }
+                        }
+                        if ui.button("Untick all").clicked() {
+                            checked_student_ids.clear();
+                        }
+                        if ui
+                            .add_enabled(
+                                !checked_student_ids.is_empty() && !*is_creating,
+                                egui::Button::new(format!(
+                                    "Delete {} ticked",
+                                    checked_student_ids.len()
+                                )),
+                            )
+                            .clicked()
+                        {
+                            let deleted = std::mem::take(checked_student_ids);
+                            toasts.info(format!("Deleted {} students", deleted.len()));
+                            let edits = deleted
+                                .into_iter()
+                                .map(|student_id| Edit::Student(student_id, None))
+                                .collect();
+                            history.apply(inputs, Edit::Batch(edits), now);
+                            let subjects_by_student_id = &inputs.subjects_by_student_id;
+                            if !subjects_by_student_id.contains_key(selected_student_id) {
+                                *selected_student_id = match subjects_by_student_id.keys().next() {
+                                    Some(id) => id.clone(),
+                                    None => timetabler::StudentId::default(),
+                                }
+                            }
+                        }
+                    });
+
+                    if (ui.button("Add student").clicked() || shortcuts.new_student)
+                        && !*is_creating
+                    {
+                        new_state = AppState::StudentConfig(true);
+                        new_student_id_txt.clear();
+                        new_student_subjects_txt.clear();
+                        *editing_student_id = None;
+                        *input_error = None;
+                    }
+
+                    if ui.button("Add random students").clicked() && !*is_creating {
+                        // We vary the seed so that pressing the button again
+                        // adds different students.
+                        let school =
+                            timetabler::generate::school(&timetabler::generate::SchoolConfig {
+                                students: 30,
+                                seed: inputs.subjects_by_student_id.len() as u64 + 1,
+                                ..Default::default()
+                            });
+                        // The generated IDs restart from 1 each time, so we
+                        // number the students after any already added.
+                        let mut number = inputs.subjects_by_student_id.len();
+                        let mut edits = Vec::new();
+                        for student in &school.students {
+                            let id = loop {
+                                number += 1;
+                                let id = format!("Student {}", number);
+                                if !inputs.subjects_by_student_id.contains_key(id.as_str()) {
+                                    break id;
+                                }
+                            };
+                            let taken = student
+                                .subjects()
+                                .iter()
+                                .map(|&subject| {
+                                    inputs.subjects.register(school.subjects.name(subject))
+                                })
+                                .collect();
+                            edits.push(Edit::Student(id.into(), Some(taken)));
+                        }
+                        history.apply(inputs, Edit::Batch(edits), now);
+                        toasts.info("Added 30 random students");
+                        if selected_student_id.as_str().is_empty() {
+                            if let Some(id) = inputs.subjects_by_student_id.keys().next() {
+                                *selected_student_id = id.clone();
+                            }
+                        }
+                    }
+
+                    // Imported students are added to those already entered,
+                    // replacing any with the same ID.
+                    ui.horizontal(|ui| {
+                        ui.label("CSV of students, with an ID then subjects on each row: ");
+                        ui.text_edit_singleline(roster_path);
+                        if ui.button("Import CSV").clicked() && !*is_creating {
+                            match timetabler::read_roster_file(roster_path.trim()) {
+                                Ok(roster) => {
+                                    let known = known_subjects(
+                                        &inputs.subject_list,
+                                        &inputs.subjects_by_student_id,
+                                    );
+                                    let imported = roster.len();
+                                    let edits = roster
+                                        .into_iter()
+                                        .map(|(student_id, taken)| {
+                                            let taken = match_known_subjects(
+                                                taken,
+                                                &known,
+                                                &mut inputs.subjects,
+                                            );
+                                            Edit::Student(student_id, Some(taken))
+                                        })
+                                        .collect();
+                                    history.apply(inputs, Edit::Batch(edits), now);
+                                    toasts.info(format!("Imported {} students", imported));
+                                    if selected_student_id.as_str().is_empty() {
+                                        if let Some(id) =
+                                            inputs.subjects_by_student_id.keys().next()
+                                        {
+                                            *selected_student_id = id.clone();
+                                        }
+                                    }
+                                    *roster_error = None;
+                                }
+                                Err(error) => {
+                                    toasts.error("Import failed");
+                                    *roster_error = Some(sentence(&error));
+                                }
+                            }
+                        }
+                    });
+                    if let Some(error) = roster_error {
+                        ui.colored_label(egui::Color32::RED, error.as_str());
+                    }
+
+                    if !selected_student_id.as_str().is_empty() {
+                        ui.label(format!(
+                            "Subjects: {}",
+                            subject_names(
+                                &inputs.subjects,
+                                &inputs.subjects_by_student_id[selected_student_id]
+                            )
+                            .join(",")
+                        ));
+
+                        if ui.button("Edit").clicked() && !*is_creating {
+                            new_state = AppState::StudentConfig(true);
+                            *new_student_subjects_txt = subject_names(
+                                &inputs.subjects,
+                                &inputs.subjects_by_student_id[selected_student_id],
+                            )
+                            .join(", ");
+                            *editing_student_id = Some(selected_student_id.clone());
+                            *input_error = None;
+                        }
+
+                        ui.horizontal(|ui| {
+                            ui.text_edit_singleline(rename_student_id_txt);
+                            if ui.button("Rename").clicked() && !*is_creating {
+                                let student_id = rename_student_id_txt.trim();
+                                if student_id.is_empty() {
+                                    *input_error = Some(InputError::NoStudentId);
+                                } else if inputs.subjects_by_student_id.contains_key(student_id) {
+                                    *input_error =
+                                        Some(InputError::DuplicateStudentId(student_id.into()));
+                                } else {
+                                    let taken =
+                                        inputs.subjects_by_student_id[selected_student_id].clone();
+                                    if checked_student_ids.remove(selected_student_id) {
+                                        checked_student_ids.insert(student_id.into());
+                                    }
+                                    let edits = vec![
+                                        Edit::Student(selected_student_id.clone(), None),
+                                        Edit::Student(student_id.into(), Some(taken)),
+                                    ];
+                                    history.apply(inputs, Edit::Batch(edits), now);
+                                    *selected_student_id = student_id.into();
+                                    rename_student_id_txt.clear();
+                                    *input_error = None;
+                                    toasts.info("Student renamed");
+                                }
+                            }
+                        });
+                        // The create window shows its own errors.
+                        if !*is_creating {
+                            if let Some(error) = input_error {
+                                ui.colored_label(egui::Color32::RED, sentence(error));
+                            }
+                        }
+
+                        // Many students take the same subjects, so this saves
+                        // typing them all again.
+                        if ui.button("Duplicate").clicked() && !*is_creating {
+                            new_state = AppState::StudentConfig(true);
+                            new_student_id_txt.clear();
+                            *new_student_subjects_txt = subject_names(
+                                &inputs.subjects,
+                                &inputs.subjects_by_student_id[selected_student_id],
+                            )
+                            .join(", ");
+                            *editing_student_id = None;
+                            *input_error = None;
+                        }
+
+                        if ui.button("Delete").clicked() && !*is_creating {
+                            *confirming_delete = true;
+                        }
+
+                        // There is at least 1 student.
+                        if ui.button("Submit").clicked() || (shortcuts.solve && !*is_creating) {
+                            // Curricula are expanded first so that their subjects
+                            // are normalized along with the rest, and subjects
+                            // typed in slightly different ways would otherwise be
+                            // timetabled separately.
+                            let parsed = timetabler::Curricula::parse(&inputs.curricula_txt)
+                                .and_then(|curricula| {
+                                    Ok((
+                                        curricula,
+                                        timetabler::SubjectAliases::parse(
+                                            &inputs.subject_aliases_txt,
+                                        )?,
+                                    ))
+                                });
+                            match parsed {
+                                Err(error) => *input_error = Some(InputError::Parse(error)),
+                                Ok((curricula, aliases)) => {
+                                    *input_error = None;
+                                    let mut info = entered_info(
+                                        imported_info.as_ref(),
+                                        inputs,
+                                        *max_groups_per_slot,
+                                    );
+                                    curricula.apply(&mut info);
+                                    aliases.apply(&mut info);
+                                    #[cfg(feature = "scripting")]
+                                    {
+                                        *script_error = None;
+                                        if !constraint_script_path.trim().is_empty() {
+                                            match timetabler::ScriptConstraint::from_file(
+                                                constraint_script_path.trim(),
+                                            ) {
+                                                Ok(script) => info
+                                                    .constraints
+                                                    .push(std::sync::Arc::new(script)),
+                                                Err(error) => {
+                                                    *script_error = Some(sentence(&error))
+                                                }
+                                            }
+                                        }
+                                    }
+                                    // The problems found are listed on the review
+                                    // screen, which only solves once there are none.
+                                    *validation_issues = timetabler::validate(&info);
+                                    *reviewed_info = Some(info);
+                                    new_state = AppState::Review;
+                                }
+                            }
+                        }
+                    }
+                });
+
+                if *is_creating && matches!(new_state, AppState::StudentConfig(_)) {
+                    let known =
+                        known_subjects(&inputs.subject_list, &inputs.subjects_by_student_id);
+                    let title = match editing_student_id {
+                        Some(_) => "Edit student",
+                        None => "Create student",
+                    };
+                    egui::Window::new(title).show(ctx, |ui| {
+                        match editing_student_id {
+                            Some(student_id) => {
+                                ui.label(format!("ID: {}", student_id));
+                            }
+                            None => {
+                                ui.horizontal(|ui| {
+                                    ui.label("Enter ID: ");
+                                    ui.text_edit_singleline(new_student_id_txt);
+                                });
+                            }
+                        }
+
+                        ui.horizontal(|ui| {
+                            ui.label("Enter subjects or curricula (comma separated): ");
+                            ui.text_edit_singleline(new_student_subjects_txt);
+                        });
+
+                        // Clicking a completion replaces what is being typed
+                        // with it.
+                        let completions =
+                            subject_completions(&inputs.subjects, &known, new_student_subjects_txt);
+                        if !completions.is_empty() {
+                            ui.horizontal(|ui| {
+                                for subject in completions {
+                                    if ui.small_button(subject).clicked() {
+                                        let kept = match new_student_subjects_txt.rfind(',') {
+                                            Some(idx) => &new_student_subjects_txt[..=idx],
+                                            None => "",
+                                        };
+                                        *new_student_subjects_txt = format!(
+                                            "{}{}{}, ",
+                                            kept,
+                                            if kept.is_empty() { "" } else { " " },
+                                            subject
+                                        );
+                                    }
+                                }
+                            });
+                        }
+
+                        ui.horizontal(|ui| {
+                            let add_button = ui.button(match editing_student_id {
+                                Some(_) => "Save",
+                                None => "Add",
+                            });
+                            let cancel_button = ui.button("Cancel");
+                            if add_button.clicked() || shortcuts.confirm {
+                                let student_id = match &*editing_student_id {
+                                    Some(student_id) => student_id.as_str(),
+                                    None => new_student_id_txt.trim(),
+                                };
+                                let taken = if student_id.is_empty() {
+                                    Err(InputError::NoStudentId)
+                                } else if editing_student_id.is_none()
+                                    && inputs.subjects_by_student_id.contains_key(student_id)
+                                {
+                                    Err(InputError::DuplicateStudentId(student_id.into()))
+                                } else {
+                                    parse_subjects(new_student_subjects_txt).map(|taken| {
+                                        match_known_subjects(taken, &known, &mut inputs.subjects)
+                                    })
+                                };
+                                match taken {
+                                    Ok(taken) => {
+                                        let student_id = timetabler::StudentId::from(student_id);
+                                        history.apply(
+                                            inputs,
+                                            Edit::Student(student_id.clone(), Some(taken)),
+                                            now,
+                                        );
+                                        toasts.info(match editing_student_id {
+                                            Some(_) => "Student saved",
+                                            None => "Student added",
+                                        });
+                                        *selected_student_id = student_id;
+                                        *editing_student_id = None;
+                                        *input_error = None;
+                                        new_state = AppState::StudentConfig(false);
+                                    }
+                                    Err(error) => *input_error = Some(error),
+                                }
+                            } else if cancel_button.clicked() || shortcuts.cancel {
+                                // We do else if so we don't handle both being
+                                // clicked in the same frame, which would be
+                                // bad.
+                                *editing_student_id = None;
+                                *input_error = None;
+                                new_state = AppState::StudentConfig(false);
+                            }
+                        });
+                        if let Some(error) = input_error {
+                            ui.colored_label(egui::Color32::RED, sentence(error));
+                        }
+                    });
+                }
+
+                if *confirming_delete
+                    && matches!(new_state, AppState::StudentConfig(false))
+                    && inputs
+                        .subjects_by_student_id
+                        .contains_key(selected_student_id)
+                {
+                    egui::Window::new("Delete student")
+                        .collapsible(false)
+                        .resizable(false)
+                        .show(ctx, |ui| {
+                            ui.label(format!(
+                                "Delete {}, who takes {} subjects?",
+                                selected_student_id,
+                                inputs.subjects_by_student_id[selected_student_id].len()
+                            ));
+                            ui.horizontal(|ui| {
+                                let delete_button = ui.button("Delete");
+                                let cancel_button = ui.button("Cancel");
+                                if delete_button.clicked() || shortcuts.confirm {
+                                    history.apply(
+                                        inputs,
+                                        Edit::Student(selected_student_id.clone(), None),
+                                        now,
+                                    );
+                                    checked_student_ids.remove(selected_student_id);
+                                    // We change the selected student ID since the current one doesn't exist anymore.
+                                    *selected_student_id =
+                                        match inputs.subjects_by_student_id.keys().next() {
+                                            Some(id) => id.clone(),
+                                            None => timetabler::StudentId::default(),
+                                        };
+                                    *confirming_delete = false;
+                                    toasts.info("Student deleted");
+                                } else if cancel_button.clicked() || shortcuts.cancel {
+                                    *confirming_delete = false;
+                                }
+                            });
+                        });
+                } else {
+                    *confirming_delete = false;
+                }
 
-        *state = match &*state {
-            AppState::GeneralConfig => {
-                let mut new_state = AppState::GeneralConfig;
+                new_state
+            }
+            AppState::SubjectConfig => {
+                let mut new_state = AppState::SubjectConfig;
 
                 egui::CentralPanel::default().show(ctx, |ui| {
-                    ui.heading("General Configuration");
+                    ui.heading("Subjects");
 
-                    ui.horizontal(|ui| {
-                        ui.label("Enter max groups: ");
-                        ui.text_edit_singleline(max_groups_txt);
-                    });
+                    if ui.button("Back").clicked() {
+                        *input_error = None;
+                        new_state = AppState::StudentConfig(false);
+                    }
+
+                    let known =
+                        known_subjects(&inputs.subject_list, &inputs.subjects_by_student_id);
+                    let mut listed: Vec<timetabler::SubjectId> = known.iter().copied().collect();
+                    listed.sort_by_key(|&subject| inputs.subjects.name(subject));
+                    egui::ScrollArea::vertical()
+                        .max_height(200.0)
+                        .show(ui, |ui| {
+                            for subject in listed {
+                                let students = inputs
+                                    .subjects_by_student_id
+                                    .values()
+                                    .filter(|taken| taken.contains(&subject))
+                                    .count();
+                                ui.selectable_value(
+                                    selected_subject,
+                                    Some(subject),
+                                    format!(
+                                        "{} ({} students)",
+                                        inputs.subjects.name(subject),
+                                        students
+                                    ),
+                                );
+                            }
+                        });
 
                     ui.horizontal(|ui| {
-                        ui.label("Enter daily lesson capacity: ");
-                        ui.text_edit_singleline(daily_lesson_capacity_txt);
+                        ui.label("New subject: ");
+                        ui.text_edit_singleline(new_subject_txt);
+                        if ui.button("Add").clicked() {
+                            let name = new_subject_txt.trim();
+                            if name.is_empty() {
+                                *input_error = Some(InputError::NoSubjectName);
+                            } else if known
+                                .iter()
+                                .any(|&subject| inputs.subjects.name(subject) == name)
+                            {
+                                *input_error = Some(InputError::DuplicateSubject(name.to_string()));
+                            } else {
+                                let subject = inputs.subjects.register(name);
+                                history.apply(inputs, Edit::ListSubject(subject, true), now);
+                                *selected_subject = Some(subject);
+                                toasts.info("Subject added");
+                                new_subject_txt.clear();
+                                *input_error = None;
+                            }
+                        }
                     });
 
-                    if ui.button("Next").clicked()
-                        && match (max_groups_txt.parse(), daily_lesson_capacity_txt.parse()) {
-                            (Ok(new_max_groups), Ok(new_daily_lesson_capacity)) => {
-                                if new_max_groups > 0 && new_daily_lesson_capacity > 0 {
-                                    *max_groups = Some(new_max_groups);
-                                    *daily_lesson_capacity = Some(new_daily_lesson_capacity);
-                                    true
+                    if let Some(selected) =
+                        selected_subject.filter(|subject| known.contains(subject))
+                    {
+                        ui.horizontal(|ui| {
+                            ui.text_edit_singleline(rename_subject_txt);
+                            if ui.button("Rename").clicked() {
+                                let name = rename_subject_txt.trim();
+                                if name.is_empty() {
+                                    *input_error = Some(InputError::NoSubjectName);
                                 } else {
-                                    false
+                                    match inputs.subjects.id(name) {
+                                        // Renaming to a subject which already
+                                        // exists merges the two, which fixes
+                                        // subjects typed two ways.
+                                        Some(into) if into != selected => {
+                                            let subjects = &inputs.subjects;
+                                            let mut edits = merge_subject(
+                                                &inputs.subjects_by_student_id,
+                                                selected,
+                                                into,
+                                            );
+                                            if inputs.subject_list.contains(&selected) {
+                                                edits.push(Edit::ListSubject(selected, false));
+                                                edits.push(Edit::ListSubject(into, true));
+                                            }
+                                            if subjects[into].term.is_none() {
+                                                edits.push(Edit::SubjectTerm(
+                                                    into,
+                                                    subjects[selected].term,
+                                                ));
+                                            }
+                                            if subjects[into].max_groups.is_none() {
+                                                edits.push(Edit::SubjectMaxGroups(
+                                                    into,
+                                                    subjects[selected].max_groups,
+                                                ));
+                                            }
+                                            history.apply(inputs, Edit::Batch(edits), now);
+                                            *selected_subject = Some(into);
+                                        }
+                                        _ => history.apply(
+                                            inputs,
+                                            Edit::RenameSubject(selected, name.to_string()),
+                                            now,
+                                        ),
+                                    }
+                                    rename_subject_txt.clear();
+                                    toasts.info("Subject renamed");
+                                    *input_error = None;
                                 }
                             }
-                            _ => false,
+                        });
+
+                        // Subjects which only last part of the year can share
+                        // slots with those in other terms.
+                        if inputs.terms > 1 {
+                            let term = inputs.subjects[selected].term;
+                            let mut chosen = term;
+                            egui::ComboBox::from_label("Runs in")
+                                .selected_text(match term {
+                                    Some(term) => format!("Term {}", term + 1),
+                                    None => "Every term".to_string(),
+                                })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut chosen, None, "Every term");
+                                    for term in 0..inputs.terms {
+                                        ui.selectable_value(
+                                            &mut chosen,
+                                            Some(term),
+                                            format!("Term {}", term + 1),
+                                        );
+                                    }
+                                });
+                            if chosen != term {
+                                history.apply(inputs, Edit::SubjectTerm(selected, chosen), now);
+                            }
                         }
-                    {
-                        new_state = AppState::StudentConfig(false);
+
+                        if ui.button("Delete").clicked() {
+                            let only_taker = inputs
+                                .subjects_by_student_id
+                                .iter()
+                                .find(|(_, taken)| taken.as_slice() == [selected]);
+                            match only_taker {
+                                Some((student_id, _)) => {
+                                    *input_error = Some(InputError::OnlySubject {
+                                        student_id: student_id.clone(),
+                                        subject: inputs.subjects.name(selected).to_string(),
+                                    });
+                                }
+                                None => {
+                                    // The subject stays registered so the
+                                    // deletion can be undone, but nothing
+                                    // refers to it any more, so it is left out
+                                    // of what is solved and exported.
+                                    let mut edits: Vec<Edit> = inputs
+                                        .subjects_by_student_id
+                                        .iter()
+                                        .filter(|(_, taken)| taken.contains(&selected))
+                                        .map(|(student_id, taken)| {
+                                            let mut taken = taken.clone();
+                                            taken.retain(|&subject| subject != selected);
+                                            Edit::Student(student_id.clone(), Some(taken))
+                                        })
+                                        .collect();
+                                    edits.push(Edit::ListSubject(selected, false));
+                                    edits.push(Edit::SubjectTerm(selected, None));
+                                    edits.push(Edit::SubjectMaxGroups(selected, None));
+                                    history.apply(inputs, Edit::Batch(edits), now);
+                                    *selected_subject = None;
+                                    toasts.info("Subject deleted");
+                                    *input_error = None;
+                                }
+                            }
+                        }
+                    }
+
+                    if let Some(error) = input_error {
+                        ui.colored_label(egui::Color32::RED, sentence(error));
                     }
                 });
 
                 new_state
             }
-            AppState::StudentConfig(is_creating) => {
-                let mut new_state = AppState::StudentConfig(*is_creating);
+            AppState::Review => {
+                let mut new_state = AppState::Review;
 
                 egui::CentralPanel::default().show(ctx, |ui| {
-                    ui.heading("Student Configuration");
-
-                    egui::ComboBox::from_label("Select student")
-                        .selected_text(selected_student_id.clone())
-                        .show_ui(ui, |ui| {
-                            for student_id in subjects_by_student_id.keys() {
-                                ui.selectable_value(
-                                    selected_student_id,
-                                    student_id.clone(),
-                                    student_id,
-                                );
-                            }
-                        });
+                    ui.heading("Review");
 
-                    if ui.button("Add student").clicked() && !*is_creating {
-                        new_state = AppState::StudentConfig(true);
-                        new_student_id_txt.clear();
-                        new_student_subjects_txt.clear();
+                    if ui.button("Back").clicked() {
+                        new_state = AppState::StudentConfig(false);
                     }
 
-                    if !selected_student_id.is_empty() {
-                        ui.label(format!(
-                            "Subjects: {}",
-                            subjects_by_student_id[selected_student_id].join(",")
-                        ));
-
-                        if ui.button("Delete").clicked() {
-                            subjects_by_student_id.remove(&selected_student_id.clone());
-                            // We change the selected student ID since the current one doesn't exist anymore.
-                            *selected_student_id = match subjects_by_student_id.keys().next() {
-                                Some(id) => id.clone(),
-                                None => String::new(),
-                            }
+                    // Nothing is kept to review after the app is restarted.
+                    let info = match reviewed_info {
+                        Some(info) => info,
+                        None => {
+                            ui.label("Submit the students to review them.");
+                            return;
                         }
+                    };
+                    review_summary(ui, info, inputs.exam_mode);
 
-                        // There is at least 1 student.
-                        if ui.button("Submit").clicked() {
-                            let mut student_infos = Vec::new();
-                            for (student_id, subjects) in subjects_by_student_id.iter() {
-                                let subjects =
-                                    subjects.iter().map(|x| &x[..]).collect::<Vec<&str>>();
-                                student_infos
-                                    .push(timetabler::StudentInfo::new(student_id, subjects));
-                            }
-                            let info = timetabler::TimetableInfo {
-                                // We can safely unwrap these two as for the app
-                                // to be in this state, a value must have been
-                                // provided to them already.
-                                max_groups: max_groups.unwrap(),
-                                daily_lesson_capacity: daily_lesson_capacity.unwrap(),
-                                students: &student_infos,
+                    for issue in validation_issues.iter() {
+                        ui.colored_label(egui::Color32::RED, sentence(issue));
+                    }
+                    #[cfg(feature = "scripting")]
+                    if let Some(error) = script_error {
+                        ui.colored_label(egui::Color32::RED, error.as_str());
+                    }
+                    #[cfg(feature = "scripting")]
+                    let script_loaded = script_error.is_none();
+                    #[cfg(not(feature = "scripting"))]
+                    let script_loaded = true;
+                    // Exams only need the students and the week, so they
+                    // aren't held up by validation.
+                    let can_solve =
+                        inputs.exam_mode || (validation_issues.is_empty() && script_loaded);
+                    if validation_issues.is_empty() && script_loaded {
+                        ui.label("Nothing is obviously wrong.");
+                    }
+
+                    let label = if inputs.exam_mode {
+                        "Schedule exams"
+                    } else {
+                        "Solve"
+                    };
+                    if (ui
+                        .add_enabled(can_solve, egui::Button::new(label))
+                        .clicked()
+                        || shortcuts.solve)
+                        && can_solve
+                    {
+                        let info = info.clone();
+                        let replaced_info = submitted_info.replace(info.clone());
+                        if inputs.exam_mode {
+                            *exams = Some(timetabler::schedule_exams(&info));
+                            *invigilation = match exams {
+                                Some(Ok(schedule)) if !info.teachers.is_empty() => {
+                                    Some(timetabler::assign_invigilators(&info, schedule, None))
+                                }
+                                _ => None,
                             };
-                            *result = Some(timetabler::solve_timetable(&info));
+                            *result = None;
+                            violations.clear();
+                            trace.clear();
+                            relaxations.clear();
+                            match exams {
+                                Some(Ok(_)) => toasts.info("Exams scheduled"),
+                                _ => toasts.error("Scheduling exams failed"),
+                            }
                             new_state = AppState::Submitted;
+                        } else {
+                            *exams = None;
+                            // The result being replaced is kept to compare the
+                            // new one with.
+                            if let (
+                                Some(old_info),
+                                Some(old_result @ timetabler::TimetableResult::Solved { .. }),
+                            ) = (replaced_info, result.take())
+                            {
+                                *previous_result = Some((old_info, old_result));
+                            }
+                            *solving = Some(Solving::start(
+                                info,
+                                *record_trace,
+                                inputs.fewest_groups,
+                                ui.input().time,
+                            ));
+                            new_state = AppState::Solving;
                         }
                     }
                 });
 
-                if *is_creating && matches!(new_state, AppState::StudentConfig(_)) {
-                    egui::Window::new("Create student").show(ctx, |ui| {
+                new_state
+            }
+            AppState::Solving => {
+                let mut new_state = AppState::Solving;
+
+                egui::CentralPanel::default().show(ctx, |ui| match solving {
+                    Some(solving) => {
+                        ui.heading("Solving");
+                        let progress = solving.progress.lock().unwrap();
                         ui.horizontal(|ui| {
-                            ui.label("Enter ID: ");
-                            ui.text_edit_singleline(new_student_id_txt);
+                            ui.add(egui::Spinner::new());
+                            ui.label(match progress.phase {
+                                Some(phase) => phase_label(phase),
+                                None => "Starting",
+                            });
                         });
+                        if progress.total > 0 {
+                            ui.add(
+                                egui::ProgressBar::new(
+                                    progress.placed as f32 / progress.total as f32,
+                                )
+                                .text(format!(
+                                    "{} of {} students placed",
+                                    group_digits(progress.placed),
+                                    group_digits(progress.total)
+                                )),
+                            );
+                        }
+                        ui.label(format!(
+                            "{:.0} seconds so far",
+                            ui.input().time - solving.started
+                        ));
+                        if ui.button("Cancel").clicked() || shortcuts.cancel {
+                            cancel_clicked = true;
+                        }
+                    }
+                    // Solving doesn't carry on once the app is closed.
+                    None => new_state = AppState::StudentConfig(false),
+                });
+                // Nothing else redraws the window while the solver works.
+                ctx.request_repaint();
 
-                        ui.horizontal(|ui| {
-                            ui.label("Enter subject (comma separated): ");
-                            ui.text_edit_singleline(new_student_subjects_txt);
+                new_state
+            }
+            AppState::Submitted => {
+                let mut new_state = AppState::Submitted;
+
+                // The result was solved for the submitted settings, which may
+                // since have been changed on the general screen.
+                let (daily_lesson_capacity, fortnightly) = match &*submitted_info {
+                    Some(info) => (
+                        info.daily_lesson_capacity,
+                        info.cycle == timetabler::WeekCycle::Fortnightly,
+                    ),
+                    None => (inputs.daily_lesson_capacity, inputs.fortnightly),
+                };
+                // Timetables are shown a term at a time.
+                let terms = submitted_info.as_ref().map_or(1, |info| info.terms.max(1));
+                *shown_term = (*shown_term).min(usize::from(terms) - 1);
+                let term = *shown_term;
+                // Results give subjects by ID, which the submitted settings
+                // name.
+                let no_subjects = timetabler::SubjectRegistry::new();
+                let registry = submitted_info
+                    .as_ref()
+                    .map_or(&no_subjects, |info| &info.subjects);
+
+                let print_view = match (&*print_target, &*result) {
+                    (
+                        Some(PrintTarget::Global),
+                        Some(timetabler::TimetableResult::Solved { subjects, .. }),
+                    ) => Some((
+                        "Timetable".to_string(),
+                        global_days(
+                            registry,
+                            term_slots(subjects, terms, term),
+                            daily_lesson_capacity,
+                        ),
+                    )),
+                    (
+                        Some(PrintTarget::Student(student_id)),
+                        Some(timetabler::TimetableResult::Solved {
+                            slots_by_student_id,
+                            group_names,
+                            ..
+                        }),
+                    ) => slots_by_student_id.get(student_id).map(|slots| {
+                        (
+                            student_id.to_string(),
+                            student_days(
+                                term_slots(slots, terms, term),
+                                group_names,
+                                daily_lesson_capacity,
+                            ),
+                        )
+                    }),
+                    (
+                        Some(PrintTarget::Teacher(teacher_id)),
+                        Some(timetabler::TimetableResult::Solved {
+                            slots_by_teacher_id,
+                            group_names,
+                            ..
+                        }),
+                    ) => slots_by_teacher_id.get(teacher_id).map(|slots| {
+                        (
+                            teacher_id.clone(),
+                            student_days(
+                                term_slots(slots, terms, term),
+                                group_names,
+                                daily_lesson_capacity,
+                            ),
+                        )
+                    }),
+                    _ => None,
+                };
+                if print_view.is_none() {
+                    *print_target = None;
+                }
+
+                if let Some((title, days)) = print_view {
+                    egui::CentralPanel::default().show(ctx, |ui| {
+                        ui.label("Press Escape to leave the print view");
+                        egui::ScrollArea::both().show(ui, |ui| {
+                            print_page(ui, &title, &days, fortnightly);
                         });
+                    });
+                    if ctx.input().key_pressed(egui::Key::Escape) {
+                        *print_target = None;
+                    }
+                } else if let Some(exams) = &*exams {
+                    egui::CentralPanel::default().show(ctx, |ui| {
+                        ui.heading("Exams");
 
                         ui.horizontal(|ui| {
-                            let add_button = ui.button("Add");
-                            let cancel_button = ui.button("Cancel");
-                            if add_button.clicked() {
-                                let entry =
-                                    subjects_by_student_id.entry(new_student_id_txt.clone());
-                                match entry {
-                                    Entry::Vacant(vacant) => {
-                                        if new_student_id_txt == "" {
-                                            return;
-                                        }
-                                        let mut subjects = vec![];
-                                        for subject in new_student_subjects_txt
-                                            .split(',')
-                                            .filter(|subject| !subject.is_empty())
-                                        {
-                                            if !subjects.contains(&subject.to_string()) {
-                                                subjects.push(subject.to_string());
-                                            }
-                                        }
-                                        if subjects.is_empty() {
-                                            return;
-                                        }
-                                        vacant.insert(subjects);
-                                        *selected_student_id = new_student_id_txt.clone();
-                                        new_state = AppState::StudentConfig(false);
-                                    }
-                                    Entry::Occupied(_) => {}
-                                };
-                            } else if cancel_button.clicked() {
-                                // We do else if so we don't handle both being
-                                // clicked in the same frame, which would be
-                                // bad.
+                            if ui.button("Back to students").clicked() {
                                 new_state = AppState::StudentConfig(false);
                             }
+                            if ui.button("New timetable").clicked() {
+                                *confirming_reset = true;
+                            }
                         });
-                    });
-                }
-
-                new_state
-            }
-            AppState::Submitted => {
-                if let Some(result) = &*result {
-                    egui::CentralPanel::default().show(ctx, |ui| {
-                        ui.heading("Result");
 
-                        // We check that the timetable was solved and extract the values that
-                        // the enum wraps.
-                        if let timetabler::TimetableResult::Solved {
-                            subjects,
-                            slots_by_student_id,
-                        } = result
-                        {
-                            ui.label("Global timetable");
-
-                            // We get all the days into the days vector
-                            let mut days: Vec<Vec<Vec<String>>> = Vec::new();
-                            let mut current_day: Option<Vec<Vec<String>>> = None;
-                            for (idx, slot) in subjects.iter().enumerate() {
-                                if (idx) as u8 % daily_lesson_capacity.unwrap() == 0 {
-                                    if let Some(day) = &current_day {
-                                        days.push(day.clone());
+                        match exams {
+                            Ok(schedule) => {
+                                for (session, session_subjects) in
+                                    schedule.subjects_by_session.iter().enumerate()
+                                {
+                                    if !session_subjects.is_empty() {
+                                        ui.label(format!(
+                                            "{}: {}",
+                                            slot_label(
+                                                session,
+                                                daily_lesson_capacity,
+                                                fortnightly
+                                            ),
+                                            session_subjects
+                                                .iter()
+                                                .map(|&subject| registry.name(subject))
+                                                .collect::<Vec<_>>()
+                                                .join(", ")
+                                        ));
                                     }
-                                    current_day = Some(Vec::new());
                                 }
-                                if let Some(day) = &mut current_day {
-                                    day.push(slot.clone());
+                                ui.label(format!(
+                                    "{} students sit more than one exam in a day, {} of them back to back",
+                                    schedule.students_with_same_day_exams.len(),
+                                    schedule.students_with_back_to_back_exams.len()
+                                ));
+
+                                match invigilation {
+                                    Some(Ok(rota)) => {
+                                        ui.label("Invigilators");
+                                        egui::Grid::new("Invigilators").striped(true).show(
+                                            ui,
+                                            |ui| {
+                                                for (&subject, teacher_ids) in
+                                                    &rota.invigilators_by_subject
+                                                {
+                                                    ui.label(registry.name(subject));
+                                                    ui.label(teacher_ids.join(", "));
+                                                    ui.end_row();
+                                                }
+                                            },
+                                        );
+                                    }
+                                    Some(Err(error)) => {
+                                        ui.label(format!("Unable to assign invigilators: {}", error));
+                                    }
+                                    None => {}
                                 }
                             }
-                            // To get the last day in
-                            if let Some(day) = &current_day {
-                                days.push(day.clone());
-                            }
-
-                            // Set up the horizontal top section
-                            ui.horizontal_top(|ui| {
-                                for (idx, day) in days.iter().enumerate() {
-                                    ui.vertical(|ui| {
-                                        let week_day = WEEK_DAYS[idx];
-                                        ui.label(week_day);
-                                        // This grid is for the one specific week day
-                                        egui::Grid::new(week_day).striped(true).show(ui, |ui| {
-                                            for (slot, slot_subjects) in day.iter().enumerate() {
-                                                ui.label(format!("Slot {}", slot + 1));
-                                                ui.label(slot_subjects.join(", "));
-                                                ui.end_row();
+                            Err(error) => {
+                                ui.label(format!(
+                                    "Unable to schedule exams: {}. Try adjusting variables!",
+                                    error
+                                ));
+                            }
+                        }
+                    });
+                } else if let Some(result) = &*result {
+                    egui::CentralPanel::default().show(ctx, |ui| {
+                        ui.heading("Result");
+
+                        ui.horizontal(|ui| {
+                            if ui.button("Back to students").clicked() {
+                                new_state = AppState::StudentConfig(false);
+                            }
+                            if ui.button("New timetable").clicked() {
+                                *confirming_reset = true;
+                            }
+                        });
+
+                        // Big timetables can be wider and taller than the window.
+                        egui::ScrollArea::both()
+                            .auto_shrink([false, false])
+                            .show(ui, |ui| {
+                                // We check that the timetable was solved and extract the values that
+                                // the enum wraps.
+                                if let timetabler::TimetableResult::Solved {
+                                    subjects,
+                                    slots_by_student_id,
+                                    slots_by_teacher_id,
+                                    group_names,
+                                    stats,
+                                    ..
+                                } = result
+                                {
+                                    let placements = group_digits(stats.placements);
+                                    ui.label(match stats.wall_time {
+                                        Some(wall_time) => format!(
+                                            "Solved in {}ms after {} placements",
+                                            wall_time.as_millis(),
+                                            placements
+                                        ),
+                                        None => format!("Solved after {} placements", placements),
+                                    });
+                                    if stats.proven_optimal {
+                                        ui.label("No timetable scores better than this one");
+                                    } else if submitted_info.as_ref().map_or(false, |info| {
+                                        info.algorithm == timetabler::Algorithm::Exhaustive
+                                    }) {
+                                        ui.label(
+                                            "The search gave up before trying every timetable, \
+                                             so one may score better than this",
+                                        );
+                                    }
+                                    ui.horizontal(|ui| {
+                                        if let Some(timetabler::TimetableInfo {
+                                            student_ordering:
+                                                timetabler::StudentOrdering::Shuffled(seed),
+                                            ..
+                                        }) = &*submitted_info
+                                        {
+                                            ui.label(format!(
+                                                "Students shuffled with seed {}",
+                                                seed
+                                            ));
+                                        }
+                                        if submitted_info.is_some()
+                                            && ui.button("Re-solve with a different seed").clicked()
+                                        {
+                                            resolve_clicked = true;
+                                        }
+                                    });
+                                    if inputs.fewest_groups {
+                                        ui.label(format!(
+                                            "{} groups in total",
+                                            result.group_count()
+                                        ));
+                                    }
+
+                                    if let Some(info) = &*submitted_info {
+                                        ui.horizontal(|ui| {
+                                            ui.label("Export folder: ");
+                                            ui.text_edit_singleline(export_dir);
+                                            if ui.button("Export HTML").clicked() {
+                                                let path = std::path::Path::new(export_dir.trim())
+                                                    .join("timetable");
+                                                match timetabler::write_html(info, result, &path) {
+                                                    Ok(()) => toasts.info(format!(
+                                                        "Exported {}",
+                                                        path.join("index.html").display()
+                                                    )),
+                                                    Err(error) => toasts.error(sentence(&error)),
+                                                }
+                                            }
+                                            #[cfg(feature = "pdf")]
+                                            if ui.button("Export PDF").clicked() {
+                                                let path = std::path::Path::new(export_dir.trim())
+                                                    .join("timetable.pdf");
+                                                match timetabler::write_pdf(info, result, &path) {
+                                                    Ok(()) => toasts.info(format!(
+                                                        "Exported {}",
+                                                        path.display()
+                                                    )),
+                                                    Err(error) => toasts.error(sentence(&error)),
+                                                }
+                                            }
+                                            #[cfg(feature = "xlsx")]
+                                            if ui.button("Export Excel").clicked() {
+                                                let path = std::path::Path::new(export_dir.trim())
+                                                    .join("timetable.xlsx");
+                                                match timetabler::write_xlsx(info, result, &path) {
+                                                    Ok(()) => toasts.info(format!(
+                                                        "Exported {}",
+                                                        path.display()
+                                                    )),
+                                                    Err(error) => toasts.error(sentence(&error)),
+                                                }
                                             }
                                         });
+                                    }
+
+                                    ui.collapsing("Statistics", |ui| statistics(ui, registry, result));
+                                    if let (Some((previous_info, previous)), Some(info)) =
+                                        (&*previous_result, &*submitted_info)
+                                    {
+                                        ui.collapsing("Compare with the previous result", |ui| {
+                                            if comparison(ui, previous_info, previous, info, result)
+                                            {
+                                                keep_previous = true;
+                                            }
+                                        });
+                                    }
+                                    ui.collapsing("Charts", |ui| {
+                                        statistics_charts(
+                                            ui,
+                                            result,
+                                            daily_lesson_capacity,
+                                            fortnightly,
+                                        )
                                     });
-                                }
-                            });
 
-                            egui::ComboBox::from_label("Select student")
-                                .selected_text(selected_student_id.clone())
-                                .show_ui(ui, |ui| {
-                                    // For each key of the subjects_by_student_id hash map we
-                                    // create a selectable value for it
-                                    for student_id in subjects_by_student_id.keys() {
-                                        ui.selectable_value(
-                                            selected_student_id,
-                                            student_id.clone(),
-                                            student_id,
-                                        );
+                                    if terms > 1 {
+                                        egui::ComboBox::from_label("Term shown")
+                                            .selected_text(format!("Term {}", term + 1))
+                                            .show_ui(ui, |ui| {
+                                                for term in 0..usize::from(terms) {
+                                                    ui.selectable_value(
+                                                        shown_term,
+                                                        term,
+                                                        format!("Term {}", term + 1),
+                                                    );
+                                                }
+                                            });
                                     }
-                                });
+                                    // Slots in the grids are counted from the
+                                    // start of the term shown.
+                                    let term_start = subjects.len() / usize::from(terms) * term;
+                                    let subjects = term_slots(subjects, terms, term);
+
+                                    ui.label("Global timetable");
 
-                            if !selected_student_id.is_empty() {
-                                let slots = &slots_by_student_id[&selected_student_id.clone()];
+                                    let days =
+                                        global_days(registry, subjects, daily_lesson_capacity);
+                                    ui.horizontal(|ui| {
+                                        if ui.button("Print view").clicked() {
+                                            *print_target = Some(PrintTarget::Global);
+                                        }
+                                        if ui.button("Copy").clicked() {
+                                            ui.output().copied_text =
+                                                timetable_tsv(&days, fortnightly);
+                                        }
+                                    });
+                                    let conflicts = conflicts_by_slot(violations);
+                                    if !violations.is_empty() {
+                                        ui.colored_label(
+                                            egui::Color32::RED,
+                                            format!(
+                                                "The timetable breaks {} rules. Hover over the red slots for details.",
+                                                violations.len()
+                                            ),
+                                        );
+                                        for violation in violations.iter() {
+                                            if violation.slot().is_none() {
+                                                ui.colored_label(
+                                                    egui::Color32::RED,
+                                                    violation.to_string(),
+                                                );
+                                            }
+                                        }
+                                    }
+                                    // Clicking a subject shows the group taught
+                                    // it in that slot.
+                                    let groups = groups_by_slot(slots_by_student_id);
+                                    timetable_grid(
+                                        ui,
+                                        "Global timetable",
+                                        days.len(),
+                                        daily_lesson_capacity,
+                                        fortnightly,
+                                        |ui, slot| {
+                                            let problems = conflicts.get(&(term_start + slot));
+                                            let slot_subjects =
+                                                subjects.get(slot).map_or(&[][..], Vec::as_slice);
+                                            let links = ui.horizontal_wrapped(|ui| {
+                                                subject_links(
+                                                    ui,
+                                                    registry,
+                                                    slot_subjects,
+                                                    problems.is_some(),
+                                                )
+                                            });
+                                            if let Some(subject) = links.inner {
+                                                if let Some(&group_idx) =
+                                                    groups.get(&(term_start + slot, subject))
+                                                {
+                                                    *selected_group = Some((subject, group_idx));
+                                                }
+                                            }
+                                            if let Some(problems) = problems {
+                                                links.response.on_hover_text(problems.join("\n"));
+                                            }
+                                        },
+                                    );
 
-                                let mut days: Vec<Vec<Option<String>>> = Vec::new();
-                                let mut current_day: Option<Vec<Option<String>>> = None;
-                                for (idx, subject) in slots.iter().enumerate() {
-                                    if (idx) as u8 % daily_lesson_capacity.unwrap() == 0 {
-                                        if let Some(day) = &current_day {
-                                            days.push(day.clone());
+                                    ui.collapsing("Class lists", |ui| {
+                                        for (subject, groups) in result.rosters() {
+                                            for (group_idx, student_ids) in
+                                                groups.iter().enumerate()
+                                            {
+                                                ui.collapsing(
+                                                    &group_names[&subject][group_idx],
+                                                    |ui| {
+                                                        for student_id in student_ids {
+                                                            ui.label(student_id.as_str());
+                                                        }
+                                                    },
+                                                );
+                                            }
                                         }
-                                        current_day = Some(Vec::new());
+                                    });
+
+                                    egui::ComboBox::from_label("Select student")
+                                        .selected_text(selected_student_id.to_string())
+                                        .show_ui(ui, |ui| {
+                                            // For each student in the result we create a
+                                            // selectable value for them
+                                            for student_id in slots_by_student_id.keys() {
+                                                ui.selectable_value(
+                                                    selected_student_id,
+                                                    student_id.clone(),
+                                                    student_id.as_str(),
+                                                );
+                                            }
+                                        });
+
+                                    // Students added since solving aren't in the
+                                    // result.
+                                    if let Some(slots) = slots_by_student_id.get(selected_student_id)
+                                    {
+                                        let days = student_days(
+                                            term_slots(slots, terms, term),
+                                            group_names,
+                                            daily_lesson_capacity,
+                                        );
+                                        ui.horizontal(|ui| {
+                                            if ui.button("Print view").clicked() {
+                                                *print_target = Some(PrintTarget::Student(
+                                                    selected_student_id.clone(),
+                                                ));
+                                            }
+                                            if ui.button("Copy").clicked() {
+                                                ui.output().copied_text =
+                                                    timetable_tsv(&days, fortnightly);
+                                            }
+                                        });
+                                        let capacity = usize::from(daily_lesson_capacity);
+                                        timetable_grid(
+                                            ui,
+                                            selected_student_id.as_str(),
+                                            days.len(),
+                                            daily_lesson_capacity,
+                                            fortnightly,
+                                            |ui, slot| {
+                                                let text = days
+                                                    .get(slot / capacity)
+                                                    .and_then(|day| day.get(slot % capacity))
+                                                    .map_or("", String::as_str);
+                                                ui.add(egui::Label::new(text).wrap(true));
+                                            },
+                                        );
                                     }
-                                    if let Some(day) = &mut current_day {
-                                        day.push(subject.as_ref().map(|subject| subject.0.clone()));
+
+                                    // Teachers are only assigned when some
+                                    // were given.
+                                    if !slots_by_teacher_id.is_empty() {
+                                        egui::ComboBox::from_label("Select teacher")
+                                            .selected_text(selected_teacher_id.as_str())
+                                            .show_ui(ui, |ui| {
+                                                for teacher_id in slots_by_teacher_id.keys() {
+                                                    ui.selectable_value(
+                                                        selected_teacher_id,
+                                                        teacher_id.clone(),
+                                                        teacher_id.as_str(),
+                                                    );
+                                                }
+                                            });
                                     }
-                                }
-                                // To get the last day in
-                                if let Some(day) = &current_day {
-                                    days.push(day.clone());
-                                }
 
-                                ui.horizontal_top(|ui| {
-                                    for (idx, day) in days.iter().enumerate() {
-                                        ui.vertical(|ui| {
-                                            let week_day = WEEK_DAYS[idx];
-                                            ui.label(week_day);
-                                            // We need to create a new grid for each day as we
-                                            // cannot approach this in a 3D manner
-                                            egui::Grid::new(week_day).striped(true).show(
-                                                ui,
-                                                |ui| {
-                                                    for (slot, slot_subject) in
-                                                        day.iter().enumerate()
-                                                    {
-                                                        ui.label(format!("Slot {}", slot + 1));
-                                                        ui.label(match slot_subject {
-                                                            Some(subject) => subject,
-                                                            None => "",
-                                                        });
-                                                        ui.end_row();
-                                                    }
-                                                },
-                                            );
+                                    let slots = slots_by_teacher_id.get(selected_teacher_id);
+                                    if let Some(slots) = slots {
+                                        let capacity = daily_lesson_capacity;
+                                        let days = student_days(
+                                            term_slots(slots, terms, term),
+                                            group_names,
+                                            capacity,
+                                        );
+                                        ui.horizontal(|ui| {
+                                            if ui.button("Print view").clicked() {
+                                                *print_target = Some(PrintTarget::Teacher(
+                                                    selected_teacher_id.clone(),
+                                                ));
+                                            }
+                                            if ui.button("Copy").clicked() {
+                                                ui.output().copied_text =
+                                                    timetable_tsv(&days, fortnightly);
+                                            }
                                         });
+                                        let capacity = usize::from(daily_lesson_capacity);
+                                        timetable_grid(
+                                            ui,
+                                            &format!("Teacher {}", selected_teacher_id),
+                                            days.len(),
+                                            daily_lesson_capacity,
+                                            fortnightly,
+                                            |ui, slot| {
+                                                let text = days
+                                                    .get(slot / capacity)
+                                                    .and_then(|day| day.get(slot % capacity))
+                                                    .map_or("", String::as_str);
+                                                ui.add(egui::Label::new(text).wrap(true));
+                                            },
+                                        );
                                     }
-                                });
+                                } else if let timetabler::TimetableResult::Unsolved(error) = result
+                                {
+                                    // Slots are described in the user's terms where
+                                    // the error has one.
+                                    let message = match error {
+                                        timetabler::TimetableError::NoRoomAvailable {
+                                            subject,
+                                            slot,
+                                        } => {
+                                            format!(
+                                                "No suitable room is free for {} in {}. Try adding rooms!",
+                                                subject,
+                                                slot_label(
+                                                    *slot,
+                                                    daily_lesson_capacity,
+                                                    fortnightly
+                                                )
+                                            )
+                                        }
+                                        timetabler::TimetableError::NoTeacherAvailable {
+                                            subject,
+                                            slot,
+                                        } => format!(
+                                            "No teacher is free to teach {} in {}. Try adding teachers!",
+                                            subject,
+                                            slot_label(*slot, daily_lesson_capacity, fortnightly)
+                                        ),
+                                        timetabler::TimetableError::TeachersOff {
+                                            subject,
+                                            slot,
+                                            teacher_ids,
+                                        } => format!(
+                                            "No teacher is free to teach {} in {}, as it is a day off for {}. Try adding teachers!",
+                                            subject,
+                                            slot_label(*slot, daily_lesson_capacity, fortnightly),
+                                            teacher_ids.join(", ")
+                                        ),
+                                        error => {
+                                            format!("Unable to solve: {}. Try adjusting variables!", error)
+                                        }
+                                    };
+                                    ui.label(message);
+                                    for relaxation in relaxations.iter().take(3) {
+                                        ui.label(relaxation.to_string());
+                                    }
+                                }
+
+                                if !trace.is_empty() {
+                                    ui.collapsing("Solver decisions", |ui| {
+                                        egui::ScrollArea::vertical().max_height(300.0).show(
+                                            ui,
+                                            |ui| {
+                                                for event in trace.iter() {
+                                                    ui.label(event.to_string());
+                                                }
+                                            },
+                                        );
+                                    });
+                                }
+                            });
+                    });
+                }
+
+                if let (
+                    Some((subject, group_idx)),
+                    Some(timetabler::TimetableResult::Solved {
+                        slots_by_student_id,
+                        group_names,
+                        rooms_by_slot,
+                        teachers_by_slot,
+                        ..
+                    }),
+                    None,
+                ) = (*selected_group, &*result, &*print_target)
+                {
+                    let mut open = true;
+                    let roster: Vec<&timetabler::StudentId> = slots_by_student_id
+                        .iter()
+                        .filter(|(_, slots)| {
+                            slots
+                                .iter()
+                                .flatten()
+                                .any(|&group| group == (subject, group_idx))
+                        })
+                        .map(|(student_id, _)| student_id)
+                        .collect();
+                    // Teachers and rooms are given per slot, so a group may
+                    // have more than one of each across its lessons.
+                    let mut teachers = BTreeSet::new();
+                    let mut rooms = BTreeSet::new();
+                    if let Some(slots) = roster
+                        .first()
+                        .and_then(|student_id| slots_by_student_id.get(*student_id))
+                    {
+                        for (slot, entry) in slots.iter().enumerate() {
+                            if *entry == Some((subject, group_idx)) {
+                                if let Some(teacher) =
+                                    teachers_by_slot.get(slot).and_then(|by| by.get(&subject))
+                                {
+                                    teachers.insert(teacher.as_str());
+                                }
+                                if let Some(room) =
+                                    rooms_by_slot.get(slot).and_then(|by| by.get(&subject))
+                                {
+                                    rooms.insert(room.as_str());
+                                }
                             }
-                        } else {
-                            ui.label("Unable to solve. Try adjusting variables!");
                         }
+                    }
+                    // Each group is kept at one site, whichever rooms it has.
+                    let site = submitted_info.as_ref().and_then(|info| {
+                        let room_id = rooms.iter().next().copied();
+                        room_id.and_then(|room_id| info.site_of_lesson(subject, Some(room_id)))
+                    });
+                    let subject_name = registry.name(subject);
+                    let title = group_names
+                        .get(&subject)
+                        .and_then(|names| names.get(group_idx))
+                        .cloned()
+                        .unwrap_or_else(|| subject_name.to_string());
+                    egui::Window::new(title).open(&mut open).show(ctx, |ui| {
+                        ui.label(format!("Subject: {}", subject_name));
+                        ui.label(format!("{} students", roster.len()));
+                        if !teachers.is_empty() {
+                            ui.label(format!(
+                                "Taught by {}",
+                                teachers.into_iter().collect::<Vec<_>>().join(", ")
+                            ));
+                        }
+                        if !rooms.is_empty() {
+                            ui.label(format!(
+                                "Taught in {}",
+                                rooms.into_iter().collect::<Vec<_>>().join(", ")
+                            ));
+                        }
+                        if let Some(site) = site {
+                            ui.label(format!("At {}", site));
+                        }
+                        egui::ScrollArea::vertical()
+                            .max_height(300.0)
+                            .show(ui, |ui| {
+                                for student_id in &roster {
+                                    ui.label(student_id.as_str());
+                                }
+                            });
                     });
+                    if !open {
+                        *selected_group = None;
+                    }
+                } else {
+                    *selected_group = None;
+                }
+
+                if *confirming_reset && matches!(new_state, AppState::Submitted) {
+                    egui::Window::new("New timetable")
+                        .collapsible(false)
+                        .resizable(false)
+                        .show(ctx, |ui| {
+                            ui.label(format!(
+                                "Clear the settings, the {} students and the result to start again?",
+                                inputs.subjects_by_student_id.len()
+                            ));
+                            ui.horizontal(|ui| {
+                                let clear_button = ui.button("Clear");
+                                let cancel_button = ui.button("Cancel");
+                                if clear_button.clicked() || shortcuts.confirm {
+                                    reset_confirmed = true;
+                                } else if cancel_button.clicked() || shortcuts.cancel {
+                                    *confirming_reset = false;
+                                }
+                            });
+                        });
+                } else {
+                    *confirming_reset = false;
                 }
 
-                AppState::Submitted
+                new_state
             }
         };
+
+        toasts.show(ctx);
+
+        if undo_clicked {
+            self.undo();
+        } else if redo_clicked {
+            self.redo();
+        }
+        if reset_confirmed {
+            self.reset();
+        }
+        if keep_previous {
+            self.keep_previous_result();
+        }
+        if resolve_clicked {
+            self.resolve_with_new_seed(ctx.input().time);
+        }
+        if cancel_clicked {
+            self.cancel_solve();
+        }
+
+        #[cfg(all(feature = "project", not(target_arch = "wasm32")))]
+        if let Some(action) = self.project_action.take() {
+            self.run_project_action(action);
+            ctx.request_repaint();
+        }
     }
 }
@@ -0,0 +1,56 @@
+#![forbid(unsafe_code)]
+#![warn(clippy::all, rust_2018_idioms)]
+
+//! Solves every XHSTT instance in a folder without the app.
+//!
+//! `cargo run --release --bin batch -- <input dir> <output dir> [--exhaustive] [--fewest-groups]`
+
+use timetabler_gui::timetabler::{solve_directory, Algorithm, BatchOptions};
+
+const USAGE: &str = "Usage: batch <input dir> <output dir> [--exhaustive] [--fewest-groups]";
+
+fn main() {
+    let mut dirs = Vec::new();
+    let mut options = BatchOptions::default();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--exhaustive" => options.algorithm = Some(Algorithm::Exhaustive),
+            "--fewest-groups" => options.fewest_groups = true,
+            _ if arg.starts_with("--") => {
+                eprintln!("Unknown option {}\n{}", arg, USAGE);
+                std::process::exit(2);
+            }
+            _ => dirs.push(arg),
+        }
+    }
+    let (input_dir, output_dir) = match &dirs[..] {
+        [input_dir, output_dir] => (input_dir, output_dir),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    match solve_directory(input_dir, output_dir, &options) {
+        Ok(report) => {
+            for instance in &report.instances {
+                match &instance.error {
+                    Some(error) => println!("{}: {}", instance.file_name, error),
+                    None => println!(
+                        "{}: solved with {} groups",
+                        instance.file_name, instance.groups
+                    ),
+                }
+            }
+            println!(
+                "Solved {} of {} instances",
+                report.solved(),
+                report.instances.len()
+            );
+        }
+        Err(error) => {
+            eprintln!("error: {}", error);
+            std::process::exit(1);
+        }
+    }
+}
@@ -3,7 +3,7 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
 mod app;
-mod timetabler;
+pub mod timetabler;
 pub use app::TimetablerApp;
 
 // ----------------------------------------------------------------------------
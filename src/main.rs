@@ -1,7 +1,6 @@
 #![forbid(unsafe_code)]
 // #![cfg_attr(not(debug_assertions), deny(warnings))] // Forbid warnings in release builds
 #![warn(clippy::all, rust_2018_idioms)]
-mod timetabler;
 // When compiling natively:
 #[cfg(not(target_arch = "wasm32"))]
 fn main() {
@@ -1,314 +1,3597 @@
-use std::collections::HashMap;
-
-pub struct StudentInfo<'a> {
-    id: &'a str,
-    subjects: Vec<&'a str>,
-}
-
-impl<'a> StudentInfo<'a> {
-    pub fn new(id: &'a str, subjects: Vec<&'a str>) -> StudentInfo<'a> {
-        StudentInfo { id, subjects }
-    }
-}
-
-pub struct TimetableInfo<'a> {
-    pub max_groups: u8,
-    pub students: &'a Vec<StudentInfo<'a>>,
-    pub daily_lesson_capacity: u8,
-}
-
-#[derive(Debug)] // Allow the struct to be printed for debugging.
-pub struct Student {
-    slots: Vec<Option<(String, usize)>>,
-    id: String,
-}
-
-#[derive(Debug)] // Allow the struct to be printed for debugging.
-pub enum TimetableResult {
-    Solved {
-        subjects: Vec<Vec<String>>,
-        slots_by_student_id: HashMap<String, Vec<Option<(String, usize)>>>,
-    },
-    Unsolved,
-}
-
-#[derive(Default, Debug)]
-struct Group {
-    slot: usize,
-    student_idxs: Vec<usize>,
-}
-
-fn attendance(candidate: (&str, usize), groups_by_subject: &HashMap<&str, Vec<Group>>) -> usize {
-    groups_by_subject[candidate.0]
-        .get(candidate.1)
-        .map(|group| group.student_idxs.len())
-        .unwrap_or_default()
-}
-
-fn sort_by_ascending_attendance(
-    candidates: &mut Vec<(&str, usize)>,
-    groups_by_subject: &HashMap<&str, Vec<Group>>,
-    start: i32,
-    end: i32,
-) {
-    if start >= end {
-        return;
-    }
-
-    let pivot = attendance(candidates[start as usize], groups_by_subject);
-    let mut low_mark = start + 1;
-    let mut high_mark = end;
-
-    loop {
-        while low_mark <= high_mark
-            && attendance(candidates[low_mark as usize], groups_by_subject) <= pivot
-        {
-            low_mark += 1;
-        }
-        while low_mark <= high_mark
-            && attendance(candidates[high_mark as usize], groups_by_subject) >= pivot
-        {
-            high_mark -= 1;
-        }
-        if low_mark < high_mark {
-            candidates.swap(low_mark as usize, high_mark as usize);
-        } else {
-            break;
-        }
-    }
-
-    candidates.swap(start as usize, high_mark as usize);
-    sort_by_ascending_attendance(candidates, groups_by_subject, start, high_mark - 1);
-    sort_by_ascending_attendance(candidates, groups_by_subject, high_mark + 1, end);
-}
-
-fn try_assign_group_lazily<'a>(
-    groups_by_subject: &HashMap<&str, Vec<Group>>,
-    personal_slots: &mut Vec<Option<(&'a str, usize)>>,
-    subject: &'a str,
-) -> bool {
-    // We iterate over each group of the subject that currently exists.
-    // For each, we check if it can be used.
-    for (group_idx, group) in groups_by_subject
-        .get(subject)
-        .unwrap_or(&Vec::new())
-        .iter()
-        .enumerate()
-    {
-        if personal_slots[group.slot].is_some() {
-            // The slot is taken by another subject.
-            continue;
-        }
-        personal_slots[group.slot] = Some((subject, group_idx));
-        return true;
-    }
-
-    false
-}
-
-fn handle_subjects<'a>(
-    groups_by_subject: &mut HashMap<&'a str, Vec<Group>>,
-    personal_slots: &mut Vec<Option<(&'a str, usize)>>,
-    subjects: &Vec<&'a str>,
-    timetable_info: &TimetableInfo,
-    total_slots: u8,
-    students: &mut Vec<Student>,
-) -> bool {
-    for &subject in subjects {
-        if try_assign_group_lazily(groups_by_subject, personal_slots, subject) {
-            // We don't need to continue as we could find a suitable group.
-            continue;
-        }
-
-        // We can just unwrap this as we validate that the student always
-        // has enough personal slots to cover all their subjects.
-        let next_free_slot = personal_slots.iter().position(|x| x.is_none()).unwrap();
-
-        if groups_by_subject.get(subject).unwrap_or(&Vec::new()).len()
-            == timetable_info.max_groups.into()
-        {
-            // Groups are at capacity. One of this student's subject groups,
-            // including the potential current one, needs to be moved to the
-            // next free slot. In order of ascending attendee count, check
-            // each for each next free slot available.
-
-            // Candidates include all groups of the current subject.
-            let mut candidates: Vec<(&str, usize)> =
-                personal_slots.clone().into_iter().flatten().collect();
-            for i in 0..groups_by_subject[subject].len() {
-                candidates.push((subject, i));
-            }
-
-            let end = candidates.len() as i32 - 1;
-            sort_by_ascending_attendance(&mut candidates, &groups_by_subject, 0, end);
-
-            // We choose a subject group to move.
-            let mut next_free_slot = next_free_slot;
-            let mut personal_slots_iter = personal_slots.iter().enumerate();
-            let chosen = 'outer: loop {
-                for &(candidate_subject, candidate_group_idx) in &candidates {
-                    let mut is_candidate_ok = true;
-                    if let Some(group) =
-                        groups_by_subject[candidate_subject].get(candidate_group_idx)
-                    {
-                        for &other_student_idx in group.student_idxs.iter() {
-                            let other_student = &mut students[other_student_idx];
-                            if other_student.slots[next_free_slot].is_some() {
-                                is_candidate_ok = false;
-                                break;
-                            }
-                        }
-                    }
-
-                    if is_candidate_ok {
-                        break 'outer Some((candidate_subject, candidate_group_idx));
-                    }
-                }
-
-                println!("{next_free_slot}");
-                if next_free_slot == (total_slots - 1).into() {
-                    break None;
-                }
-                next_free_slot = personal_slots_iter
-                    .position(|(pos, x)| pos > next_free_slot && x.is_none())
-                    // We can just unwrap this as we validate that the
-                    // student always has enough personal slots to cover all
-                    // their subjects.
-                    .unwrap();
-            };
-
-            if let Some((chosen_subject, chosen_group_idx)) = chosen {
-                let chosen_group_slot;
-
-                if let Some(chosen_group) = groups_by_subject
-                    // We are `.get_mut(...).unwrap()`ing since currently we
-                    // cannot index mutably into HashMaps in Rust.
-                    .get_mut(chosen_subject)
-                    .unwrap()
-                    .get_mut(chosen_group_idx)
-                {
-                    for &other_student_idx in &chosen_group.student_idxs {
-                        // This doesn't include the current student.
-                        let other_student = &mut students[other_student_idx];
-                        other_student.slots[chosen_group.slot] = None;
-                        other_student.slots[next_free_slot] =
-                            Some((chosen_subject.to_string(), chosen_group_idx));
-                    }
-
-                    chosen_group_slot = chosen_group.slot;
-                    chosen_group.slot = next_free_slot;
-                } else {
-                    chosen_group_slot = personal_slots
-                        .iter()
-                        .position(|x| x.is_some() && x.unwrap().0 == chosen_subject)
-                        .unwrap();
-                }
-
-                if chosen_subject == subject {
-                    personal_slots[next_free_slot] = Some((subject, chosen_group_idx));
-                } else {
-                    personal_slots[next_free_slot] = personal_slots[chosen_group_slot];
-                    // We finally add the subject to the personal slot.
-                    personal_slots[chosen_group_slot] = Some((subject, chosen_group_idx));
-                }
-            } else {
-                return true;
-            }
-        } else {
-            // Groups aren't at capacity, so we can create a new group at
-            // the earliest possible position in the student's personal
-            // slots.
-
-            personal_slots[next_free_slot] = Some((
-                subject,
-                groups_by_subject.entry(subject).or_insert(Vec::new()).len(),
-            ));
-        }
-    }
-
-    false
-}
-
-fn make_global<'a>(
-    groups_by_subject: &mut HashMap<&'a str, Vec<Group>>,
-    personal_slots: &mut Vec<Option<(&'a str, usize)>>,
-    student_idx: usize,
-) {
-    for (slot, (subject, group_idx)) in personal_slots
-        .iter()
-        .enumerate()
-        .flat_map(|(i, c)| c.map(|c| (i, c)))
-    {
-        let groups = groups_by_subject.entry(subject).or_insert(Vec::new());
-        if let Some(group) = groups.get_mut(group_idx) {
-            // There will never be more than one group per subject per
-            // student, so we can just push.
-            group.student_idxs.push(student_idx);
-        } else {
-            groups.push(Group {
-                slot,
-                student_idxs: vec![student_idx],
-            });
-        }
-    }
-}
-
-pub fn solve_timetable(timetable_info: &TimetableInfo<'_>) -> TimetableResult {
-    let mut students: Vec<Student> = Vec::new();
-
-    // There are 5 days in the timetable week.
-    let total_slots = timetable_info.daily_lesson_capacity * 5;
-    let mut groups_by_subject: HashMap<&str, Vec<Group>> = HashMap::new();
-    for (student_idx, student_info) in timetable_info.students.iter().enumerate() {
-        // We map slots to possible subjects here.
-        let mut personal_slots = vec![None; total_slots.into()];
-        let subjects = &student_info.subjects;
-        if handle_subjects(
-            &mut groups_by_subject,
-            &mut personal_slots,
-            subjects,
-            timetable_info,
-            total_slots,
-            &mut students,
-        ) {
-            return TimetableResult::Unsolved;
-        }
-
-        // We add the groups we decided upon to the global vector.
-        make_global(&mut groups_by_subject, &mut personal_slots, student_idx);
-
-        // We just turn Subject &strs into Strings so that the Student instance
-        // can own them.
-        let mut returned_personal_slots = Vec::new();
-        for slot in personal_slots {
-            returned_personal_slots
-                .push(slot.map(|(subject, group_idx)| (subject.to_string(), group_idx)));
-        }
-
-        // We register the student to keep track of for later.
-        students.push(Student {
-            slots: returned_personal_slots,
-            id: student_info.id.to_string(),
-        });
-    }
-
-    // We invert groups_by_subject to help get subjects_by_slot.
-    let mut subjects = vec![Vec::new(); total_slots.into()];
-    for (subject, groups) in groups_by_subject {
-        for group in groups {
-            // It's guaranteed that this will never cause duplicate subjects, so
-            // we don't need to check.
-            subjects[group.slot].push(subject.to_string());
-        }
-    }
-
-    let mut slots_by_student_id = HashMap::new();
-    for student in students {
-        slots_by_student_id.insert(student.id, student.slots);
-    }
-
-    TimetableResult::Solved {
-        subjects,
-        slots_by_student_id,
-    }
-}
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::ops::{Index, IndexMut, Range};
+use std::sync::Arc;
+use std::time::Duration;
+
+use thiserror::Error;
+
+mod aliases;
+mod batch;
+mod constraint;
+mod cover;
+mod curricula;
+mod diff;
+mod error;
+mod exam;
+mod exhaustive;
+mod fewest_groups;
+pub mod generate;
+mod grid;
+mod html;
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "pdf")]
+mod pdf;
+mod relax;
+mod roster;
+#[cfg(feature = "scripting")]
+mod script;
+mod slot_set;
+#[cfg(feature = "stress")]
+pub mod stress;
+mod suggest;
+mod trace;
+mod validate;
+mod verify;
+mod what_if;
+mod xhstt;
+#[cfg(feature = "xlsx")]
+mod xlsx;
+
+pub use aliases::SubjectAliases;
+pub use batch::{solve_directory, BatchError, BatchOptions, BatchReport, InstanceReport};
+pub use constraint::{
+    Constraint, DailyLessonLimit, FixedEvents, GroupSize, GroupsPerSlot, Placement, RoomsFree,
+    SlotPreferences, TeacherDaysOff, TeachersFree, TravelBetweenSites,
+};
+pub use cover::{plan_cover, CoverSheet, CoveredLesson};
+pub use curricula::Curricula;
+pub use diff::{diff, GroupKey, MovedGroup, TimetableDiff};
+pub use error::{ImportError, ParseError, RenameError};
+pub use exam::{assign_invigilators, schedule_exams, ExamError, ExamSchedule, InvigilationRota};
+pub use fewest_groups::{solve_with_fewest_groups, solve_with_fewest_groups_cancellable};
+pub use grid::{day_name, school_grids, student_grids, TimetableGrid, DAY_NAMES};
+pub use html::{write_html, HtmlError};
+#[cfg(feature = "json")]
+pub use json::{from_json, from_json_file, to_json, to_json_file, JsonError, JSON_VERSION};
+#[cfg(feature = "pdf")]
+pub use pdf::{write_pdf, PdfError};
+pub use relax::{suggest_relaxations, Relaxation};
+pub use roster::{read_roster, read_roster_file, RosterError};
+#[cfg(feature = "scripting")]
+pub use script::{ScriptConstraint, ScriptError};
+use slot_set::SlotSet;
+pub use suggest::{suggest_daily_lesson_capacity, suggest_max_groups};
+pub use trace::{CancellationToken, Phase, SolveObserver, TraceEvent};
+pub use validate::{validate, ValidationIssue};
+pub use verify::{verify, Violation};
+pub use what_if::{what_if, Change, Impact};
+pub use xhstt::{from_xhstt, from_xhstt_file, to_xhstt, XhsttError};
+#[cfg(feature = "xlsx")]
+pub use xlsx::{write_xlsx, XlsxError};
+
+/// Identifies a student, such as by the ID the school already gives them.
+/// Maps keyed by student ID can be looked up with a `&str`.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct StudentId(String);
+
+impl StudentId {
+    pub fn new(id: impl Into<String>) -> StudentId {
+        StudentId(id.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for StudentId {
+    fn from(id: String) -> Self {
+        StudentId(id)
+    }
+}
+
+impl From<&str> for StudentId {
+    fn from(id: &str) -> Self {
+        StudentId(id.to_string())
+    }
+}
+
+impl Borrow<str> for StudentId {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for StudentId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// A student and the subjects they take, given by their IDs in
+/// `TimetableInfo::subjects`.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone)]
+pub struct StudentInfo {
+    id: StudentId,
+    subjects: Vec<SubjectId>,
+    cohort: Option<String>,
+}
+
+impl StudentInfo {
+    pub fn new(id: impl Into<StudentId>, subjects: Vec<SubjectId>) -> StudentInfo {
+        StudentInfo {
+            id: id.into(),
+            subjects,
+            cohort: None,
+        }
+    }
+
+    /// Puts the student in a cohort, such as a year group, for the fixed
+    /// events only some students attend.
+    pub fn with_cohort(mut self, cohort: impl Into<String>) -> StudentInfo {
+        self.cohort = Some(cohort.into());
+        self
+    }
+
+    pub fn cohort(&self) -> Option<&str> {
+        self.cohort.as_deref()
+    }
+
+    pub fn id(&self) -> &StudentId {
+        &self.id
+    }
+
+    pub fn subjects(&self) -> &[SubjectId] {
+        &self.subjects
+    }
+}
+
+/// Identifies a subject within a `SubjectRegistry`. IDs are handed out from
+/// zero in the order subjects are registered.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SubjectId(usize);
+
+impl SubjectId {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// The subjects of a timetable along with their details, each given a
+/// `SubjectId` which stays the same if the subject is renamed. IDs only change
+/// when subjects are removed with `retain`, which gives the new ones.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(try_from = "Vec<SubjectInfo>", into = "Vec<SubjectInfo>")
+)]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SubjectRegistry {
+    subjects: Vec<SubjectInfo>,
+    ids_by_name: HashMap<String, SubjectId>,
+}
+
+impl SubjectRegistry {
+    pub fn new() -> SubjectRegistry {
+        SubjectRegistry::default()
+    }
+
+    /// Returns the ID of the subject called `name`, registering it with no
+    /// special requirements first if it is new.
+    pub fn register(&mut self, name: &str) -> SubjectId {
+        match self.ids_by_name.get(name) {
+            Some(&id) => id,
+            None => self.insert(SubjectInfo::new(name)),
+        }
+    }
+
+    /// Registers a subject with the details in `info`, replacing the details
+    /// of the subject of the same name if there already is one.
+    pub fn insert(&mut self, info: SubjectInfo) -> SubjectId {
+        if let Some(&id) = self.ids_by_name.get(&info.name) {
+            self.subjects[id.0] = info;
+            return id;
+        }
+
+        let id = SubjectId(self.subjects.len());
+        self.ids_by_name.insert(info.name.clone(), id);
+        self.subjects.push(info);
+        id
+    }
+
+    pub fn id(&self, name: &str) -> Option<SubjectId> {
+        self.ids_by_name.get(name).copied()
+    }
+
+    pub fn name(&self, id: SubjectId) -> &str {
+        &self.subjects[id.0].name
+    }
+
+    /// The details of the subject called `name`, if it is registered.
+    pub fn get(&self, name: &str) -> Option<&SubjectInfo> {
+        self.id(name).map(|id| &self[id])
+    }
+
+    /// Gives a subject a new name, refusing a blank name or one another
+    /// subject already has.
+    pub fn rename(&mut self, id: SubjectId, name: &str) -> Result<(), RenameError> {
+        if name.trim().is_empty() {
+            return Err(RenameError::EmptyName);
+        }
+        if let Some(&other) = self.ids_by_name.get(name) {
+            // Renaming a subject to its current name changes nothing.
+            return if other == id {
+                Ok(())
+            } else {
+                Err(RenameError::NameTaken(name.to_string()))
+            };
+        }
+
+        self.ids_by_name.remove(&self.subjects[id.0].name);
+        self.ids_by_name.insert(name.to_string(), id);
+        self.subjects[id.0].name = name.to_string();
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.subjects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.subjects.is_empty()
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = SubjectId> {
+        (0..self.subjects.len()).map(SubjectId)
+    }
+
+    /// The details of every subject, in ID order.
+    pub fn iter(&self) -> std::slice::Iter<'_, SubjectInfo> {
+        self.subjects.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, SubjectInfo> {
+        self.subjects.iter_mut()
+    }
+
+    /// Removes every subject `keep` is false for, keeping the order of the
+    /// rest. Returns the new ID of each old one, or `None` if it was removed.
+    pub fn retain(&mut self, mut keep: impl FnMut(SubjectId) -> bool) -> Vec<Option<SubjectId>> {
+        let mut kept = SubjectRegistry::new();
+        let new_ids = std::mem::take(&mut self.subjects)
+            .into_iter()
+            .enumerate()
+            .map(|(idx, info)| keep(SubjectId(idx)).then(|| kept.insert(info)))
+            .collect();
+        *self = kept;
+        new_ids
+    }
+}
+
+impl Index<SubjectId> for SubjectRegistry {
+    type Output = SubjectInfo;
+
+    fn index(&self, id: SubjectId) -> &SubjectInfo {
+        &self.subjects[id.0]
+    }
+}
+
+impl IndexMut<SubjectId> for SubjectRegistry {
+    fn index_mut(&mut self, id: SubjectId) -> &mut SubjectInfo {
+        &mut self.subjects[id.0]
+    }
+}
+
+impl<'a> IntoIterator for &'a SubjectRegistry {
+    type Item = &'a SubjectInfo;
+    type IntoIter = std::slice::Iter<'a, SubjectInfo>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl TryFrom<Vec<SubjectInfo>> for SubjectRegistry {
+    type Error = String;
+
+    /// Registers each subject in turn, so that they keep their places as
+    /// IDs. Two subjects with the same name would share an ID, so they are
+    /// refused.
+    fn try_from(subjects: Vec<SubjectInfo>) -> Result<Self, Self::Error> {
+        let mut registry = SubjectRegistry::new();
+        for info in subjects {
+            if registry.id(&info.name).is_some() {
+                return Err(format!("there are two subjects called {}", info.name));
+            }
+            registry.insert(info);
+        }
+        Ok(registry)
+    }
+}
+
+impl From<SubjectRegistry> for Vec<SubjectInfo> {
+    fn from(registry: SubjectRegistry) -> Self {
+        registry.subjects
+    }
+}
+
+/// The number of school days in each week of the cycle.
+pub const DAYS_PER_WEEK: usize = 5;
+
+/// How often the timetable repeats.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WeekCycle {
+    /// Every week has the same timetable.
+    Weekly,
+    /// Week A and week B alternate, each with a timetable of its own.
+    Fortnightly,
+}
+
+impl WeekCycle {
+    pub fn weeks(self) -> usize {
+        match self {
+            WeekCycle::Weekly => 1,
+            WeekCycle::Fortnightly => 2,
+        }
+    }
+}
+
+impl Default for WeekCycle {
+    fn default() -> Self {
+        WeekCycle::Weekly
+    }
+}
+
+/// How students and teachers get between lessons of subjects taught at
+/// different sites on the same day.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SiteTravel {
+    /// Lessons at different sites may be back to back.
+    Anytime,
+    /// At least this many free periods are needed between lessons at
+    /// different sites.
+    FreePeriods(u8),
+    /// Nobody has lessons at more than one site in a day.
+    OneSitePerDay,
+}
+
+impl Default for SiteTravel {
+    fn default() -> Self {
+        SiteTravel::Anytime
+    }
+}
+
+/// Where a slot falls in the year, with everything counted from zero. Slots
+/// run through each day of week A, then each day of week B, with the whole
+/// cycle repeated for each term.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlotPosition {
+    pub term: usize,
+    pub week: usize,
+    /// The day of the week, so Monday of week B is day 0.
+    pub day: usize,
+    pub period: usize,
+}
+
+impl SlotPosition {
+    pub fn of(slot: usize, daily_lesson_capacity: u8, cycle: WeekCycle) -> SlotPosition {
+        let daily_lesson_capacity = usize::from(daily_lesson_capacity);
+        let day_of_year = slot / daily_lesson_capacity;
+        let week_of_year = day_of_year / DAYS_PER_WEEK;
+        SlotPosition {
+            term: week_of_year / cycle.weeks(),
+            week: week_of_year % cycle.weeks(),
+            day: day_of_year % DAYS_PER_WEEK,
+            period: slot % daily_lesson_capacity,
+        }
+    }
+}
+
+/// A soft preference about when a subject or teacher is taught. Days and
+/// periods are counted from zero, so `AvoidPeriod(0)` means "not first
+/// period".
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SlotPreference {
+    /// Avoid this period on every day.
+    AvoidPeriod(u8),
+    /// Avoid every period of this day.
+    AvoidDay(u8),
+    /// Prefer periods before this one, e.g. `Before(3)` for the morning.
+    Before(u8),
+    /// Prefer this period or any later one.
+    NotBefore(u8),
+}
+
+impl SlotPreference {
+    fn is_met(&self, slot: usize, daily_lesson_capacity: u8) -> bool {
+        // The day and period don't depend on the cycle.
+        let SlotPosition { day, period, .. } =
+            SlotPosition::of(slot, daily_lesson_capacity, WeekCycle::Weekly);
+        match *self {
+            SlotPreference::AvoidPeriod(avoided) => period != usize::from(avoided),
+            SlotPreference::AvoidDay(avoided) => day != usize::from(avoided),
+            SlotPreference::Before(end) => period < usize::from(end),
+            SlotPreference::NotBefore(start) => period >= usize::from(start),
+        }
+    }
+}
+
+/// The details of a subject. Subjects are registered with no special
+/// requirements, which these then add to.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubjectInfo {
+    /// Only `SubjectRegistry::rename` changes this, so that the registry can
+    /// still find the subject by name.
+    name: String,
+    pub slot_preferences: Vec<SlotPreference>,
+    /// The type of room, such as "lab" or "gym", the subject must be taught
+    /// in. Subjects without one can use any room.
+    pub room_type: Option<String>,
+    /// The most groups this subject may be split into, overriding
+    /// `TimetableInfo::max_groups`.
+    pub max_groups: Option<u8>,
+    /// The most students a group of this subject may have, overriding
+    /// `TimetableInfo::max_group_size`.
+    pub max_group_size: Option<u16>,
+    /// How many lessons of the subject each student has in week A and in week
+    /// B. Week B is ignored when the timetable is weekly.
+    pub lessons_per_week: [u8; 2],
+    /// The term the subject runs in, for courses which only last part of the
+    /// year. Subjects without one run in every term.
+    pub term: Option<u8>,
+    /// The site the subject is taught at, for schools split across campuses.
+    /// Each group of a subject without one is kept at the site of the rooms
+    /// it is given.
+    pub site: Option<String>,
+}
+
+impl SubjectInfo {
+    pub fn new(name: impl Into<String>) -> SubjectInfo {
+        SubjectInfo {
+            name: name.into(),
+            slot_preferences: Vec::new(),
+            room_type: None,
+            max_groups: None,
+            max_group_size: None,
+            lessons_per_week: [1, 1],
+            term: None,
+            site: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// How many of this subject's slot preferences a lesson in `slot` breaks.
+    pub fn slot_penalty(&self, slot: usize, daily_lesson_capacity: u8) -> u32 {
+        self.slot_preferences
+            .iter()
+            .filter(|preference| !preference.is_met(slot, daily_lesson_capacity))
+            .count() as u32
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone)]
+pub struct RoomInfo {
+    pub id: String,
+    /// The kind of specialist room this is, if any.
+    pub room_type: Option<String>,
+    /// The site the room is at. Subjects taught at a site must use a room
+    /// there.
+    pub site: Option<String>,
+}
+
+impl RoomInfo {
+    pub fn new(id: impl Into<String>, room_type: Option<String>) -> RoomInfo {
+        RoomInfo {
+            id: id.into(),
+            room_type,
+            site: None,
+        }
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone)]
+pub struct TeacherInfo {
+    pub id: String,
+    /// The subjects this teacher is able to teach.
+    pub subjects: Vec<SubjectId>,
+    /// The most lessons the teacher may be given in a week.
+    pub max_weekly_lessons: Option<u8>,
+    /// The most lessons the teacher may be given back to back, overriding
+    /// `TimetableInfo::max_consecutive_lessons`.
+    pub max_consecutive_lessons: Option<u8>,
+    /// When the teacher would rather teach. Groups are given to teachers
+    /// whose preferences they break least, but a teacher can still be given
+    /// lessons they would rather not teach.
+    pub slot_preferences: Vec<SlotPreference>,
+    /// The days of the week the teacher doesn't work, counted from zero, for
+    /// part-time teachers. The teacher is never given a group with a lesson
+    /// on one of them.
+    pub days_off: Vec<u8>,
+}
+
+impl TeacherInfo {
+    pub fn new(id: impl Into<String>, subjects: Vec<SubjectId>) -> TeacherInfo {
+        TeacherInfo {
+            id: id.into(),
+            subjects,
+            max_weekly_lessons: None,
+            max_consecutive_lessons: None,
+            slot_preferences: Vec::new(),
+            days_off: Vec::new(),
+        }
+    }
+
+    /// Whether `slot` falls on one of the teacher's days off.
+    pub fn is_off(&self, slot: usize, daily_lesson_capacity: u8) -> bool {
+        let day = SlotPosition::of(slot, daily_lesson_capacity, WeekCycle::Weekly).day;
+        self.days_off
+            .iter()
+            .any(|&day_off| usize::from(day_off) == day)
+    }
+
+    /// How many of this teacher's slot preferences a lesson in `slot` breaks.
+    pub fn slot_penalty(&self, slot: usize, daily_lesson_capacity: u8) -> u32 {
+        self.slot_preferences
+            .iter()
+            .filter(|preference| !preference.is_met(slot, daily_lesson_capacity))
+            .count() as u32
+    }
+
+    fn can_teach(&self, subject: SubjectId) -> bool {
+        self.subjects.contains(&subject)
+    }
+
+    /// Whether the teacher may be given `weekly_lessons` lessons in a week.
+    fn has_capacity(&self, weekly_lessons: usize) -> bool {
+        match self.max_weekly_lessons {
+            Some(max) => weekly_lessons <= usize::from(max),
+            None => true,
+        }
+    }
+}
+
+/// Something held at the same time every cycle which students can't have
+/// lessons during, such as an assembly or form time.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone)]
+pub struct FixedEvent {
+    pub name: String,
+    /// The slots of the cycle the event takes, which it takes in every term.
+    pub slots: Vec<usize>,
+    /// The cohorts attending the event. When this is empty, the whole school
+    /// attends.
+    pub cohorts: Vec<String>,
+}
+
+impl FixedEvent {
+    pub fn new(name: impl Into<String>, slots: Vec<usize>) -> FixedEvent {
+        FixedEvent {
+            name: name.into(),
+            slots,
+            cohorts: Vec::new(),
+        }
+    }
+
+    /// Whether students of `cohort` attend the event. Students without a
+    /// cohort only attend whole school events.
+    pub fn is_attended_by(&self, cohort: Option<&str>) -> bool {
+        self.cohorts.is_empty()
+            || cohort.map_or(false, |cohort| {
+                self.cohorts.iter().any(|attending| attending == cohort)
+            })
+    }
+}
+
+/// Limits on how many lessons each student has in a day, so that nobody has
+/// a packed day and an empty one in the same week.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Default)]
+pub struct DailyLessonBounds {
+    /// The fewest lessons a student should have on a day. Each lesson short
+    /// of this counts against the timetable.
+    pub min: u8,
+    /// The fewest lessons a student must have on a day, leaving out slots
+    /// taken by the events they attend. Students are placed one at a time,
+    /// so the solver works towards this as it does `min`, and fails with
+    /// `TimetableError::TooFewDailyLessons` if a day still falls short.
+    pub hard_min: u8,
+    /// The most lessons a student should have on a day. Each lesson over this
+    /// counts against the timetable.
+    pub max: Option<u8>,
+    /// The most lessons a student may have on a day. The solver never gives
+    /// a student more.
+    pub hard_max: Option<u8>,
+}
+
+impl DailyLessonBounds {
+    /// How many lessons a day with `lessons` lessons is outside the soft
+    /// bounds, or short of the hard minimum, by.
+    pub fn penalty(&self, lessons: usize) -> usize {
+        usize::from(self.min.max(self.hard_min)).saturating_sub(lessons)
+            + self.max.map_or(0, |max| lessons.saturating_sub(max.into()))
+    }
+}
+
+/// Toggles for the optimisation passes run after the initial placement.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone)]
+pub struct Objectives {
+    /// Move students between groups of the same subject so that group sizes
+    /// end up as even as possible.
+    pub balance_group_sizes: bool,
+    /// Move students between groups of the same subject so that their free
+    /// periods sit at the start or end of the day rather than between lessons.
+    pub minimize_gaps: bool,
+    /// Once the other objectives are done, repeatedly move single lessons to
+    /// better slots and students to better groups until neither improves the
+    /// timetable. This fixes many awkward placements left by placing students
+    /// one at a time, at the cost of a slower solve.
+    pub local_search: bool,
+    /// Move lessons so that each student has a similar number of lessons on
+    /// every day of the week, as long as the timetable scores no worse. This
+    /// also applies during local search.
+    pub balance_daily_workload: bool,
+    /// Give each group to the teacher it leaves with the fewest free periods
+    /// between lessons, after their slot preferences, rather than to the
+    /// least loaded teacher.
+    pub compact_teacher_days: bool,
+}
+
+/// The order students are given their groups in. The solver is greedy, so
+/// students placed early get the most freedom.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StudentOrdering {
+    /// The order of `TimetableInfo::students`.
+    AsGiven,
+    /// Students taking the most subjects first, then those taking the rarest
+    /// subject, as they are the hardest to fit in once groups fill up. Ties
+    /// go in ID order.
+    MostConstrainedFirst,
+    /// Students in an order shuffled by the seed. Different seeds often give
+    /// different timetables, and the same seed always gives the same one.
+    Shuffled(u64),
+}
+
+impl Default for StudentOrdering {
+    fn default() -> Self {
+        StudentOrdering::MostConstrainedFirst
+    }
+}
+
+/// How the solver searches for a timetable.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Place students one at a time, then improve the timetable with the
+    /// objectives. This is fast but may miss the best timetable.
+    Greedy,
+    /// Solve greedily, then search every way of grouping the students for a
+    /// better timetable, skipping any which can't beat the best found so far.
+    /// The search gives up after a set amount of work, which is often before
+    /// it finishes for schools of more than about ten students, so this is
+    /// only worth using for the smallest schools.
+    /// `SolveStats::proven_optimal` tells whether the search finished.
+    Exhaustive,
+}
+
+impl Default for Algorithm {
+    fn default() -> Self {
+        Algorithm::Greedy
+    }
+}
+
+/// How much each kind of flaw counts against a timetable, so schools can
+/// decide what matters most to them. A weight of zero ignores the flaw.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone)]
+pub struct Weights {
+    /// Each free period between two lessons on the same day.
+    pub gap: u32,
+    /// Each student of difference between the sizes of groups of a subject.
+    pub group_imbalance: u32,
+    /// Each point of soft constraint penalty, such as a broken slot
+    /// preference.
+    pub slot_preference: u32,
+    /// Each student whose timetable changes, when comparing with an earlier
+    /// timetable.
+    pub disruption: u32,
+    /// Each lesson a student's day is outside the soft bounds of
+    /// `TimetableInfo::daily_lessons` by.
+    pub daily_lessons: u32,
+    /// Each slot preference of a teacher broken by a lesson they are given.
+    pub teacher_preference: u32,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            gap: 1,
+            group_imbalance: 1,
+            slot_preference: 1,
+            disruption: 1,
+            daily_lessons: 1,
+            teacher_preference: 1,
+        }
+    }
+}
+
+impl Default for Objectives {
+    fn default() -> Self {
+        Objectives {
+            balance_group_sizes: true,
+            minimize_gaps: true,
+            local_search: true,
+            balance_daily_workload: false,
+            compact_teacher_days: false,
+        }
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone)]
+pub struct TimetableInfo {
+    /// The most groups any subject may be split into, unless the subject has
+    /// its own limit.
+    pub max_groups: u8,
+    pub students: Vec<StudentInfo>,
+    pub daily_lesson_capacity: u8,
+    /// Every subject taken by a student or taught by a teacher, whose IDs
+    /// they refer to it by.
+    pub subjects: SubjectRegistry,
+    /// The rooms groups can be taught in. When this is empty, rooms aren't
+    /// assigned at all.
+    pub rooms: Vec<RoomInfo>,
+    /// The teachers who can be given groups. When this is empty, teachers
+    /// aren't assigned at all.
+    pub teachers: Vec<TeacherInfo>,
+    /// The most lessons any teacher may be given back to back, unless the
+    /// teacher has their own limit.
+    pub max_consecutive_lessons: Option<u8>,
+    /// The most groups, across every subject, which may be taught in the same
+    /// slot, such as when only so many rooms or teachers are free at once.
+    /// When this is `None`, any number may be.
+    pub max_groups_per_slot: Option<u8>,
+    /// The most students any group may have, unless the subject has its own
+    /// limit. When a group is full and another is made, the full group's
+    /// students are shared with the new one. When this is `None`, groups may
+    /// be any size.
+    pub max_group_size: Option<u16>,
+    pub daily_lessons: DailyLessonBounds,
+    /// The most students one teacher may supervise in an exam.
+    pub students_per_invigilator: u8,
+    pub site_travel: SiteTravel,
+    /// Events which take slots away from lessons for the students attending
+    /// them.
+    pub fixed_events: Vec<FixedEvent>,
+    pub cycle: WeekCycle,
+    /// The number of terms the year is split into. Subjects which run in
+    /// different terms can share a slot.
+    pub terms: u8,
+    pub objectives: Objectives,
+    pub weights: Weights,
+    pub student_ordering: StudentOrdering,
+    pub algorithm: Algorithm,
+    /// Extra rules checked whenever lessons are placed, on top of the
+    /// built-in ones.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub constraints: Vec<Arc<dyn Constraint>>,
+}
+
+impl TimetableInfo {
+    /// A timetable without any students yet, which `add_student` adds.
+    pub fn new(max_groups: u8, daily_lesson_capacity: u8) -> TimetableInfo {
+        TimetableInfo {
+            max_groups,
+            students: Vec::new(),
+            daily_lesson_capacity,
+            subjects: SubjectRegistry::new(),
+            rooms: Vec::new(),
+            teachers: Vec::new(),
+            max_consecutive_lessons: None,
+            max_groups_per_slot: None,
+            max_group_size: None,
+            daily_lessons: DailyLessonBounds::default(),
+            students_per_invigilator: 30,
+            site_travel: SiteTravel::default(),
+            fixed_events: Vec::new(),
+            cycle: WeekCycle::Weekly,
+            terms: 1,
+            objectives: Objectives::default(),
+            weights: Weights::default(),
+            student_ordering: StudentOrdering::default(),
+            algorithm: Algorithm::default(),
+            constraints: Vec::new(),
+        }
+    }
+
+    /// Adds a student taking the subjects called `subjects`, registering any
+    /// which are new.
+    pub fn add_student(
+        &mut self,
+        id: impl Into<StudentId>,
+        subjects: &[impl AsRef<str>],
+    ) -> &mut StudentInfo {
+        let subjects = subjects
+            .iter()
+            .map(|subject| self.subjects.register(subject.as_ref()))
+            .collect();
+        self.students.push(StudentInfo::new(id, subjects));
+        self.students.last_mut().unwrap()
+    }
+
+    /// The site `subject` is taught at, if it has one.
+    pub fn site_of(&self, subject: SubjectId) -> Option<&str> {
+        self.subjects[subject].site.as_deref()
+    }
+
+    /// The site a lesson of `subject` is at: the site of `room_id`, the room
+    /// it was given, or the subject's own site if it wasn't given one.
+    pub fn site_of_lesson(&self, subject: SubjectId, room_id: Option<&str>) -> Option<&str> {
+        match room_id.and_then(|room_id| self.rooms.iter().find(|room| room.id == room_id)) {
+            Some(room) => room.site.as_deref(),
+            None => self.site_of(subject),
+        }
+    }
+
+    /// Whether someone can have a lesson of `subject` in `slot` and one of
+    /// `other` in `other_slot`, given the sites they are taught at.
+    pub fn can_travel_between(
+        &self,
+        (subject, slot): (SubjectId, usize),
+        (other, other_slot): (SubjectId, usize),
+    ) -> bool {
+        self.can_travel_between_sites(
+            (self.site_of(subject), slot),
+            (self.site_of(other), other_slot),
+        )
+    }
+
+    /// Whether someone can have a lesson at `site` in `slot` and one at
+    /// `other_site` in `other_slot`. Lessons at no particular site can be
+    /// got to from anywhere.
+    pub fn can_travel_between_sites(
+        &self,
+        (site, slot): (Option<&str>, usize),
+        (other_site, other_slot): (Option<&str>, usize),
+    ) -> bool {
+        let daily_lesson_capacity = usize::from(self.daily_lesson_capacity);
+        if self.site_travel == SiteTravel::Anytime
+            || slot / daily_lesson_capacity != other_slot / daily_lesson_capacity
+        {
+            return true;
+        }
+        match (site, other_site) {
+            (Some(site), Some(other_site)) if site != other_site => match self.site_travel {
+                SiteTravel::Anytime => true,
+                SiteTravel::FreePeriods(periods) => {
+                    slot.max(other_slot) - slot.min(other_slot) > usize::from(periods)
+                }
+                SiteTravel::OneSitePerDay => false,
+            },
+            _ => true,
+        }
+    }
+
+    /// The most groups `subject` may be split into.
+    pub fn max_groups_for(&self, subject: SubjectId) -> u8 {
+        self.subjects[subject].max_groups.unwrap_or(self.max_groups)
+    }
+
+    /// The most students a group of `subject` may have, if there is a limit.
+    pub fn max_group_size_for(&self, subject: SubjectId) -> Option<usize> {
+        self.subjects[subject]
+            .max_group_size
+            .or(self.max_group_size)
+            .map(usize::from)
+    }
+
+    pub fn slots_per_week(&self) -> usize {
+        usize::from(self.daily_lesson_capacity) * DAYS_PER_WEEK
+    }
+
+    /// The number of slots across the whole cycle.
+    pub fn total_slots(&self) -> usize {
+        self.slots_per_week() * self.cycle.weeks()
+    }
+
+    /// The number of slots across every term of the year.
+    pub fn slots_per_year(&self) -> usize {
+        self.total_slots() * usize::from(self.terms)
+    }
+
+    /// The terms in which `subject` is taught.
+    pub fn terms_of(&self, subject: SubjectId) -> Range<usize> {
+        match self.subjects[subject].term {
+            Some(term) => usize::from(term)..usize::from(term) + 1,
+            None => 0..usize::from(self.terms),
+        }
+    }
+
+    /// How many lessons of `subject` each student has in the given week of the
+    /// cycle.
+    pub fn lessons_in_week(&self, subject: SubjectId, week: usize) -> u8 {
+        self.subjects[subject].lessons_per_week[week]
+    }
+
+    fn placement<'a>(
+        &'a self,
+        subject: SubjectId,
+        slot: usize,
+        student: Option<StudentContext<'a>>,
+        groups: Option<GroupContext<'a>>,
+    ) -> Placement<'a> {
+        Placement {
+            subject,
+            slot,
+            position: SlotPosition::of(slot, self.daily_lesson_capacity, self.cycle),
+            timetable_info: self,
+            student,
+            groups,
+        }
+    }
+
+    /// Removes every subject `keep` is false for, such as those nobody takes
+    /// any more, from the registry, the students and the teachers, and gives
+    /// the rest their new IDs. Constraints aren't changed.
+    pub fn retain_subjects(&mut self, keep: impl FnMut(SubjectId) -> bool) {
+        let new_ids = self.subjects.retain(keep);
+        let remap = |subjects: &mut Vec<SubjectId>| {
+            *subjects = subjects
+                .iter()
+                .filter_map(|subject| new_ids[subject.0])
+                .collect();
+        };
+        for student in &mut self.students {
+            remap(&mut student.subjects);
+        }
+        for teacher in &mut self.teachers {
+            remap(&mut teacher.subjects);
+        }
+    }
+
+    /// Takes `subject` off the subjects of the student with the ID.
+    fn drop_subject(&mut self, student_id: &StudentId, subject: SubjectId) {
+        for student in &mut self.students {
+            if student.id == *student_id {
+                student.subjects.retain(|&taken| taken != subject);
+            }
+        }
+    }
+
+    /// The indices of `students` in the order they should be placed. Apart
+    /// from `StudentOrdering::AsGiven`, this doesn't depend on the order the
+    /// students were given in, so neither does the timetable.
+    fn student_order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.students.len()).collect();
+        if self.student_ordering != StudentOrdering::AsGiven {
+            order.sort_by(|&a, &b| self.students[a].id.cmp(&self.students[b].id));
+        }
+        match self.student_ordering {
+            StudentOrdering::AsGiven => {}
+            StudentOrdering::MostConstrainedFirst => {
+                let mut takers = vec![0; self.subjects.len()];
+                for student in &self.students {
+                    for subject in &student.subjects {
+                        takers[subject.0] += 1;
+                    }
+                }
+                // The sort is stable, so equally constrained students stay in
+                // ID order.
+                order.sort_by_cached_key(|&student_idx| {
+                    let subjects = &self.students[student_idx].subjects;
+                    let rarest = subjects
+                        .iter()
+                        .map(|subject| takers[subject.0])
+                        .min()
+                        .unwrap_or(0);
+                    (std::cmp::Reverse(subjects.len()), rarest)
+                });
+            }
+            StudentOrdering::Shuffled(seed) => {
+                // A Fisher-Yates shuffle.
+                let mut rng = generate::Rng::new(seed);
+                for idx in (1..order.len()).rev() {
+                    order.swap(idx, rng.in_range(&(0..=idx)));
+                }
+            }
+        }
+        order
+    }
+
+    fn all_constraints(&self) -> impl Iterator<Item = &dyn Constraint> {
+        constraint::BUILT_IN_CONSTRAINTS.iter().copied().chain(
+            self.constraints
+                .iter()
+                .map(|constraint| constraint.as_ref()),
+        )
+    }
+
+    /// Whether every hard constraint lets a lesson of `subject` go in `slot`.
+    fn allows(
+        &self,
+        subject: SubjectId,
+        slot: usize,
+        student: Option<StudentContext<'_>>,
+        groups: Option<GroupContext<'_>>,
+    ) -> bool {
+        let placement = self.placement(subject, slot, student, groups);
+        self.all_constraints()
+            .all(|constraint| constraint.check(&placement))
+    }
+
+    /// Whether every hard constraint lets the student have the lessons of
+    /// `subject` in `slots`. Each lesson is checked with the ones before it in
+    /// place, so that rules about the student's day see all of them.
+    fn allows_lessons(
+        &self,
+        subject: SubjectId,
+        slots: &[usize],
+        student: StudentContext<'_>,
+        groups: Option<GroupContext<'_>>,
+    ) -> bool {
+        // Constraints only look at the subjects of other lessons, so the group
+        // doesn't matter.
+        let lesson = Some((subject, 0));
+        let mut trial = student.slots.to_vec();
+        slots.iter().all(|&slot| {
+            let student = StudentContext {
+                slots: &trial,
+                ..student
+            };
+            let allowed = self.allows(subject, slot, Some(student), groups);
+            trial[slot] = lesson;
+            allowed
+        })
+    }
+
+    /// The total of the soft constraints' penalties for a lesson of `subject`
+    /// in `slot`.
+    fn slot_penalty(
+        &self,
+        subject: SubjectId,
+        slot: usize,
+        student: Option<StudentContext<'_>>,
+    ) -> u32 {
+        let placement = self.placement(subject, slot, student, None);
+        self.all_constraints()
+            .map(|constraint| constraint.penalty(&placement))
+            .sum()
+    }
+
+    fn group_penalty(
+        &self,
+        subject: SubjectId,
+        group: &Group,
+        student: Option<StudentContext<'_>>,
+    ) -> u32 {
+        // A group has the same lessons in every term it runs in, so we only
+        // look at the first term's.
+        let lessons_per_term = group.slots.len() / self.terms_of(subject).len();
+        group
+            .slots
+            .iter()
+            .take(lessons_per_term)
+            .map(|&slot| self.slot_penalty(subject, slot, student))
+            .sum()
+    }
+}
+
+/// The timetable of the student being placed, for constraints which look at
+/// the student's other lessons.
+#[derive(Clone, Copy)]
+struct StudentContext<'a> {
+    slots: &'a [Option<(SubjectId, usize)>],
+    cohort: Option<&'a str>,
+}
+
+/// The groups placed so far, for constraints which look at the other groups
+/// taught in a slot or at the group a student joins.
+#[derive(Clone, Copy)]
+struct GroupContext<'a> {
+    groups_by_subject: &'a BTreeMap<SubjectId, Vec<Group>>,
+    /// The group the lesson is of, once it exists.
+    group: Option<(SubjectId, usize)>,
+    /// Whether a student is joining `group`, whose lessons are already in
+    /// place, rather than a lesson being put in the slot.
+    joining: bool,
+}
+
+impl<'a> GroupContext<'a> {
+    /// For the lessons of a group which hasn't been made yet.
+    fn new_group(groups_by_subject: &'a BTreeMap<SubjectId, Vec<Group>>) -> GroupContext<'a> {
+        GroupContext {
+            groups_by_subject,
+            group: None,
+            joining: false,
+        }
+    }
+
+    /// For moving the lessons of `group`.
+    fn moving(
+        groups_by_subject: &'a BTreeMap<SubjectId, Vec<Group>>,
+        group: (SubjectId, usize),
+    ) -> GroupContext<'a> {
+        GroupContext {
+            group: Some(group),
+            ..GroupContext::new_group(groups_by_subject)
+        }
+    }
+
+    /// For a student joining `group`.
+    fn joining(
+        groups_by_subject: &'a BTreeMap<SubjectId, Vec<Group>>,
+        group: (SubjectId, usize),
+    ) -> GroupContext<'a> {
+        GroupContext {
+            joining: true,
+            ..GroupContext::moving(groups_by_subject, group)
+        }
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone)] // Allow the struct to be printed for debugging.
+pub struct Student {
+    slots: Vec<Option<(SubjectId, usize)>>,
+    id: StudentId,
+    cohort: Option<String>,
+}
+
+impl Student {
+    fn context(&self) -> StudentContext<'_> {
+        StudentContext {
+            slots: &self.slots,
+            cohort: self.cohort.as_deref(),
+        }
+    }
+}
+
+/// Measurements describing the quality of a solved timetable.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Default, PartialEq)]
+pub struct Metrics {
+    /// The largest difference in size between two groups of the same subject
+    /// straight after the initial placement.
+    pub group_size_spread_before: usize,
+    /// The same measurement once the objectives have been applied.
+    pub group_size_spread_after: usize,
+    /// The total number of free periods which fall between two lessons on the
+    /// same day, summed over all students, straight after the initial
+    /// placement.
+    pub student_gaps_before: usize,
+    /// The same measurement once the objectives have been applied.
+    pub student_gaps_after: usize,
+    /// The total penalty of the soft constraints, such as subject slot
+    /// preferences, broken by the timetable, counted once per group.
+    pub slot_preference_penalty: u32,
+    /// The number of lessons by which students' days fall outside the soft
+    /// bounds of `TimetableInfo::daily_lessons`, summed over every day of
+    /// every student.
+    pub daily_lesson_penalty: usize,
+    /// The variance of the number of lessons a student has on each day of a
+    /// week, averaged over every week of every student. Zero means every
+    /// student has the same number of lessons on each day of a week.
+    pub daily_workload_variance: f64,
+    /// The total number of teachers' slot preferences broken by the lessons
+    /// they were given, counted once per group.
+    pub teacher_preference_penalty: u32,
+    /// The free periods each teacher has between two lessons on the same day,
+    /// by teacher ID.
+    pub teacher_idle_periods: BTreeMap<String, usize>,
+    /// The teachers who were given as many lessons as their weekly load
+    /// allows in at least one week of the cycle.
+    pub teachers_at_capacity: Vec<String>,
+}
+
+impl Metrics {
+    /// A single measure of how good the timetable is, adding up the gaps,
+    /// group size spread, soft constraint penalty, daily lesson penalty and
+    /// teacher preference penalty once the objectives have been applied, each
+    /// multiplied by its weight. Lower is better.
+    pub fn score(&self, weights: &Weights) -> u64 {
+        self.student_gaps_after as u64 * u64::from(weights.gap)
+            + self.group_size_spread_after as u64 * u64::from(weights.group_imbalance)
+            + u64::from(self.slot_preference_penalty) * u64::from(weights.slot_preference)
+            + self.daily_lesson_penalty as u64 * u64::from(weights.daily_lessons)
+            + u64::from(self.teacher_preference_penalty) * u64::from(weights.teacher_preference)
+    }
+}
+
+/// The name of a group of a subject, lettered in order from "A", so group 1
+/// of Maths is "Maths B". After "Z" come "AA", "AB" and so on.
+pub fn group_name(subject: &str, group_idx: usize) -> String {
+    let mut letters = Vec::new();
+    let mut remaining = group_idx + 1;
+    while remaining > 0 {
+        remaining -= 1;
+        letters.push(char::from(b'A' + (remaining % 26) as u8));
+        remaining /= 26;
+    }
+    format!("{} {}", subject, letters.iter().rev().collect::<String>())
+}
+
+/// Counts of the work done while solving a timetable.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SolveStats {
+    /// How long solving took. This isn't measured on the web, where there is
+    /// no clock to read.
+    pub wall_time: Option<Duration>,
+    /// The number of times a subject was placed for a student.
+    pub placements: usize,
+    /// The number of lessons of existing groups which were moved to make room
+    /// for a student.
+    pub groups_moved: usize,
+    /// The number of times a group couldn't be made to fit a student, so the
+    /// moves carried on from the next group instead.
+    pub backtracks: usize,
+    /// Whether the timetable is known to have the best score possible. This
+    /// is only ever set by `Algorithm::Exhaustive`.
+    pub proven_optimal: bool,
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+// Allow the enum to be printed for debugging and compared in tests.
+#[derive(Debug, PartialEq)]
+// A result is only made once per solve, so its size doesn't matter.
+#[allow(clippy::large_enum_variant)]
+pub enum TimetableResult {
+    Solved {
+        /// The subjects taught in each slot, covering every term of the year
+        /// one after another. `SlotPosition` gives the term of a slot.
+        subjects: Vec<Vec<SubjectId>>,
+        slots_by_student_id: BTreeMap<StudentId, Vec<Option<(SubjectId, usize)>>>,
+        /// The name of each group of each subject, by group index.
+        group_names: BTreeMap<SubjectId, Vec<String>>,
+        /// The room each subject is taught in, for every slot. This is empty
+        /// if no rooms were given.
+        rooms_by_slot: Vec<BTreeMap<SubjectId, String>>,
+        /// The subject and group taught in each room in every slot, in the
+        /// same form as `slots_by_student_id`. This is empty if no rooms were
+        /// given.
+        slots_by_room: BTreeMap<String, Vec<Option<(SubjectId, usize)>>>,
+        /// The teacher of each subject, for every slot. This is empty if no
+        /// teachers were given.
+        teachers_by_slot: Vec<BTreeMap<SubjectId, String>>,
+        /// Each teacher's subject and group in every slot, in the same form
+        /// as `slots_by_student_id`. This is empty if no teachers were given.
+        slots_by_teacher_id: BTreeMap<String, Vec<Option<(SubjectId, usize)>>>,
+        metrics: Metrics,
+        stats: SolveStats,
+    },
+    Unsolved(TimetableError),
+}
+
+impl TimetableResult {
+    /// The IDs of the students in each group of each subject, by group index
+    /// and in ID order. This is empty if the timetable wasn't solved.
+    pub fn rosters(&self) -> BTreeMap<SubjectId, Vec<Vec<StudentId>>> {
+        let mut rosters = BTreeMap::new();
+        if let TimetableResult::Solved {
+            slots_by_student_id,
+            group_names,
+            ..
+        } = self
+        {
+            for (&subject, names) in group_names {
+                rosters.insert(subject, vec![Vec::new(); names.len()]);
+            }
+            for (student_id, slots) in slots_by_student_id {
+                let mut student_groups: Vec<(SubjectId, usize)> = Vec::new();
+                for &entry in slots.iter().flatten() {
+                    if !student_groups.contains(&entry) {
+                        student_groups.push(entry);
+                    }
+                }
+                for (subject, group_idx) in student_groups {
+                    // Every group a student is in has a name, so it has a
+                    // roster. Students are visited in ID order, so the rosters
+                    // end up sorted.
+                    rosters.get_mut(&subject).unwrap()[group_idx].push(student_id.clone());
+                }
+            }
+        }
+        rosters
+    }
+
+    /// The number of groups across every subject. This is zero if the
+    /// timetable wasn't solved.
+    pub fn group_count(&self) -> usize {
+        match self {
+            TimetableResult::Solved { group_names, .. } => {
+                group_names.values().map(|names| names.len()).sum()
+            }
+            TimetableResult::Unsolved(_) => 0,
+        }
+    }
+}
+
+/// Why a timetable couldn't be solved.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TimetableError {
+    /// `subject` needed a new group but the student didn't have enough free
+    /// slots for its lessons, even after moving other lessons around.
+    #[error("{student_id} doesn't have enough free slots for a new group of {subject}")]
+    InsufficientSlots {
+        student_id: StudentId,
+        subject: String,
+    },
+    /// `subject` already had as many groups as it may have and none of them
+    /// could be made to fit the student.
+    #[error("{subject} already has as many groups as it may have and none fit {student_id}")]
+    GroupCapacityExhausted {
+        student_id: StudentId,
+        subject: String,
+    },
+    /// Every room compatible with `subject` was already in use in `slot`, or
+    /// was at a site the group's students couldn't get to.
+    #[error("no suitable room is free for {subject} in slot {slot}")]
+    NoRoomAvailable { subject: String, slot: usize },
+    /// Every teacher of `subject` was either teaching in `slot` already, had
+    /// reached their weekly load, or had taught too many lessons in a row.
+    #[error("no teacher is free to teach {subject} in slot {slot}")]
+    NoTeacherAvailable { subject: String, slot: usize },
+    /// No teacher could take a group of `subject` because its lesson in
+    /// `slot` falls on a day off of each of `teacher_ids`, who were otherwise
+    /// free to take it.
+    #[error(
+        "no teacher is free to teach {subject} in slot {slot}, as it is a day off for {}",
+        .teacher_ids.join(", ")
+    )]
+    TeachersOff {
+        subject: String,
+        slot: usize,
+        teacher_ids: Vec<String>,
+    },
+    /// A student has fewer lessons on the day starting at `slot` than
+    /// `DailyLessonBounds::hard_min` allows.
+    #[error(
+        "{student_id} only has {lessons} lessons on the day starting at slot {slot} but must have {min}"
+    )]
+    TooFewDailyLessons {
+        student_id: StudentId,
+        slot: usize,
+        lessons: usize,
+        min: u8,
+    },
+    /// The solve was stopped through its `CancellationToken` before it
+    /// finished.
+    #[error("solving was cancelled")]
+    Cancelled,
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Default, Debug, Clone)]
+struct Group {
+    /// The slots of the group's lessons across the year, term by term.
+    slots: Vec<usize>,
+    /// The same slots as a set, for quick clash checks.
+    busy: SlotSet,
+    student_idxs: Vec<usize>,
+}
+
+impl Group {
+    fn new(slots: Vec<usize>, slots_per_year: usize) -> Group {
+        Group {
+            busy: SlotSet::from_slots(&slots, slots_per_year),
+            slots,
+            student_idxs: Vec::new(),
+        }
+    }
+
+    /// Whether the group has as many students as `max_size` allows.
+    fn is_full(&self, max_size: Option<usize>) -> bool {
+        max_size.map_or(false, |max_size| self.student_idxs.len() >= max_size)
+    }
+}
+
+/// The timetable of the student being placed, along with the slots they are
+/// busy in as a set.
+struct PersonalTimetable {
+    slots: Vec<Option<(SubjectId, usize)>>,
+    busy: SlotSet,
+    cohort: Option<String>,
+}
+
+impl PersonalTimetable {
+    fn new(slots_per_year: usize) -> PersonalTimetable {
+        PersonalTimetable {
+            slots: vec![None; slots_per_year],
+            busy: SlotSet::new(slots_per_year),
+            cohort: None,
+        }
+    }
+
+    fn is_free(&self, slot: usize) -> bool {
+        !self.busy.contains(slot)
+    }
+
+    fn assign(&mut self, slot: usize, entry: (SubjectId, usize)) {
+        self.slots[slot] = Some(entry);
+        self.busy.insert(slot);
+    }
+
+    fn unassign(&mut self, slot: usize) {
+        self.slots[slot] = None;
+        self.busy.remove(slot);
+    }
+
+    /// Moves the lesson in `from` to the free slot `to`.
+    fn move_lesson(&mut self, from: usize, to: usize) {
+        self.slots[to] = self.slots[from].take();
+        self.busy.remove(from);
+        self.busy.insert(to);
+    }
+
+    fn context(&self) -> StudentContext<'_> {
+        StudentContext {
+            slots: &self.slots,
+            cohort: self.cohort.as_deref(),
+        }
+    }
+}
+
+/// What the solver has done so far, passed on to the observer if there is
+/// one.
+#[derive(Default)]
+struct SolveLog<'a> {
+    stats: SolveStats,
+    observer: Option<&'a mut dyn SolveObserver>,
+    cancellation: Option<&'a CancellationToken>,
+}
+
+impl SolveLog<'_> {
+    /// Records a decision. The event is only made when there is an observer,
+    /// so this costs nothing otherwise.
+    fn record(&mut self, event: impl FnOnce() -> TraceEvent) {
+        if let Some(observer) = &mut self.observer {
+            observer.decision(event());
+        }
+    }
+
+    fn phase_started(&mut self, phase: Phase) {
+        if let Some(observer) = &mut self.observer {
+            observer.phase_started(phase);
+        }
+    }
+
+    fn student_placed(&mut self, student_id: &StudentId, placed: usize, total: usize) {
+        if let Some(observer) = &mut self.observer {
+            observer.student_placed(student_id, placed, total);
+        }
+    }
+
+    fn improvement_found(&mut self, phase: Phase) {
+        if let Some(observer) = &mut self.observer {
+            observer.improvement_found(phase);
+        }
+    }
+
+    /// Whether the solve has been asked to stop. The optimisation passes and
+    /// the exhaustive search check this as they go and stop early, leaving
+    /// `check_cancelled` to fail the solve.
+    fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .map_or(false, |cancellation| cancellation.is_cancelled())
+    }
+
+    /// Fails with `TimetableError::Cancelled` once the solve has been asked
+    /// to stop.
+    fn check_cancelled(&self) -> Result<(), TimetableError> {
+        match self.is_cancelled() {
+            true => Err(TimetableError::Cancelled),
+            false => Ok(()),
+        }
+    }
+}
+
+fn attendance(
+    candidate: (SubjectId, usize),
+    groups_by_subject: &BTreeMap<SubjectId, Vec<Group>>,
+) -> usize {
+    groups_by_subject[&candidate.0]
+        .get(candidate.1)
+        .map(|group| group.student_idxs.len())
+        .unwrap_or_default()
+}
+
+fn try_assign_group_lazily(
+    groups_by_subject: &BTreeMap<SubjectId, Vec<Group>>,
+    personal: &mut PersonalTimetable,
+    subject: SubjectId,
+    timetable_info: &TimetableInfo,
+) -> bool {
+    // We iterate over each group of the subject that currently exists.
+    // For each, we check if it can be used, keeping the one whose lessons best
+    // fit the subject's slot preferences.
+    let mut best: Option<(u32, usize)> = None;
+    for (group_idx, group) in groups_by_subject
+        .get(&subject)
+        .unwrap_or(&Vec::new())
+        .iter()
+        .enumerate()
+    {
+        let student = personal.context();
+        let groups = GroupContext::joining(groups_by_subject, (subject, group_idx));
+        if !group_fits(group, personal)
+            || !timetable_info.allows_lessons(subject, &group.slots, student, Some(groups))
+        {
+            // The group has no places left, or one of its slots is taken by
+            // another subject or breaks a hard constraint for the student.
+            continue;
+        }
+        let penalty = timetable_info.group_penalty(subject, group, Some(student));
+        let is_better = match best {
+            Some((best_penalty, _)) => penalty < best_penalty,
+            None => true,
+        };
+        if is_better {
+            best = Some((penalty, group_idx));
+        }
+    }
+
+    if let Some((_, group_idx)) = best {
+        for &slot in &groups_by_subject[&subject][group_idx].slots {
+            personal.assign(slot, (subject, group_idx));
+        }
+        return true;
+    }
+
+    false
+}
+
+fn group_fits(group: &Group, personal: &PersonalTimetable) -> bool {
+    group.busy.is_disjoint(&personal.busy)
+}
+
+/// Picks the slots for a new group of `subject` from the student's free
+/// slots, giving each week of the cycle its own number of lessons. The
+/// lessons are repeated in each term the subject runs in. Returns `None` if
+/// the student doesn't have enough free slots.
+fn choose_new_group_slots(
+    groups_by_subject: &BTreeMap<SubjectId, Vec<Group>>,
+    personal: &PersonalTimetable,
+    subject: SubjectId,
+    timetable_info: &TimetableInfo,
+) -> Option<Vec<usize>> {
+    let groups = &groups_by_subject[&subject];
+    let new_lesson = Some((subject, groups.len()));
+    let new_group = Some(GroupContext::new_group(groups_by_subject));
+    // The student's timetable with the lessons chosen so far, so that rules
+    // about the student's day see them.
+    let mut trial = personal.slots.clone();
+    let slots_per_week = timetable_info.slots_per_week();
+    let total_slots = timetable_info.total_slots();
+    let daily_lesson_capacity = usize::from(timetable_info.daily_lesson_capacity);
+    let terms = timetable_info.terms_of(subject);
+    let mut chosen: Vec<usize> = Vec::new();
+    for week in 0..timetable_info.cycle.weeks() {
+        for _ in 0..timetable_info.lessons_in_week(subject, week) {
+            let student = Some(StudentContext {
+                slots: &trial,
+                ..personal.context()
+            });
+            let slot = (week * slots_per_week..(week + 1) * slots_per_week)
+                .filter(|&slot| {
+                    !chosen.contains(&slot)
+                        && terms.clone().all(|term| {
+                            let slot = term * total_slots + slot;
+                            personal.is_free(slot)
+                                && timetable_info.allows(subject, slot, student, new_group)
+                                // Only one group of a subject is taught at a
+                                // time.
+                                && !groups.iter().any(|group| group.busy.contains(slot))
+                        })
+                })
+                // We favour the subject's slot preferences, then days which
+                // don't have a lesson of the subject yet, then earlier slots.
+                .min_by_key(|&slot| {
+                    let same_day_lessons = chosen
+                        .iter()
+                        .filter(|&&other| {
+                            other / daily_lesson_capacity == slot / daily_lesson_capacity
+                        })
+                        .count();
+                    (
+                        timetable_info.slot_penalty(subject, slot, student),
+                        same_day_lessons,
+                    )
+                })?;
+            chosen.push(slot);
+            for term in terms.clone() {
+                trial[term * total_slots + slot] = new_lesson;
+            }
+        }
+    }
+
+    Some(
+        terms
+            .flat_map(|term| chosen.iter().map(move |&slot| term * total_slots + slot))
+            .collect(),
+    )
+}
+
+/// Finds a slot in the same week of the cycle as `from` that one lesson of a
+/// group could be moved to, in every term the group runs in, without clashing
+/// for the current student, any of the group's students, another group of
+/// the same subject, or the lessons of `target`, the group being made to fit
+/// the student. The slot returned is within the first term.
+#[allow(clippy::too_many_arguments)]
+fn find_slot_to_move_to(
+    groups_by_subject: &BTreeMap<SubjectId, Vec<Group>>,
+    personal: &PersonalTimetable,
+    (subject, group_idx): (SubjectId, usize),
+    target: (SubjectId, usize),
+    from: usize,
+    timetable_info: &TimetableInfo,
+    students: &[Student],
+) -> Option<usize> {
+    let slots_per_week = timetable_info.slots_per_week();
+    let total_slots = timetable_info.total_slots();
+    let week = from % total_slots / slots_per_week;
+    let student = Some(personal.context());
+    let groups = &groups_by_subject[&subject];
+    let moving = Some(GroupContext::moving(
+        groups_by_subject,
+        (subject, group_idx),
+    ));
+    let target = &groups_by_subject[&target.0][target.1];
+    (week * slots_per_week..(week + 1) * slots_per_week)
+        .filter(|&slot| {
+            timetable_info.terms_of(subject).all(|term| {
+                let slot = term * total_slots + slot;
+                personal.is_free(slot)
+                    // Moving the student's lesson into another of the target
+                    // group's slots would only swap one clash for another.
+                    && !target.busy.contains(slot)
+                    && timetable_info.allows(subject, slot, student, moving)
+                    && !groups.iter().any(|group| group.busy.contains(slot))
+                    // The rules about the slot's other groups were checked
+                    // above.
+                    && groups[group_idx].student_idxs.iter().all(|&student_idx| {
+                        let student = students[student_idx].context();
+                        student.slots[slot].is_none()
+                            && timetable_info.allows(subject, slot, Some(student), None)
+                    })
+            })
+        })
+        .min_by_key(|&slot| timetable_info.slot_penalty(subject, slot, student))
+}
+
+/// Moves the lesson of a group in `from` to the first term's slot `to`, in
+/// every term, for the group and everybody in it, apart from the student
+/// currently being placed. Returns the slots which were moved between.
+fn move_lesson(
+    groups_by_subject: &mut BTreeMap<SubjectId, Vec<Group>>,
+    (subject, group_idx): (SubjectId, usize),
+    from: usize,
+    to: usize,
+    total_slots: usize,
+    students: &mut [Student],
+) -> Vec<(usize, usize)> {
+    // We are `.get_mut(...).unwrap()`ing since currently we cannot index
+    // mutably into maps in Rust.
+    let group = &mut groups_by_subject.get_mut(&subject).unwrap()[group_idx];
+    let mut moves = Vec::new();
+    for slot in group
+        .slots
+        .iter_mut()
+        .filter(|slot| **slot % total_slots == from % total_slots)
+    {
+        let moved_to = *slot - *slot % total_slots + to;
+        moves.push((*slot, moved_to));
+        *slot = moved_to;
+    }
+    for &(from, to) in &moves {
+        group.busy.remove(from);
+        group.busy.insert(to);
+    }
+    for &student_idx in &group.student_idxs {
+        let slots = &mut students[student_idx].slots;
+        for &(from, to) in &moves {
+            slots[to] = slots[from].take();
+        }
+    }
+
+    moves
+}
+
+/// Tries to make one of the existing groups of `subject` fit the student by
+/// moving lessons which clash. For each clash, either the lesson of the
+/// subject's group or the student's clashing lesson is moved to one of their
+/// free slots, trying whichever group has fewer students first.
+fn try_make_room(
+    groups_by_subject: &mut BTreeMap<SubjectId, Vec<Group>>,
+    personal: &mut PersonalTimetable,
+    subject: SubjectId,
+    timetable_info: &TimetableInfo,
+    students: &mut [Student],
+    log: &mut SolveLog<'_>,
+) -> bool {
+    // Candidates include the student's groups and all groups of the current
+    // subject.
+    let mut candidates: Vec<(SubjectId, usize)> = Vec::new();
+    for &candidate in personal.slots.iter().flatten() {
+        if !candidates.contains(&candidate) {
+            candidates.push(candidate);
+        }
+    }
+    for group_idx in 0..groups_by_subject[&subject].len() {
+        candidates.push((subject, group_idx));
+    }
+
+    // Emptier groups are tried first, as moving their students disturbs the
+    // fewest other timetables.
+    candidates.sort_by_cached_key(|&candidate| attendance(candidate, groups_by_subject));
+    let rank = |candidate: (SubjectId, usize)| candidates.iter().position(|&c| c == candidate);
+
+    let max_size = timetable_info.max_group_size_for(subject);
+    for &(candidate_subject, group_idx) in &candidates {
+        if candidate_subject != subject || groups_by_subject[&subject][group_idx].is_full(max_size)
+        {
+            continue;
+        }
+
+        // Each move clears a clash without making another, so a move is never
+        // made twice. Should one repeat, the moves are going round in circles
+        // and the group can't be made to fit.
+        let mut moves_made: Vec<(usize, usize)> = Vec::new();
+        while let Some(from) = groups_by_subject[&subject][group_idx]
+            .busy
+            .first_shared(&personal.busy)
+        {
+            // We can unwrap this as we only look at slots which are taken.
+            let clashing = personal.slots[from].unwrap();
+            let mut options = [(subject, group_idx), clashing];
+            options.sort_by_key(|&option| rank(option));
+
+            let moved = options.iter().find_map(|&option| {
+                find_slot_to_move_to(
+                    groups_by_subject,
+                    personal,
+                    option,
+                    (subject, group_idx),
+                    from,
+                    timetable_info,
+                    students,
+                )
+                .map(|to| (option, to))
+            });
+            match moved {
+                Some((_, to)) if moves_made.contains(&(from, to)) => {
+                    log.stats.backtracks += 1;
+                    break;
+                }
+                Some((option, to)) => {
+                    moves_made.push((from, to));
+                    let moves = move_lesson(
+                        groups_by_subject,
+                        option,
+                        from,
+                        to,
+                        timetable_info.total_slots(),
+                        students,
+                    );
+                    log.stats.groups_moved += 1;
+                    log.record(|| TraceEvent::Moved {
+                        subject: timetable_info.subjects.name(option.0).to_string(),
+                        group_idx: option.1,
+                        from,
+                        to,
+                    });
+                    if option == clashing {
+                        // The student is in the clashing group, so their own
+                        // lessons move with it.
+                        for (from, to) in moves {
+                            personal.move_lesson(from, to);
+                        }
+                    }
+                }
+                // We can't clear this clash, so we try the next candidate.
+                None => {
+                    log.stats.backtracks += 1;
+                    log.record(|| TraceEvent::ClashNotMoved {
+                        subject: timetable_info.subjects.name(clashing.0).to_string(),
+                        group_idx: clashing.1,
+                        slot: from,
+                    });
+                    break;
+                }
+            }
+        }
+
+        let group = &groups_by_subject[&subject][group_idx];
+        if group_fits(group, personal)
+            && timetable_info.allows_lessons(
+                subject,
+                &group.slots,
+                personal.context(),
+                Some(GroupContext::joining(
+                    groups_by_subject,
+                    (subject, group_idx),
+                )),
+            )
+        {
+            for &slot in &group.slots {
+                personal.assign(slot, (subject, group_idx));
+            }
+            return true;
+        }
+    }
+
+    false
+}
+
+fn handle_subjects(
+    groups_by_subject: &mut BTreeMap<SubjectId, Vec<Group>>,
+    personal: &mut PersonalTimetable,
+    student_info: &StudentInfo,
+    timetable_info: &TimetableInfo,
+    students: &mut [Student],
+    log: &mut SolveLog<'_>,
+) -> Result<(), TimetableError> {
+    let joined = |personal: &PersonalTimetable, subject: SubjectId| {
+        // We can unwrap this as the student has just been given the subject.
+        let group_idx = personal
+            .slots
+            .iter()
+            .flatten()
+            .find(|&&(other, _)| other == subject)
+            .unwrap()
+            .1;
+        TraceEvent::Joined {
+            student_id: student_info.id.clone(),
+            subject: timetable_info.subjects.name(subject).to_string(),
+            group_idx,
+        }
+    };
+
+    for &subject in &student_info.subjects {
+        let name = timetable_info.subjects.name(subject);
+        log.stats.placements += 1;
+        if try_assign_group_lazily(groups_by_subject, personal, subject, timetable_info) {
+            // We don't need to continue as we could find a suitable group.
+            log.record(|| joined(personal, subject));
+            continue;
+        }
+
+        let group_count = groups_by_subject.entry(subject).or_default().len();
+        if group_count > 0 {
+            log.record(|| TraceEvent::NoGroupFits {
+                student_id: student_info.id.clone(),
+                subject: name.to_string(),
+            });
+        }
+        let is_at_capacity = group_count >= timetable_info.max_groups_for(subject).into();
+        if is_at_capacity {
+            log.record(|| TraceEvent::GroupLimitReached {
+                student_id: student_info.id.clone(),
+                subject: name.to_string(),
+            });
+        } else {
+            // Groups aren't at capacity, so we can create a new group in the
+            // student's free personal slots.
+            if let Some(slots) =
+                choose_new_group_slots(groups_by_subject, personal, subject, timetable_info)
+            {
+                let group_idx = group_count;
+                for &slot in &slots {
+                    personal.assign(slot, (subject, group_idx));
+                }
+                log.record(|| TraceEvent::Created {
+                    student_id: student_info.id.clone(),
+                    subject: name.to_string(),
+                    group_idx,
+                    slots: slots.clone(),
+                });
+                let groups = groups_by_subject.get_mut(&subject).unwrap();
+                groups.push(Group::new(slots, timetable_info.slots_per_year()));
+                split_full_group(groups, subject, group_idx, timetable_info, students, log);
+                continue;
+            }
+            log.record(|| TraceEvent::NoSlotsForNewGroup {
+                student_id: student_info.id.clone(),
+                subject: name.to_string(),
+            });
+        }
+
+        // Either groups are at capacity or the student doesn't have the free
+        // slots for a new one, so existing lessons need to be moved around.
+        if !try_make_room(
+            groups_by_subject,
+            personal,
+            subject,
+            timetable_info,
+            students,
+            log,
+        ) {
+            let student_id = student_info.id.clone();
+            let subject = name.to_string();
+            return Err(if is_at_capacity {
+                TimetableError::GroupCapacityExhausted {
+                    student_id,
+                    subject,
+                }
+            } else {
+                TimetableError::InsufficientSlots {
+                    student_id,
+                    subject,
+                }
+            });
+        }
+        log.record(|| joined(personal, subject));
+    }
+
+    Ok(())
+}
+
+fn make_global(
+    groups_by_subject: &mut BTreeMap<SubjectId, Vec<Group>>,
+    personal_slots: &[Option<(SubjectId, usize)>],
+    student_idx: usize,
+) {
+    for &(subject, group_idx) in personal_slots.iter().flatten() {
+        let group = &mut groups_by_subject.get_mut(&subject).unwrap()[group_idx];
+        // A group with several lessons a week appears in several slots, but
+        // the student should only be added to it once.
+        if !group.student_idxs.contains(&student_idx) {
+            group.student_idxs.push(student_idx);
+        }
+    }
+}
+
+fn group_size_spread(groups_by_subject: &BTreeMap<SubjectId, Vec<Group>>) -> usize {
+    groups_by_subject
+        .values()
+        .map(|groups| {
+            let sizes = groups.iter().map(|group| group.student_idxs.len());
+            sizes.clone().max().unwrap_or_default() - sizes.min().unwrap_or_default()
+        })
+        .max()
+        .unwrap_or_default()
+}
+
+/// Moves a student from one group of `subject` to another, where the student
+/// is known to be free for all of the new group's lessons.
+fn switch_group(
+    groups: &mut [Group],
+    subject: SubjectId,
+    (from, to): (usize, usize),
+    student_idx: usize,
+    slots: &mut [Option<(SubjectId, usize)>],
+) {
+    groups[from]
+        .student_idxs
+        .retain(|&other_student_idx| other_student_idx != student_idx);
+    for &slot in &groups[from].slots {
+        slots[slot] = None;
+    }
+    groups[to].student_idxs.push(student_idx);
+    for &slot in &groups[to].slots {
+        slots[slot] = Some((subject, to));
+    }
+}
+
+/// Shares the students of the biggest full group of `subject` with the group
+/// `new_idx`, which has just been made for the student being placed, so that
+/// a group which reached `TimetableInfo::max_group_size` is split in two.
+/// Students are only moved if they are free for the new group's lessons, and
+/// only until the two groups are as even as they can be.
+fn split_full_group(
+    groups: &mut [Group],
+    subject: SubjectId,
+    new_idx: usize,
+    timetable_info: &TimetableInfo,
+    students: &mut [Student],
+    log: &mut SolveLog<'_>,
+) {
+    let max_size = timetable_info.max_group_size_for(subject);
+    let full_idx = match (0..new_idx)
+        .filter(|&group_idx| groups[group_idx].is_full(max_size))
+        .max_by_key(|&group_idx| groups[group_idx].student_idxs.len())
+    {
+        Some(full_idx) => full_idx,
+        None => return,
+    };
+
+    let mut moved = 0;
+    // The student being placed joins the new group once they are placed, so
+    // it already counts as having one student.
+    while groups[full_idx].student_idxs.len() > groups[new_idx].student_idxs.len() + 2 {
+        let student_idx = groups[full_idx]
+            .student_idxs
+            .iter()
+            .copied()
+            .find(|&student_idx| {
+                let student = students[student_idx].context();
+                // The new group is the smaller one, so only the student's own
+                // rules need checking.
+                groups[new_idx]
+                    .slots
+                    .iter()
+                    .all(|&slot| student.slots[slot].is_none())
+                    && timetable_info.allows_lessons(subject, &groups[new_idx].slots, student, None)
+            });
+        match student_idx {
+            Some(student_idx) => {
+                switch_group(
+                    groups,
+                    subject,
+                    (full_idx, new_idx),
+                    student_idx,
+                    &mut students[student_idx].slots,
+                );
+                moved += 1;
+            }
+            None => break,
+        }
+    }
+    if moved > 0 {
+        log.record(|| TraceEvent::Split {
+            subject: timetable_info.subjects.name(subject).to_string(),
+            group_idx: full_idx,
+            new_group_idx: new_idx,
+            students_moved: moved,
+        });
+    }
+}
+
+fn balance_group_sizes(
+    groups_by_subject: &mut BTreeMap<SubjectId, Vec<Group>>,
+    students: &mut [Student],
+    timetable_info: &TimetableInfo,
+    log: &mut SolveLog<'_>,
+) {
+    for (&subject, groups) in groups_by_subject.iter_mut() {
+        if log.is_cancelled() {
+            return;
+        }
+        // Each move shrinks a group which is at least two bigger than the one
+        // it grows, so the sizes always get closer together and this ends.
+        loop {
+            let mut pairs = Vec::new();
+            for from in 0..groups.len() {
+                for to in 0..groups.len() {
+                    let (from_size, to_size) = (
+                        groups[from].student_idxs.len(),
+                        groups[to].student_idxs.len(),
+                    );
+                    if from_size > to_size + 1 {
+                        pairs.push((from_size - to_size, from, to));
+                    }
+                }
+            }
+            // We try to even out the most lopsided pair of groups first.
+            pairs.sort_by_key(|&(difference, _, _)| std::cmp::Reverse(difference));
+
+            let mut moved = None;
+            for (_, from, to) in pairs {
+                if let Some(&student_idx) = groups[from].student_idxs.iter().find(|&&student_idx| {
+                    let student = students[student_idx].context();
+                    // Students only move to smaller groups, so only their own
+                    // rules need checking.
+                    groups[to]
+                        .slots
+                        .iter()
+                        .all(|&slot| student.slots[slot].is_none())
+                        && timetable_info.allows_lessons(subject, &groups[to].slots, student, None)
+                }) {
+                    moved = Some((from, to, student_idx));
+                    break;
+                }
+            }
+
+            if let Some((from, to, student_idx)) = moved {
+                switch_group(
+                    groups,
+                    subject,
+                    (from, to),
+                    student_idx,
+                    &mut students[student_idx].slots,
+                );
+                log.improvement_found(Phase::BalancingGroupSizes);
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+fn count_gaps<T>(slots: &[Option<T>], daily_lesson_capacity: usize) -> usize {
+    slots
+        .chunks(daily_lesson_capacity)
+        .map(|day| {
+            let first = day.iter().position(Option::is_some);
+            let last = day.iter().rposition(Option::is_some);
+            match (first, last) {
+                (Some(first), Some(last)) => day[first..=last]
+                    .iter()
+                    .filter(|slot| slot.is_none())
+                    .count(),
+                _ => 0,
+            }
+        })
+        .sum()
+}
+
+fn total_gaps(students: &[Student], daily_lesson_capacity: usize) -> usize {
+    students
+        .iter()
+        .map(|student| count_gaps(&student.slots, daily_lesson_capacity))
+        .sum()
+}
+
+/// How many lessons the days of a student's timetable are outside the soft
+/// daily bounds by, in total.
+fn daily_lesson_penalty<T>(slots: &[Option<T>], timetable_info: &TimetableInfo) -> usize {
+    slots
+        .chunks(timetable_info.daily_lesson_capacity.into())
+        .map(|day| {
+            let lessons = day.iter().filter(|slot| slot.is_some()).count();
+            timetable_info.daily_lessons.penalty(lessons)
+        })
+        .sum()
+}
+
+/// The first slot of the first day on which a student with `slots` has fewer
+/// lessons than `DailyLessonBounds::hard_min`, along with how many lessons
+/// they have then. Slots taken by the events the student attends are left
+/// out, so a day of events never falls short.
+fn first_short_day<T>(
+    slots: &[Option<T>],
+    cohort: Option<&str>,
+    timetable_info: &TimetableInfo,
+) -> Option<(usize, usize)> {
+    let hard_min = usize::from(timetable_info.daily_lessons.hard_min);
+    if hard_min == 0 {
+        return None;
+    }
+    let daily_lesson_capacity = usize::from(timetable_info.daily_lesson_capacity);
+    let total_slots = timetable_info.total_slots();
+    slots
+        .chunks(daily_lesson_capacity)
+        .enumerate()
+        .find_map(|(day, day_slots)| {
+            let day_start = day * daily_lesson_capacity;
+            let free = (day_start..day_start + daily_lesson_capacity)
+                .filter(|&slot| {
+                    !timetable_info.fixed_events.iter().any(|event| {
+                        event.slots.contains(&(slot % total_slots)) && event.is_attended_by(cohort)
+                    })
+                })
+                .count();
+            let lessons = day_slots.iter().filter(|slot| slot.is_some()).count();
+            (lessons < hard_min.min(free)).then(|| (day_start, lessons))
+        })
+}
+
+/// Fails with `TimetableError::TooFewDailyLessons` for the first student with
+/// a day shorter than `DailyLessonBounds::hard_min` allows.
+fn check_daily_minimum(
+    students: &[Student],
+    timetable_info: &TimetableInfo,
+) -> Result<(), TimetableError> {
+    for student in students {
+        if let Some((slot, lessons)) =
+            first_short_day(&student.slots, student.cohort.as_deref(), timetable_info)
+        {
+            return Err(TimetableError::TooFewDailyLessons {
+                student_id: student.id.clone(),
+                slot,
+                lessons,
+                min: timetable_info.daily_lessons.hard_min,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn total_daily_lesson_penalty(students: &[Student], timetable_info: &TimetableInfo) -> usize {
+    students
+        .iter()
+        .map(|student| daily_lesson_penalty(&student.slots, timetable_info))
+        .sum()
+}
+
+/// The variance of each student's lessons per day within a week, averaged
+/// over every week of every student.
+fn daily_workload_variance(students: &[Student], timetable_info: &TimetableInfo) -> f64 {
+    let daily_lesson_capacity = usize::from(timetable_info.daily_lesson_capacity);
+    let variances: Vec<f64> = students
+        .iter()
+        .flat_map(|student| student.slots.chunks(timetable_info.slots_per_week()))
+        .map(|week| {
+            let lessons: Vec<f64> = week
+                .chunks(daily_lesson_capacity)
+                .map(|day| day.iter().filter(|slot| slot.is_some()).count() as f64)
+                .collect();
+            let mean = lessons.iter().sum::<f64>() / lessons.len() as f64;
+            lessons
+                .iter()
+                .map(|lessons| (lessons - mean).powi(2))
+                .sum::<f64>()
+                / lessons.len() as f64
+        })
+        .collect();
+    if variances.is_empty() {
+        0.0
+    } else {
+        variances.iter().sum::<f64>() / variances.len() as f64
+    }
+}
+
+fn minimize_gaps(
+    groups_by_subject: &mut BTreeMap<SubjectId, Vec<Group>>,
+    students: &mut [Student],
+    timetable_info: &TimetableInfo,
+    keep_balanced: bool,
+    phase: Phase,
+    log: &mut SolveLog<'_>,
+) -> bool {
+    let daily_lesson_capacity = usize::from(timetable_info.daily_lesson_capacity);
+    let weights = &timetable_info.weights;
+    let mut switched = false;
+    let gap_weight = i64::from(weights.gap);
+    let daily_weight = i64::from(weights.daily_lessons);
+    let imbalance_weight = if keep_balanced {
+        i64::from(weights.group_imbalance)
+    } else {
+        0
+    };
+    for (student_idx, student) in students.iter_mut().enumerate() {
+        if log.is_cancelled() {
+            break;
+        }
+        // Every switch strictly lowers the student's gap count, so this ends.
+        loop {
+            let gaps = count_gaps(&student.slots, daily_lesson_capacity) as i64;
+            let daily_penalty = daily_lesson_penalty(&student.slots, timetable_info) as i64;
+            // The best switch found so far, by how much it changes the
+            // weighted cost. Only switches which lower it are made.
+            let mut best_change = 0;
+            let mut best_switch = None;
+            let mut student_groups: Vec<(SubjectId, usize)> = Vec::new();
+            for &entry in student.slots.iter().flatten() {
+                if !student_groups.contains(&entry) {
+                    student_groups.push(entry);
+                }
+            }
+            for (subject, group_idx) in student_groups {
+                let groups = &groups_by_subject[&subject];
+                let group = &groups[group_idx];
+                for (other_idx, other) in groups.iter().enumerate() {
+                    if other_idx == group_idx
+                        || other
+                            .slots
+                            .iter()
+                            .any(|&slot| student.slots[slot].is_some())
+                    {
+                        continue;
+                    }
+                    // Switching changes the difference in size between the
+                    // two groups by two.
+                    let (from_size, to_size) = (
+                        group.student_idxs.len() as i64,
+                        other.student_idxs.len() as i64,
+                    );
+                    let imbalance_change =
+                        (from_size - to_size - 2).abs() - (from_size - to_size).abs();
+
+                    let mut trial = student.slots.clone();
+                    for &slot in &group.slots {
+                        trial[slot] = None;
+                    }
+                    for &slot in &other.slots {
+                        trial[slot] = Some((subject, other_idx));
+                    }
+                    let gap_change = count_gaps(&trial, daily_lesson_capacity) as i64 - gaps;
+                    let daily_change =
+                        daily_lesson_penalty(&trial, timetable_info) as i64 - daily_penalty;
+                    let change = gap_change * gap_weight
+                        + imbalance_change * imbalance_weight
+                        + daily_change * daily_weight;
+                    let student = StudentContext {
+                        slots: &trial,
+                        cohort: student.cohort.as_deref(),
+                    };
+                    if gap_change < 0
+                        && change < best_change
+                        && timetable_info.allows_lessons(
+                            subject,
+                            &other.slots,
+                            student,
+                            Some(GroupContext::joining(
+                                groups_by_subject,
+                                (subject, other_idx),
+                            )),
+                        )
+                    {
+                        best_change = change;
+                        best_switch = Some((subject, group_idx, other_idx));
+                    }
+                }
+            }
+
+            if let Some((subject, group_idx, other_idx)) = best_switch {
+                // We are `.get_mut(...).unwrap()`ing since currently we cannot
+                // index mutably into maps in Rust.
+                let groups = groups_by_subject.get_mut(&subject).unwrap();
+                switch_group(
+                    groups,
+                    subject,
+                    (group_idx, other_idx),
+                    student_idx,
+                    &mut student.slots,
+                );
+                switched = true;
+                log.improvement_found(phase);
+            } else {
+                break;
+            }
+        }
+    }
+    switched
+}
+
+/// The most rounds of local search run, in case moving lessons and switching
+/// students keep undoing each other's work.
+const MAX_LOCAL_SEARCH_ROUNDS: usize = 20;
+
+/// Hill-climbs from the timetable found so far. Each round moves lessons to
+/// better slots, then switches students to groups which suit them better,
+/// until a round finds nothing to improve.
+fn local_search(
+    groups_by_subject: &mut BTreeMap<SubjectId, Vec<Group>>,
+    students: &mut [Student],
+    timetable_info: &TimetableInfo,
+    log: &mut SolveLog<'_>,
+) {
+    for _ in 0..MAX_LOCAL_SEARCH_ROUNDS {
+        if log.is_cancelled() {
+            return;
+        }
+        let moved = move_lessons_to_better_slots(
+            groups_by_subject,
+            students,
+            timetable_info,
+            Phase::LocalSearch,
+            log,
+        );
+        let switched = minimize_gaps(
+            groups_by_subject,
+            students,
+            timetable_info,
+            timetable_info.objectives.balance_group_sizes,
+            Phase::LocalSearch,
+            log,
+        );
+        if !moved && !switched {
+            break;
+        }
+    }
+}
+
+/// Moves each lesson of every group to the slot in the same week which most
+/// lowers the weighted cost of its students' gaps and daily lesson penalties
+/// and the subject's soft constraint penalties, if any does. Returns whether
+/// a lesson was moved.
+fn move_lessons_to_better_slots(
+    groups_by_subject: &mut BTreeMap<SubjectId, Vec<Group>>,
+    students: &mut [Student],
+    timetable_info: &TimetableInfo,
+    phase: Phase,
+    log: &mut SolveLog<'_>,
+) -> bool {
+    let total_slots = timetable_info.total_slots();
+    let subjects: Vec<SubjectId> = groups_by_subject.keys().copied().collect();
+    let mut moved = false;
+    for subject in subjects {
+        if log.is_cancelled() {
+            break;
+        }
+        let lessons_per_term =
+            |group: &Group| group.slots.len() / timetable_info.terms_of(subject).len();
+        for group_idx in 0..groups_by_subject[&subject].len() {
+            // Moving a lesson keeps its place in the group's slots, so the
+            // first term's lessons stay at the front.
+            for lesson in 0..lessons_per_term(&groups_by_subject[&subject][group_idx]) {
+                let from = groups_by_subject[&subject][group_idx].slots[lesson];
+                if let Some(to) = find_better_slot(
+                    groups_by_subject,
+                    (subject, group_idx),
+                    from,
+                    timetable_info,
+                    students,
+                ) {
+                    move_lesson(
+                        groups_by_subject,
+                        (subject, group_idx),
+                        from,
+                        to,
+                        total_slots,
+                        students,
+                    );
+                    moved = true;
+                    log.improvement_found(phase);
+                }
+            }
+        }
+    }
+    moved
+}
+
+/// Finds the slot in the same week of the cycle as `from` which the lesson of
+/// the group in `from` is best moved to, if moving it there in every term
+/// keeps to the hard constraints and strictly lowers the weighted cost. With
+/// the daily workload objective on, a move which leaves the cost as it is but
+/// evens out the students' days counts as lowering it. The slot returned is
+/// within the first term.
+fn find_better_slot(
+    groups_by_subject: &BTreeMap<SubjectId, Vec<Group>>,
+    (subject, group_idx): (SubjectId, usize),
+    from: usize,
+    timetable_info: &TimetableInfo,
+    students: &[Student],
+) -> Option<usize> {
+    let slots_per_week = timetable_info.slots_per_week();
+    let total_slots = timetable_info.total_slots();
+    let daily_lesson_capacity = usize::from(timetable_info.daily_lesson_capacity);
+    let gap_weight = i64::from(timetable_info.weights.gap);
+    let penalty_weight = i64::from(timetable_info.weights.slot_preference);
+    let daily_weight = i64::from(timetable_info.weights.daily_lessons);
+    let bounds = &timetable_info.daily_lessons;
+    let balance_workload = timetable_info.objectives.balance_daily_workload;
+    let groups = &groups_by_subject[&subject];
+    let group = &groups[group_idx];
+    let from_in_cycle = from % total_slots;
+    let week = from_in_cycle / slots_per_week;
+    let from_penalty = i64::from(timetable_info.slot_penalty(subject, from, None));
+    let moving = Some(GroupContext::moving(
+        groups_by_subject,
+        (subject, group_idx),
+    ));
+
+    // The change in weighted cost, then in the sum of the squares of the
+    // students' lessons per day, which is lowest when days are even.
+    let mut best_change = (0, 0);
+    let mut best_slot = None;
+    for to in week * slots_per_week..(week + 1) * slots_per_week {
+        if to == from_in_cycle {
+            continue;
+        }
+        let fits = timetable_info.terms_of(subject).all(|term| {
+            let slot = term * total_slots + to;
+            timetable_info.allows(subject, slot, None, moving)
+                && !groups.iter().any(|group| group.busy.contains(slot))
+                && group.student_idxs.iter().all(|&student_idx| {
+                    let student = students[student_idx].context();
+                    student.slots[slot].is_none()
+                        && timetable_info.allows(subject, slot, Some(student), None)
+                })
+        });
+        if !fits {
+            continue;
+        }
+
+        let moves: Vec<(usize, usize)> = timetable_info
+            .terms_of(subject)
+            .map(|term| (term * total_slots + from_in_cycle, term * total_slots + to))
+            .collect();
+        let mut gap_change = 0;
+        let mut daily_change = 0;
+        let mut workload_change = 0;
+        for &student_idx in &group.student_idxs {
+            let slots = &students[student_idx].slots;
+            gap_change += gap_change_of_moves(slots, &moves, daily_lesson_capacity);
+            for (before, after) in day_lessons_after_moves(slots, &moves, daily_lesson_capacity) {
+                daily_change += bounds.penalty(after) as i64 - bounds.penalty(before) as i64;
+                if balance_workload {
+                    workload_change += (after * after) as i64 - (before * before) as i64;
+                }
+            }
+        }
+        let to_penalty =
+            i64::from(timetable_info.slot_penalty(subject, from - from_in_cycle + to, None));
+        let change = (
+            gap_change * gap_weight
+                + daily_change * daily_weight
+                + (to_penalty - from_penalty) * penalty_weight,
+            workload_change,
+        );
+        if change < best_change {
+            best_change = change;
+            best_slot = Some(to);
+        }
+    }
+    best_slot
+}
+
+/// How a student's gap count would change if each lesson were moved between
+/// the slots given. Only the days the lessons move between are counted.
+fn gap_change_of_moves<T>(
+    slots: &[Option<T>],
+    moves: &[(usize, usize)],
+    daily_lesson_capacity: usize,
+) -> i64 {
+    let mut days: Vec<usize> = moves
+        .iter()
+        .flat_map(|&(from, to)| vec![from / daily_lesson_capacity, to / daily_lesson_capacity])
+        .collect();
+    days.sort_unstable();
+    days.dedup();
+    days.into_iter()
+        .map(|day| {
+            let start = day * daily_lesson_capacity;
+            let day_slots = &slots[start..start + daily_lesson_capacity];
+            let mut trial: Vec<Option<()>> = day_slots
+                .iter()
+                .map(|slot| slot.as_ref().map(|_| ()))
+                .collect();
+            for &(from, _) in moves {
+                if from / daily_lesson_capacity == day {
+                    trial[from - start] = None;
+                }
+            }
+            for &(_, to) in moves {
+                if to / daily_lesson_capacity == day {
+                    trial[to - start] = Some(());
+                }
+            }
+            count_gaps(&trial, daily_lesson_capacity) as i64
+                - count_gaps(day_slots, daily_lesson_capacity) as i64
+        })
+        .sum()
+}
+
+/// The number of lessons a student has before and after each lesson is moved
+/// between the slots given, on each day the lessons move between.
+fn day_lessons_after_moves<T>(
+    slots: &[Option<T>],
+    moves: &[(usize, usize)],
+    daily_lesson_capacity: usize,
+) -> Vec<(usize, usize)> {
+    let mut lessons_by_day: BTreeMap<usize, (usize, usize)> = BTreeMap::new();
+    for &(from, to) in moves {
+        for (slot, moved_in) in [(from, false), (to, true)] {
+            let day = slot / daily_lesson_capacity;
+            let start = day * daily_lesson_capacity;
+            let (_, after) = lessons_by_day.entry(day).or_insert_with(|| {
+                let lessons = slots[start..start + daily_lesson_capacity]
+                    .iter()
+                    .filter(|slot| slot.is_some())
+                    .count();
+                (lessons, lessons)
+            });
+            if moved_in {
+                *after += 1;
+            } else {
+                *after -= 1;
+            }
+        }
+    }
+    lessons_by_day.into_values().collect()
+}
+
+/// For every slot, the room or teacher given to each subject taught in it.
+type AssignmentsBySlot = Vec<BTreeMap<SubjectId, String>>;
+
+/// A student's lesson in each slot, as `TimetableResult::Solved` gives it.
+type StudentLessons = [Option<(SubjectId, usize)>];
+
+/// Turns assignments by slot into the subject and group each room or teacher
+/// has in every slot. Everything in `ids` is included, even if it was never
+/// assigned a lesson.
+fn slots_by_assignee<'a>(
+    assignments_by_slot: &AssignmentsBySlot,
+    ids: impl Iterator<Item = &'a String>,
+    group_idxs_by_slot: &[BTreeMap<SubjectId, usize>],
+) -> BTreeMap<String, Vec<Option<(SubjectId, usize)>>> {
+    let mut slots_by_assignee: BTreeMap<String, Vec<Option<(SubjectId, usize)>>> = ids
+        .map(|id| (id.clone(), vec![None; group_idxs_by_slot.len()]))
+        .collect();
+    for (slot, assignments) in assignments_by_slot.iter().enumerate() {
+        for (subject, id) in assignments {
+            let group_idx = group_idxs_by_slot[slot][subject];
+            slots_by_assignee.get_mut(id).unwrap()[slot] = Some((*subject, group_idx));
+        }
+    }
+    slots_by_assignee
+}
+
+/// Gives every lesson a room, keeping each group at one site for all of its
+/// lessons. Groups of subjects taught at a site stay there, and the rest are
+/// put at the first site with a room free for every lesson which its
+/// students can get to from their other lessons.
+fn assign_rooms(
+    group_idxs_by_slot: &[BTreeMap<SubjectId, usize>],
+    slots_by_student_id: &BTreeMap<StudentId, Vec<Option<(SubjectId, usize)>>>,
+    timetable_info: &TimetableInfo,
+) -> Result<AssignmentsBySlot, (SubjectId, usize)> {
+    let rooms = &timetable_info.rooms;
+    if rooms.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut slots_by_group: BTreeMap<(SubjectId, usize), Vec<usize>> = BTreeMap::new();
+    for (slot, group_idxs) in group_idxs_by_slot.iter().enumerate() {
+        for (&subject, &group_idx) in group_idxs {
+            slots_by_group
+                .entry((subject, group_idx))
+                .or_default()
+                .push(slot);
+        }
+    }
+    let mut students_by_group: BTreeMap<(SubjectId, usize), Vec<&StudentLessons>> = BTreeMap::new();
+    for slots in slots_by_student_id.values() {
+        let groups: BTreeSet<(SubjectId, usize)> = slots.iter().flatten().copied().collect();
+        for group in groups {
+            students_by_group.entry(group).or_default().push(slots);
+        }
+    }
+
+    let room_type = |subject: SubjectId| timetable_info.subjects[subject].room_type.as_deref();
+    // Groups that need a specialist room go first so that they don't find it
+    // taken by a group which could have gone anywhere, then groups which must
+    // be at a particular site.
+    let mut groups: Vec<((SubjectId, usize), Vec<usize>)> = slots_by_group.into_iter().collect();
+    groups.sort_by_key(|&((subject, _), _)| {
+        (
+            room_type(subject).is_none(),
+            timetable_info.site_of(subject).is_none(),
+        )
+    });
+    let mut sites: Vec<Option<&str>> = Vec::new();
+    for room in rooms {
+        if !sites.contains(&room.site.as_deref()) {
+            sites.push(room.site.as_deref());
+        }
+    }
+
+    let daily_lesson_capacity = usize::from(timetable_info.daily_lesson_capacity);
+    let mut is_room_used = vec![vec![false; rooms.len()]; group_idxs_by_slot.len()];
+    let mut room_idxs_by_slot: Vec<BTreeMap<SubjectId, usize>> =
+        vec![BTreeMap::new(); group_idxs_by_slot.len()];
+    let mut sites_by_group: BTreeMap<(SubjectId, usize), Option<&str>> = BTreeMap::new();
+    for ((subject, group_idx), slots) in groups {
+        let required_type = room_type(subject);
+        let candidate_sites = match timetable_info.site_of(subject) {
+            Some(site) => vec![Some(site)],
+            None => sites.clone(),
+        };
+        let students = students_by_group
+            .get(&(subject, group_idx))
+            .map_or(&[][..], Vec::as_slice);
+        // The room the group would be given in each of its slots at `site`,
+        // or the first slot it couldn't be taught in there.
+        let rooms_at = |site: Option<&str>| -> Result<Vec<usize>, usize> {
+            slots
+                .iter()
+                .map(|&slot| {
+                    let day_start = slot - slot % daily_lesson_capacity;
+                    let can_travel = students.iter().all(|lessons| {
+                        (day_start..day_start + daily_lesson_capacity).all(|other_slot| {
+                            match lessons[other_slot] {
+                                Some(other_group) => {
+                                    // Groups not yet given a site may be at
+                                    // their subject's.
+                                    let other_site = sites_by_group
+                                        .get(&other_group)
+                                        .copied()
+                                        .unwrap_or_else(|| timetable_info.site_of(other_group.0));
+                                    timetable_info.can_travel_between_sites(
+                                        (site, slot),
+                                        (other_site, other_slot),
+                                    )
+                                }
+                                None => true,
+                            }
+                        })
+                    });
+                    rooms
+                        .iter()
+                        .enumerate()
+                        .filter(|&(room_idx, room)| {
+                            can_travel
+                                && !is_room_used[slot][room_idx]
+                                && (required_type.is_none()
+                                    || room.room_type.as_deref() == required_type)
+                                && room.site.as_deref() == site
+                        })
+                        // Plain rooms are preferred to keep specialist ones
+                        // free.
+                        .min_by_key(|(_, room)| room.room_type.is_some())
+                        .map(|(room_idx, _)| room_idx)
+                        .ok_or(slot)
+                })
+                .collect()
+        };
+        let mut failed_slot = None;
+        let (site, room_idxs) = candidate_sites
+            .into_iter()
+            .find_map(|site| match rooms_at(site) {
+                Ok(room_idxs) => Some((site, room_idxs)),
+                Err(slot) => {
+                    failed_slot.get_or_insert(slot);
+                    None
+                }
+            })
+            .ok_or_else(|| (subject, failed_slot.unwrap_or(slots[0])))?;
+        for (&slot, room_idx) in slots.iter().zip(room_idxs) {
+            is_room_used[slot][room_idx] = true;
+            room_idxs_by_slot[slot].insert(subject, room_idx);
+        }
+        sites_by_group.insert((subject, group_idx), site);
+    }
+
+    Ok(room_idxs_by_slot
+        .into_iter()
+        .map(|room_idxs| {
+            room_idxs
+                .into_iter()
+                .map(|(subject, room_idx)| (subject, rooms[room_idx].id.clone()))
+                .collect()
+        })
+        .collect())
+}
+
+/// The teachers given to each subject in every slot, along with the teacher
+/// measurements for `Metrics`.
+#[derive(Default)]
+struct TeacherAssignment {
+    teachers_by_slot: AssignmentsBySlot,
+    teachers_at_capacity: Vec<String>,
+    preference_penalty: u32,
+    idle_periods: BTreeMap<String, usize>,
+}
+
+/// Gives every group one teacher for all of its lessons, so each group keeps
+/// the same teacher throughout the year. Teachers travel between the sites of
+/// the rooms in `rooms_by_slot`.
+fn assign_teachers(
+    group_idxs_by_slot: &[BTreeMap<SubjectId, usize>],
+    rooms_by_slot: &[BTreeMap<SubjectId, String>],
+    timetable_info: &TimetableInfo,
+) -> Result<TeacherAssignment, TimetableError> {
+    let teachers = &timetable_info.teachers;
+    if teachers.is_empty() {
+        return Ok(TeacherAssignment::default());
+    }
+
+    let mut slots_by_group: BTreeMap<(SubjectId, usize), Vec<usize>> = BTreeMap::new();
+    for (slot, group_idxs) in group_idxs_by_slot.iter().enumerate() {
+        for (&subject, &group_idx) in group_idxs {
+            slots_by_group
+                .entry((subject, group_idx))
+                .or_default()
+                .push(slot);
+        }
+    }
+    // Groups of subjects with the fewest possible teachers are handled first.
+    let mut groups: Vec<((SubjectId, usize), Vec<usize>)> = slots_by_group.into_iter().collect();
+    groups.sort_by_key(|&((subject, _), _)| {
+        teachers
+            .iter()
+            .filter(|teacher| teacher.can_teach(subject))
+            .count()
+    });
+
+    let slot_count = group_idxs_by_slot.len();
+    let slots_per_week = timetable_info.slots_per_week();
+    let daily_lesson_capacity = usize::from(timetable_info.daily_lesson_capacity);
+    let lesson_site = |subject: SubjectId, slot: usize| {
+        let room_id = rooms_by_slot
+            .get(slot)
+            .and_then(|rooms| rooms.get(&subject));
+        timetable_info.site_of_lesson(subject, room_id.map(String::as_str))
+    };
+    // The length of the run of back to back lessons which `slot` is part of.
+    let run_through = |is_busy: &[bool], slot: usize| {
+        let day_start = slot - slot % daily_lesson_capacity;
+        let before = is_busy[day_start..slot]
+            .iter()
+            .rev()
+            .take_while(|&&is_busy| is_busy)
+            .count();
+        let after = is_busy[slot + 1..day_start + daily_lesson_capacity]
+            .iter()
+            .take_while(|&&is_busy| is_busy)
+            .count();
+        before + 1 + after
+    };
+    // Whether each teacher is teaching in each slot, what they are teaching,
+    // and how many lessons they have in each week.
+    let mut is_busy = vec![vec![false; slot_count]; teachers.len()];
+    let mut taught: Vec<Vec<Option<SubjectId>>> = vec![vec![None; slot_count]; teachers.len()];
+    let mut loads = vec![vec![0usize; slot_count / slots_per_week]; teachers.len()];
+    let mut teachers_by_slot = vec![BTreeMap::new(); slot_count];
+    let mut preference_penalty = 0;
+    let compact_days = timetable_info.objectives.compact_teacher_days;
+    for ((subject, _), slots) in groups {
+        let mut lessons_by_week = vec![0usize; slot_count / slots_per_week];
+        for &slot in &slots {
+            lessons_by_week[slot / slots_per_week] += 1;
+        }
+        // A group has the same lessons in every term it runs in, so only the
+        // first term's count towards a teacher's preferences.
+        let lessons_per_term = slots.len() / timetable_info.terms_of(subject).len();
+        let penalty = |teacher: &TeacherInfo| -> u32 {
+            slots
+                .iter()
+                .take(lessons_per_term)
+                .map(|&slot| teacher.slot_penalty(slot, timetable_info.daily_lesson_capacity))
+                .sum()
+        };
+
+        // Whether the teacher could take the group, leaving aside their days
+        // off.
+        let is_free = |teacher_idx: usize, teacher: &TeacherInfo| {
+            if !teacher.can_teach(subject) || slots.iter().any(|&slot| is_busy[teacher_idx][slot]) {
+                return false;
+            }
+            let fits_load = loads[teacher_idx]
+                .iter()
+                .zip(&lessons_by_week)
+                .all(|(load, lessons)| teacher.has_capacity(load + lessons));
+            let mut trial = is_busy[teacher_idx].clone();
+            for &slot in &slots {
+                trial[slot] = true;
+            }
+            let is_rested = match teacher
+                .max_consecutive_lessons
+                .or(timetable_info.max_consecutive_lessons)
+            {
+                Some(max) => slots
+                    .iter()
+                    .all(|&slot| run_through(&trial, slot) <= usize::from(max)),
+                None => true,
+            };
+            let can_travel = slots.iter().all(|&slot| {
+                let day_start = slot - slot % daily_lesson_capacity;
+                (day_start..day_start + daily_lesson_capacity).all(|other_slot| {
+                    taught[teacher_idx][other_slot].map_or(true, |other| {
+                        timetable_info.can_travel_between_sites(
+                            (lesson_site(subject, slot), slot),
+                            (lesson_site(other, other_slot), other_slot),
+                        )
+                    })
+                })
+            });
+            fits_load && is_rested && can_travel
+        };
+        let first_day_off = |teacher: &TeacherInfo| {
+            slots
+                .iter()
+                .copied()
+                .find(|&slot| teacher.is_off(slot, timetable_info.daily_lesson_capacity))
+        };
+        let teacher_idx = teachers
+            .iter()
+            .enumerate()
+            .filter(|&(teacher_idx, teacher)| {
+                is_free(teacher_idx, teacher) && first_day_off(teacher).is_none()
+            })
+            // We favour teachers who would rather teach the lessons, then
+            // those left with the fewest free periods between lessons if
+            // teachers' days are being compacted, then share lessons out by
+            // giving them to the least loaded teacher.
+            .min_by_key(|&(teacher_idx, teacher)| {
+                let idle_change = if compact_days {
+                    let mut trial = taught[teacher_idx].clone();
+                    for &slot in &slots {
+                        trial[slot] = Some(subject);
+                    }
+                    count_gaps(&trial, daily_lesson_capacity) as i64
+                        - count_gaps(&taught[teacher_idx], daily_lesson_capacity) as i64
+                } else {
+                    0
+                };
+                (
+                    penalty(teacher),
+                    idle_change,
+                    loads[teacher_idx].iter().sum::<usize>(),
+                )
+            })
+            .map(|(teacher_idx, _)| teacher_idx);
+        let teacher_idx = match teacher_idx {
+            Some(teacher_idx) => teacher_idx,
+            None => {
+                // Teachers who are only kept away by their days off are worth
+                // telling the user about.
+                let off: Vec<(&TeacherInfo, usize)> = teachers
+                    .iter()
+                    .enumerate()
+                    .filter(|&(teacher_idx, teacher)| is_free(teacher_idx, teacher))
+                    .filter_map(|(_, teacher)| first_day_off(teacher).map(|slot| (teacher, slot)))
+                    .collect();
+                return Err(match off.first() {
+                    Some(&(_, slot)) => TimetableError::TeachersOff {
+                        subject: timetable_info.subjects.name(subject).to_string(),
+                        slot,
+                        teacher_ids: off.iter().map(|(teacher, _)| teacher.id.clone()).collect(),
+                    },
+                    None => TimetableError::NoTeacherAvailable {
+                        subject: timetable_info.subjects.name(subject).to_string(),
+                        slot: slots[0],
+                    },
+                });
+            }
+        };
+        preference_penalty += penalty(&teachers[teacher_idx]);
+
+        for &slot in &slots {
+            is_busy[teacher_idx][slot] = true;
+            taught[teacher_idx][slot] = Some(subject);
+            teachers_by_slot[slot].insert(subject, teachers[teacher_idx].id.clone());
+        }
+        for (load, lessons) in loads[teacher_idx].iter_mut().zip(lessons_by_week) {
+            *load += lessons;
+        }
+    }
+
+    let teachers_at_capacity = teachers
+        .iter()
+        .zip(loads)
+        .filter(|(teacher, loads)| {
+            // A teacher is at capacity if they couldn't take another lesson
+            // in one of the weeks.
+            loads.iter().any(|&load| !teacher.has_capacity(load + 1))
+        })
+        .map(|(teacher, _)| teacher.id.clone())
+        .collect();
+
+    let idle_periods = teachers
+        .iter()
+        .zip(&taught)
+        .map(|(teacher, taught)| {
+            (
+                teacher.id.clone(),
+                count_gaps(taught, daily_lesson_capacity),
+            )
+        })
+        .collect();
+
+    Ok(TeacherAssignment {
+        teachers_by_slot,
+        teachers_at_capacity,
+        preference_penalty,
+        idle_periods,
+    })
+}
+
+pub fn solve_timetable(timetable_info: &TimetableInfo) -> TimetableResult {
+    solve(timetable_info, SolveLog::default())
+}
+
+/// Solves the timetable like `solve_timetable`, also returning every decision
+/// the solver made along the way, which shows why it ended up with the result
+/// it did.
+pub fn solve_timetable_traced(
+    timetable_info: &TimetableInfo,
+) -> (TimetableResult, Vec<TraceEvent>) {
+    let mut trace = Vec::new();
+    let result = solve_timetable_observed(timetable_info, &mut trace);
+    (result, trace)
+}
+
+/// Solves the timetable like `solve_timetable`, telling `observer` about the
+/// solver's progress as it goes.
+pub fn solve_timetable_observed(
+    timetable_info: &TimetableInfo,
+    observer: &mut dyn SolveObserver,
+) -> TimetableResult {
+    solve(
+        timetable_info,
+        SolveLog {
+            observer: Some(observer),
+            ..Default::default()
+        },
+    )
+}
+
+/// Solves the timetable like `solve_timetable_observed`, giving up with
+/// `TimetableError::Cancelled` once `cancellation` is cancelled. The solver
+/// checks after placing each student and between the stages of solving.
+pub fn solve_timetable_cancellable(
+    timetable_info: &TimetableInfo,
+    observer: &mut dyn SolveObserver,
+    cancellation: &CancellationToken,
+) -> TimetableResult {
+    solve(
+        timetable_info,
+        SolveLog {
+            observer: Some(observer),
+            cancellation: Some(cancellation),
+            ..Default::default()
+        },
+    )
+}
+
+/// Solves like `solve_timetable_cancellable` without an observer, for
+/// functions which solve many times over.
+fn solve_unobserved(
+    timetable_info: &TimetableInfo,
+    cancellation: &CancellationToken,
+) -> TimetableResult {
+    solve(
+        timetable_info,
+        SolveLog {
+            cancellation: Some(cancellation),
+            ..Default::default()
+        },
+    )
+}
+
+/// The groups of every subject, along with the students in them.
+type Grouping = (BTreeMap<SubjectId, Vec<Group>>, Vec<Student>);
+
+/// Gives every student their groups, one student at a time in the order set
+/// by `TimetableInfo::student_ordering`.
+fn place_students(
+    timetable_info: &TimetableInfo,
+    log: &mut SolveLog<'_>,
+) -> Result<Grouping, TimetableError> {
+    let slots_per_year = timetable_info.slots_per_year();
+    let mut groups_by_subject: BTreeMap<SubjectId, Vec<Group>> = BTreeMap::new();
+    let mut students: Vec<Student> = Vec::new();
+    log.phase_started(Phase::PlacingStudents);
+    // Students are pushed in the order they are placed, so `student_idx`
+    // indexes `students` rather than `timetable_info.students`.
+    for (student_idx, info_idx) in timetable_info.student_order().into_iter().enumerate() {
+        let student_info = &timetable_info.students[info_idx];
+        // We map slots to possible subjects here.
+        let mut personal = PersonalTimetable::new(slots_per_year);
+        personal.cohort = student_info.cohort.clone();
+        handle_subjects(
+            &mut groups_by_subject,
+            &mut personal,
+            student_info,
+            timetable_info,
+            &mut students,
+            log,
+        )?;
+
+        // We add the groups we decided upon to the global vector.
+        make_global(&mut groups_by_subject, &personal.slots, student_idx);
+
+        // We register the student to keep track of for later.
+        students.push(Student {
+            slots: personal.slots,
+            id: student_info.id.clone(),
+            cohort: personal.cohort,
+        });
+        log.student_placed(
+            &student_info.id,
+            students.len(),
+            timetable_info.students.len(),
+        );
+        log.check_cancelled()?;
+    }
+    Ok((groups_by_subject, students))
+}
+
+/// Runs each of the objectives which are turned on.
+fn apply_objectives(
+    groups_by_subject: &mut BTreeMap<SubjectId, Vec<Group>>,
+    students: &mut [Student],
+    timetable_info: &TimetableInfo,
+    log: &mut SolveLog<'_>,
+) {
+    let objectives = &timetable_info.objectives;
+    if objectives.balance_group_sizes {
+        log.phase_started(Phase::BalancingGroupSizes);
+        balance_group_sizes(groups_by_subject, students, timetable_info, log);
+    }
+    if objectives.minimize_gaps {
+        log.phase_started(Phase::MinimizingGaps);
+        minimize_gaps(
+            groups_by_subject,
+            students,
+            timetable_info,
+            objectives.balance_group_sizes,
+            Phase::MinimizingGaps,
+            log,
+        );
+    }
+    if objectives.local_search {
+        log.phase_started(Phase::LocalSearch);
+        local_search(groups_by_subject, students, timetable_info, log);
+    }
+    if objectives.balance_daily_workload {
+        log.phase_started(Phase::BalancingDailyWorkload);
+        for _ in 0..MAX_LOCAL_SEARCH_ROUNDS {
+            if log.is_cancelled() {
+                break;
+            }
+            let moved = move_lessons_to_better_slots(
+                groups_by_subject,
+                students,
+                timetable_info,
+                Phase::BalancingDailyWorkload,
+                log,
+            );
+            if !moved {
+                break;
+            }
+        }
+    }
+}
+
+/// Measures the timetable as it is now. The measurements from straight after
+/// the initial placement are left at zero.
+fn measure(
+    groups_by_subject: &BTreeMap<SubjectId, Vec<Group>>,
+    students: &[Student],
+    timetable_info: &TimetableInfo,
+) -> Metrics {
+    Metrics {
+        group_size_spread_after: group_size_spread(groups_by_subject),
+        student_gaps_after: total_gaps(students, timetable_info.daily_lesson_capacity.into()),
+        slot_preference_penalty: groups_by_subject
+            .iter()
+            .flat_map(|(subject, groups)| {
+                groups
+                    .iter()
+                    .map(|group| timetable_info.group_penalty(*subject, group, None))
+            })
+            .sum(),
+        daily_lesson_penalty: total_daily_lesson_penalty(students, timetable_info),
+        daily_workload_variance: daily_workload_variance(students, timetable_info),
+        ..Default::default()
+    }
+}
+
+/// The score `Metrics::score` gives the timetable as it is now, including the
+/// teacher preference penalty of the teachers it would be given. Timetables
+/// which can't be given teachers have no score.
+fn score(
+    groups_by_subject: &BTreeMap<SubjectId, Vec<Group>>,
+    students: &[Student],
+    timetable_info: &TimetableInfo,
+) -> Option<u64> {
+    let mut metrics = measure(groups_by_subject, students, timetable_info);
+    if !timetable_info.teachers.is_empty() {
+        let mut group_idxs_by_slot = vec![BTreeMap::new(); timetable_info.slots_per_year()];
+        for (subject, groups) in groups_by_subject {
+            for (group_idx, group) in groups.iter().enumerate() {
+                for &slot in &group.slots {
+                    group_idxs_by_slot[slot].insert(*subject, group_idx);
+                }
+            }
+        }
+        // Rooms aren't given yet, so teachers travel between the subjects'
+        // own sites.
+        let assignment = assign_teachers(&group_idxs_by_slot, &[], timetable_info).ok()?;
+        metrics.teacher_preference_penalty = assignment.preference_penalty;
+    }
+    Some(metrics.score(&timetable_info.weights))
+}
+
+fn solve(timetable_info: &TimetableInfo, mut log: SolveLog<'_>) -> TimetableResult {
+    // `Instant` panics on the web, so we only time native builds.
+    #[cfg(not(target_arch = "wasm32"))]
+    let start = std::time::Instant::now();
+
+    let slots_per_year = timetable_info.slots_per_year();
+    let daily_lesson_capacity = timetable_info.daily_lesson_capacity.into();
+    let placed = place_students(timetable_info, &mut log).and_then(
+        |(mut groups_by_subject, mut students)| {
+            let before = (
+                group_size_spread(&groups_by_subject),
+                total_gaps(&students, daily_lesson_capacity),
+            );
+            apply_objectives(
+                &mut groups_by_subject,
+                &mut students,
+                timetable_info,
+                &mut log,
+            );
+            log.check_cancelled()?;
+            check_daily_minimum(&students, timetable_info)?;
+            Ok((groups_by_subject, students, before))
+        },
+    );
+    let (groups_by_subject, students, (group_size_spread_before, student_gaps_before)) =
+        match (placed, timetable_info.algorithm) {
+            (Ok(placed), Algorithm::Greedy) => placed,
+            (Err(error @ TimetableError::Cancelled), _) | (Err(error), Algorithm::Greedy) => {
+                return TimetableResult::Unsolved(error)
+            }
+            (placed, Algorithm::Exhaustive) => {
+                log.phase_started(Phase::ExhaustiveSearch);
+                // Only timetables which beat the greedy one are worth finding.
+                let score_to_beat =
+                    placed
+                        .as_ref()
+                        .ok()
+                        .and_then(|(groups_by_subject, students, _)| {
+                            score(groups_by_subject, students, timetable_info)
+                        });
+                let search = exhaustive::search(timetable_info, score_to_beat, &mut log);
+                log.stats.proven_optimal =
+                    search.finished && (placed.is_ok() || search.best.is_some());
+                match (search.best, placed) {
+                    (Some((groups_by_subject, students)), placed) => {
+                        let before = match placed {
+                            Ok((_, _, before)) => before,
+                            Err(_) => (
+                                group_size_spread(&groups_by_subject),
+                                total_gaps(&students, daily_lesson_capacity),
+                            ),
+                        };
+                        (groups_by_subject, students, before)
+                    }
+                    (None, Ok(placed)) => placed,
+                    (None, Err(error)) => return TimetableResult::Unsolved(error),
+                }
+            }
+        };
+
+    if let Err(error) = log.check_cancelled() {
+        return TimetableResult::Unsolved(error);
+    }
+
+    let mut metrics = Metrics {
+        group_size_spread_before,
+        student_gaps_before,
+        ..measure(&groups_by_subject, &students, timetable_info)
+    };
+
+    // We invert groups_by_subject to help get subjects_by_slot.
+    let mut subjects = vec![Vec::new(); slots_per_year];
+    let mut group_idxs_by_slot = vec![BTreeMap::new(); slots_per_year];
+    let mut group_names = BTreeMap::new();
+    for (subject, groups) in groups_by_subject {
+        let name = timetable_info.subjects.name(subject);
+        group_names.insert(
+            subject,
+            (0..groups.len())
+                .map(|group_idx| group_name(name, group_idx))
+                .collect(),
+        );
+        for (group_idx, group) in groups.into_iter().enumerate() {
+            for slot in group.slots {
+                // It's guaranteed that this will never cause duplicate
+                // subjects, so we don't need to check.
+                subjects[slot].push(subject);
+                group_idxs_by_slot[slot].insert(subject, group_idx);
+            }
+        }
+    }
+    // Subjects are visited in ID order, so each slot's are already sorted
+    // and the same input always gives the same output.
+
+    let slots_by_student_id: BTreeMap<StudentId, Vec<Option<(SubjectId, usize)>>> = students
+        .into_iter()
+        .map(|student| (student.id, student.slots))
+        .collect();
+
+    if !timetable_info.rooms.is_empty() {
+        log.phase_started(Phase::AssigningRooms);
+    }
+    let rooms_by_slot =
+        match assign_rooms(&group_idxs_by_slot, &slots_by_student_id, timetable_info) {
+            Ok(rooms_by_slot) => rooms_by_slot,
+            Err((subject, slot)) => {
+                return TimetableResult::Unsolved(TimetableError::NoRoomAvailable {
+                    subject: timetable_info.subjects.name(subject).to_string(),
+                    slot,
+                });
+            }
+        };
+    if !timetable_info.teachers.is_empty() {
+        log.phase_started(Phase::AssigningTeachers);
+    }
+    let teachers_by_slot =
+        match assign_teachers(&group_idxs_by_slot, &rooms_by_slot, timetable_info) {
+            Ok(assignment) => {
+                metrics.teachers_at_capacity = assignment.teachers_at_capacity;
+                metrics.teacher_preference_penalty = assignment.preference_penalty;
+                metrics.teacher_idle_periods = assignment.idle_periods;
+                assignment.teachers_by_slot
+            }
+            Err(error) => return TimetableResult::Unsolved(error),
+        };
+    let slots_by_room = slots_by_assignee(
+        &rooms_by_slot,
+        timetable_info.rooms.iter().map(|room| &room.id),
+        &group_idxs_by_slot,
+    );
+    let slots_by_teacher_id = slots_by_assignee(
+        &teachers_by_slot,
+        timetable_info.teachers.iter().map(|teacher| &teacher.id),
+        &group_idxs_by_slot,
+    );
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        log.stats.wall_time = Some(start.elapsed());
+    }
+
+    TimetableResult::Solved {
+        subjects,
+        slots_by_student_id,
+        group_names,
+        rooms_by_slot,
+        slots_by_room,
+        teachers_by_slot,
+        slots_by_teacher_id,
+        metrics,
+        stats: log.stats,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::generate::{self, SchoolConfig};
+    use super::*;
+
+    /// Solves `info` on another thread, failing the test rather than hanging
+    /// it if the solver doesn't finish.
+    fn solve_within_time_limit(info: &TimetableInfo) -> TimetableResult {
+        let (sender, receiver) = mpsc::channel();
+        let info = info.clone();
+        std::thread::spawn(move || {
+            // The test has already failed if nobody is left to receive this.
+            let _ = sender.send(solve_timetable(&info));
+        });
+        receiver
+            .recv_timeout(Duration::from_secs(30))
+            .expect("the solver should finish")
+    }
+
+    /// Solves `info`, leaving out how long it took so results can be
+    /// compared.
+    fn solve_untimed(info: &TimetableInfo) -> TimetableResult {
+        let mut result = solve_timetable(info);
+        if let TimetableResult::Solved { stats, .. } = &mut result {
+            stats.wall_time = None;
+        }
+        result
+    }
+
+    #[test]
+    fn solving_gives_the_same_timetable_whatever_the_order_of_students() {
+        let info = generate::school(&SchoolConfig {
+            students: 60,
+            rooms: 8,
+            teachers: 10,
+            ..SchoolConfig::default()
+        });
+        let result = solve_untimed(&info);
+        assert!(matches!(result, TimetableResult::Solved { .. }));
+        assert_eq!(solve_untimed(&info), result);
+
+        let mut reversed = info.clone();
+        reversed.students.reverse();
+        assert_eq!(solve_untimed(&reversed), result);
+    }
+
+    #[test]
+    fn cancelling_stops_the_exhaustive_search() {
+        let mut info = generate::school(&SchoolConfig {
+            students: 40,
+            ..SchoolConfig::default()
+        });
+        info.algorithm = Algorithm::Exhaustive;
+        let cancellation = CancellationToken::new();
+        let (sender, receiver) = mpsc::channel();
+        let solver_cancellation = cancellation.clone();
+        std::thread::spawn(move || {
+            let result = solve_unobserved(&info, &solver_cancellation);
+            let _ = sender.send(result);
+        });
+        std::thread::sleep(Duration::from_millis(50));
+        cancellation.cancel();
+        let result = receiver
+            .recv_timeout(Duration::from_secs(1))
+            .expect("the search should stop soon after being cancelled");
+        assert!(matches!(
+            result,
+            TimetableResult::Unsolved(TimetableError::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn hard_daily_minimum_is_kept() {
+        let mut info = TimetableInfo::new(1, 2);
+        info.add_student("A", &["Art", "Biology", "Chemistry", "Drama", "English"]);
+        info.daily_lessons.hard_min = 1;
+        assert!(validate(&info).is_empty());
+        let result = solve_timetable(&info);
+        assert!(verify(&info, &result).is_empty());
+
+        // Five lessons can't cover five days with two lessons each.
+        info.daily_lessons.hard_min = 2;
+        assert!(matches!(
+            validate(&info)[..],
+            [ValidationIssue::TooFewLessons { needed: 10, .. }]
+        ));
+        assert!(matches!(
+            solve_timetable(&info),
+            TimetableResult::Unsolved(TimetableError::TooFewDailyLessons { .. })
+        ));
+    }
+
+    #[test]
+    fn making_room_for_a_fortnightly_group_finishes() {
+        // Moving a student's clashing lesson into another of the group's
+        // slots used to undo the previous move, forever.
+        let mut info = generate::school(&SchoolConfig {
+            students: 10,
+            subjects: 6,
+            subjects_per_student: 3..=4,
+            max_groups: 1,
+            daily_lesson_capacity: 4,
+            seed: 1,
+            ..SchoolConfig::default()
+        });
+        info.cycle = WeekCycle::Fortnightly;
+        for subject in info.subjects.iter_mut() {
+            subject.lessons_per_week = [2, 1];
+        }
+        assert!(validate(&info).is_empty());
+
+        let result = solve_within_time_limit(&info);
+        let violations = verify(&info, &result);
+        assert!(violations.is_empty() || violations == [Violation::NotSolved]);
+    }
+
+    /// A room at each of two sites, with the south listed first so that it
+    /// is tried first, and Maths, then Art, each taught three times a week.
+    fn two_sites(students: &[(&str, &[&str])], daily_lesson_capacity: u8) -> TimetableInfo {
+        let mut info = TimetableInfo::new(1, daily_lesson_capacity);
+        let mut south = RoomInfo::new("South 1", None);
+        south.site = Some("South".to_string());
+        let mut north = RoomInfo::new("North 1", None);
+        north.site = Some("North".to_string());
+        info.rooms = vec![south, north];
+        for subject in ["Maths", "Art"] {
+            let mut subject_info = SubjectInfo::new(subject);
+            subject_info.lessons_per_week = [3, 3];
+            info.subjects.insert(subject_info);
+        }
+        for &(id, subjects) in students {
+            info.add_student(id, subjects);
+        }
+        info
+    }
+
+    /// The sites of the rooms the lessons of `subject` are in.
+    fn sites_of(info: &TimetableInfo, result: &TimetableResult, subject: &str) -> Vec<String> {
+        let subject = info.subjects.id(subject).unwrap();
+        let mut sites: Vec<String> = match result {
+            TimetableResult::Solved { rooms_by_slot, .. } => rooms_by_slot
+                .iter()
+                .filter_map(|rooms| rooms.get(&subject))
+                .filter_map(|room_id| info.site_of_lesson(subject, Some(room_id)))
+                .map(str::to_string)
+                .collect(),
+            TimetableResult::Unsolved(error) => panic!("unsolved: {}", error),
+        };
+        sites.dedup();
+        sites
+    }
+
+    #[test]
+    fn each_group_is_kept_at_one_site() {
+        // Maths is taught in the south, so Art can't have the south's only
+        // room in at least one of its slots and goes north for all of them.
+        let mut info = two_sites(&[("A", &["Maths"]), ("B", &["Art"])], 1);
+        let maths = info.subjects.id("Maths").unwrap();
+        info.subjects[maths].site = Some("South".to_string());
+        let result = solve_within_time_limit(&info);
+        assert!(verify(&info, &result).is_empty());
+        assert_eq!(sites_of(&info, &result, "Maths"), ["South"]);
+        assert_eq!(sites_of(&info, &result, "Art"), ["North"]);
+    }
+
+    #[test]
+    fn groups_are_put_at_sites_their_students_can_get_to() {
+        // The student has Maths and Art on the same day at least once, so
+        // Art joins Maths in the north.
+        let mut info = two_sites(&[("A", &["Maths", "Art"])], 2);
+        let maths = info.subjects.id("Maths").unwrap();
+        info.subjects[maths].site = Some("North".to_string());
+        info.site_travel = SiteTravel::OneSitePerDay;
+        let result = solve_within_time_limit(&info);
+        assert!(verify(&info, &result).is_empty());
+        assert_eq!(sites_of(&info, &result, "Art"), ["North"]);
+    }
+
+    /// The number of students in each group of `subject`, smallest first.
+    fn group_sizes(info: &TimetableInfo, result: &TimetableResult, subject: &str) -> Vec<usize> {
+        let subject = info.subjects.id(subject).unwrap();
+        let mut sizes: Vec<usize> = result.rosters()[&subject].iter().map(Vec::len).collect();
+        sizes.sort_unstable();
+        sizes
+    }
+
+    #[test]
+    fn a_full_group_is_split_with_the_new_group() {
+        let mut info = TimetableInfo::new(2, 2);
+        for id in ["A", "B", "C", "D", "E"] {
+            info.add_student(id, &["Maths"]);
+        }
+        info.max_group_size = Some(3);
+        let (result, trace) = solve_timetable_traced(&info);
+        assert!(verify(&info, &result).is_empty());
+        // The first three students fill the first group, so the fourth
+        // needs a new one, which takes one of them along.
+        assert!(trace.iter().any(|event| matches!(
+            event,
+            TraceEvent::Split {
+                group_idx: 0,
+                new_group_idx: 1,
+                students_moved: 1,
+                ..
+            }
+        )));
+        assert_eq!(group_sizes(&info, &result, "Maths"), [2, 3]);
+    }
+
+    #[test]
+    fn groups_below_the_size_limit_are_not_split() {
+        let mut info = TimetableInfo::new(2, 2);
+        for id in ["A", "B", "C"] {
+            info.add_student(id, &["Maths"]);
+        }
+        info.max_group_size = Some(3);
+        let (result, trace) = solve_timetable_traced(&info);
+        assert!(verify(&info, &result).is_empty());
+        assert!(!trace
+            .iter()
+            .any(|event| matches!(event, TraceEvent::Split { .. })));
+        assert_eq!(group_sizes(&info, &result, "Maths"), [3]);
+    }
+
+    #[test]
+    fn subjects_keep_their_name_unless_the_new_one_is_free() {
+        let mut registry = SubjectRegistry::new();
+        let maths = registry.register("Maths");
+        registry.register("Art");
+        assert_eq!(registry.rename(maths, " "), Err(RenameError::EmptyName));
+        assert_eq!(
+            registry.rename(maths, "Art"),
+            Err(RenameError::NameTaken("Art".to_string()))
+        );
+        assert_eq!(registry.rename(maths, "Maths"), Ok(()));
+        assert_eq!(registry.rename(maths, "Mathematics"), Ok(()));
+        assert_eq!(registry.name(maths), "Mathematics");
+        assert_eq!(registry.id("Maths"), None);
+    }
+
+    #[test]
+    fn curricula_and_aliases_without_an_equals_sign_are_mistakes() {
+        assert!(Curricula::parse("Core=English,Maths; ;").is_ok());
+        assert_eq!(
+            Curricula::parse("Core=English;Maths").unwrap_err(),
+            ParseError::Curriculum("Maths".to_string())
+        );
+        assert!(SubjectAliases::parse("Math=Maths, ").is_ok());
+        assert_eq!(
+            SubjectAliases::parse("Math=Maths, Maths").unwrap_err(),
+            ParseError::Alias("Maths".to_string())
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+
+use super::{ParseError, RenameError, SubjectId, TimetableInfo};
+
+/// Other names subjects go by, such as "Math" for "Maths", so that the same
+/// subject typed differently isn't timetabled as two subjects. Aliases match
+/// regardless of case and surrounding whitespace.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Default)]
+pub struct SubjectAliases {
+    subjects_by_alias: BTreeMap<String, String>,
+}
+
+impl SubjectAliases {
+    pub fn new() -> SubjectAliases {
+        SubjectAliases::default()
+    }
+
+    /// Reads aliases written as `alias=subject`, separated by commas, such as
+    /// "Math=Maths, Mathematics=Maths". Blank entries are skipped.
+    pub fn parse(text: &str) -> Result<SubjectAliases, ParseError> {
+        let mut aliases = SubjectAliases::new();
+        for entry in text.split(',').filter(|entry| !entry.trim().is_empty()) {
+            match entry.split_once('=') {
+                Some((alias, subject)) if !alias.trim().is_empty() => aliases.add(alias, subject),
+                _ => return Err(ParseError::Alias(entry.trim().to_string())),
+            }
+        }
+        Ok(aliases)
+    }
+
+    /// Makes `alias` stand for `subject`.
+    pub fn add(&mut self, alias: &str, subject: &str) {
+        let subject = tidy(subject);
+        if !subject.is_empty() {
+            self.subjects_by_alias.insert(key(alias), subject);
+        }
+    }
+
+    /// The name `subject` should be timetabled under, with its whitespace
+    /// tidied and any alias replaced.
+    pub fn resolve(&self, subject: &str) -> String {
+        self.subjects_by_alias
+            .get(&key(subject))
+            .cloned()
+            .unwrap_or_else(|| tidy(subject))
+    }
+
+    /// Renames every subject of the timetable to the name these aliases give
+    /// it. Names which then differ only in case or whitespace take the
+    /// spelling seen first, in ID order. A subject renamed to the name of
+    /// another is merged into it, keeping the other's details, and anything
+    /// listing the same subject twice keeps only the first.
+    pub fn apply(&self, timetable_info: &mut TimetableInfo) {
+        let mut spellings: BTreeMap<String, String> = BTreeMap::new();
+        let mut normalize = |subject: &str| {
+            let resolved = self.resolve(subject);
+            spellings.entry(key(&resolved)).or_insert(resolved).clone()
+        };
+
+        let registry = &mut timetable_info.subjects;
+        let mut merged_into: Vec<SubjectId> = Vec::new();
+        for subject in registry.ids().collect::<Vec<_>>() {
+            let name = normalize(registry.name(subject));
+            merged_into.push(match registry.rename(subject, &name) {
+                Err(RenameError::NameTaken(_)) => registry.id(&name).unwrap(),
+                Ok(()) | Err(RenameError::EmptyName) => subject,
+            });
+        }
+        // The subject merged into may itself have been merged into another.
+        let kept = |&subject: &SubjectId| {
+            let mut kept = subject;
+            while merged_into[kept.index()] != kept {
+                kept = merged_into[kept.index()];
+            }
+            kept
+        };
+        for student in &mut timetable_info.students {
+            student.subjects = dedup(student.subjects.iter().map(kept));
+        }
+        for teacher in &mut timetable_info.teachers {
+            teacher.subjects = dedup(teacher.subjects.iter().map(kept));
+        }
+    }
+}
+
+/// `name` without leading or trailing whitespace and with single spaces
+/// between words.
+fn tidy(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// What names are compared by, so that "MATHS " and "Maths" match.
+fn key(name: &str) -> String {
+    tidy(name).to_lowercase()
+}
+
+/// `subjects` in order, without any repeats.
+fn dedup(subjects: impl Iterator<Item = SubjectId>) -> Vec<SubjectId> {
+    let mut kept: Vec<SubjectId> = Vec::new();
+    for subject in subjects {
+        if !kept.contains(&subject) {
+            kept.push(subject);
+        }
+    }
+    kept
+}
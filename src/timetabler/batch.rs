@@ -0,0 +1,224 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use thiserror::Error;
+
+use super::{
+    from_xhstt_file, solve_timetable, solve_with_fewest_groups, to_xhstt, Algorithm,
+    TimetableResult,
+};
+
+/// How every instance of a batch is solved, overriding what its file gives.
+#[derive(Debug, Clone, Default)]
+pub struct BatchOptions {
+    /// The algorithm to solve with, or `None` to keep each instance's own.
+    pub algorithm: Option<Algorithm>,
+    /// Whether to solve with `solve_with_fewest_groups` rather than
+    /// `solve_timetable`.
+    pub fewest_groups: bool,
+}
+
+/// How one instance of a batch went.
+#[derive(Debug, Clone)]
+pub struct InstanceReport {
+    pub file_name: String,
+    pub students: usize,
+    pub solved: bool,
+    pub groups: usize,
+    /// The score of the timetable under the instance's weights, if it was
+    /// solved.
+    pub score: Option<u64>,
+    pub wall_time: Option<Duration>,
+    /// Why the instance couldn't be read or solved.
+    pub error: Option<String>,
+}
+
+/// How every instance of a batch went, in file name order.
+#[derive(Debug, Clone, Default)]
+pub struct BatchReport {
+    pub instances: Vec<InstanceReport>,
+}
+
+impl BatchReport {
+    pub fn solved(&self) -> usize {
+        self.instances
+            .iter()
+            .filter(|instance| instance.solved)
+            .count()
+    }
+
+    /// The report as comma separated values, with a header row and a row for
+    /// each instance.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("file,students,solved,groups,score,wall_time_ms,error\n");
+        for instance in &self.instances {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                csv_field(&instance.file_name),
+                instance.students,
+                instance.solved,
+                instance.groups,
+                instance
+                    .score
+                    .map_or_else(String::new, |score| score.to_string()),
+                instance
+                    .wall_time
+                    .map_or_else(String::new, |time| time.as_millis().to_string()),
+                csv_field(instance.error.as_deref().unwrap_or_default()),
+            ));
+        }
+        csv
+    }
+}
+
+/// `field` quoted if it would otherwise break the row up.
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Why a batch couldn't be run at all, as opposed to a single instance
+/// failing.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("couldn't use {}: {message}", .path.display())]
+pub struct BatchError {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+fn io_error(path: &Path) -> impl FnOnce(std::io::Error) -> BatchError + '_ {
+    move |error| BatchError {
+        path: path.to_path_buf(),
+        message: error.to_string(),
+    }
+}
+
+/// The ending of the files solutions are written to.
+const SOLUTION_SUFFIX: &str = ".solution.xml";
+
+/// The file the solution to the instance at `path` is written to.
+fn solution_path(output_dir: &Path, path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    output_dir.join(format!("{}{}", stem, SOLUTION_SUFFIX))
+}
+
+/// Solves every XHSTT file (ending in `.xml`) in `input_dir`, in file name
+/// order. Each solved timetable is written to `output_dir` as an XHSTT file
+/// with its solution, named after the instance, and `statistics.csv` there
+/// sums up every instance. An instance which can't be read or solved is
+/// reported rather than stopping the batch.
+///
+/// Files ending in `.solution.xml` are solutions from an earlier batch and
+/// are skipped, so `output_dir` may be `input_dir`.
+pub fn solve_directory(
+    input_dir: impl AsRef<Path>,
+    output_dir: impl AsRef<Path>,
+    options: &BatchOptions,
+) -> Result<BatchReport, BatchError> {
+    let (input_dir, output_dir) = (input_dir.as_ref(), output_dir.as_ref());
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(input_dir).map_err(io_error(input_dir))? {
+        let path = entry.map_err(io_error(input_dir))?.path();
+        let is_solution = path.file_name().map_or(false, |name| {
+            name.to_string_lossy().ends_with(SOLUTION_SUFFIX)
+        });
+        if path.is_file()
+            && !is_solution
+            && path
+                .extension()
+                .map_or(false, |extension| extension == "xml")
+        {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    fs::create_dir_all(output_dir).map_err(io_error(output_dir))?;
+
+    let mut report = BatchReport::default();
+    for path in paths {
+        let mut instance = InstanceReport {
+            file_name: path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+            students: 0,
+            solved: false,
+            groups: 0,
+            score: None,
+            wall_time: None,
+            error: None,
+        };
+        let mut timetable_info = match from_xhstt_file(&path) {
+            Ok(timetable_info) => timetable_info,
+            Err(error) => {
+                instance.error = Some(error.to_string());
+                report.instances.push(instance);
+                continue;
+            }
+        };
+        if let Some(algorithm) = options.algorithm {
+            timetable_info.algorithm = algorithm;
+        }
+        instance.students = timetable_info.students.len();
+
+        let result = if options.fewest_groups {
+            solve_with_fewest_groups(&timetable_info)
+        } else {
+            solve_timetable(&timetable_info)
+        };
+        match &result {
+            TimetableResult::Solved { metrics, stats, .. } => {
+                instance.solved = true;
+                instance.groups = result.group_count();
+                instance.score = Some(metrics.score(&timetable_info.weights));
+                instance.wall_time = stats.wall_time;
+                let solution_path = solution_path(output_dir, &path);
+                fs::write(&solution_path, to_xhstt(&timetable_info, Some(&result)))
+                    .map_err(io_error(&solution_path))?;
+            }
+            TimetableResult::Unsolved(error) => instance.error = Some(error.to_string()),
+        }
+        report.instances.push(instance);
+    }
+
+    let statistics_path = output_dir.join("statistics.csv");
+    fs::write(&statistics_path, report.to_csv()).map_err(io_error(&statistics_path))?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{solve_directory, BatchOptions};
+    use crate::timetabler::{to_xhstt, TimetableInfo};
+
+    #[test]
+    fn solutions_in_the_input_folder_are_skipped() {
+        let dir = std::env::temp_dir().join(format!("timetabler-batch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut info = TimetableInfo::new(1, 2);
+        info.add_student("A", &["Maths"]);
+        fs::write(dir.join("school.xml"), to_xhstt(&info, None)).unwrap();
+
+        // The second batch finds the first one's solution beside the
+        // instance, but only solves the instance again.
+        for _ in 0..2 {
+            let report = solve_directory(&dir, &dir, &BatchOptions::default()).unwrap();
+            let file_names: Vec<&str> = report
+                .instances
+                .iter()
+                .map(|instance| instance.file_name.as_str())
+                .collect();
+            assert_eq!(file_names, ["school.xml"]);
+            assert_eq!(report.solved(), 1);
+        }
+        assert!(dir.join("school.solution.xml").is_file());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,390 @@
+use std::fmt;
+
+use super::{GroupContext, SlotPosition, StudentContext, SubjectId, SubjectInfo, TimetableInfo};
+
+/// Where the solver is thinking of putting a lesson.
+pub struct Placement<'a> {
+    pub subject: SubjectId,
+    /// The slot across the whole year.
+    pub slot: usize,
+    pub position: SlotPosition,
+    pub timetable_info: &'a TimetableInfo,
+    pub(super) student: Option<StudentContext<'a>>,
+    pub(super) groups: Option<GroupContext<'a>>,
+}
+
+impl<'a> Placement<'a> {
+    /// The details of the lesson's subject.
+    pub fn subject_info(&self) -> &'a SubjectInfo {
+        &self.timetable_info.subjects[self.subject]
+    }
+
+    /// The subjects of the lessons the student being placed already has on
+    /// the same day. This is empty when no particular student is being
+    /// placed, such as when the finished timetable is scored.
+    pub fn day_subjects(&self) -> impl Iterator<Item = SubjectId> + 'a {
+        self.day_lessons().map(|(_, subject)| subject)
+    }
+
+    /// The slots and subjects of the lessons the student being placed already
+    /// has on the same day, in the same way as `day_subjects`.
+    pub fn day_lessons(&self) -> impl Iterator<Item = (usize, SubjectId)> + 'a {
+        let daily_lesson_capacity = usize::from(self.timetable_info.daily_lesson_capacity);
+        let day_start = self.slot - self.slot % daily_lesson_capacity;
+        self.student.into_iter().flat_map(move |student| {
+            student.slots[day_start..day_start + daily_lesson_capacity]
+                .iter()
+                .enumerate()
+                .filter_map(move |(period, lesson)| {
+                    lesson.map(|(subject, _)| (day_start + period, subject))
+                })
+        })
+    }
+
+    /// Whether a student is joining a group whose lessons are already in
+    /// place, so that nothing new is taught in the slot.
+    pub fn is_joining(&self) -> bool {
+        self.groups.map_or(false, |groups| groups.joining)
+    }
+
+    /// The subjects of the groups other than the lesson's own which are
+    /// taught in the slot. This is empty when the groups aren't known, such
+    /// as when a finished timetable is checked a student at a time.
+    pub fn other_groups(&self) -> impl Iterator<Item = SubjectId> + 'a {
+        let slot = self.slot;
+        self.groups.into_iter().flat_map(move |groups| {
+            groups
+                .groups_by_subject
+                .iter()
+                .flat_map(move |(&subject, subject_groups)| {
+                    subject_groups
+                        .iter()
+                        .enumerate()
+                        .filter(move |&(group_idx, group)| {
+                            groups.group != Some((subject, group_idx)) && group.busy.contains(slot)
+                        })
+                        .map(move |_| subject)
+                })
+        })
+    }
+
+    /// How many students are in the group being joined, when a student is
+    /// joining one.
+    pub fn group_size(&self) -> Option<usize> {
+        let groups = self.groups.filter(|groups| groups.joining)?;
+        let (subject, group_idx) = groups.group?;
+        Some(
+            groups.groups_by_subject[&subject][group_idx]
+                .student_idxs
+                .len(),
+        )
+    }
+}
+
+/// A rule about where lessons may go, evaluated by the solver whenever it
+/// places or moves a lesson. Hard rules implement `check`, soft rules
+/// implement `penalty`, and a rule can be both.
+pub trait Constraint: fmt::Debug + Send + Sync {
+    /// Whether the lesson may go in the slot at all.
+    fn check(&self, _placement: &Placement<'_>) -> bool {
+        true
+    }
+
+    /// How much the lesson going in the slot should be avoided. Slots with
+    /// lower penalties are preferred.
+    fn penalty(&self, _placement: &Placement<'_>) -> u32 {
+        0
+    }
+}
+
+/// The soft rule given by each subject's `SubjectInfo::slot_preferences`,
+/// with a penalty of one per broken preference.
+#[derive(Debug, Clone, Copy)]
+pub struct SlotPreferences;
+
+impl Constraint for SlotPreferences {
+    fn penalty(&self, placement: &Placement<'_>) -> u32 {
+        placement.subject_info().slot_penalty(
+            placement.slot,
+            placement.timetable_info.daily_lesson_capacity,
+        )
+    }
+}
+
+/// The hard rule given by `TimetableInfo::site_travel`, which keeps students
+/// from having lessons at different sites too close together.
+#[derive(Debug, Clone, Copy)]
+pub struct TravelBetweenSites;
+
+impl Constraint for TravelBetweenSites {
+    fn check(&self, placement: &Placement<'_>) -> bool {
+        placement.day_lessons().all(|(slot, subject)| {
+            placement
+                .timetable_info
+                .can_travel_between((placement.subject, placement.slot), (subject, slot))
+        })
+    }
+}
+
+/// The hard rule given by `TimetableInfo::fixed_events`, which keeps lessons
+/// out of the events students attend. When no particular student is being
+/// placed, only whole school events are kept clear.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedEvents;
+
+impl Constraint for FixedEvents {
+    fn check(&self, placement: &Placement<'_>) -> bool {
+        let timetable_info = placement.timetable_info;
+        let slot = placement.slot % timetable_info.total_slots();
+        let cohort = placement.student.and_then(|student| student.cohort);
+        !timetable_info
+            .fixed_events
+            .iter()
+            .any(|event| event.slots.contains(&slot) && event.is_attended_by(cohort))
+    }
+}
+
+/// The hard rule given by `DailyLessonBounds::hard_max`, which keeps students
+/// from having too many lessons in one day.
+#[derive(Debug, Clone, Copy)]
+pub struct DailyLessonLimit;
+
+impl Constraint for DailyLessonLimit {
+    fn check(&self, placement: &Placement<'_>) -> bool {
+        placement
+            .timetable_info
+            .daily_lessons
+            .hard_max
+            .map_or(true, |max| {
+                let others = placement
+                    .day_lessons()
+                    .filter(|&(slot, _)| slot != placement.slot)
+                    .count();
+                others < usize::from(max)
+            })
+    }
+}
+
+/// The hard rule given by each teacher's `TeacherInfo::days_off`, which keeps
+/// a subject's lessons off the days when every teacher of it is away. Which
+/// teacher takes a group is only decided once the groups are placed, so this
+/// can't rule out every clash on its own.
+#[derive(Debug, Clone, Copy)]
+pub struct TeacherDaysOff;
+
+impl Constraint for TeacherDaysOff {
+    fn check(&self, placement: &Placement<'_>) -> bool {
+        let timetable_info = placement.timetable_info;
+        let mut teachers = timetable_info
+            .teachers
+            .iter()
+            .filter(|teacher| teacher.can_teach(placement.subject))
+            .peekable();
+        // A subject nobody teaches fails when teachers are assigned instead.
+        teachers.peek().is_none()
+            || teachers.any(|teacher| {
+                !teacher.is_off(placement.slot, timetable_info.daily_lesson_capacity)
+            })
+    }
+}
+
+/// The hard rule given by `TimetableInfo::max_groups_per_slot`, which keeps
+/// too many groups from being taught at once.
+#[derive(Debug, Clone, Copy)]
+pub struct GroupsPerSlot;
+
+impl Constraint for GroupsPerSlot {
+    fn check(&self, placement: &Placement<'_>) -> bool {
+        placement.is_joining()
+            || placement
+                .timetable_info
+                .max_groups_per_slot
+                .map_or(true, |max| {
+                    placement.other_groups().count() < usize::from(max)
+                })
+    }
+}
+
+/// The hard rule given by `TimetableInfo::max_group_size` and each subject's
+/// `SubjectInfo::max_group_size`, which keeps students out of full groups.
+#[derive(Debug, Clone, Copy)]
+pub struct GroupSize;
+
+impl Constraint for GroupSize {
+    fn check(&self, placement: &Placement<'_>) -> bool {
+        match (
+            placement.group_size(),
+            placement
+                .timetable_info
+                .max_group_size_for(placement.subject),
+        ) {
+            (Some(size), Some(max_size)) => size < max_size,
+            _ => true,
+        }
+    }
+}
+
+/// The hard rule given by `TimetableInfo::rooms`, which keeps more groups
+/// from being taught in a slot than there are rooms for. Which room a group
+/// gets is only decided once the groups are placed, so this can't rule out
+/// every clash on its own.
+#[derive(Debug, Clone, Copy)]
+pub struct RoomsFree;
+
+impl Constraint for RoomsFree {
+    fn check(&self, placement: &Placement<'_>) -> bool {
+        let timetable_info = placement.timetable_info;
+        if timetable_info.rooms.is_empty() || placement.is_joining() {
+            return true;
+        }
+        let needs = |subject: SubjectId| {
+            (
+                timetable_info.subjects[subject].room_type.as_deref(),
+                timetable_info.site_of(subject),
+            )
+        };
+        let (room_type, site) = needs(placement.subject);
+        let suitable = timetable_info
+            .rooms
+            .iter()
+            .filter(|room| {
+                (room_type.is_none() || room.room_type.as_deref() == room_type)
+                    && (site.is_none() || room.site.as_deref() == site)
+            })
+            .count();
+        // Groups which need the same kind of room as this one compete for the
+        // rooms which suit it.
+        let (mut others, mut rivals) = (0, 0);
+        for subject in placement.other_groups() {
+            others += 1;
+            if needs(subject) == (room_type, site) {
+                rivals += 1;
+            }
+        }
+        others < timetable_info.rooms.len() && rivals < suitable
+    }
+}
+
+/// The hard rule given by `TimetableInfo::teachers`, which keeps more groups
+/// from being taught in a slot than there are teachers in for them. As with
+/// `RoomsFree`, the teachers are only assigned once the groups are placed, so
+/// this can't rule out every clash on its own.
+#[derive(Debug, Clone, Copy)]
+pub struct TeachersFree;
+
+impl Constraint for TeachersFree {
+    fn check(&self, placement: &Placement<'_>) -> bool {
+        let timetable_info = placement.timetable_info;
+        if timetable_info.teachers.is_empty() || placement.is_joining() {
+            return true;
+        }
+        // The teachers who could take a lesson of `subject` in the slot.
+        let teachers_in = |subject: SubjectId| -> Vec<usize> {
+            timetable_info
+                .teachers
+                .iter()
+                .enumerate()
+                .filter(|(_, teacher)| {
+                    teacher.can_teach(subject)
+                        && !teacher.is_off(placement.slot, timetable_info.daily_lesson_capacity)
+                })
+                .map(|(teacher_idx, _)| teacher_idx)
+                .collect()
+        };
+        let teacher_idxs = teachers_in(placement.subject);
+        // Subjects nobody can teach in the slot are left to
+        // `TeacherDaysOff` and to assigning the teachers.
+        if teacher_idxs.is_empty() {
+            return true;
+        }
+        // Groups which only these teachers can take would leave them no
+        // teacher for this one.
+        let rivals = placement
+            .other_groups()
+            .filter(|&subject| {
+                let others = teachers_in(subject);
+                !others.is_empty()
+                    && others
+                        .iter()
+                        .all(|teacher_idx| teacher_idxs.contains(teacher_idx))
+            })
+            .count();
+        rivals < teacher_idxs.len()
+    }
+}
+
+/// The rules every timetable follows, before any in
+/// `TimetableInfo::constraints`.
+pub(super) static BUILT_IN_CONSTRAINTS: [&dyn Constraint; 9] = [
+    &SlotPreferences,
+    &TravelBetweenSites,
+    &FixedEvents,
+    &DailyLessonLimit,
+    &TeacherDaysOff,
+    &GroupsPerSlot,
+    &GroupSize,
+    &RoomsFree,
+    &TeachersFree,
+];
+
+#[cfg(test)]
+mod tests {
+    use crate::timetabler::{
+        solve_timetable, verify, RoomInfo, TeacherInfo, TimetableInfo, TimetableResult,
+    };
+
+    /// Two students who share no subjects, whose lessons could go in the same
+    /// slot if nothing else stopped them.
+    fn two_students() -> TimetableInfo {
+        let mut info = TimetableInfo::new(1, 2);
+        info.add_student("A", &["Maths"]);
+        info.add_student("B", &["Art"]);
+        info
+    }
+
+    /// Solves `info`, checking the timetable keeps to every rule and that
+    /// Maths and Art are taught at different times.
+    fn assert_kept_apart(info: &TimetableInfo) {
+        let result = solve_timetable(info);
+        assert!(verify(info, &result).is_empty());
+        match result {
+            TimetableResult::Solved { subjects, .. } => {
+                assert!(subjects.iter().all(|slot| slot.len() <= 1))
+            }
+            TimetableResult::Unsolved(error) => panic!("unsolved: {}", error),
+        }
+    }
+
+    #[test]
+    fn groups_per_slot_keeps_groups_apart() {
+        let mut info = two_students();
+        info.max_groups_per_slot = Some(1);
+        assert_kept_apart(&info);
+    }
+
+    #[test]
+    fn rooms_free_keeps_groups_apart_when_there_is_one_room() {
+        let mut info = two_students();
+        info.rooms = vec![RoomInfo::new("Room 1", None)];
+        assert_kept_apart(&info);
+    }
+
+    #[test]
+    fn teachers_free_keeps_groups_apart_when_one_teacher_teaches_both() {
+        let mut info = two_students();
+        let subjects = info.subjects.ids().collect();
+        info.teachers = vec![TeacherInfo::new("Teacher", subjects)];
+        assert_kept_apart(&info);
+    }
+
+    #[test]
+    fn group_size_keeps_students_out_of_full_groups() {
+        let mut info = TimetableInfo::new(2, 2);
+        info.add_student("A", &["Maths"]);
+        info.add_student("B", &["Maths"]);
+        info.max_group_size = Some(1);
+        let result = solve_timetable(&info);
+        assert!(verify(&info, &result).is_empty());
+        assert_eq!(result.group_count(), 2);
+    }
+}
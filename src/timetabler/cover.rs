@@ -0,0 +1,294 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use super::{group_name, SubjectId, TimetableInfo, TimetableResult};
+
+/// A lesson of an absent teacher, and who covers it.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoveredLesson {
+    pub slot: usize,
+    pub subject: String,
+    pub group_idx: usize,
+    /// The room the lesson stays in, if rooms were assigned.
+    pub room: Option<String>,
+    /// The teacher covering the lesson, or `None` if nobody could.
+    pub cover_teacher_id: Option<String>,
+}
+
+/// Who covers each lesson of a teacher while they are away. Nothing else
+/// about the timetable changes: the groups keep their slots, students and
+/// rooms.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoverSheet {
+    pub absent_teacher_id: String,
+    /// Every lesson the absent teacher would have taught on their days away,
+    /// in slot order.
+    pub lessons: Vec<CoveredLesson>,
+}
+
+impl CoverSheet {
+    /// The lessons nobody was free to cover.
+    pub fn uncovered(&self) -> impl Iterator<Item = &CoveredLesson> {
+        self.lessons
+            .iter()
+            .filter(|lesson| lesson.cover_teacher_id.is_none())
+    }
+}
+
+/// Lists the lessons one per line, e.g. "Slot 3: Maths B in R1, covered by
+/// T2".
+impl fmt::Display for CoverSheet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Cover for {}", self.absent_teacher_id)?;
+        for lesson in &self.lessons {
+            write!(
+                f,
+                "Slot {}: {}",
+                lesson.slot,
+                group_name(&lesson.subject, lesson.group_idx)
+            )?;
+            if let Some(room) = &lesson.room {
+                write!(f, " in {}", room)?;
+            }
+            match &lesson.cover_teacher_id {
+                Some(teacher_id) => writeln!(f, ", covered by {}", teacher_id)?,
+                None => writeln!(f, ", not covered")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Finds cover for the lessons `absent_teacher_id` teaches in `result` on
+/// `days`. Days are counted like slots, across every term, so day 0 is the
+/// first day of the cycle in the first term.
+///
+/// A covering teacher must be working that day, free in the lesson's slot and
+/// able to get there from their other lessons that day, and the cover counts
+/// towards their weekly load and the lessons they may teach back to back.
+/// Teachers of the subject are picked first, then whoever has covered the
+/// fewest lessons so far. An unsolved result, or one without teachers, needs
+/// no cover.
+pub fn plan_cover(
+    timetable_info: &TimetableInfo,
+    result: &TimetableResult,
+    absent_teacher_id: &str,
+    days: &[usize],
+) -> CoverSheet {
+    let mut cover_sheet = CoverSheet {
+        absent_teacher_id: absent_teacher_id.to_string(),
+        lessons: Vec::new(),
+    };
+    let (rooms_by_slot, slots_by_teacher_id) = match result {
+        TimetableResult::Solved {
+            rooms_by_slot,
+            slots_by_teacher_id,
+            ..
+        } => (rooms_by_slot, slots_by_teacher_id),
+        TimetableResult::Unsolved(_) => return cover_sheet,
+    };
+    let absent_slots = match slots_by_teacher_id.get(absent_teacher_id) {
+        Some(slots) => slots,
+        None => return cover_sheet,
+    };
+
+    let daily_lesson_capacity = usize::from(timetable_info.daily_lesson_capacity);
+    let slots_per_week = timetable_info.slots_per_week();
+    let lesson_site = |subject: SubjectId, slot: usize| {
+        let room_id = rooms_by_slot
+            .get(slot)
+            .and_then(|rooms| rooms.get(&subject));
+        timetable_info.site_of_lesson(subject, room_id.map(String::as_str))
+    };
+    let days: BTreeSet<usize> = days.iter().copied().collect();
+    // What every other teacher is teaching, which the cover is added to as
+    // it is handed out.
+    let teachers: Vec<_> = timetable_info
+        .teachers
+        .iter()
+        .filter(|teacher| teacher.id != absent_teacher_id)
+        .collect();
+    let mut taught: Vec<Vec<Option<SubjectId>>> = teachers
+        .iter()
+        .map(|teacher| {
+            slots_by_teacher_id
+                .get(&teacher.id)
+                .map(|slots| {
+                    slots
+                        .iter()
+                        .map(|entry| entry.map(|(subject, _)| subject))
+                        .collect()
+                })
+                .unwrap_or_else(|| vec![None; absent_slots.len()])
+        })
+        .collect();
+    let mut covers = vec![0usize; teachers.len()];
+
+    for (slot, entry) in absent_slots.iter().enumerate() {
+        let (subject, group_idx) = match *entry {
+            Some(entry) if days.contains(&(slot / daily_lesson_capacity)) => entry,
+            _ => continue,
+        };
+        let day_start = slot - slot % daily_lesson_capacity;
+        let week_start = slot - slot % slots_per_week;
+        let cover_idx = (0..teachers.len())
+            .filter(|&teacher_idx| {
+                let teacher = teachers[teacher_idx];
+                let taught = &taught[teacher_idx];
+                if taught[slot].is_some()
+                    || teacher.is_off(slot, timetable_info.daily_lesson_capacity)
+                {
+                    return false;
+                }
+                let weekly_lessons = taught[week_start..week_start + slots_per_week]
+                    .iter()
+                    .filter(|entry| entry.is_some())
+                    .count();
+                // The run of back to back lessons the cover would be part of.
+                let before = taught[day_start..slot]
+                    .iter()
+                    .rev()
+                    .take_while(|entry| entry.is_some())
+                    .count();
+                let after = taught[slot + 1..day_start + daily_lesson_capacity]
+                    .iter()
+                    .take_while(|entry| entry.is_some())
+                    .count();
+                let is_rested = teacher
+                    .max_consecutive_lessons
+                    .or(timetable_info.max_consecutive_lessons)
+                    .map_or(true, |max| before + 1 + after <= usize::from(max));
+                let can_travel = (day_start..day_start + daily_lesson_capacity).all(|other_slot| {
+                    taught[other_slot].map_or(true, |other| {
+                        timetable_info.can_travel_between_sites(
+                            (lesson_site(subject, slot), slot),
+                            (lesson_site(other, other_slot), other_slot),
+                        )
+                    })
+                });
+                teacher.has_capacity(weekly_lessons + 1) && is_rested && can_travel
+            })
+            .min_by_key(|&teacher_idx| {
+                (
+                    !teachers[teacher_idx].can_teach(subject),
+                    covers[teacher_idx],
+                )
+            });
+
+        if let Some(teacher_idx) = cover_idx {
+            taught[teacher_idx][slot] = Some(subject);
+            covers[teacher_idx] += 1;
+        }
+        cover_sheet.lessons.push(CoveredLesson {
+            slot,
+            subject: timetable_info.subjects.name(subject).to_string(),
+            group_idx,
+            room: rooms_by_slot
+                .get(slot)
+                .and_then(|rooms| rooms.get(&subject))
+                .cloned(),
+            cover_teacher_id: cover_idx.map(|teacher_idx| teachers[teacher_idx].id.clone()),
+        });
+    }
+    cover_sheet
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::plan_cover;
+    use crate::timetabler::{
+        Metrics, SolveStats, SubjectId, TeacherInfo, TimetableInfo, TimetableResult,
+    };
+
+    /// Two lessons a day, with Maths and Art registered.
+    fn school() -> (TimetableInfo, SubjectId, SubjectId) {
+        let mut info = TimetableInfo::new(1, 2);
+        info.add_student("Ann", &["Maths", "Art"]);
+        let maths = info.subjects.id("Maths").unwrap();
+        let art = info.subjects.id("Art").unwrap();
+        (info, maths, art)
+    }
+
+    /// A timetable in which each teacher teaches group A of the subject in
+    /// each of their (slot, subject) lessons.
+    fn taught(info: &TimetableInfo, lessons: &[(&str, &[(usize, SubjectId)])]) -> TimetableResult {
+        let mut slots_by_teacher_id = BTreeMap::new();
+        for &(teacher_id, teacher_lessons) in lessons {
+            let mut slots = vec![None; info.slots_per_year()];
+            for &(slot, subject) in teacher_lessons {
+                slots[slot] = Some((subject, 0));
+            }
+            slots_by_teacher_id.insert(teacher_id.to_string(), slots);
+        }
+        TimetableResult::Solved {
+            subjects: Vec::new(),
+            slots_by_student_id: BTreeMap::new(),
+            group_names: BTreeMap::new(),
+            rooms_by_slot: Vec::new(),
+            slots_by_room: BTreeMap::new(),
+            teachers_by_slot: Vec::new(),
+            slots_by_teacher_id,
+            metrics: Metrics::default(),
+            stats: SolveStats::default(),
+        }
+    }
+
+    #[test]
+    fn a_lesson_nobody_is_free_for_goes_uncovered() {
+        let (mut info, maths, art) = school();
+        info.max_consecutive_lessons = Some(1);
+        let mut off = TeacherInfo::new("Off", vec![maths]);
+        off.days_off = vec![0];
+        let mut full = TeacherInfo::new("Full", vec![maths]);
+        full.max_weekly_lessons = Some(1);
+        info.teachers = vec![
+            TeacherInfo::new("Away", vec![maths]),
+            off,
+            full,
+            // Covering would give them two lessons in a row.
+            TeacherInfo::new("Tired", vec![art]),
+        ];
+        let result = taught(
+            &info,
+            &[
+                ("Away", &[(0, maths)]),
+                ("Full", &[(3, art)]),
+                ("Tired", &[(1, art)]),
+            ],
+        );
+
+        let cover_sheet = plan_cover(&info, &result, "Away", &[0]);
+        assert_eq!(cover_sheet.lessons.len(), 1);
+        assert_eq!(cover_sheet.lessons[0].slot, 0);
+        assert_eq!(cover_sheet.lessons[0].cover_teacher_id, None);
+        assert_eq!(cover_sheet.uncovered().count(), 1);
+
+        // Lessons on other days aren't covered at all.
+        assert!(plan_cover(&info, &result, "Away", &[1]).lessons.is_empty());
+    }
+
+    #[test]
+    fn teachers_of_the_subject_cover_before_those_with_fewer_covers() {
+        let (mut info, maths, art) = school();
+        info.teachers = vec![
+            TeacherInfo::new("Away", vec![maths, art]),
+            TeacherInfo::new("Other", Vec::new()),
+            TeacherInfo::new("Specialist", vec![maths, art]),
+        ];
+        // The last lesson of Monday and the first of Tuesday, at either end
+        // of their days.
+        let result = taught(&info, &[("Away", &[(1, maths), (2, art)])]);
+
+        let cover_sheet = plan_cover(&info, &result, "Away", &[0, 1]);
+        let covers: Vec<_> = cover_sheet
+            .lessons
+            .iter()
+            .map(|lesson| (lesson.slot, lesson.cover_teacher_id.as_deref()))
+            .collect();
+        assert_eq!(covers, [(1, Some("Specialist")), (2, Some("Specialist"))]);
+    }
+}
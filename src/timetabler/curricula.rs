@@ -0,0 +1,87 @@
+use std::collections::BTreeMap;
+
+use super::{ParseError, TimetableInfo};
+
+/// Named bundles of subjects, such as "Year 10 core" for English, Maths,
+/// Science and PE, so that students taking the same subjects can be given
+/// the bundle's name instead of every subject in it.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Default)]
+pub struct Curricula {
+    subjects_by_name: BTreeMap<String, Vec<String>>,
+}
+
+impl Curricula {
+    pub fn new() -> Curricula {
+        Curricula::default()
+    }
+
+    /// Reads curricula written as `name=subject,subject`, separated by
+    /// semicolons, such as "Year 10 core=English,Maths,Science,PE". Blank
+    /// entries are skipped.
+    pub fn parse(text: &str) -> Result<Curricula, ParseError> {
+        let mut curricula = Curricula::new();
+        for entry in text.split(';').filter(|entry| !entry.trim().is_empty()) {
+            match entry.split_once('=') {
+                Some((name, subjects)) if !name.trim().is_empty() => {
+                    curricula.add(name, subjects.split(',').map(str::to_string).collect())
+                }
+                _ => return Err(ParseError::Curriculum(entry.trim().to_string())),
+            }
+        }
+        Ok(curricula)
+    }
+
+    /// Makes `name` stand for `subjects`, replacing any curriculum of the
+    /// same name. Blank subjects are left out.
+    pub fn add(&mut self, name: &str, subjects: Vec<String>) {
+        let subjects = subjects
+            .iter()
+            .map(|subject| subject.trim())
+            .filter(|subject| !subject.is_empty())
+            .map(str::to_string)
+            .collect();
+        self.subjects_by_name
+            .insert(name.trim().to_string(), subjects);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&[String]> {
+        self.subjects_by_name.get(name.trim()).map(Vec::as_slice)
+    }
+
+    /// `subjects` with each curriculum among them replaced by the subjects in
+    /// it. A subject which would then appear twice is only kept once.
+    pub fn expand(&self, subjects: &[String]) -> Vec<String> {
+        let mut expanded: Vec<String> = Vec::new();
+        for taken in subjects {
+            let bundle = match self.get(taken) {
+                Some(curriculum) => curriculum,
+                None => std::slice::from_ref(taken),
+            };
+            for subject in bundle {
+                if !expanded.contains(subject) {
+                    expanded.push(subject.clone());
+                }
+            }
+        }
+        expanded
+    }
+
+    /// Expands the curricula every student of the timetable takes,
+    /// registering any subjects of them which are new.
+    pub fn apply(&self, timetable_info: &mut TimetableInfo) {
+        let registry = &mut timetable_info.subjects;
+        for student in &mut timetable_info.students {
+            let names: Vec<String> = student
+                .subjects
+                .iter()
+                .map(|&subject| registry.name(subject).to_string())
+                .collect();
+            student.subjects = self
+                .expand(&names)
+                .iter()
+                .map(|subject| registry.register(subject))
+                .collect();
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{StudentId, SubjectId, TimetableResult};
+
+/// A group of a subject, by subject ID and group index.
+pub type GroupKey = (SubjectId, usize);
+
+/// A group whose lessons are in different slots in the two results.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MovedGroup {
+    pub group: GroupKey,
+    pub old_slots: Vec<usize>,
+    pub new_slots: Vec<usize>,
+}
+
+/// What changed between two results, e.g. since a timetable was last
+/// published. Everything is sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TimetableDiff {
+    pub moved_groups: Vec<MovedGroup>,
+    /// Groups only found in the new result.
+    pub new_groups: Vec<GroupKey>,
+    /// Groups only found in the old result.
+    pub removed_groups: Vec<GroupKey>,
+    /// Students whose timetable differs in any slot, including students only
+    /// found in one of the results.
+    pub changed_students: Vec<StudentId>,
+}
+
+impl TimetableDiff {
+    pub fn is_empty(&self) -> bool {
+        self.moved_groups.is_empty()
+            && self.new_groups.is_empty()
+            && self.removed_groups.is_empty()
+            && self.changed_students.is_empty()
+    }
+}
+
+/// Finds the slots of every group from its students' timetables. An unsolved
+/// result has no groups.
+pub(super) fn slots_by_group(result: &TimetableResult) -> BTreeMap<GroupKey, BTreeSet<usize>> {
+    let mut slots_by_group: BTreeMap<GroupKey, BTreeSet<usize>> = BTreeMap::new();
+    if let TimetableResult::Solved {
+        slots_by_student_id,
+        ..
+    } = result
+    {
+        for slots in slots_by_student_id.values() {
+            for (slot, entry) in slots.iter().enumerate() {
+                if let Some(group) = *entry {
+                    slots_by_group.entry(group).or_default().insert(slot);
+                }
+            }
+        }
+    }
+    slots_by_group
+}
+
+type StudentSlots = BTreeMap<StudentId, Vec<Option<(SubjectId, usize)>>>;
+
+fn students(result: &TimetableResult) -> Option<&StudentSlots> {
+    match result {
+        TimetableResult::Solved {
+            slots_by_student_id,
+            ..
+        } => Some(slots_by_student_id),
+        TimetableResult::Unsolved(_) => None,
+    }
+}
+
+/// Compares two results. Groups are matched by subject and index, so a group
+/// which keeps its slots but changes its index shows up as removed and new.
+pub fn diff(old: &TimetableResult, new: &TimetableResult) -> TimetableDiff {
+    let mut timetable_diff = TimetableDiff::default();
+
+    let old_groups = slots_by_group(old);
+    let new_groups = slots_by_group(new);
+    for (group, old_slots) in &old_groups {
+        match new_groups.get(group) {
+            Some(new_slots) if new_slots != old_slots => {
+                timetable_diff.moved_groups.push(MovedGroup {
+                    group: *group,
+                    old_slots: old_slots.iter().copied().collect(),
+                    new_slots: new_slots.iter().copied().collect(),
+                });
+            }
+            Some(_) => {}
+            None => timetable_diff.removed_groups.push(*group),
+        }
+    }
+    for group in new_groups.keys() {
+        if !old_groups.contains_key(group) {
+            timetable_diff.new_groups.push(*group);
+        }
+    }
+
+    let (old_students, new_students) = (students(old), students(new));
+    let student_ids: BTreeSet<&StudentId> = old_students
+        .iter()
+        .chain(new_students.iter())
+        .flat_map(|students| students.keys())
+        .collect();
+    for student_id in student_ids {
+        let old_slots = old_students.and_then(|students| students.get(student_id));
+        let new_slots = new_students.and_then(|students| students.get(student_id));
+        if old_slots != new_slots {
+            timetable_diff.changed_students.push(student_id.clone());
+        }
+    }
+
+    timetable_diff
+}
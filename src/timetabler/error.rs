@@ -0,0 +1,44 @@
+use thiserror::Error;
+
+#[cfg(feature = "json")]
+use super::JsonError;
+#[cfg(feature = "scripting")]
+use super::ScriptError;
+use super::{RosterError, XhsttError};
+
+/// Why text typed in for part of a timetable couldn't be read. Blank entries
+/// are skipped rather than being mistakes.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ParseError {
+    /// A curriculum wasn't written as `name=subject,subject`.
+    #[error("\"{0}\" should be written as name=subject,subject")]
+    Curriculum(String),
+    /// An alias wasn't written as `alias=subject`.
+    #[error("\"{0}\" should be written as alias=subject")]
+    Alias(String),
+}
+
+/// Why a file couldn't be imported, whichever kind of file it was.
+#[derive(Debug, Error)]
+pub enum ImportError {
+    #[error(transparent)]
+    Xhstt(#[from] XhsttError),
+    #[cfg(feature = "json")]
+    #[error(transparent)]
+    Json(#[from] JsonError),
+    #[error(transparent)]
+    Roster(#[from] RosterError),
+    #[cfg(feature = "scripting")]
+    #[error(transparent)]
+    Script(#[from] ScriptError),
+}
+
+/// Why `SubjectRegistry::rename` left a subject as it was.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum RenameError {
+    #[error("a subject needs a name")]
+    EmptyName,
+    /// Another subject already has the name.
+    #[error("there is already a subject called {0}")]
+    NameTaken(String),
+}
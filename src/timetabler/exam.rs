@@ -0,0 +1,414 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use thiserror::Error;
+
+use super::{StudentId, SubjectId, TimetableInfo, TimetableResult};
+
+/// How many times the exams are gone over looking for better sessions before
+/// the schedule is given as it is.
+const MAX_IMPROVEMENT_ROUNDS: usize = 20;
+
+/// When each subject's exam is sat. Exams are held in the slots of the week
+/// cycle, one exam per subject, which are called sessions here.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExamSchedule {
+    /// The session of each subject's exam.
+    pub session_by_subject: BTreeMap<SubjectId, usize>,
+    /// The exams sat in each session, in ID order.
+    pub subjects_by_session: Vec<Vec<SubjectId>>,
+    /// The students sitting more than one exam on the same day.
+    pub students_with_same_day_exams: Vec<StudentId>,
+    /// The students sitting two exams one straight after the other.
+    pub students_with_back_to_back_exams: Vec<StudentId>,
+}
+
+/// Why exams couldn't be scheduled.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ExamError {
+    /// Every session already had an exam sat by one of the subject's
+    /// students, or as many exams as may be sat at once.
+    #[error("no session is free for the {subject} exam")]
+    NoSessionFree { subject: String },
+    /// Every teacher was off, teaching, invigilating another exam, or had
+    /// reached their weekly load in `session`.
+    #[error("no teacher is free to invigilate the {subject} exam in session {session}")]
+    NoInvigilatorAvailable { subject: String, session: usize },
+}
+
+/// Which teachers supervise each exam.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InvigilationRota {
+    /// The IDs of the teachers invigilating each subject's exam.
+    pub invigilators_by_subject: BTreeMap<SubjectId, Vec<String>>,
+    /// The sessions each teacher invigilates in, in order. Teachers without
+    /// any are left out.
+    pub sessions_by_teacher_id: BTreeMap<String, Vec<usize>>,
+}
+
+/// The students and sessions of the exams being scheduled.
+struct Exams {
+    subjects: Vec<SubjectId>,
+    /// The subjects each student sits, by subject index.
+    subjects_by_student: Vec<Vec<usize>>,
+    /// The students sitting each subject.
+    students_by_subject: Vec<Vec<usize>>,
+    sessions: usize,
+    daily_sessions: usize,
+    /// The most exams which may be sat in one session.
+    max_exams_per_session: Option<usize>,
+}
+
+impl Exams {
+    /// How badly `student`'s exams are spread: one for sitting several exams
+    /// on the same day, and one more for sitting two back to back.
+    fn student_cost(&self, student: usize, session_by_subject: &[Option<usize>]) -> usize {
+        let sessions: BTreeSet<usize> = self.subjects_by_student[student]
+            .iter()
+            .filter_map(|&subject| session_by_subject[subject])
+            .collect();
+        let (same_day, back_to_back) = sessions.iter().zip(sessions.iter().skip(1)).fold(
+            (false, false),
+            |(same_day, back_to_back), (&first, &second)| {
+                let shares_day = first / self.daily_sessions == second / self.daily_sessions;
+                (
+                    same_day || shares_day,
+                    back_to_back || (shares_day && second == first + 1),
+                )
+            },
+        );
+        usize::from(same_day) + usize::from(back_to_back)
+    }
+
+    /// The total cost of the students sitting `subject`.
+    fn subject_cost(&self, subject: usize, session_by_subject: &[Option<usize>]) -> usize {
+        self.students_by_subject[subject]
+            .iter()
+            .map(|&student| self.student_cost(student, session_by_subject))
+            .sum()
+    }
+
+    /// Whether `subject`'s exam could be sat in `session` without a student
+    /// sitting two exams at once.
+    fn is_free(
+        &self,
+        subject: usize,
+        session: usize,
+        session_by_subject: &[Option<usize>],
+    ) -> bool {
+        let clashes = self.students_by_subject[subject].iter().any(|&student| {
+            self.subjects_by_student[student]
+                .iter()
+                .any(|&other| other != subject && session_by_subject[other] == Some(session))
+        });
+        let full = self.max_exams_per_session.map_or(false, |max_exams| {
+            session_by_subject
+                .iter()
+                .enumerate()
+                .filter(|&(other, &other_session)| {
+                    other != subject && other_session == Some(session)
+                })
+                .count()
+                >= max_exams
+        });
+        !clashes && !full
+    }
+
+    /// The free session which costs `subject`'s students the least, with
+    /// earlier sessions winning ties.
+    fn best_session(
+        &self,
+        subject: usize,
+        session_by_subject: &mut [Option<usize>],
+    ) -> Option<(usize, usize)> {
+        let current = session_by_subject[subject];
+        let mut best = None;
+        for session in 0..self.sessions {
+            if !self.is_free(subject, session, session_by_subject) {
+                continue;
+            }
+            session_by_subject[subject] = Some(session);
+            let cost = self.subject_cost(subject, session_by_subject);
+            if best.map_or(true, |(_, best_cost)| cost < best_cost) {
+                best = Some((session, cost));
+            }
+        }
+        session_by_subject[subject] = current;
+        best
+    }
+}
+
+/// Schedules one exam for every subject taken by a student, using the
+/// students' subjects and the week cycle of `timetable_info`. No student sits
+/// two exams at once, and as few students as possible sit several exams on
+/// one day, or two back to back. `TimetableInfo::max_groups_per_slot` limits
+/// the exams sat at once.
+///
+/// Exams are placed one at a time, those sat alongside the most other exams
+/// first, each in the session which costs its students the least. Then each
+/// exam is moved to a better session until none can be.
+pub fn schedule_exams(timetable_info: &TimetableInfo) -> Result<ExamSchedule, ExamError> {
+    let mut subject_idxs: BTreeMap<SubjectId, usize> = BTreeMap::new();
+    let mut subjects = Vec::new();
+    let mut subjects_by_student = Vec::new();
+    for student in &timetable_info.students {
+        let mut sat = Vec::new();
+        for &subject in &student.subjects {
+            let subject_idx = *subject_idxs.entry(subject).or_insert_with(|| {
+                subjects.push(subject);
+                subjects.len() - 1
+            });
+            if !sat.contains(&subject_idx) {
+                sat.push(subject_idx);
+            }
+        }
+        subjects_by_student.push(sat);
+    }
+    let mut students_by_subject = vec![Vec::new(); subjects.len()];
+    for (student_idx, sat) in subjects_by_student.iter().enumerate() {
+        for &subject_idx in sat {
+            students_by_subject[subject_idx].push(student_idx);
+        }
+    }
+    let exams = Exams {
+        subjects,
+        subjects_by_student,
+        students_by_subject,
+        sessions: timetable_info.total_slots(),
+        daily_sessions: timetable_info.daily_lesson_capacity.into(),
+        max_exams_per_session: timetable_info.max_groups_per_slot.map(usize::from),
+    };
+
+    // Exams which share students with the most other exams are the hardest
+    // to place, so they go first.
+    let clashing_exams = |subject: usize| {
+        exams.students_by_subject[subject]
+            .iter()
+            .flat_map(|&student| &exams.subjects_by_student[student])
+            .collect::<BTreeSet<_>>()
+            .len()
+    };
+    let mut order: Vec<usize> = (0..exams.subjects.len()).collect();
+    order.sort_by_cached_key(|&subject| {
+        (
+            std::cmp::Reverse(clashing_exams(subject)),
+            std::cmp::Reverse(exams.students_by_subject[subject].len()),
+        )
+    });
+
+    let mut session_by_subject = vec![None; exams.subjects.len()];
+    for &subject in &order {
+        match exams.best_session(subject, &mut session_by_subject) {
+            Some((session, _)) => session_by_subject[subject] = Some(session),
+            None => {
+                return Err(ExamError::NoSessionFree {
+                    subject: timetable_info
+                        .subjects
+                        .name(exams.subjects[subject])
+                        .to_string(),
+                })
+            }
+        }
+    }
+
+    for _ in 0..MAX_IMPROVEMENT_ROUNDS {
+        let mut improved = false;
+        for &subject in &order {
+            let cost = exams.subject_cost(subject, &session_by_subject);
+            if let Some((session, new_cost)) = exams.best_session(subject, &mut session_by_subject)
+            {
+                if new_cost < cost {
+                    session_by_subject[subject] = Some(session);
+                    improved = true;
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+
+    let mut schedule = ExamSchedule {
+        session_by_subject: BTreeMap::new(),
+        subjects_by_session: vec![Vec::new(); exams.sessions],
+        students_with_same_day_exams: Vec::new(),
+        students_with_back_to_back_exams: Vec::new(),
+    };
+    for (&subject, session) in exams.subjects.iter().zip(&session_by_subject) {
+        // Every exam was given a session above.
+        let session = session.unwrap();
+        schedule.session_by_subject.insert(subject, session);
+        schedule.subjects_by_session[session].push(subject);
+    }
+    for subjects in &mut schedule.subjects_by_session {
+        subjects.sort();
+    }
+    for (student_idx, student) in timetable_info.students.iter().enumerate() {
+        let cost = exams.student_cost(student_idx, &session_by_subject);
+        if cost >= 1 {
+            schedule
+                .students_with_same_day_exams
+                .push(student.id.clone());
+        }
+        if cost == 2 {
+            schedule
+                .students_with_back_to_back_exams
+                .push(student.id.clone());
+        }
+    }
+    schedule.students_with_same_day_exams.sort();
+    schedule.students_with_back_to_back_exams.sort();
+    Ok(schedule)
+}
+
+/// Gives every exam of `schedule` enough invigilators for its students, with
+/// one teacher for each `TimetableInfo::students_per_invigilator` students or
+/// part of that. Teachers can't invigilate on their days off, while teaching
+/// a lesson of `lessons`, if given, or another exam, and the lessons and
+/// exams of a week together count towards their
+/// `TeacherInfo::max_weekly_lessons`. Each exam goes to the teachers with the
+/// fewest exams so far.
+pub fn assign_invigilators(
+    timetable_info: &TimetableInfo,
+    schedule: &ExamSchedule,
+    lessons: Option<&TimetableResult>,
+) -> Result<InvigilationRota, ExamError> {
+    let teachers = &timetable_info.teachers;
+    let slots_per_week = timetable_info.slots_per_week();
+    let weeks = timetable_info.cycle.weeks();
+    // Whether each teacher is busy in each session, and their load in each
+    // week of the cycle.
+    let mut is_busy = vec![vec![false; timetable_info.total_slots()]; teachers.len()];
+    let mut loads = vec![vec![0usize; weeks]; teachers.len()];
+    if let Some(TimetableResult::Solved {
+        slots_by_teacher_id,
+        ..
+    }) = lessons
+    {
+        for (teacher_idx, teacher) in teachers.iter().enumerate() {
+            let slots = match slots_by_teacher_id.get(&teacher.id) {
+                Some(slots) => slots,
+                None => continue,
+            };
+            // Exams are sat in the first term, so only its lessons clash.
+            for (slot, entry) in slots.iter().enumerate().take(is_busy[teacher_idx].len()) {
+                if entry.is_some() {
+                    is_busy[teacher_idx][slot] = true;
+                    loads[teacher_idx][slot / slots_per_week] += 1;
+                }
+            }
+        }
+    }
+
+    let mut takers: BTreeMap<SubjectId, usize> = BTreeMap::new();
+    for student in &timetable_info.students {
+        for &subject in &student.subjects {
+            *takers.entry(subject).or_insert(0) += 1;
+        }
+    }
+    let students_per_invigilator = usize::from(timetable_info.students_per_invigilator.max(1));
+    let mut exams_invigilated = vec![0usize; teachers.len()];
+    let mut rota = InvigilationRota::default();
+    for (session, subjects) in schedule.subjects_by_session.iter().enumerate() {
+        let week = session / slots_per_week;
+        for &subject in subjects {
+            let students = takers.get(&subject).copied().unwrap_or_default();
+            let needed = (students + students_per_invigilator - 1) / students_per_invigilator;
+            let mut available: Vec<usize> = (0..teachers.len())
+                .filter(|&teacher_idx| {
+                    let teacher = &teachers[teacher_idx];
+                    !is_busy[teacher_idx][session]
+                        && !teacher.is_off(session, timetable_info.daily_lesson_capacity)
+                        && teacher.has_capacity(loads[teacher_idx][week] + 1)
+                })
+                .collect();
+            if available.len() < needed {
+                return Err(ExamError::NoInvigilatorAvailable {
+                    subject: timetable_info.subjects.name(subject).to_string(),
+                    session,
+                });
+            }
+            // The sort is stable, so teachers with as many exams as each
+            // other are taken in the order they were given.
+            available.sort_by_key(|&teacher_idx| exams_invigilated[teacher_idx]);
+            let mut invigilators = Vec::new();
+            for &teacher_idx in available.iter().take(needed) {
+                is_busy[teacher_idx][session] = true;
+                loads[teacher_idx][week] += 1;
+                exams_invigilated[teacher_idx] += 1;
+                let teacher_id = &teachers[teacher_idx].id;
+                invigilators.push(teacher_id.clone());
+                rota.sessions_by_teacher_id
+                    .entry(teacher_id.clone())
+                    .or_insert_with(Vec::new)
+                    .push(session);
+            }
+            rota.invigilators_by_subject.insert(subject, invigilators);
+        }
+    }
+    Ok(rota)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetabler::TeacherInfo;
+
+    #[test]
+    fn exams_sharing_a_student_are_sat_on_different_days() {
+        let mut info = TimetableInfo::new(1, 2);
+        info.add_student("Ann", &["Maths", "Art"]);
+        info.add_student("Bob", &["Maths", "Music"]);
+        let schedule = schedule_exams(&info).unwrap();
+
+        let maths = info.subjects.id("Maths").unwrap();
+        let art = info.subjects.id("Art").unwrap();
+        let music = info.subjects.id("Music").unwrap();
+        let day = |subject| schedule.session_by_subject[&subject] / 2;
+        assert_ne!(day(maths), day(art));
+        assert_ne!(day(maths), day(music));
+        assert!(schedule.students_with_same_day_exams.is_empty());
+        assert!(schedule.students_with_back_to_back_exams.is_empty());
+        for (session, subjects) in schedule.subjects_by_session.iter().enumerate() {
+            for subject in subjects {
+                assert_eq!(schedule.session_by_subject[subject], session);
+            }
+        }
+    }
+
+    #[test]
+    fn more_exams_than_sessions_cannot_be_scheduled() {
+        // One lesson a day gives five sessions for Ann's six exams.
+        let mut info = TimetableInfo::new(1, 1);
+        info.add_student("Ann", &["A", "B", "C", "D", "E", "F"]);
+        assert!(matches!(
+            schedule_exams(&info),
+            Err(ExamError::NoSessionFree { .. })
+        ));
+    }
+
+    #[test]
+    fn each_exam_gets_an_invigilator_per_group_of_students() {
+        let mut info = TimetableInfo::new(1, 2);
+        info.add_student("Ann", &["Maths"]);
+        info.add_student("Bob", &["Maths"]);
+        info.add_student("Cat", &["Maths"]);
+        info.students_per_invigilator = 2;
+        info.teachers.push(TeacherInfo::new("T1", Vec::new()));
+        info.teachers.push(TeacherInfo::new("T2", Vec::new()));
+        let schedule = schedule_exams(&info).unwrap();
+        let rota = assign_invigilators(&info, &schedule, None).unwrap();
+
+        let maths = info.subjects.id("Maths").unwrap();
+        assert_eq!(rota.invigilators_by_subject[&maths], ["T1", "T2"]);
+
+        // Three students need two invigilators, so one teacher isn't enough.
+        info.teachers.pop();
+        assert!(matches!(
+            assign_invigilators(&info, &schedule, None),
+            Err(ExamError::NoInvigilatorAvailable { .. })
+        ));
+    }
+}
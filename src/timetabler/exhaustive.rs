@@ -0,0 +1,477 @@
+use std::collections::BTreeMap;
+
+use super::{
+    check_daily_minimum, count_gaps, daily_lesson_penalty, group_fits, score, Group, GroupContext,
+    Grouping, PersonalTimetable, Phase, SolveLog, Student, StudentInfo, SubjectId, TimetableInfo,
+};
+
+/// How many partial timetables the search may look at before giving up. This
+/// keeps the search to a few seconds even when it can't finish.
+const MAX_NODES: usize = 1_000_000;
+
+/// What `search` found.
+pub(super) struct Search {
+    /// The best timetable found, if any beat the score the search was given.
+    pub(super) best: Option<Grouping>,
+    /// Whether every way of grouping the students was either looked at or
+    /// ruled out, so that no better timetable exists. A search which runs out
+    /// of nodes or is cancelled doesn't finish.
+    pub(super) finished: bool,
+}
+
+/// Every way of picking `count` of `slots`, keeping them in order.
+fn combinations(slots: &[usize], count: usize) -> Vec<Vec<usize>> {
+    if count == 0 {
+        return vec![Vec::new()];
+    }
+    (0..slots.len())
+        .flat_map(|first| {
+            combinations(&slots[first + 1..], count - 1)
+                .into_iter()
+                .map(move |mut rest| {
+                    rest.insert(0, slots[first]);
+                    rest
+                })
+        })
+        .collect()
+}
+
+struct Searcher<'a, 'b> {
+    timetable_info: &'a TimetableInfo,
+    /// Each student and their subjects, in the order they are placed.
+    /// Students taking the same subjects in the same cohort are placed one
+    /// after another.
+    students: Vec<(&'a StudentInfo, Vec<SubjectId>)>,
+    personals: Vec<PersonalTimetable>,
+    /// The group index each student has been given for each of their
+    /// subjects so far.
+    group_idxs: Vec<Vec<usize>>,
+    groups_by_subject: BTreeMap<SubjectId, Vec<Group>>,
+    /// The number of students yet to be given a group of each subject, by
+    /// subject index.
+    takers_left: Vec<usize>,
+    /// The weighted soft constraint penalty of the groups made so far.
+    penalty: u64,
+    /// The weighted gaps and daily lesson penalties of the students who have
+    /// all of their groups.
+    finished_cost: u64,
+    score_to_beat: Option<u64>,
+    best: Option<Grouping>,
+    nodes: usize,
+    log: &'a mut SolveLog<'b>,
+}
+
+impl Searcher<'_, '_> {
+    fn can_beat(&self, score: u64) -> bool {
+        self.score_to_beat.map_or(true, |to_beat| score < to_beat)
+    }
+
+    /// The smallest group size spread the timetable could end up with. The
+    /// students still to be placed could all join the smallest group of a
+    /// subject, but the largest can't shrink. Groups are never taken away
+    /// either, so if none of the numbers of groups the subject could end up
+    /// with divides its students evenly, the spread is at least one.
+    fn least_spread(&self) -> usize {
+        self.groups_by_subject
+            .iter()
+            .map(|(subject, groups)| {
+                let sizes = groups.iter().map(|group| group.student_idxs.len());
+                let largest = sizes.clone().max().unwrap_or_default();
+                let smallest = sizes.clone().min().unwrap_or_default();
+                let takers_left = self.takers_left[subject.index()];
+                let takers = sizes.sum::<usize>() + takers_left;
+                let max_groups = self.timetable_info.max_groups_for(*subject);
+                let can_be_even = (groups.len().max(1)..=max_groups.into())
+                    .any(|group_count| takers % group_count == 0);
+                largest
+                    .saturating_sub(smallest + takers_left)
+                    .max(usize::from(!can_be_even))
+            })
+            .max()
+            .unwrap_or_default()
+    }
+
+    fn join(&mut self, student: usize, subject: SubjectId, group_idx: usize) {
+        let group = &mut self.groups_by_subject.get_mut(&subject).unwrap()[group_idx];
+        for &slot in &group.slots {
+            self.personals[student].assign(slot, (subject, group_idx));
+        }
+        group.student_idxs.push(student);
+        self.group_idxs[student].push(group_idx);
+        self.takers_left[subject.index()] -= 1;
+    }
+
+    fn leave(&mut self, student: usize, subject: SubjectId, group_idx: usize) {
+        let group = &mut self.groups_by_subject.get_mut(&subject).unwrap()[group_idx];
+        for &slot in &group.slots {
+            self.personals[student].unassign(slot);
+        }
+        group.student_idxs.pop();
+        self.group_idxs[student].pop();
+        self.takers_left[subject.index()] += 1;
+    }
+
+    /// Every new group of `subject` the student could be given, along with
+    /// its penalty, with the least penalised first. The slots are chosen as
+    /// in `choose_new_group_slots`, except that every choice is tried.
+    fn new_groups(&self, student: usize, subject: SubjectId) -> Vec<(u32, Group)> {
+        let timetable_info = self.timetable_info;
+        let personal = &self.personals[student];
+        let context = Some(personal.context());
+        let groups = &self.groups_by_subject[&subject];
+        let new_group = Some(GroupContext::new_group(&self.groups_by_subject));
+        let slots_per_week = timetable_info.slots_per_week();
+        let total_slots = timetable_info.total_slots();
+        let terms = timetable_info.terms_of(subject);
+
+        let mut choices: Vec<Vec<usize>> = vec![Vec::new()];
+        for week in 0..timetable_info.cycle.weeks() {
+            let usable: Vec<usize> = (week * slots_per_week..(week + 1) * slots_per_week)
+                .filter(|&slot| {
+                    terms.clone().all(|term| {
+                        let slot = term * total_slots + slot;
+                        personal.is_free(slot)
+                            && timetable_info.allows(subject, slot, context, new_group)
+                            && !groups.iter().any(|group| group.busy.contains(slot))
+                    })
+                })
+                .collect();
+            let week_choices = combinations(
+                &usable,
+                timetable_info.lessons_in_week(subject, week).into(),
+            );
+            choices = choices
+                .iter()
+                .flat_map(|chosen| {
+                    week_choices.iter().map(move |week_chosen| {
+                        let mut chosen = chosen.clone();
+                        chosen.extend(week_chosen);
+                        chosen
+                    })
+                })
+                .collect();
+        }
+
+        let slots_per_year = timetable_info.slots_per_year();
+        let mut new_groups: Vec<(u32, Group)> = choices
+            .into_iter()
+            .map(|chosen| {
+                let slots = terms
+                    .clone()
+                    .flat_map(|term| chosen.iter().map(move |&slot| term * total_slots + slot))
+                    .collect();
+                let group = Group::new(slots, slots_per_year);
+                (timetable_info.group_penalty(subject, &group, None), group)
+            })
+            .filter(|(_, group)| {
+                timetable_info.allows_lessons(subject, &group.slots, personal.context(), new_group)
+            })
+            .collect();
+        new_groups.sort_by_key(|&(penalty, _)| penalty);
+        new_groups
+    }
+
+    /// Tries every group of the student's `subject_idx`th subject in turn,
+    /// going on to the rest of the students' subjects after each.
+    ///
+    /// Two students taking the same subjects could swap timetables without
+    /// changing the score, so only one of the two ways round is searched:
+    /// `tied` is whether the student takes the same subjects as the one
+    /// before and has been given the same groups so far, in which case they
+    /// can't be given an earlier group than that student.
+    fn place(&mut self, student: usize, subject_idx: usize, tied: bool) {
+        // The search can take seconds, so cancelling is checked at every node
+        // rather than only once it ends.
+        if self.nodes == MAX_NODES || self.log.is_cancelled() {
+            return;
+        }
+        self.nodes += 1;
+        let weights = &self.timetable_info.weights;
+        let least_score = self.penalty
+            + self.finished_cost
+            + self.least_spread() as u64 * u64::from(weights.group_imbalance);
+        if !self.can_beat(least_score) {
+            return;
+        }
+        if student == self.students.len() {
+            // Every student has their groups, and the spread is final. The
+            // teachers' preferences are left out of the least score, as
+            // teachers are only given once the groups are, so the full score
+            // is measured here.
+            let students: Vec<Student> = self
+                .students
+                .iter()
+                .zip(&self.personals)
+                .map(|(&(student, _), personal)| Student {
+                    slots: personal.slots.clone(),
+                    id: student.id.clone(),
+                    cohort: student.cohort.clone(),
+                })
+                .collect();
+            // Timetables with a day too short for the hard minimum don't count.
+            let score = check_daily_minimum(&students, self.timetable_info)
+                .ok()
+                .and_then(|()| score(&self.groups_by_subject, &students, self.timetable_info));
+            if let Some(score) = score.filter(|&score| self.can_beat(score)) {
+                self.best = Some((self.groups_by_subject.clone(), students));
+                self.score_to_beat = Some(score);
+                self.log.improvement_found(Phase::ExhaustiveSearch);
+            }
+            return;
+        }
+        if subject_idx == self.students[student].1.len() {
+            // The student's gaps and days can't change once they have all of
+            // their groups, as groups don't move.
+            let slots = &self.personals[student].slots;
+            let cost = count_gaps(slots, self.timetable_info.daily_lesson_capacity.into()) as u64
+                * u64::from(weights.gap)
+                + daily_lesson_penalty(slots, self.timetable_info) as u64
+                    * u64::from(weights.daily_lessons);
+            self.finished_cost += cost;
+            let next_tied = self.students.get(student + 1).map_or(false, |next| {
+                let (info, subjects) = &self.students[student];
+                next.1 == *subjects && next.0.cohort == info.cohort
+            });
+            self.place(student + 1, 0, next_tied);
+            self.finished_cost -= cost;
+            return;
+        }
+
+        let timetable_info = self.timetable_info;
+        let subject = self.students[student].1[subject_idx];
+        let first_group_idx = match tied {
+            true => self.group_idxs[student - 1][subject_idx],
+            false => 0,
+        };
+        for group_idx in first_group_idx..self.groups_by_subject[&subject].len() {
+            let group = &self.groups_by_subject[&subject][group_idx];
+            let personal = &self.personals[student];
+            let joining = GroupContext::joining(&self.groups_by_subject, (subject, group_idx));
+            if group_fits(group, personal)
+                && timetable_info.allows_lessons(
+                    subject,
+                    &group.slots,
+                    personal.context(),
+                    Some(joining),
+                )
+            {
+                self.join(student, subject, group_idx);
+                self.place(
+                    student,
+                    subject_idx + 1,
+                    tied && group_idx == first_group_idx,
+                );
+                self.leave(student, subject, group_idx);
+            }
+        }
+
+        let group_idx = self.groups_by_subject[&subject].len();
+        if group_idx < timetable_info.max_groups_for(subject).into() {
+            let penalty_weight = u64::from(timetable_info.weights.slot_preference);
+            for (penalty, group) in self.new_groups(student, subject) {
+                let penalty = u64::from(penalty) * penalty_weight;
+                self.groups_by_subject
+                    .get_mut(&subject)
+                    .unwrap()
+                    .push(group);
+                self.penalty += penalty;
+                self.join(student, subject, group_idx);
+                self.place(student, subject_idx + 1, false);
+                self.leave(student, subject, group_idx);
+                self.penalty -= penalty;
+                self.groups_by_subject.get_mut(&subject).unwrap().pop();
+            }
+        }
+    }
+}
+
+/// Searches every way of grouping the students for the timetable with the
+/// lowest score, only keeping timetables which score below `score_to_beat`.
+/// Groups are never moved once made, so every choice of slots is tried when a
+/// group is made instead. Branches which can't beat the best score so far
+/// are skipped, which is what keeps the search quick enough for small
+/// schools.
+pub(super) fn search(
+    timetable_info: &TimetableInfo,
+    score_to_beat: Option<u64>,
+    log: &mut SolveLog<'_>,
+) -> Search {
+    let slots_per_year = timetable_info.slots_per_year();
+    let mut students: Vec<(&StudentInfo, Vec<SubjectId>)> = timetable_info
+        .student_order()
+        .into_iter()
+        .map(|info_idx| {
+            let student = &timetable_info.students[info_idx];
+            let mut subjects = student.subjects.clone();
+            subjects.sort();
+            (student, subjects)
+        })
+        .collect();
+    // Students taking the same subjects in the same cohort are moved up to
+    // the first of them, keeping the order otherwise.
+    let mut first_taking: BTreeMap<(Option<&str>, Vec<SubjectId>), usize> = BTreeMap::new();
+    for (position, (student, subjects)) in students.iter().enumerate() {
+        first_taking
+            .entry((student.cohort(), subjects.clone()))
+            .or_insert(position);
+    }
+    students.sort_by_cached_key(|(student, subjects)| {
+        first_taking[&(student.cohort(), subjects.clone())]
+    });
+    let mut groups_by_subject = BTreeMap::new();
+    let mut takers_left = vec![0; timetable_info.subjects.len()];
+    for &subject in students.iter().flat_map(|(_, subjects)| subjects) {
+        groups_by_subject.insert(subject, Vec::new());
+        takers_left[subject.index()] += 1;
+    }
+
+    let mut searcher = Searcher {
+        timetable_info,
+        personals: students
+            .iter()
+            .map(|(student, _)| {
+                let mut personal = PersonalTimetable::new(slots_per_year);
+                personal.cohort = student.cohort.clone();
+                personal
+            })
+            .collect(),
+        group_idxs: vec![Vec::new(); students.len()],
+        students,
+        groups_by_subject,
+        takers_left,
+        penalty: 0,
+        finished_cost: 0,
+        score_to_beat,
+        best: None,
+        nodes: 0,
+        log,
+    };
+    searcher.place(0, 0, false);
+    Search {
+        finished: searcher.nodes < MAX_NODES && !searcher.log.is_cancelled(),
+        best: searcher.best,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::timetabler::{
+        count_gaps, solve_timetable, Algorithm, FixedEvent, SlotPreference, SubjectId, TeacherInfo,
+        TimetableInfo, TimetableResult, DAYS_PER_WEEK,
+    };
+
+    const DAILY_LESSON_CAPACITY: u8 = 3;
+
+    /// Three students taking two subjects each, with every day but the first
+    /// two taken by a fixed event so that there are only six slots to try.
+    /// Art would rather be late in the day, and the teacher of Maths would
+    /// rather not teach first thing.
+    fn tiny_school() -> TimetableInfo {
+        let mut info = TimetableInfo::new(2, DAILY_LESSON_CAPACITY);
+        info.add_student("A", &["Maths", "Art"]);
+        info.add_student("B", &["Maths", "Biology"]);
+        info.add_student("C", &["Art", "Biology"]);
+        let capacity = usize::from(DAILY_LESSON_CAPACITY);
+        info.fixed_events = vec![FixedEvent::new(
+            "Trip",
+            (2 * capacity..DAYS_PER_WEEK * capacity).collect(),
+        )];
+        let [maths, art, biology] =
+            ["Maths", "Art", "Biology"].map(|name| info.subjects.register(name));
+        info.subjects[art].slot_preferences = vec![SlotPreference::NotBefore(2)];
+        let mut maths_teacher = TeacherInfo::new("Maths teacher", vec![maths]);
+        maths_teacher.slot_preferences = vec![SlotPreference::NotBefore(1)];
+        info.teachers = vec![
+            maths_teacher,
+            TeacherInfo::new("Art teacher", vec![art]),
+            TeacherInfo::new("Biology teacher", vec![biology]),
+        ];
+        info.algorithm = Algorithm::Exhaustive;
+        info
+    }
+
+    /// The lowest score of any timetable of `tiny_school`, found by giving
+    /// each student's lessons every combination of the free slots. A group is
+    /// a subject taught in a slot, as each subject has one lesson a week.
+    fn brute_force_best_score(info: &TimetableInfo) -> u64 {
+        let capacity = usize::from(DAILY_LESSON_CAPACITY);
+        let free_slots = 2 * capacity;
+        let lessons: Vec<(usize, SubjectId)> = info
+            .students
+            .iter()
+            .enumerate()
+            .flat_map(|(student_idx, student)| {
+                student
+                    .subjects
+                    .iter()
+                    .map(move |&subject| (student_idx, subject))
+            })
+            .collect();
+        let mut best = u64::MAX;
+        let mut slots = vec![0; lessons.len()];
+        'combinations: loop {
+            let mut students = vec![vec![None; info.slots_per_week()]; info.students.len()];
+            let mut groups: BTreeMap<SubjectId, BTreeMap<usize, usize>> = BTreeMap::new();
+            let mut clashes = false;
+            for (&(student_idx, subject), &slot) in lessons.iter().zip(&slots) {
+                clashes |= students[student_idx][slot].replace(subject).is_some();
+                *groups.entry(subject).or_default().entry(slot).or_default() += 1;
+            }
+            let too_many_groups = groups
+                .values()
+                .any(|groups| groups.len() > usize::from(info.max_groups));
+            if !clashes && !too_many_groups {
+                let gaps: usize = students
+                    .iter()
+                    .map(|slots| count_gaps(slots, capacity))
+                    .sum();
+                let spread = groups
+                    .values()
+                    .map(|groups| groups.values().max().unwrap() - groups.values().min().unwrap())
+                    .max()
+                    .unwrap();
+                let penalty: u32 = groups
+                    .iter()
+                    .flat_map(|(&subject, groups)| groups.keys().map(move |&slot| (subject, slot)))
+                    .map(|(subject, slot)| {
+                        let subject_penalty =
+                            info.subjects[subject].slot_penalty(slot, DAILY_LESSON_CAPACITY);
+                        let teacher_penalty = info
+                            .teachers
+                            .iter()
+                            .find(|teacher| teacher.can_teach(subject))
+                            .unwrap()
+                            .slot_penalty(slot, DAILY_LESSON_CAPACITY);
+                        subject_penalty + teacher_penalty
+                    })
+                    .sum();
+                best = best.min((gaps + spread) as u64 + u64::from(penalty));
+            }
+
+            // We count through every combination of slots, like an odometer.
+            for slot in &mut slots {
+                *slot += 1;
+                if *slot < free_slots {
+                    continue 'combinations;
+                }
+                *slot = 0;
+            }
+            return best;
+        }
+    }
+
+    #[test]
+    fn search_finds_the_best_timetable() {
+        let info = tiny_school();
+        let best_score = brute_force_best_score(&info);
+        match solve_timetable(&info) {
+            TimetableResult::Solved { metrics, stats, .. } => {
+                assert!(stats.proven_optimal);
+                assert_eq!(metrics.score(&info.weights), best_score);
+            }
+            TimetableResult::Unsolved(error) => panic!("tiny school wasn't solved: {}", error),
+        }
+    }
+}
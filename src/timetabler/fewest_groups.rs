@@ -0,0 +1,64 @@
+use super::{
+    solve_unobserved, CancellationToken, SubjectId, TimetableError, TimetableInfo, TimetableResult,
+};
+
+/// Solves the timetable with as few groups in total as it can, for schools
+/// which have to staff every group they run. After solving as usual, each
+/// subject in turn, starting with those with the most groups, has its max
+/// groups lowered to one fewer than it used, and the change is kept whenever
+/// the timetable still solves with fewer groups in total. This goes on until
+/// no subject can lose a group.
+///
+/// The timetable is solved many times over, and the result is only as small
+/// as the solver manages to find, rather than the smallest possible.
+pub fn solve_with_fewest_groups(timetable_info: &TimetableInfo) -> TimetableResult {
+    solve_with_fewest_groups_cancellable(timetable_info, &CancellationToken::new())
+}
+
+/// Solves the timetable like `solve_with_fewest_groups`, giving up with
+/// `TimetableError::Cancelled` once `cancellation` is cancelled, even part way
+/// through one of the solves.
+pub fn solve_with_fewest_groups_cancellable(
+    timetable_info: &TimetableInfo,
+    cancellation: &CancellationToken,
+) -> TimetableResult {
+    let mut timetable_info = timetable_info.clone();
+    let mut best = solve_unobserved(&timetable_info, cancellation);
+    loop {
+        let mut group_counts: Vec<(SubjectId, usize)> = match &best {
+            TimetableResult::Solved { group_names, .. } => group_names
+                .iter()
+                .map(|(&subject, names)| (subject, names.len()))
+                .collect(),
+            TimetableResult::Unsolved(_) => return best,
+        };
+        // The sort is stable, so subjects with as many groups as each other
+        // are tried in ID order.
+        group_counts.sort_by_key(|&(_, groups)| std::cmp::Reverse(groups));
+
+        let mut improved = false;
+        for (subject, groups) in group_counts {
+            if groups <= 1 {
+                continue;
+            }
+            let mut trial = timetable_info.clone();
+            // Groups are counted in `u8`s, so a subject can't have more.
+            trial.subjects[subject].max_groups = Some((groups - 1) as u8);
+            let result = solve_unobserved(&trial, cancellation);
+            if let TimetableResult::Unsolved(TimetableError::Cancelled) = result {
+                return result;
+            }
+            if matches!(result, TimetableResult::Solved { .. })
+                && result.group_count() < best.group_count()
+            {
+                timetable_info = trial;
+                best = result;
+                improved = true;
+                break;
+            }
+        }
+        if !improved {
+            return best;
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//! Random schools for testing, benchmarking and trying out the app.
+
+use std::ops::RangeInclusive;
+
+use super::{
+    DailyLessonBounds, FixedEvent, RoomInfo, SubjectInfo, TeacherInfo, TimetableInfo, DAYS_PER_WEEK,
+};
+
+/// Names given to the first subjects, in order of popularity. Any further
+/// subjects are numbered.
+const SUBJECT_NAMES: [&str; 16] = [
+    "Maths",
+    "English",
+    "Biology",
+    "Chemistry",
+    "Physics",
+    "History",
+    "Geography",
+    "French",
+    "Computing",
+    "Spanish",
+    "Art",
+    "Music",
+    "Economics",
+    "Psychology",
+    "Drama",
+    "Latin",
+];
+
+/// What kind of school to generate.
+#[derive(Debug, Clone)]
+pub struct SchoolConfig {
+    pub students: usize,
+    pub subjects: usize,
+    /// The fewest and most subjects a student takes. Each student's count is
+    /// picked evenly from this range.
+    pub subjects_per_student: RangeInclusive<usize>,
+    /// How strongly students favour the first subjects. At 0 every subject is
+    /// as popular as any other; at 1 the second subject is half as popular as
+    /// the first, the third a third as popular, and so on.
+    pub popularity_skew: f64,
+    pub max_groups: u8,
+    pub daily_lesson_capacity: u8,
+    /// How many rooms to make. A quarter of them are labs, and about a
+    /// quarter of subjects must be taught in one.
+    pub rooms: usize,
+    /// How many teachers to make. Every subject is given to at least one
+    /// teacher, and each teacher can teach one other subject as well.
+    pub teachers: usize,
+    /// How many terms the year is split into. About a third of subjects only
+    /// run in one of them.
+    pub terms: u8,
+    /// How many sites the school is split across. Rooms are shared out
+    /// between them, and about half of subjects are taught at one.
+    pub sites: usize,
+    /// How many whole school events, each taking one slot a week, to hold.
+    pub fixed_events: usize,
+    pub daily_lessons: DailyLessonBounds,
+    pub max_group_size: Option<u16>,
+    /// Schools generated with the same seed and config are identical.
+    pub seed: u64,
+}
+
+impl Default for SchoolConfig {
+    fn default() -> Self {
+        SchoolConfig {
+            students: 100,
+            subjects: 12,
+            subjects_per_student: 3..=4,
+            popularity_skew: 0.5,
+            max_groups: 4,
+            daily_lesson_capacity: 5,
+            rooms: 0,
+            teachers: 0,
+            terms: 1,
+            sites: 0,
+            fixed_events: 0,
+            daily_lessons: DailyLessonBounds::default(),
+            max_group_size: None,
+            seed: 1,
+        }
+    }
+}
+
+/// A xorshift generator. It isn't suitable for anything needing real
+/// randomness, but it is fast, and seeding it makes schools reproducible.
+pub(super) struct Rng(u64);
+
+impl Rng {
+    pub(super) fn new(seed: u64) -> Rng {
+        // Xorshift never leaves zero, so that seed is swapped for another.
+        Rng(if seed == 0 {
+            0x2545_f491_4f6c_dd1d
+        } else {
+            seed
+        })
+    }
+
+    pub(super) fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A number in `0.0..1.0`.
+    pub(super) fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub(super) fn in_range(&mut self, range: &RangeInclusive<usize>) -> usize {
+        let span = range.end().saturating_sub(*range.start()) as u64 + 1;
+        range.start() + (self.next() % span) as usize
+    }
+}
+
+pub fn subject_name(subject_idx: usize) -> String {
+    match SUBJECT_NAMES.get(subject_idx) {
+        Some(name) => name.to_string(),
+        None => format!("Subject {}", subject_idx + 1),
+    }
+}
+
+/// Generates a school. Anything the config doesn't cover is left at its
+/// default.
+pub fn school(config: &SchoolConfig) -> TimetableInfo {
+    let mut rng = Rng::new(config.seed);
+    let names: Vec<String> = (0..config.subjects).map(subject_name).collect();
+    let weights: Vec<f64> = (0..config.subjects)
+        .map(|subject_idx| 1.0 / ((subject_idx + 1) as f64).powf(config.popularity_skew))
+        .collect();
+
+    let mut info = TimetableInfo::new(config.max_groups, config.daily_lesson_capacity);
+    for student_idx in 0..config.students {
+        let count = rng
+            .in_range(&config.subjects_per_student)
+            .min(config.subjects);
+        // We draw subjects by weight without replacement, taking each chosen
+        // subject's weight out of the draw.
+        let mut remaining = weights.clone();
+        let mut subjects = Vec::with_capacity(count);
+        for _ in 0..count {
+            let mut target = rng.unit() * remaining.iter().sum::<f64>();
+            let mut chosen = remaining.iter().rposition(|&weight| weight > 0.0).unwrap();
+            for (subject_idx, &weight) in remaining.iter().enumerate() {
+                if weight > 0.0 && target < weight {
+                    chosen = subject_idx;
+                    break;
+                }
+                target -= weight;
+            }
+            remaining[chosen] = 0.0;
+            subjects.push(&names[chosen]);
+        }
+        info.add_student(format!("Student {}", student_idx + 1), &subjects);
+    }
+
+    info.terms = config.terms.max(1);
+    info.daily_lessons = config.daily_lessons.clone();
+    info.max_group_size = config.max_group_size;
+
+    // Everything past the students is drawn afterwards, so that a config
+    // with none of it makes the same students as before.
+    let site_name = |site_idx: usize| format!("Site {}", site_idx + 1);
+    let lab_count = config.rooms / 4;
+    for room_idx in 0..config.rooms {
+        let room_type = (room_idx < lab_count).then(|| "Lab".to_string());
+        let mut room = RoomInfo::new(format!("Room {}", room_idx + 1), room_type);
+        if config.sites > 1 {
+            room.site = Some(site_name(room_idx % config.sites));
+        }
+        info.rooms.push(room);
+    }
+
+    for name in &names {
+        let mut subject = SubjectInfo::new(name.clone());
+        if lab_count > 0 && rng.unit() < 0.25 {
+            subject.room_type = Some("Lab".to_string());
+        }
+        if info.terms > 1 && rng.unit() < 1.0 / 3.0 {
+            subject.term = Some(rng.in_range(&(0..=usize::from(info.terms) - 1)) as u8);
+        }
+        if config.sites > 1 && rng.unit() < 0.5 {
+            subject.site = Some(site_name(rng.in_range(&(0..=config.sites - 1))));
+        }
+        // Subjects keep the IDs they were registered with by the students,
+        // so that they are in the same order as with no config at all.
+        if subject.room_type.is_some() || subject.term.is_some() || subject.site.is_some() {
+            info.subjects.insert(subject);
+        }
+    }
+
+    for teacher_idx in 0..config.teachers {
+        let mut subjects: Vec<String> = names
+            .iter()
+            .skip(teacher_idx)
+            .step_by(config.teachers)
+            .cloned()
+            .collect();
+        if !names.is_empty() {
+            let extra = &names[rng.in_range(&(0..=names.len() - 1))];
+            if !subjects.contains(extra) {
+                subjects.push(extra.clone());
+            }
+        }
+        let subjects = subjects
+            .iter()
+            .map(|subject| info.subjects.register(subject))
+            .collect();
+        let mut teacher = TeacherInfo::new(format!("Teacher {}", teacher_idx + 1), subjects);
+        if rng.unit() < 0.2 {
+            teacher
+                .days_off
+                .push(rng.in_range(&(0..=DAYS_PER_WEEK - 1)) as u8);
+        }
+        info.teachers.push(teacher);
+    }
+
+    let slots_per_week = info.slots_per_week();
+    for event_idx in 0..config.fixed_events {
+        if slots_per_week == 0 {
+            break;
+        }
+        let slot = rng.in_range(&(0..=slots_per_week - 1));
+        info.fixed_events.push(FixedEvent::new(
+            format!("Event {}", event_idx + 1),
+            vec![slot],
+        ));
+    }
+
+    info
+}
@@ -0,0 +1,114 @@
+use super::{StudentId, TimetableInfo, TimetableResult, WeekCycle, DAYS_PER_WEEK};
+
+pub const DAY_NAMES: [&str; DAYS_PER_WEEK] =
+    ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday"];
+
+/// The heading for a day of the cycle, naming the week as well when the
+/// timetable is fortnightly, e.g. "Monday (week B)".
+pub fn day_name(day_of_cycle: usize, cycle: WeekCycle) -> String {
+    let name = DAY_NAMES[day_of_cycle % DAYS_PER_WEEK];
+    match cycle {
+        WeekCycle::Weekly => name.to_string(),
+        WeekCycle::Fortnightly => {
+            let week = char::from(b'A' + (day_of_cycle / DAYS_PER_WEEK) as u8);
+            format!("{} (week {})", name, week)
+        }
+    }
+}
+
+/// A timetable laid out for exporting, with a column for each day of the
+/// cycle and a row for each period.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimetableGrid {
+    pub title: String,
+    /// The heading of each column.
+    pub days: Vec<String>,
+    /// What is taught in each period of each day, by period and then day.
+    /// Free periods are empty.
+    pub periods: Vec<Vec<String>>,
+}
+
+/// Lays `cells`, one for each slot of the year, out as a grid for each term.
+/// The term is added to the title when there is more than one.
+fn grids(title: &str, timetable_info: &TimetableInfo, cells: &[String]) -> Vec<TimetableGrid> {
+    let daily_lesson_capacity = usize::from(timetable_info.daily_lesson_capacity);
+    let days = timetable_info.total_slots() / daily_lesson_capacity;
+    let terms = usize::from(timetable_info.terms);
+    cells
+        .chunks(timetable_info.total_slots())
+        .enumerate()
+        .map(|(term, term_cells)| TimetableGrid {
+            title: if terms > 1 {
+                format!("{}, term {}", title, term + 1)
+            } else {
+                title.to_string()
+            },
+            days: (0..days)
+                .map(|day| day_name(day, timetable_info.cycle))
+                .collect(),
+            periods: (0..daily_lesson_capacity)
+                .map(|period| {
+                    (0..days)
+                        .map(|day| {
+                            term_cells
+                                .get(day * daily_lesson_capacity + period)
+                                .cloned()
+                                .unwrap_or_default()
+                        })
+                        .collect()
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// The subjects taught across the school in each slot, a grid for each term.
+/// This is empty if the timetable wasn't solved.
+pub fn school_grids(
+    timetable_info: &TimetableInfo,
+    result: &TimetableResult,
+) -> Vec<TimetableGrid> {
+    match result {
+        TimetableResult::Solved { subjects, .. } => {
+            let cells: Vec<String> = subjects
+                .iter()
+                .map(|slot| {
+                    slot.iter()
+                        .map(|&subject| timetable_info.subjects.name(subject))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                })
+                .collect();
+            grids("Timetable", timetable_info, &cells)
+        }
+        TimetableResult::Unsolved(_) => Vec::new(),
+    }
+}
+
+/// The groups a student is in for each slot, a grid for each term. This is
+/// empty if the timetable wasn't solved or the student isn't in it.
+pub fn student_grids(
+    timetable_info: &TimetableInfo,
+    result: &TimetableResult,
+    student_id: &StudentId,
+) -> Vec<TimetableGrid> {
+    let (slots, group_names) = match result {
+        TimetableResult::Solved {
+            slots_by_student_id,
+            group_names,
+            ..
+        } => match slots_by_student_id.get(student_id) {
+            Some(slots) => (slots, group_names),
+            None => return Vec::new(),
+        },
+        TimetableResult::Unsolved(_) => return Vec::new(),
+    };
+    let cells: Vec<String> = slots
+        .iter()
+        .map(|slot| match slot {
+            Some((subject, group_idx)) => group_names[subject][*group_idx].clone(),
+            None => String::new(),
+        })
+        .collect();
+    grids(student_id.as_str(), timetable_info, &cells)
+}
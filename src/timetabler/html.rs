@@ -0,0 +1,130 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use thiserror::Error;
+
+use super::{
+    school_grids, student_grids, StudentId, TimetableGrid, TimetableInfo, TimetableResult,
+};
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+caption { font-weight: bold; text-align: left; padding-bottom: 0.5em; }
+th, td { border: 1px solid #999; padding: 0.3em 0.6em; vertical-align: top; }
+th { background: #eee; }";
+
+/// Why the pages couldn't be written.
+#[derive(Debug, Error)]
+pub enum HtmlError {
+    #[error("there is no timetable, as it wasn't solved")]
+    Unsolved,
+    #[error("couldn't write the HTML pages: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Escapes the characters HTML gives a meaning to.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The name of a student's page. Anything besides ASCII letters, digits and
+/// dashes is written as its bytes in hex, so every student gets their own
+/// page whatever their ID.
+fn student_file_name(student_id: &StudentId) -> String {
+    let mut name = String::new();
+    for byte in student_id.as_str().bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' {
+            name.push(char::from(byte));
+        } else {
+            write!(name, "_{:02x}", byte).unwrap();
+        }
+    }
+    name.push_str(".html");
+    name
+}
+
+fn table(html: &mut String, grid: &TimetableGrid) {
+    writeln!(html, "<table>\n<caption>{}</caption>", escape(&grid.title)).unwrap();
+    html.push_str("<tr><th></th>");
+    for day in &grid.days {
+        write!(html, "<th>{}</th>", escape(day)).unwrap();
+    }
+    html.push_str("</tr>\n");
+    for (period, cells) in grid.periods.iter().enumerate() {
+        write!(html, "<tr><th>Period {}</th>", period + 1).unwrap();
+        for cell in cells {
+            write!(html, "<td>{}</td>", escape(cell)).unwrap();
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n");
+}
+
+/// A whole page, with `body` already escaped.
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>\n{}\n</style>\n</head>\n<body>\n<h1>{}</h1>\n{}</body>\n</html>\n",
+        escape(title),
+        STYLE,
+        escape(title),
+        body
+    )
+}
+
+/// Writes the solved timetable to `dir` as static pages: `index.html` holds
+/// the school's timetable and links to a page for every student.
+pub fn write_html(
+    timetable_info: &TimetableInfo,
+    result: &TimetableResult,
+    dir: impl AsRef<Path>,
+) -> Result<(), HtmlError> {
+    let slots_by_student_id = match result {
+        TimetableResult::Solved {
+            slots_by_student_id,
+            ..
+        } => slots_by_student_id,
+        TimetableResult::Unsolved(_) => return Err(HtmlError::Unsolved),
+    };
+    let dir = dir.as_ref();
+    let write = |file_name: &str, contents: String| fs::write(dir.join(file_name), contents);
+    fs::create_dir_all(dir)?;
+
+    let mut index = String::new();
+    for grid in school_grids(timetable_info, result) {
+        table(&mut index, &grid);
+    }
+    index.push_str("<h2>Students</h2>\n<ul>\n");
+    for student_id in slots_by_student_id.keys() {
+        let file_name = student_file_name(student_id);
+        writeln!(
+            index,
+            "<li><a href=\"{}\">{}</a></li>",
+            file_name,
+            escape(student_id.as_str())
+        )
+        .unwrap();
+
+        let mut body = String::from("<p><a href=\"index.html\">Back to the timetable</a></p>\n");
+        for grid in student_grids(timetable_info, result, student_id) {
+            table(&mut body, &grid);
+        }
+        write(&file_name, page(student_id.as_str(), &body))?;
+    }
+    index.push_str("</ul>\n");
+    write("index.html", page("Timetable", &index))?;
+    Ok(())
+}
@@ -0,0 +1,187 @@
+use std::io;
+use std::path::Path;
+
+use thiserror::Error;
+
+use super::TimetableInfo;
+
+/// The version of the format written by `to_json`. It goes up whenever a
+/// change stops older files from being read.
+pub const JSON_VERSION: u32 = 2;
+
+/// A configuration file, as `to_json` describes.
+#[derive(serde::Deserialize, serde::Serialize)]
+struct ConfigFile {
+    version: u32,
+    timetable: TimetableInfo,
+}
+
+/// Why a configuration couldn't be read or written.
+#[derive(Debug, Error)]
+pub enum JsonError {
+    #[error("couldn't read or write the configuration: {0}")]
+    Io(#[from] io::Error),
+    /// The JSON was malformed or didn't describe a timetable.
+    #[error("the configuration isn't valid: {0}")]
+    Format(#[from] serde_json::Error),
+    #[error("the file is version {found}, but only version {expected} can be read")]
+    Version { found: u32, expected: u32 },
+    /// A student or teacher gave a subject by a place past the end of
+    /// `subjects`.
+    #[error("subject {subject} is taken or taught, but there are only {subjects} subjects")]
+    UnknownSubject { subject: usize, subjects: usize },
+}
+
+/// Writes a timetable's configuration as JSON, with the version of the format
+/// and then the timetable itself. Custom constraints aren't written.
+///
+/// ```json
+/// {
+///   "version": 2,
+///   "timetable": {
+///     "max_groups": 3,
+///     "daily_lesson_capacity": 6,
+///     "students": [
+///       { "id": "Ann", "subjects": [0, 1], "cohort": "Year 10" }
+///     ],
+///     "subjects": [{ "name": "Maths", ... }, { "name": "Art", ... }],
+///     "rooms": [...],
+///     "teachers": [...],
+///     ...
+///   }
+/// }
+/// ```
+///
+/// `timetable` has a field for every public field of `TimetableInfo` besides
+/// `constraints`, named as it is in Rust, and each of them must be given. The
+/// same goes for the structs inside it, such as `SubjectInfo` and
+/// `TeacherInfo`, and students have `id`, `subjects` and `cohort`. Students
+/// and teachers give their subjects by their place in `subjects`, counting
+/// from zero, and no two subjects may have the same name. Missing
+/// options are written as `null`. Enums without data are written as their
+/// variant's name, such as `"Fortnightly"`, and enums with data as an object
+/// with the variant's name as its only key, such as `{ "FreePeriods": 1 }`.
+/// Exporting a timetable gives a complete example to start from.
+pub fn to_json(timetable_info: &TimetableInfo) -> Result<String, JsonError> {
+    let config_file = ConfigFile {
+        version: JSON_VERSION,
+        timetable: timetable_info.clone(),
+    };
+    Ok(serde_json::to_string_pretty(&config_file)?)
+}
+
+/// Reads a timetable's configuration from JSON in the format `to_json`
+/// writes.
+pub fn from_json(json: &str) -> Result<TimetableInfo, JsonError> {
+    let config_file: ConfigFile = serde_json::from_str(json)?;
+    if config_file.version != JSON_VERSION {
+        return Err(JsonError::Version {
+            found: config_file.version,
+            expected: JSON_VERSION,
+        });
+    }
+    let timetable = config_file.timetable;
+    let subjects = timetable.subjects.len();
+    let unknown = timetable
+        .students
+        .iter()
+        .flat_map(|student| &student.subjects)
+        .chain(
+            timetable
+                .teachers
+                .iter()
+                .flat_map(|teacher| &teacher.subjects),
+        )
+        .find(|subject| subject.index() >= subjects);
+    if let Some(subject) = unknown {
+        return Err(JsonError::UnknownSubject {
+            subject: subject.index(),
+            subjects,
+        });
+    }
+    Ok(timetable)
+}
+
+/// Reads a configuration file, as `from_json` does.
+pub fn from_json_file(path: impl AsRef<Path>) -> Result<TimetableInfo, JsonError> {
+    let json = std::fs::read_to_string(path)?;
+    from_json(&json)
+}
+
+/// Writes a configuration file, as `to_json` does.
+pub fn to_json_file(
+    timetable_info: &TimetableInfo,
+    path: impl AsRef<Path>,
+) -> Result<(), JsonError> {
+    let json = to_json(timetable_info)?;
+    Ok(std::fs::write(path, json)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetabler::TeacherInfo;
+
+    #[test]
+    fn a_configuration_reads_back_as_it_was_written() {
+        let mut info = TimetableInfo::new(2, 3);
+        info.add_student("Ann", &["Maths", "Art"]);
+        let maths = info.subjects.id("Maths").unwrap();
+        info.teachers.push(TeacherInfo::new("T1", vec![maths]));
+        let json = to_json(&info).unwrap();
+        let read = from_json(&json).unwrap();
+
+        assert_eq!(read.subjects, info.subjects);
+        assert_eq!(read.students[0].id, info.students[0].id);
+        assert_eq!(read.students[0].subjects, info.students[0].subjects);
+        assert_eq!(read.teachers[0].subjects, [maths]);
+        assert_eq!(to_json(&read).unwrap(), json);
+    }
+
+    #[test]
+    fn a_removed_subject_is_left_out_of_the_export() {
+        let mut info = TimetableInfo::new(2, 3);
+        info.add_student("Ann", &["Maths", "Art", "Music"]);
+        let art = info.subjects.id("Art").unwrap();
+        let music = info.subjects.id("Music").unwrap();
+        info.teachers.push(TeacherInfo::new("T1", vec![art, music]));
+        info.drop_subject(&"Ann".into(), art);
+        info.retain_subjects(|subject| subject != art);
+        let read = from_json(&to_json(&info).unwrap()).unwrap();
+
+        assert_eq!(read.subjects.len(), 2);
+        assert_eq!(read.subjects.id("Art"), None);
+        // Music takes the place Art had.
+        let music = read.subjects.id("Music").unwrap();
+        assert_eq!(
+            read.students[0].subjects(),
+            [read.subjects.id("Maths").unwrap(), music]
+        );
+        assert_eq!(read.teachers[0].subjects, [music]);
+    }
+
+    #[test]
+    fn unknown_subjects_and_other_versions_are_rejected() {
+        let mut info = TimetableInfo::new(2, 3);
+        info.add_student("Ann", &["Maths"]);
+        let json: serde_json::Value = serde_json::from_str(&to_json(&info).unwrap()).unwrap();
+
+        let mut old = json.clone();
+        old["version"] = serde_json::json!(JSON_VERSION - 1);
+        assert!(matches!(
+            from_json(&old.to_string()),
+            Err(JsonError::Version { found, expected })
+                if found == JSON_VERSION - 1 && expected == JSON_VERSION
+        ));
+        // Ann takes the second subject, but there is only one.
+        let mut unknown = json;
+        unknown["timetable"]["students"][0]["subjects"] = serde_json::json!([1]);
+        assert!(matches!(
+            from_json(&unknown.to_string()),
+            Err(JsonError::UnknownSubject {
+                subject: 1,
+                subjects: 1
+            })
+        ));
+    }
+}
@@ -0,0 +1,175 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use printpdf::{
+    BuiltinFont, IndirectFontRef, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference,
+};
+use thiserror::Error;
+
+use super::{school_grids, student_grids, TimetableGrid, TimetableInfo, TimetableResult};
+
+/// A4 in landscape, in millimetres.
+const PAGE_WIDTH: f64 = 297.0;
+const PAGE_HEIGHT: f64 = 210.0;
+const MARGIN: f64 = 15.0;
+
+const TITLE_SIZE: f64 = 16.0;
+const TEXT_SIZE: f64 = 9.0;
+/// The height of a line of text, in millimetres.
+const LINE_HEIGHT: f64 = 4.5;
+/// Roughly how wide a character of text is, in millimetres, for wrapping.
+const CHAR_WIDTH: f64 = 1.7;
+
+/// Why the PDF couldn't be made or written.
+#[derive(Debug, Error)]
+pub enum PdfError {
+    #[error("couldn't make the PDF: {0}")]
+    Pdf(#[from] printpdf::Error),
+    #[error("couldn't write the PDF: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Splits `text` into lines of at most `max_chars` characters, breaking
+/// between words. A word longer than a line gets a line to itself.
+fn wrap(text: &str, max_chars: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > max_chars {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Writes grids onto pages, starting a new page whenever one fills up.
+struct PageWriter<'a> {
+    document: &'a PdfDocumentReference,
+    font: IndirectFontRef,
+    bold_font: IndirectFontRef,
+    /// The page the document was made with, until it is used.
+    first_layer: Option<PdfLayerReference>,
+    /// How far down the current page has been written to, in millimetres
+    /// from the bottom.
+    y: f64,
+}
+
+impl PageWriter<'_> {
+    fn page(&mut self) -> PdfLayerReference {
+        self.y = PAGE_HEIGHT - MARGIN;
+        match self.first_layer.take() {
+            Some(layer) => layer,
+            None => {
+                let (page, layer) =
+                    self.document
+                        .add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Timetable");
+                self.document.get_page(page).get_layer(layer)
+            }
+        }
+    }
+
+    /// Writes a row of cells, each wrapped to the width of its column, and
+    /// moves down past it.
+    fn row(
+        &mut self,
+        layer: &PdfLayerReference,
+        cells: &[Vec<String>],
+        col_width: f64,
+        bold: bool,
+    ) {
+        let font = if bold { &self.bold_font } else { &self.font };
+        for (col, lines) in cells.iter().enumerate() {
+            for (line_idx, line) in lines.iter().enumerate() {
+                layer.use_text(
+                    line.as_str(),
+                    TEXT_SIZE,
+                    Mm(MARGIN + col as f64 * col_width),
+                    Mm(self.y - line_idx as f64 * LINE_HEIGHT),
+                    font,
+                );
+            }
+        }
+        let lines = cells.iter().map(Vec::len).max().unwrap_or(0).max(1);
+        self.y -= (lines as f64 + 0.5) * LINE_HEIGHT;
+    }
+
+    /// Starts a page with a title and the day headings.
+    fn grid_page(
+        &mut self,
+        title: &str,
+        headings: &[Vec<String>],
+        col_width: f64,
+    ) -> PdfLayerReference {
+        let layer = self.page();
+        layer.use_text(title, TITLE_SIZE, Mm(MARGIN), Mm(self.y), &self.bold_font);
+        self.y -= LINE_HEIGHT * 2.5;
+        self.row(&layer, headings, col_width, true);
+        layer
+    }
+
+    /// Writes a grid starting on a new page, running on over more pages with
+    /// the headings repeated when it doesn't fit on one.
+    fn grid(&mut self, grid: &TimetableGrid) {
+        // The periods get a column of their own before the days.
+        let col_width = (PAGE_WIDTH - 2.0 * MARGIN) / (grid.days.len() + 1) as f64;
+        let max_chars = ((col_width - 2.0) / CHAR_WIDTH).max(1.0) as usize;
+        let mut headings = vec![Vec::new()];
+        headings.extend(grid.days.iter().map(|day| wrap(day, max_chars)));
+
+        let mut layer = self.grid_page(&grid.title, &headings, col_width);
+        for (period, cells) in grid.periods.iter().enumerate() {
+            let mut row = vec![vec![format!("Period {}", period + 1)]];
+            row.extend(cells.iter().map(|cell| wrap(cell, max_chars)));
+            let lines = row.iter().map(Vec::len).max().unwrap_or(0).max(1);
+            if self.y - lines as f64 * LINE_HEIGHT < MARGIN {
+                let title = format!("{} (continued)", grid.title);
+                layer = self.grid_page(&title, &headings, col_width);
+            }
+            self.row(&layer, &row, col_width, false);
+        }
+    }
+}
+
+/// Writes the school's timetable and then every student's timetable to a
+/// PDF at `path`, each starting on a new A4 page. Long timetables run on
+/// over as many pages as they need.
+pub fn write_pdf(
+    timetable_info: &TimetableInfo,
+    result: &TimetableResult,
+    path: impl AsRef<Path>,
+) -> Result<(), PdfError> {
+    let mut grids = school_grids(timetable_info, result);
+    if let TimetableResult::Solved {
+        slots_by_student_id,
+        ..
+    } = result
+    {
+        for student_id in slots_by_student_id.keys() {
+            grids.extend(student_grids(timetable_info, result, student_id));
+        }
+    }
+
+    let (document, page, layer) =
+        PdfDocument::new("Timetable", Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Timetable");
+    let mut writer = PageWriter {
+        document: &document,
+        font: document.add_builtin_font(BuiltinFont::Helvetica)?,
+        bold_font: document.add_builtin_font(BuiltinFont::HelveticaBold)?,
+        first_layer: Some(document.get_page(page).get_layer(layer)),
+        y: PAGE_HEIGHT - MARGIN,
+    };
+    for grid in &grids {
+        writer.grid(grid);
+    }
+
+    let file = File::create(path)?;
+    Ok(document.save(&mut BufWriter::new(file))?)
+}
@@ -0,0 +1,234 @@
+use std::fmt;
+
+use super::{
+    solve_unobserved, CancellationToken, RoomInfo, StudentId, SubjectId, TeacherInfo,
+    TimetableError, TimetableInfo, TimetableResult,
+};
+
+/// A single change which makes a timetable that couldn't be solved solvable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Relaxation {
+    RaiseMaxGroups {
+        subject: String,
+        from: u8,
+        to: u8,
+    },
+    AddTeacher {
+        subject: String,
+    },
+    /// Adds a room of the given type, or a general room if there is no type.
+    AddRoom {
+        room_type: Option<String>,
+    },
+    RaiseMaxGroupsPerSlot {
+        from: u8,
+        to: u8,
+    },
+    RaiseDailyLessonCapacity {
+        from: u8,
+        to: u8,
+    },
+    DropSubject {
+        student_id: StudentId,
+        subject: String,
+    },
+}
+
+impl Relaxation {
+    pub fn apply(&self, timetable_info: &mut TimetableInfo) {
+        match self {
+            Relaxation::RaiseMaxGroups { subject, to, .. } => {
+                let subject = timetable_info.subjects.register(subject);
+                timetable_info.subjects[subject].max_groups = Some(*to);
+            }
+            Relaxation::AddTeacher { subject } => {
+                let subject = timetable_info.subjects.register(subject);
+                timetable_info
+                    .teachers
+                    .push(TeacherInfo::new("New teacher", vec![subject]));
+            }
+            Relaxation::AddRoom { room_type } => timetable_info
+                .rooms
+                .push(RoomInfo::new("New room", room_type.clone())),
+            Relaxation::RaiseMaxGroupsPerSlot { to, .. } => {
+                timetable_info.max_groups_per_slot = Some(*to)
+            }
+            Relaxation::RaiseDailyLessonCapacity { to, .. } => {
+                timetable_info.daily_lesson_capacity = *to
+            }
+            Relaxation::DropSubject {
+                student_id,
+                subject,
+            } => {
+                if let Some(subject) = timetable_info.subjects.id(subject) {
+                    timetable_info.drop_subject(student_id, subject);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Relaxation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Relaxation::RaiseMaxGroups { subject, from, to } => write!(
+                f,
+                "Raising max groups for {} from {} to {} solves it",
+                subject, from, to
+            ),
+            Relaxation::AddTeacher { subject } => {
+                write!(f, "Adding another teacher of {} solves it", subject)
+            }
+            Relaxation::AddRoom {
+                room_type: Some(room_type),
+            } => write!(f, "Adding another {} solves it", room_type),
+            Relaxation::AddRoom { room_type: None } => {
+                write!(f, "Adding another room solves it")
+            }
+            Relaxation::RaiseMaxGroupsPerSlot { from, to } => write!(
+                f,
+                "Raising max groups at once from {} to {} solves it",
+                from, to
+            ),
+            Relaxation::RaiseDailyLessonCapacity { from, to } => write!(
+                f,
+                "Raising daily lesson capacity from {} to {} solves it",
+                from, to
+            ),
+            Relaxation::DropSubject {
+                student_id,
+                subject,
+            } => write!(f, "{} dropping {} solves it", student_id, subject),
+        }
+    }
+}
+
+/// The relaxations worth trying for `error`, with the smallest changes first.
+fn candidates(timetable_info: &TimetableInfo, error: &TimetableError) -> Vec<Relaxation> {
+    let mut candidates = Vec::new();
+    match error {
+        TimetableError::InsufficientSlots {
+            student_id,
+            subject,
+        }
+        | TimetableError::GroupCapacityExhausted {
+            student_id,
+            subject,
+        } => {
+            // Another group of the subject the student couldn't be given is
+            // the most likely fix, but more groups of the student's other
+            // subjects free up room to move their lessons around. Other
+            // students' subjects are left out, so that only a handful of
+            // changes are tried.
+            let mut subjects: Vec<SubjectId> = timetable_info
+                .students
+                .iter()
+                .find(|student| student.id == *student_id)
+                .map(|student| student.subjects.clone())
+                .unwrap_or_default();
+            if let Some(subject) = timetable_info.subjects.id(subject) {
+                subjects.retain(|&other| other != subject);
+                subjects.insert(0, subject);
+            }
+            for other in subjects {
+                let from = timetable_info.max_groups_for(other);
+                if let Some(to) = from.checked_add(1) {
+                    candidates.push(Relaxation::RaiseMaxGroups {
+                        subject: timetable_info.subjects.name(other).to_string(),
+                        from,
+                        to,
+                    });
+                }
+            }
+            if let Some(from) = timetable_info.max_groups_per_slot {
+                if let Some(to) = from.checked_add(1) {
+                    candidates.push(Relaxation::RaiseMaxGroupsPerSlot { from, to });
+                }
+            }
+            let from = timetable_info.daily_lesson_capacity;
+            if let Some(to) = from.checked_add(1) {
+                candidates.push(Relaxation::RaiseDailyLessonCapacity { from, to });
+            }
+            candidates.push(Relaxation::DropSubject {
+                student_id: student_id.clone(),
+                subject: subject.clone(),
+            });
+        }
+        TimetableError::NoRoomAvailable { subject, .. } => {
+            candidates.push(Relaxation::AddRoom {
+                room_type: timetable_info
+                    .subjects
+                    .get(subject)
+                    .and_then(|info| info.room_type.clone()),
+            });
+        }
+        TimetableError::NoTeacherAvailable { subject, .. }
+        | TimetableError::TeachersOff { subject, .. } => {
+            candidates.push(Relaxation::AddTeacher {
+                subject: subject.clone(),
+            });
+        }
+        // Only lowering the minimum is sure to help, which the user can see
+        // for themselves.
+        TimetableError::TooFewDailyLessons { .. } => {}
+        // Nothing stopped the timetable being solved but the user.
+        TimetableError::Cancelled => {}
+    }
+    candidates
+}
+
+/// Tries each single change which could make `timetable_info` solvable,
+/// given the `error` solving it failed with, returning those which do with
+/// the smallest changes first. This solves the timetable once for each change
+/// tried. Once `cancellation` is cancelled, no more changes are tried and
+/// those found so far are returned.
+pub fn suggest_relaxations(
+    timetable_info: &TimetableInfo,
+    error: &TimetableError,
+    cancellation: &CancellationToken,
+) -> Vec<Relaxation> {
+    let mut relaxations = Vec::new();
+    for relaxation in candidates(timetable_info, error) {
+        let mut relaxed = timetable_info.clone();
+        relaxation.apply(&mut relaxed);
+        match solve_unobserved(&relaxed, cancellation) {
+            TimetableResult::Solved { .. } => relaxations.push(relaxation),
+            TimetableResult::Unsolved(TimetableError::Cancelled) => break,
+            TimetableResult::Unsolved(_) => {}
+        }
+    }
+    relaxations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{suggest_relaxations, Relaxation};
+    use crate::timetabler::{
+        solve_timetable, CancellationToken, StudentId, TimetableInfo, TimetableResult,
+    };
+
+    #[test]
+    fn suggests_more_slots_for_a_student_with_too_many_subjects() {
+        let mut info = TimetableInfo::new(1, 1);
+        info.add_student(
+            "A",
+            &["Art", "Biology", "Chemistry", "Drama", "English", "French"],
+        );
+        let error = match solve_timetable(&info) {
+            TimetableResult::Unsolved(error) => error,
+            TimetableResult::Solved { .. } => panic!("six lessons shouldn't fit in five slots"),
+        };
+
+        let relaxations = suggest_relaxations(&info, &error, &CancellationToken::new());
+        assert_eq!(
+            relaxations,
+            [
+                Relaxation::RaiseDailyLessonCapacity { from: 1, to: 2 },
+                Relaxation::DropSubject {
+                    student_id: StudentId::new("A"),
+                    subject: "French".to_string(),
+                },
+            ]
+        );
+    }
+}
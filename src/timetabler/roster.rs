@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+use thiserror::Error;
+
+use super::StudentId;
+
+/// What the first cell of a header row may say, ignoring case.
+const HEADER_IDS: [&str; 4] = ["id", "student", "student id", "student_id"];
+
+/// Why a roster couldn't be read. Rows are counted from 1, as spreadsheets
+/// number them.
+#[derive(Debug, Error)]
+pub enum RosterError {
+    #[error("couldn't read the roster: {0}")]
+    Io(#[from] io::Error),
+    #[error("row {row} has no student ID")]
+    MissingId { row: usize },
+    #[error("{student_id} on row {row} has no subjects")]
+    NoSubjects { student_id: StudentId, row: usize },
+    /// The student was already on an earlier row.
+    #[error("{student_id} is on more than one row, the second being row {row}")]
+    DuplicateStudent { student_id: StudentId, row: usize },
+}
+
+/// Splits a line of CSV into its cells. Cells may be quoted, with `""`
+/// standing for a quote inside them.
+fn cells(line: &str) -> Vec<String> {
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                chars.next();
+                cell.push('"');
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => cells.push(std::mem::take(&mut cell)),
+            c => cell.push(c),
+        }
+    }
+    cells.push(cell);
+    cells
+}
+
+/// Reads a CSV roster with a row for each student: their ID, then a subject
+/// in each cell after it. A cell may also hold several subjects separated by
+/// semicolons. Blank lines are skipped, as is a first row headed "ID" or
+/// "Student". Repeated subjects are taken once.
+pub fn read_roster(csv: &str) -> Result<BTreeMap<StudentId, Vec<String>>, RosterError> {
+    // Spreadsheets often start their CSV files with a byte order mark.
+    let csv = csv.trim_start_matches('\u{feff}');
+    let mut subjects_by_student_id = BTreeMap::new();
+    for (line_idx, line) in csv.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let cells = cells(line);
+        let student_id = cells[0].trim();
+        if line_idx == 0 && HEADER_IDS.contains(&student_id.to_lowercase().as_str()) {
+            continue;
+        }
+        let row = line_idx + 1;
+        if student_id.is_empty() {
+            return Err(RosterError::MissingId { row });
+        }
+
+        let mut subjects: Vec<String> = Vec::new();
+        for subject in cells[1..].iter().flat_map(|cell| cell.split(';')) {
+            let subject = subject.trim();
+            if !subject.is_empty() && !subjects.iter().any(|taken| taken == subject) {
+                subjects.push(subject.to_string());
+            }
+        }
+        let student_id = StudentId::from(student_id);
+        if subjects.is_empty() {
+            return Err(RosterError::NoSubjects { student_id, row });
+        }
+        if subjects_by_student_id.contains_key(&student_id) {
+            return Err(RosterError::DuplicateStudent { student_id, row });
+        }
+        subjects_by_student_id.insert(student_id, subjects);
+    }
+    Ok(subjects_by_student_id)
+}
+
+/// Reads a CSV roster from a file, as `read_roster` does.
+pub fn read_roster_file(
+    path: impl AsRef<Path>,
+) -> Result<BTreeMap<StudentId, Vec<String>>, RosterError> {
+    let csv = std::fs::read_to_string(path)?;
+    read_roster(&csv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_give_each_student_their_subjects() {
+        let csv = "\u{feff}Student,Subjects\n\
+                   Ann,Maths,\"Art; Music\"\n\
+                   \n\
+                   Bob,Maths;Maths,\"Design \"\"&\"\" Tech\"\n";
+        let roster = read_roster(csv).unwrap();
+        assert_eq!(roster.len(), 2);
+        assert_eq!(roster[&StudentId::from("Ann")], ["Maths", "Art", "Music"]);
+        assert_eq!(
+            roster[&StudentId::from("Bob")],
+            ["Maths", "Design \"&\" Tech"]
+        );
+    }
+
+    #[test]
+    fn rows_without_an_id_or_subjects_or_repeated_are_errors() {
+        assert!(matches!(
+            read_roster(",Maths"),
+            Err(RosterError::MissingId { row: 1 })
+        ));
+        assert!(matches!(
+            read_roster("Ann,Maths\nBob, ;"),
+            Err(RosterError::NoSubjects { row: 2, .. })
+        ));
+        assert!(matches!(
+            read_roster("Ann,Maths\nAnn,Art"),
+            Err(RosterError::DuplicateStudent { row: 2, .. })
+        ));
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt;
+use std::path::Path;
+
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST, INT};
+use thiserror::Error;
+
+use super::{Constraint, Placement};
+
+/// Stops a script which loops forever from hanging the solver.
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// A constraint written as a Rhai script, so rules can be changed without
+/// recompiling. The script can define either or both of:
+///
+/// ```text
+/// // Return false to stop the lesson going in the slot.
+/// fn check(lesson) {
+///     let sciences = ["Biology", "Chemistry", "Physics"];
+///     if !sciences.contains(lesson.subject) { return true; }
+///     lesson.day_subjects.filter(|subject| sciences.contains(subject)).len() < 2
+/// }
+///
+/// // Return how much the slot should be avoided.
+/// fn penalty(lesson) {
+///     if lesson.subject == "PE" && lesson.period == 0 { 1 } else { 0 }
+/// }
+/// ```
+///
+/// `lesson` has the fields `subject`, `slot`, `term`, `week`, `day`, `period`
+/// and `day_subjects`, as in `Placement`, with subjects given by name. A
+/// script which fails while running forbids the slot and gives no penalty.
+pub struct ScriptConstraint {
+    engine: Engine,
+    ast: AST,
+    has_check: bool,
+    has_penalty: bool,
+}
+
+/// Why a script couldn't be read or compiled.
+#[derive(Debug, Error)]
+pub enum ScriptError {
+    #[error("couldn't read the constraint script: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("couldn't compile the constraint script: {0}")]
+    Parse(#[from] rhai::ParseError),
+    #[error("the constraint script needs a check(lesson) or penalty(lesson) function")]
+    NoConstraint,
+}
+
+impl ScriptConstraint {
+    pub fn from_source(source: &str) -> Result<ScriptConstraint, ScriptError> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        let ast = engine.compile(source)?;
+
+        let defines = |name: &str| {
+            ast.iter_functions()
+                .any(|function| function.name == name && function.params.len() == 1)
+        };
+        let (has_check, has_penalty) = (defines("check"), defines("penalty"));
+        if !has_check && !has_penalty {
+            return Err(ScriptError::NoConstraint);
+        }
+
+        Ok(ScriptConstraint {
+            engine,
+            ast,
+            has_check,
+            has_penalty,
+        })
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<ScriptConstraint, ScriptError> {
+        let source = std::fs::read_to_string(path)?;
+        ScriptConstraint::from_source(&source)
+    }
+
+    fn call<T: Clone + Send + Sync + 'static>(
+        &self,
+        name: &str,
+        placement: &Placement<'_>,
+    ) -> Option<T> {
+        let position = placement.position;
+        let subjects = &placement.timetable_info.subjects;
+        // Scripts are written with subjects' names rather than their IDs.
+        let mut lesson = Map::new();
+        lesson.insert("subject".into(), subjects.name(placement.subject).into());
+        lesson.insert("slot".into(), (placement.slot as INT).into());
+        lesson.insert("term".into(), (position.term as INT).into());
+        lesson.insert("week".into(), (position.week as INT).into());
+        lesson.insert("day".into(), (position.day as INT).into());
+        lesson.insert("period".into(), (position.period as INT).into());
+        let day_subjects: Array = placement
+            .day_subjects()
+            .map(|subject| Dynamic::from(subjects.name(subject).to_string()))
+            .collect();
+        lesson.insert("day_subjects".into(), day_subjects.into());
+
+        self.engine
+            .call_fn(&mut Scope::new(), &self.ast, name, (lesson,))
+            .ok()
+    }
+}
+
+impl fmt::Debug for ScriptConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScriptConstraint")
+            .field("has_check", &self.has_check)
+            .field("has_penalty", &self.has_penalty)
+            .finish()
+    }
+}
+
+impl Constraint for ScriptConstraint {
+    fn check(&self, placement: &Placement<'_>) -> bool {
+        !self.has_check || self.call("check", placement).unwrap_or(false)
+    }
+
+    fn penalty(&self, placement: &Placement<'_>) -> u32 {
+        if !self.has_penalty {
+            return 0;
+        }
+        self.call::<INT>("penalty", placement)
+            .and_then(|penalty| u32::try_from(penalty).ok())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::ScriptConstraint;
+    use crate::timetabler::{
+        solve_timetable, verify, StudentOrdering, TimetableInfo, TimetableResult,
+    };
+
+    #[test]
+    fn scripted_rule_is_kept_when_joining_a_group() {
+        let script = ScriptConstraint::from_source(
+            r#"
+            fn check(lesson) {
+                let sciences = ["Biology", "Chemistry"];
+                !sciences.contains(lesson.subject)
+                    || lesson.day_subjects.filter(|subject| sciences.contains(subject)).len() == 0
+            }
+            "#,
+        )
+        .unwrap();
+        // C would fit the Chemistry group made for B, on the same day as their
+        // Biology lesson, if the script weren't asked before they joined it.
+        let mut info = TimetableInfo::new(2, 2);
+        info.add_student("A", &["Biology"]);
+        info.add_student("B", &["Art", "Chemistry"]);
+        info.add_student("C", &["Biology", "Chemistry"]);
+        info.student_ordering = StudentOrdering::AsGiven;
+        info.constraints.push(Arc::new(script));
+
+        let result = solve_timetable(&info);
+        assert!(verify(&info, &result).is_empty());
+        assert!(matches!(result, TimetableResult::Solved { .. }));
+        assert_eq!(result.group_count(), 4);
+    }
+}
@@ -0,0 +1,90 @@
+/// A set of slots of the year, kept as one bit per slot. Two sets can be
+/// checked for a shared slot 64 slots at a time, rather than slot by slot.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) struct SlotSet {
+    words: Vec<u64>,
+}
+
+impl SlotSet {
+    pub(super) fn new(slots_per_year: usize) -> SlotSet {
+        SlotSet {
+            words: vec![0; (slots_per_year + 63) / 64],
+        }
+    }
+
+    pub(super) fn from_slots(slots: &[usize], slots_per_year: usize) -> SlotSet {
+        let mut set = SlotSet::new(slots_per_year);
+        for &slot in slots {
+            set.insert(slot);
+        }
+        set
+    }
+
+    pub(super) fn insert(&mut self, slot: usize) {
+        self.words[slot / 64] |= 1 << (slot % 64);
+    }
+
+    pub(super) fn remove(&mut self, slot: usize) {
+        self.words[slot / 64] &= !(1 << (slot % 64));
+    }
+
+    pub(super) fn contains(&self, slot: usize) -> bool {
+        self.words[slot / 64] & 1 << (slot % 64) != 0
+    }
+
+    /// The earliest slot in both sets, if there is one.
+    pub(super) fn first_shared(&self, other: &SlotSet) -> Option<usize> {
+        self.words
+            .iter()
+            .zip(&other.words)
+            .enumerate()
+            .find_map(|(idx, (word, other_word))| match word & other_word {
+                0 => None,
+                shared => Some(idx * 64 + shared.trailing_zeros() as usize),
+            })
+    }
+
+    pub(super) fn is_disjoint(&self, other: &SlotSet) -> bool {
+        self.first_shared(other).is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SlotSet;
+
+    #[test]
+    fn slots_are_added_and_removed() {
+        // A fortnight of ten lessons a day runs past the first word.
+        let mut set = SlotSet::from_slots(&[0, 63, 64, 99], 100);
+        for slot in 0..100 {
+            assert_eq!(set.contains(slot), [0, 63, 64, 99].contains(&slot));
+        }
+        set.remove(63);
+        set.remove(1);
+        set.insert(64);
+        assert!(!set.contains(63));
+        assert!(!set.contains(1));
+        assert_eq!(set, SlotSet::from_slots(&[0, 64, 99], 100));
+    }
+
+    #[test]
+    fn the_earliest_shared_slot_is_found_in_any_word() {
+        let set = SlotSet::from_slots(&[3, 70, 130], 192);
+        assert_eq!(
+            set.first_shared(&SlotSet::from_slots(&[3, 130], 192)),
+            Some(3)
+        );
+        assert_eq!(
+            set.first_shared(&SlotSet::from_slots(&[4, 130, 70], 192)),
+            Some(70)
+        );
+        assert_eq!(
+            set.first_shared(&SlotSet::from_slots(&[131, 130], 192)),
+            Some(130)
+        );
+        assert!(set.is_disjoint(&SlotSet::from_slots(&[2, 69, 71, 191], 192)));
+        assert!(set.is_disjoint(&SlotSet::new(192)));
+    }
+}
@@ -0,0 +1,324 @@
+//! Solves many random schools and checks every timetable with `verify`, to
+//! shake out slot and index bookkeeping bugs in the solver. Each failure is
+//! shrunk to the smallest school which still fails, so that it is quick to
+//! reproduce.
+
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use super::generate::{self, Rng, SchoolConfig};
+use super::{
+    solve_timetable_cancellable, verify, Algorithm, CancellationToken, DailyLessonBounds,
+    SolveObserver, TimetableInfo, Violation, WeekCycle,
+};
+
+/// How many schools to try and how big they may be.
+#[derive(Debug, Clone)]
+pub struct StressConfig {
+    pub instances: usize,
+    /// Runs with the same seed and config try the same schools.
+    pub seed: u64,
+    pub max_students: usize,
+    pub max_subjects: usize,
+    /// Whether to try the exhaustive search as well as the greedy solver. The
+    /// search is much slower, so it is only tried on schools of up to 20
+    /// students.
+    pub exhaustive: bool,
+    /// How long each school may take to solve before it counts as a failure.
+    pub time_limit: Duration,
+}
+
+impl Default for StressConfig {
+    fn default() -> Self {
+        StressConfig {
+            instances: 1000,
+            seed: 1,
+            max_students: 60,
+            max_subjects: 12,
+            exhaustive: false,
+            time_limit: Duration::from_secs(10),
+        }
+    }
+}
+
+/// One school to solve, along with the settings it is solved with.
+#[derive(Debug, Clone)]
+pub struct Instance {
+    pub school: SchoolConfig,
+    pub cycle: WeekCycle,
+    pub max_groups_per_slot: Option<u8>,
+    pub algorithm: Algorithm,
+}
+
+impl Instance {
+    pub fn timetable_info(&self) -> TimetableInfo {
+        let mut info = generate::school(&self.school);
+        info.cycle = self.cycle;
+        info.max_groups_per_slot = self.max_groups_per_slot;
+        info.algorithm = self.algorithm;
+        info
+    }
+
+    /// The instances one step smaller than this one, with the biggest steps
+    /// first.
+    fn smaller(&self) -> Vec<Instance> {
+        let mut smaller = Vec::new();
+        let school = &self.school;
+        let mut with = |change: &dyn Fn(&mut Instance)| {
+            let mut instance = self.clone();
+            change(&mut instance);
+            smaller.push(instance);
+        };
+        if school.students > 1 {
+            with(&|instance| instance.school.students /= 2);
+            with(&|instance| instance.school.students -= 1);
+        }
+        if school.subjects > 1 {
+            with(&|instance| instance.school.subjects -= 1);
+        }
+        if *school.subjects_per_student.end() > 1 {
+            with(&|instance| {
+                let end = *instance.school.subjects_per_student.end() - 1;
+                let start = (*instance.school.subjects_per_student.start()).min(end);
+                instance.school.subjects_per_student = start..=end;
+            });
+        }
+        if school.max_groups > 1 {
+            with(&|instance| instance.school.max_groups -= 1);
+        }
+        if school.daily_lesson_capacity > 1 {
+            with(&|instance| instance.school.daily_lesson_capacity -= 1);
+        }
+        if school.rooms > 0 {
+            with(&|instance| instance.school.rooms = 0);
+            with(&|instance| instance.school.rooms -= 1);
+        }
+        if school.teachers > 0 {
+            with(&|instance| instance.school.teachers = 0);
+            with(&|instance| instance.school.teachers -= 1);
+        }
+        if school.terms > 1 {
+            with(&|instance| instance.school.terms = 1);
+        }
+        if school.sites > 0 {
+            with(&|instance| instance.school.sites = 0);
+        }
+        if school.fixed_events > 0 {
+            with(&|instance| instance.school.fixed_events -= 1);
+        }
+        let bounds = &school.daily_lessons;
+        if bounds.min > 0
+            || bounds.hard_min > 0
+            || bounds.max.is_some()
+            || bounds.hard_max.is_some()
+        {
+            with(&|instance| instance.school.daily_lessons = DailyLessonBounds::default());
+        }
+        if school.max_group_size.is_some() {
+            with(&|instance| instance.school.max_group_size = None);
+        }
+        if self.max_groups_per_slot.is_some() {
+            with(&|instance| instance.max_groups_per_slot = None);
+        }
+        if self.cycle != WeekCycle::Weekly {
+            with(&|instance| instance.cycle = WeekCycle::Weekly);
+        }
+        if self.algorithm != Algorithm::Greedy {
+            with(&|instance| instance.algorithm = Algorithm::Greedy);
+        }
+        smaller
+    }
+}
+
+/// How solving an instance went wrong.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Failure {
+    /// The solver returned a timetable which breaks the rules.
+    Violations(Vec<Violation>),
+    /// The solver panicked, with the given message.
+    Panicked(String),
+    /// The solver didn't finish within `StressConfig::time_limit`.
+    TimedOut,
+}
+
+#[derive(Debug, Clone)]
+pub struct FailedInstance {
+    pub instance: Instance,
+    pub failure: Failure,
+}
+
+/// Nothing is done with the solver's progress.
+struct Unobserved;
+
+impl SolveObserver for Unobserved {}
+
+/// Solves `instance` and verifies the timetable. Instances which can't be
+/// solved don't fail, as many random schools have no timetable. A solve which
+/// takes longer than `time_limit` is asked to stop and counts as a failure.
+pub fn check(instance: &Instance, time_limit: Duration) -> Option<Failure> {
+    let info = instance.timetable_info();
+    let cancellation = CancellationToken::new();
+    let (sender, receiver) = mpsc::channel();
+    let solve = {
+        let info = info.clone();
+        let cancellation = cancellation.clone();
+        move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                solve_timetable_cancellable(&info, &mut Unobserved, &cancellation)
+            }));
+            // The receiver is gone if the solve took too long.
+            let _ = sender.send(result);
+        }
+    };
+    thread::spawn(solve);
+
+    match receiver.recv_timeout(time_limit) {
+        Ok(Ok(result)) => {
+            let violations = verify(&info, &result);
+            if violations.is_empty() || violations == [Violation::NotSolved] {
+                None
+            } else {
+                Some(Failure::Violations(violations))
+            }
+        }
+        Ok(Err(payload)) => Some(Failure::Panicked(panic_message(payload))),
+        Err(RecvTimeoutError::Timeout) => {
+            cancellation.cancel();
+            Some(Failure::TimedOut)
+        }
+        Err(RecvTimeoutError::Disconnected) => {
+            unreachable!("the solving thread always sends its result")
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic".to_string(),
+        },
+    }
+}
+
+/// The instance to try for each of `config.instances`.
+pub fn instances(config: &StressConfig) -> Vec<Instance> {
+    let mut rng = Rng::new(config.seed);
+    (0..config.instances)
+        .map(|_| {
+            let students = rng.in_range(&(1..=config.max_students.max(1)));
+            let subjects = rng.in_range(&(1..=config.max_subjects.max(1)));
+            let most_taken = rng.in_range(&(1..=subjects.min(6)));
+            let daily_lesson_capacity = rng.in_range(&(1..=8)) as u8;
+            let mut chance = |probability: f64| rng.unit() < probability;
+            let with_rooms = chance(0.3);
+            let with_teachers = chance(0.3);
+            let with_terms = chance(0.2);
+            let with_sites = chance(0.2);
+            let with_fixed_events = chance(0.25);
+            let with_daily_lessons = chance(0.25);
+            let with_max_group_size = chance(0.25);
+            let daily_lessons = if with_daily_lessons {
+                let capacity = usize::from(daily_lesson_capacity);
+                DailyLessonBounds {
+                    min: rng.in_range(&(0..=2)) as u8,
+                    hard_min: rng.in_range(&(0..=1)) as u8,
+                    max: Some(rng.in_range(&(1..=capacity)) as u8),
+                    hard_max: Some(rng.in_range(&(1..=capacity)) as u8),
+                }
+            } else {
+                DailyLessonBounds::default()
+            };
+            let school = SchoolConfig {
+                students,
+                subjects,
+                subjects_per_student: rng.in_range(&(1..=most_taken))..=most_taken,
+                popularity_skew: rng.unit() * 2.0,
+                max_groups: rng.in_range(&(1..=6)) as u8,
+                daily_lesson_capacity,
+                rooms: if with_rooms {
+                    rng.in_range(&(1..=12))
+                } else {
+                    0
+                },
+                teachers: if with_teachers {
+                    rng.in_range(&(1..=12))
+                } else {
+                    0
+                },
+                terms: if with_terms {
+                    rng.in_range(&(2..=3)) as u8
+                } else {
+                    1
+                },
+                sites: if with_sites {
+                    rng.in_range(&(2..=3))
+                } else {
+                    0
+                },
+                fixed_events: if with_fixed_events {
+                    rng.in_range(&(1..=3))
+                } else {
+                    0
+                },
+                daily_lessons,
+                max_group_size: if with_max_group_size {
+                    Some(rng.in_range(&(1..=30)) as u16)
+                } else {
+                    None
+                },
+                seed: rng.next(),
+            };
+            let cycle = if rng.unit() < 0.25 {
+                WeekCycle::Fortnightly
+            } else {
+                WeekCycle::Weekly
+            };
+            let max_groups_per_slot = if rng.unit() < 0.25 {
+                Some(rng.in_range(&(1..=8)) as u8)
+            } else {
+                None
+            };
+            let algorithm = if config.exhaustive && students <= 20 && rng.unit() < 0.5 {
+                Algorithm::Exhaustive
+            } else {
+                Algorithm::Greedy
+            };
+            Instance {
+                school,
+                cycle,
+                max_groups_per_slot,
+                algorithm,
+            }
+        })
+        .collect()
+}
+
+/// Makes `failed` as small as possible while it still fails, trying each
+/// smaller instance in turn and moving to the first which fails.
+pub fn shrink(mut failed: FailedInstance, time_limit: Duration) -> FailedInstance {
+    'shrinking: loop {
+        for instance in failed.instance.smaller() {
+            if let Some(failure) = check(&instance, time_limit) {
+                failed = FailedInstance { instance, failure };
+                continue 'shrinking;
+            }
+        }
+        return failed;
+    }
+}
+
+/// Checks every instance of `config`, returning each failure shrunk.
+pub fn run(config: &StressConfig) -> Vec<FailedInstance> {
+    instances(config)
+        .into_iter()
+        .filter_map(|instance| {
+            check(&instance, config.time_limit)
+                .map(|failure| shrink(FailedInstance { instance, failure }, config.time_limit))
+        })
+        .collect()
+}
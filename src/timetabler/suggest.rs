@@ -0,0 +1,53 @@
+use std::collections::BTreeMap;
+
+use super::{StudentInfo, SubjectId, SubjectRegistry, WeekCycle, DAYS_PER_WEEK};
+
+/// How many groups each subject taken by `students` needs so that no group
+/// has more than `target_group_size` students, for filling in max groups
+/// before the timetable is solved. Every subject needs at least one group.
+pub fn suggest_max_groups(
+    students: &[StudentInfo],
+    target_group_size: usize,
+) -> BTreeMap<SubjectId, u8> {
+    let mut takers: BTreeMap<SubjectId, usize> = BTreeMap::new();
+    for student in students {
+        for &subject in &student.subjects {
+            *takers.entry(subject).or_insert(0) += 1;
+        }
+    }
+    let target_group_size = target_group_size.max(1);
+    takers
+        .into_iter()
+        .map(|(subject, takers)| {
+            let groups = (takers + target_group_size - 1) / target_group_size;
+            (subject, groups.clamp(1, u8::MAX.into()) as u8)
+        })
+        .collect()
+}
+
+/// The fewest lessons a day which plausibly fit the busiest student's week,
+/// with one lesson a day to spare so that groups have some choice of slots.
+/// `subjects` are those the students' subject IDs refer to.
+pub fn suggest_daily_lesson_capacity(
+    students: &[StudentInfo],
+    subjects: &SubjectRegistry,
+    cycle: WeekCycle,
+) -> u8 {
+    let lessons_in_week =
+        |subject: SubjectId, week: usize| usize::from(subjects[subject].lessons_per_week[week]);
+    let busiest_week = students
+        .iter()
+        .flat_map(|student| {
+            (0..cycle.weeks()).map(move |week| {
+                student
+                    .subjects
+                    .iter()
+                    .map(|&subject| lessons_in_week(subject, week))
+                    .sum::<usize>()
+            })
+        })
+        .max()
+        .unwrap_or_default();
+    let daily_lessons = (busiest_week + DAYS_PER_WEEK - 1) / DAYS_PER_WEEK;
+    (daily_lessons + 1).min(u8::MAX.into()) as u8
+}
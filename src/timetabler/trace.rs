@@ -0,0 +1,203 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use super::{group_name, StudentId};
+
+/// A decision made by the solver, recorded by `solve_timetable_traced`.
+/// Slots count across the whole year, as in `TimetableResult::Solved`.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceEvent {
+    /// The student was put in an existing group.
+    Joined {
+        student_id: StudentId,
+        subject: String,
+        group_idx: usize,
+    },
+    /// A new group was made for the student in the given slots.
+    Created {
+        student_id: StudentId,
+        subject: String,
+        group_idx: usize,
+        slots: Vec<usize>,
+    },
+    /// A lesson of a group was moved to make room for another student.
+    Moved {
+        subject: String,
+        group_idx: usize,
+        from: usize,
+        to: usize,
+    },
+    /// None of the existing groups of `subject` fit around the student's
+    /// other lessons.
+    NoGroupFits {
+        student_id: StudentId,
+        subject: String,
+    },
+    /// `subject` couldn't have a new group as it had as many as it may have.
+    GroupLimitReached {
+        student_id: StudentId,
+        subject: String,
+    },
+    /// The student didn't have the free slots for a new group of `subject`.
+    NoSlotsForNewGroup {
+        student_id: StudentId,
+        subject: String,
+    },
+    /// `group_idx` was full when `new_group_idx` was made, so some of its
+    /// students were moved to the new group.
+    Split {
+        subject: String,
+        group_idx: usize,
+        new_group_idx: usize,
+        students_moved: usize,
+    },
+    /// A lesson clashing in `slot` couldn't be moved anywhere, so the group
+    /// couldn't be made to fit.
+    ClashNotMoved {
+        subject: String,
+        group_idx: usize,
+        slot: usize,
+    },
+}
+
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceEvent::Joined {
+                student_id,
+                subject,
+                group_idx,
+            } => write!(
+                f,
+                "{} joined {}",
+                student_id,
+                group_name(subject, *group_idx)
+            ),
+            TraceEvent::Created {
+                student_id,
+                subject,
+                group_idx,
+                slots,
+            } => write!(
+                f,
+                "{} was made for {} in slots {:?}",
+                group_name(subject, *group_idx),
+                student_id,
+                slots
+            ),
+            TraceEvent::Moved {
+                subject,
+                group_idx,
+                from,
+                to,
+            } => write!(
+                f,
+                "{}'s lesson in slot {} was moved to slot {}",
+                group_name(subject, *group_idx),
+                from,
+                to
+            ),
+            TraceEvent::NoGroupFits {
+                student_id,
+                subject,
+            } => write!(f, "No group of {} fits {}", subject, student_id),
+            TraceEvent::GroupLimitReached {
+                student_id,
+                subject,
+            } => write!(f, "{} can't have another group for {}", subject, student_id),
+            TraceEvent::NoSlotsForNewGroup {
+                student_id,
+                subject,
+            } => write!(
+                f,
+                "{} doesn't have the free slots for a new group of {}",
+                student_id, subject
+            ),
+            TraceEvent::Split {
+                subject,
+                group_idx,
+                new_group_idx,
+                students_moved,
+            } => write!(
+                f,
+                "{} was full, so {} of its students moved to {}",
+                group_name(subject, *group_idx),
+                students_moved,
+                group_name(subject, *new_group_idx)
+            ),
+            TraceEvent::ClashNotMoved {
+                subject,
+                group_idx,
+                slot,
+            } => write!(
+                f,
+                "The clash with {} in slot {} couldn't be moved",
+                group_name(subject, *group_idx),
+                slot
+            ),
+        }
+    }
+}
+
+/// The stages of solving, in the order they run. Stages for objectives which
+/// are turned off, or for rooms and teachers when there are none, are
+/// skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    PlacingStudents,
+    BalancingGroupSizes,
+    MinimizingGaps,
+    LocalSearch,
+    BalancingDailyWorkload,
+    ExhaustiveSearch,
+    AssigningRooms,
+    AssigningTeachers,
+}
+
+/// Receives the solver's progress as it works, so applications can show
+/// their own progress. Every method does nothing unless overridden.
+pub trait SolveObserver {
+    fn phase_started(&mut self, _phase: Phase) {}
+
+    /// Called once a student has been given all of their subjects, with the
+    /// number of students placed so far out of the total.
+    fn student_placed(&mut self, _student_id: &StudentId, _placed: usize, _total: usize) {}
+
+    /// Called for every decision made while placing students, including
+    /// moving groups.
+    fn decision(&mut self, _event: TraceEvent) {}
+
+    /// Called whenever an optimisation phase moves a student to a group, or a
+    /// lesson to a slot, which makes the timetable better.
+    fn improvement_found(&mut self, _phase: Phase) {}
+}
+
+/// Collects every decision, as `solve_timetable_traced` does.
+impl SolveObserver for Vec<TraceEvent> {
+    fn decision(&mut self, event: TraceEvent) {
+        self.push(event);
+    }
+}
+
+/// Asks a solve started by `solve_timetable_cancellable` to stop, from
+/// another thread. Clones share the same request.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Asks the solver to stop. It gives up at the next point it checks,
+    /// finishing with `TimetableError::Cancelled`.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
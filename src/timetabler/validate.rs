@@ -0,0 +1,702 @@
+use std::collections::{BTreeSet, HashSet};
+
+use thiserror::Error;
+
+use super::{StudentId, SubjectId, TimetableInfo, DAYS_PER_WEEK};
+
+/// A problem with a `TimetableInfo` which means it can't be solved as given.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ValidationIssue {
+    /// The timetable has no slots to put lessons in.
+    #[error("there are no slots to put lessons in")]
+    NoSlots,
+    /// Subjects can't be split into any groups.
+    #[error("subjects can't be split into any groups")]
+    NoGroups,
+    /// No groups may be taught in any slot.
+    #[error("no groups may be taught at once")]
+    NoGroupsPerSlot,
+    /// Two students share an ID.
+    #[error("more than one student has the ID {0}")]
+    DuplicateStudentId(String),
+    /// Two rooms share an ID.
+    #[error("more than one room has the ID {0}")]
+    DuplicateRoomId(String),
+    /// Two teachers share an ID.
+    #[error("more than one teacher has the ID {0}")]
+    DuplicateTeacherId(String),
+    /// A student or `SubjectInfo` has a subject with a blank name.
+    #[error("{}", unnamed_subject(.student_id.as_ref()))]
+    EmptySubjectName { student_id: Option<StudentId> },
+    /// A student takes the same subject more than once.
+    #[error("{student_id} takes {subject} more than once")]
+    DuplicateSubject {
+        student_id: StudentId,
+        subject: String,
+    },
+    /// A subject runs in a term which the year doesn't have.
+    #[error("{subject} runs in term {}, which doesn't exist", .term + 1)]
+    TermOutOfRange { subject: String, term: u8 },
+    /// A subject is taught at a site which has none of the rooms.
+    #[error("{subject} is taught at {site}, which has no rooms")]
+    NoRoomAtSite { subject: String, site: String },
+    /// Every teacher of a subject has every day of the week off.
+    #[error("every teacher of {subject} has every day off")]
+    NoTeacherWorking { subject: String },
+    /// More students take a subject than there are places in as many groups
+    /// of it as it may have, given `TimetableInfo::max_group_size`.
+    #[error("{students} students take {subject} but its groups only have {places} places")]
+    TooFewPlaces {
+        subject: String,
+        students: usize,
+        places: usize,
+    },
+    /// A fixed event is in a slot past the end of the cycle.
+    #[error("{event} is in slot {slot}, past the end of the cycle")]
+    EventSlotOutOfRange { event: String, slot: usize },
+    /// A student has more lessons in one week than there are slots free of
+    /// fixed events in it, or than the daily limit allows.
+    #[error("{student_id} has {lessons} lessons in a week with only {slots} slots")]
+    TooManyLessons {
+        student_id: StudentId,
+        term: usize,
+        week: usize,
+        lessons: usize,
+        slots: usize,
+    },
+    /// `DailyLessonBounds::hard_min` is above `DailyLessonBounds::hard_max`.
+    #[error("students must have at least {hard_min} lessons a day but at most {hard_max}")]
+    DailyBoundsCross { hard_min: u8, hard_max: u8 },
+    /// A student has fewer lessons in one week than
+    /// `DailyLessonBounds::hard_min` needs across its days.
+    #[error(
+        "{student_id} has {lessons} lessons in a week which needs at least {needed} for the daily minimum"
+    )]
+    TooFewLessons {
+        student_id: StudentId,
+        term: usize,
+        week: usize,
+        lessons: usize,
+        needed: usize,
+    },
+    /// Even with one group of each subject, a week has more lessons than can
+    /// be taught in it with `TimetableInfo::max_groups_per_slot` groups at
+    /// once.
+    #[error(
+        "week {} of term {} needs at least {lessons} lessons, but only {capacity} fit with the limit on groups at once",
+        .week + 1,
+        .term + 1
+    )]
+    TooFewGroupsAtOnce {
+        term: usize,
+        week: usize,
+        lessons: usize,
+        capacity: usize,
+    },
+    /// Even with one group of each subject, a week has more lessons needing a
+    /// room of `room_type`, or any room when it is `None`, than those rooms
+    /// have slots.
+    #[error(
+        "week {} of term {} needs at least {lessons} lessons in {}, but they only have {capacity} slots",
+        .week + 1,
+        .term + 1,
+        rooms_of_type(.room_type.as_deref())
+    )]
+    TooFewRooms {
+        room_type: Option<String>,
+        term: usize,
+        week: usize,
+        lessons: usize,
+        capacity: usize,
+    },
+    /// Even with one group of each subject, a week has more lessons than the
+    /// teachers may teach between them.
+    #[error(
+        "week {} of term {} needs at least {lessons} lessons, but the teachers may only teach {capacity}",
+        .week + 1,
+        .term + 1
+    )]
+    TooFewTeachers {
+        term: usize,
+        week: usize,
+        lessons: usize,
+        capacity: usize,
+    },
+}
+
+/// What `ValidationIssue::EmptySubjectName` says, depending on whether a
+/// student or a `SubjectInfo` has the blank name.
+fn unnamed_subject(student_id: Option<&StudentId>) -> String {
+    match student_id {
+        Some(student_id) => format!("{} has a subject with no name", student_id),
+        None => "a subject's details were given without a name".to_string(),
+    }
+}
+
+/// The rooms `ValidationIssue::TooFewRooms` is about.
+fn rooms_of_type(room_type: Option<&str>) -> String {
+    match room_type {
+        Some(room_type) => format!("{} rooms", room_type),
+        None => "rooms".to_string(),
+    }
+}
+
+/// Pushes an issue for every ID which appears more than once.
+fn find_duplicates<'a>(
+    ids: impl Iterator<Item = &'a str>,
+    issue: fn(String) -> ValidationIssue,
+    issues: &mut Vec<ValidationIssue>,
+) {
+    let mut seen = HashSet::new();
+    let mut reported = HashSet::new();
+    for id in ids {
+        if !seen.insert(id) && reported.insert(id) {
+            issues.push(issue(id.to_string()));
+        }
+    }
+}
+
+/// Checks `timetable_info` for inputs which can never be solved, so they can
+/// be shown to the user before trying to solve it. An empty result doesn't
+/// mean a solution exists.
+pub fn validate(timetable_info: &TimetableInfo) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+
+    if timetable_info.daily_lesson_capacity == 0 || timetable_info.terms == 0 {
+        issues.push(ValidationIssue::NoSlots);
+    }
+    if timetable_info.max_groups == 0 {
+        issues.push(ValidationIssue::NoGroups);
+    }
+    if timetable_info.max_groups_per_slot == Some(0) {
+        issues.push(ValidationIssue::NoGroupsPerSlot);
+    }
+    let bounds = &timetable_info.daily_lessons;
+    if let Some(hard_max) = bounds.hard_max {
+        if bounds.hard_min > hard_max {
+            issues.push(ValidationIssue::DailyBoundsCross {
+                hard_min: bounds.hard_min,
+                hard_max,
+            });
+        }
+    }
+
+    let students = &timetable_info.students;
+    find_duplicates(
+        students.iter().map(|student| student.id.as_str()),
+        ValidationIssue::DuplicateStudentId,
+        &mut issues,
+    );
+    find_duplicates(
+        timetable_info.rooms.iter().map(|room| room.id.as_str()),
+        ValidationIssue::DuplicateRoomId,
+        &mut issues,
+    );
+    find_duplicates(
+        timetable_info
+            .teachers
+            .iter()
+            .map(|teacher| teacher.id.as_str()),
+        ValidationIssue::DuplicateTeacherId,
+        &mut issues,
+    );
+
+    for subject_info in &timetable_info.subjects {
+        if subject_info.name().trim().is_empty() {
+            issues.push(ValidationIssue::EmptySubjectName { student_id: None });
+        }
+        // Rooms are only assigned when there are some.
+        if let Some(site) = &subject_info.site {
+            let rooms = &timetable_info.rooms;
+            if !rooms.is_empty() && !rooms.iter().any(|room| room.site.as_ref() == Some(site)) {
+                issues.push(ValidationIssue::NoRoomAtSite {
+                    subject: subject_info.name().to_string(),
+                    site: site.clone(),
+                });
+            }
+        }
+        if let Some(term) = subject_info.term {
+            if term >= timetable_info.terms {
+                issues.push(ValidationIssue::TermOutOfRange {
+                    subject: subject_info.name().to_string(),
+                    term,
+                });
+            }
+        }
+    }
+
+    for subject in timetable_info.subjects.ids() {
+        let name = timetable_info.subjects.name(subject);
+        let mut teachers = timetable_info
+            .teachers
+            .iter()
+            .filter(|teacher| teacher.can_teach(subject))
+            .peekable();
+        let is_taught = teachers.peek().is_some();
+        if is_taught
+            && teachers.all(|teacher| {
+                (0..DAYS_PER_WEEK).all(|day| {
+                    teacher
+                        .days_off
+                        .iter()
+                        .any(|&day_off| usize::from(day_off) == day)
+                })
+            })
+        {
+            issues.push(ValidationIssue::NoTeacherWorking {
+                subject: name.to_string(),
+            });
+        }
+
+        if let Some(max_size) = timetable_info.max_group_size_for(subject) {
+            let takers = students
+                .iter()
+                .filter(|student| student.subjects.contains(&subject))
+                .count();
+            let places = usize::from(timetable_info.max_groups_for(subject)) * max_size;
+            if takers > places {
+                issues.push(ValidationIssue::TooFewPlaces {
+                    subject: name.to_string(),
+                    students: takers,
+                    places,
+                });
+            }
+        }
+    }
+
+    let total_slots = timetable_info.total_slots();
+    for event in &timetable_info.fixed_events {
+        for &slot in &event.slots {
+            if slot >= total_slots {
+                issues.push(ValidationIssue::EventSlotOutOfRange {
+                    event: event.name.clone(),
+                    slot,
+                });
+            }
+        }
+    }
+
+    for student in students {
+        if student
+            .subjects
+            .iter()
+            .any(|&subject| timetable_info.subjects.name(subject).trim().is_empty())
+        {
+            issues.push(ValidationIssue::EmptySubjectName {
+                student_id: Some(student.id.clone()),
+            });
+        }
+
+        let mut seen = HashSet::new();
+        for &subject in &student.subjects {
+            if !seen.insert(subject) {
+                issues.push(ValidationIssue::DuplicateSubject {
+                    student_id: student.id.clone(),
+                    subject: timetable_info.subjects.name(subject).to_string(),
+                });
+            }
+        }
+
+        // Each week of each term needs room for all of the student's lessons
+        // in it, around the events they go to and within the daily limit, and
+        // enough lessons to fill each day to the daily minimum.
+        let slots_per_week = timetable_info.slots_per_week();
+        let daily_lesson_capacity = usize::from(timetable_info.daily_lesson_capacity);
+        let hard_max = timetable_info
+            .daily_lessons
+            .hard_max
+            .map_or(daily_lesson_capacity, usize::from);
+        let hard_min = usize::from(timetable_info.daily_lessons.hard_min);
+        let event_slots: HashSet<usize> = timetable_info
+            .fixed_events
+            .iter()
+            .filter(|event| event.is_attended_by(student.cohort()))
+            .flat_map(|event| event.slots.iter().copied())
+            .collect();
+        'weeks: for term in 0..usize::from(timetable_info.terms) {
+            for week in 0..timetable_info.cycle.weeks() {
+                let week_start = week * slots_per_week;
+                let free_by_day: Vec<usize> = (week_start..week_start + slots_per_week)
+                    .step_by(daily_lesson_capacity.max(1))
+                    .map(|day_start| {
+                        let day_slots = day_start..day_start + daily_lesson_capacity;
+                        daily_lesson_capacity
+                            - event_slots
+                                .iter()
+                                .filter(|slot| day_slots.contains(slot))
+                                .count()
+                    })
+                    .collect();
+                let slots = free_by_day.iter().map(|free| free.min(&hard_max)).sum();
+                let needed = free_by_day.iter().map(|free| free.min(&hard_min)).sum();
+                let lessons = student
+                    .subjects
+                    .iter()
+                    .filter(|&&subject| timetable_info.terms_of(subject).contains(&term))
+                    .map(|&subject| usize::from(timetable_info.lessons_in_week(subject, week)))
+                    .sum();
+                if lessons > slots {
+                    issues.push(ValidationIssue::TooManyLessons {
+                        student_id: student.id.clone(),
+                        term,
+                        week,
+                        lessons,
+                        slots,
+                    });
+                    // One week is enough to tell the user about.
+                    break 'weeks;
+                }
+                if lessons < needed {
+                    issues.push(ValidationIssue::TooFewLessons {
+                        student_id: student.id.clone(),
+                        term,
+                        week,
+                        lessons,
+                        needed,
+                    });
+                    break 'weeks;
+                }
+            }
+        }
+    }
+
+    find_shortfalls(timetable_info, &mut issues);
+
+    issues
+}
+
+/// Pushes an issue for each resource, such as rooms, which can't fit the
+/// lessons of a week even if every subject only has one group. The first week
+/// short of each is reported.
+fn find_shortfalls(timetable_info: &TimetableInfo, issues: &mut Vec<ValidationIssue>) {
+    let taken: BTreeSet<SubjectId> = timetable_info
+        .students
+        .iter()
+        .flat_map(|student| student.subjects.iter().copied())
+        .collect();
+    let slots_per_week = timetable_info.slots_per_week();
+    let daily_lesson_capacity = usize::from(timetable_info.daily_lesson_capacity);
+    let mut room_types: BTreeSet<Option<&str>> = taken
+        .iter()
+        .filter_map(|&subject| {
+            timetable_info.subjects[subject]
+                .room_type
+                .as_deref()
+                .map(Some)
+        })
+        .collect();
+    room_types.insert(None);
+    let teacher_capacity: usize = timetable_info
+        .teachers
+        .iter()
+        .map(|teacher| {
+            let working_days = (0..DAYS_PER_WEEK)
+                .filter(|&day| {
+                    !teacher
+                        .days_off
+                        .iter()
+                        .any(|&day_off| usize::from(day_off) == day)
+                })
+                .count();
+            let working_slots = working_days * daily_lesson_capacity;
+            teacher
+                .max_weekly_lessons
+                .map_or(working_slots, |max| working_slots.min(max.into()))
+        })
+        .sum();
+
+    let mut groups_reported = false;
+    let mut rooms_reported = BTreeSet::new();
+    let mut teachers_reported = false;
+    for term in 0..usize::from(timetable_info.terms) {
+        for week in 0..timetable_info.cycle.weeks() {
+            // The lessons of one group of every subject running this week
+            // which need a room of `room_type`, or any room when it is `None`.
+            let lessons = |room_type: Option<&str>| -> usize {
+                taken
+                    .iter()
+                    .filter(|&&subject| timetable_info.terms_of(subject).contains(&term))
+                    .filter(|&&subject| {
+                        room_type.is_none()
+                            || timetable_info.subjects[subject].room_type.as_deref() == room_type
+                    })
+                    .map(|&subject| usize::from(timetable_info.lessons_in_week(subject, week)))
+                    .sum()
+            };
+            let all_lessons = lessons(None);
+
+            if let Some(max_groups_per_slot) = timetable_info.max_groups_per_slot {
+                let capacity = slots_per_week * usize::from(max_groups_per_slot);
+                if all_lessons > capacity && !groups_reported {
+                    groups_reported = true;
+                    issues.push(ValidationIssue::TooFewGroupsAtOnce {
+                        term,
+                        week,
+                        lessons: all_lessons,
+                        capacity,
+                    });
+                }
+            }
+            // Rooms and teachers are only assigned when there are some.
+            if !timetable_info.rooms.is_empty() {
+                for &room_type in &room_types {
+                    let rooms = timetable_info
+                        .rooms
+                        .iter()
+                        .filter(|room| {
+                            room_type.is_none() || room.room_type.as_deref() == room_type
+                        })
+                        .count();
+                    let capacity = slots_per_week * rooms;
+                    let lessons = lessons(room_type);
+                    if lessons > capacity && rooms_reported.insert(room_type) {
+                        issues.push(ValidationIssue::TooFewRooms {
+                            room_type: room_type.map(str::to_string),
+                            term,
+                            week,
+                            lessons,
+                            capacity,
+                        });
+                    }
+                }
+            }
+            if !timetable_info.teachers.is_empty()
+                && all_lessons > teacher_capacity
+                && !teachers_reported
+            {
+                teachers_reported = true;
+                issues.push(ValidationIssue::TooFewTeachers {
+                    term,
+                    week,
+                    lessons: all_lessons,
+                    capacity: teacher_capacity,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate, ValidationIssue};
+    use crate::timetabler::{
+        FixedEvent, RoomInfo, StudentId, StudentInfo, SubjectInfo, TeacherInfo, TimetableInfo,
+    };
+
+    /// Two lessons a day, with Ann taking Maths and Art and Bob taking Maths.
+    fn school() -> TimetableInfo {
+        let mut info = TimetableInfo::new(1, 2);
+        info.add_student("Ann", &["Maths", "Art"]);
+        info.add_student("Bob", &["Maths"]);
+        info
+    }
+
+    fn student(id: &str) -> StudentId {
+        StudentId::new(id)
+    }
+
+    #[test]
+    fn a_solvable_timetable_has_no_issues() {
+        assert_eq!(validate(&school()), []);
+    }
+
+    #[test]
+    fn settings_leaving_no_room_for_lessons_are_reported() {
+        let mut info = school();
+        info.terms = 0;
+        assert_eq!(validate(&info), [ValidationIssue::NoSlots]);
+
+        let mut info = school();
+        info.max_groups = 0;
+        assert_eq!(validate(&info), [ValidationIssue::NoGroups]);
+
+        let mut info = school();
+        info.max_groups_per_slot = Some(0);
+        assert_eq!(
+            validate(&info),
+            [
+                ValidationIssue::NoGroupsPerSlot,
+                ValidationIssue::TooFewGroupsAtOnce {
+                    term: 0,
+                    week: 0,
+                    lessons: 2,
+                    capacity: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn ids_used_twice_are_reported_once() {
+        let mut info = school();
+        info.add_student("Ann", &["Art"]);
+        info.add_student("Ann", &["Art"]);
+        info.rooms = vec![RoomInfo::new("R1", None), RoomInfo::new("R1", None)];
+        let maths = info.subjects.id("Maths").unwrap();
+        let art = info.subjects.id("Art").unwrap();
+        info.teachers = vec![
+            TeacherInfo::new("T1", vec![maths, art]),
+            TeacherInfo::new("T1", vec![maths, art]),
+        ];
+
+        assert_eq!(
+            validate(&info),
+            [
+                ValidationIssue::DuplicateStudentId("Ann".to_string()),
+                ValidationIssue::DuplicateRoomId("R1".to_string()),
+                ValidationIssue::DuplicateTeacherId("T1".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn subjects_which_cant_be_taught_are_reported() {
+        let mut info = school();
+        let blank = info.subjects.insert(SubjectInfo::new(" "));
+        let maths = info.subjects.id("Maths").unwrap();
+        let art = info.subjects.id("Art").unwrap();
+        info.students
+            .push(StudentInfo::new("Cat", vec![maths, maths, blank]));
+        info.subjects[art].term = Some(1);
+        info.subjects[art].site = Some("South".to_string());
+        info.rooms = vec![RoomInfo::new("R1", None)];
+        let mut teacher = TeacherInfo::new("T1", vec![maths]);
+        teacher.days_off = (0..5).collect();
+        info.teachers = vec![teacher, TeacherInfo::new("T2", vec![art, blank])];
+
+        assert_eq!(
+            validate(&info),
+            [
+                ValidationIssue::NoRoomAtSite {
+                    subject: "Art".to_string(),
+                    site: "South".to_string(),
+                },
+                ValidationIssue::TermOutOfRange {
+                    subject: "Art".to_string(),
+                    term: 1,
+                },
+                ValidationIssue::EmptySubjectName { student_id: None },
+                ValidationIssue::NoTeacherWorking {
+                    subject: "Maths".to_string(),
+                },
+                ValidationIssue::EmptySubjectName {
+                    student_id: Some(student("Cat")),
+                },
+                ValidationIssue::DuplicateSubject {
+                    student_id: student("Cat"),
+                    subject: "Maths".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn subjects_with_too_few_places_are_reported() {
+        let mut info = school();
+        info.max_group_size = Some(1);
+
+        assert_eq!(
+            validate(&info),
+            [ValidationIssue::TooFewPlaces {
+                subject: "Maths".to_string(),
+                students: 2,
+                places: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn events_past_the_end_of_the_cycle_are_reported() {
+        let mut info = school();
+        info.fixed_events = vec![FixedEvent::new("Assembly", vec![0, 10])];
+
+        assert_eq!(
+            validate(&info),
+            [ValidationIssue::EventSlotOutOfRange {
+                event: "Assembly".to_string(),
+                slot: 10,
+            }]
+        );
+    }
+
+    #[test]
+    fn weeks_with_too_many_or_too_few_lessons_are_reported() {
+        let mut info = school();
+        let maths = info.subjects.id("Maths").unwrap();
+        info.subjects[maths].lessons_per_week = [10, 10];
+        assert_eq!(
+            validate(&info),
+            [ValidationIssue::TooManyLessons {
+                student_id: student("Ann"),
+                term: 0,
+                week: 0,
+                lessons: 11,
+                slots: 10,
+            }]
+        );
+
+        let mut info = school();
+        info.daily_lessons.hard_min = 1;
+        assert_eq!(
+            validate(&info),
+            [
+                ValidationIssue::TooFewLessons {
+                    student_id: student("Ann"),
+                    term: 0,
+                    week: 0,
+                    lessons: 2,
+                    needed: 5,
+                },
+                ValidationIssue::TooFewLessons {
+                    student_id: student("Bob"),
+                    term: 0,
+                    week: 0,
+                    lessons: 1,
+                    needed: 5,
+                },
+            ]
+        );
+
+        let mut info = school();
+        info.daily_lessons.hard_min = 2;
+        info.daily_lessons.hard_max = Some(1);
+        assert_eq!(
+            validate(&info)[0],
+            ValidationIssue::DailyBoundsCross {
+                hard_min: 2,
+                hard_max: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn too_few_rooms_or_teachers_for_a_week_are_reported() {
+        let mut info = school();
+        let maths = info.subjects.id("Maths").unwrap();
+        let art = info.subjects.id("Art").unwrap();
+        info.subjects[art].room_type = Some("Studio".to_string());
+        info.rooms = vec![RoomInfo::new("R1", None)];
+        let mut teacher = TeacherInfo::new("T1", vec![maths, art]);
+        teacher.max_weekly_lessons = Some(1);
+        info.teachers = vec![teacher];
+
+        assert_eq!(
+            validate(&info),
+            [
+                ValidationIssue::TooFewRooms {
+                    room_type: Some("Studio".to_string()),
+                    term: 0,
+                    week: 0,
+                    lessons: 1,
+                    capacity: 0,
+                },
+                ValidationIssue::TooFewTeachers {
+                    term: 0,
+                    week: 0,
+                    lessons: 2,
+                    capacity: 1,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,1253 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use super::{
+    first_short_day, StudentContext, StudentId, SubjectId, TimetableInfo, TimetableResult,
+};
+
+/// A way in which a timetable breaks the rules given by its `TimetableInfo`.
+/// Slots count across the whole year, as in `TimetableResult::Solved`, and
+/// weeks count across the whole year too.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// The result is unsolved, so there is no timetable to check.
+    NotSolved,
+    /// A student's timetable, or the list of subjects by slot when there is
+    /// no student, doesn't have an entry for every slot of the year.
+    WrongSlotCount {
+        student_id: Option<StudentId>,
+        slots: usize,
+        expected: usize,
+    },
+    /// A student in the `TimetableInfo` has no timetable.
+    MissingStudent(StudentId),
+    /// A timetable belongs to a student who isn't in the `TimetableInfo`.
+    UnknownStudent(StudentId),
+    /// A student has the wrong number of lessons of a subject in a week,
+    /// including lessons of subjects they don't take.
+    WrongLessonCount {
+        student_id: StudentId,
+        subject: String,
+        week: usize,
+        lessons: usize,
+        expected: usize,
+    },
+    /// The students of a group don't all have its lessons in the same slots.
+    GroupSlotsDiffer {
+        subject: String,
+        group_idx: usize,
+    },
+    /// A student has a lesson in a slot which doesn't list its subject.
+    UnlistedLesson {
+        subject: String,
+        slot: usize,
+    },
+    /// More than one group of a subject is taught in the same slot.
+    SameSubjectClash {
+        subject: String,
+        slot: usize,
+    },
+    TooManyGroups {
+        subject: String,
+        groups: usize,
+        max: u8,
+    },
+    TooManyGroupsInSlot {
+        slot: usize,
+        groups: usize,
+        max: u8,
+    },
+    /// A group has more students than `TimetableInfo::max_group_size`
+    /// allows.
+    GroupTooBig {
+        subject: String,
+        group_idx: usize,
+        students: usize,
+        max: usize,
+    },
+    /// A lesson is in a slot taken by one of `TimetableInfo::fixed_events`
+    /// which the student attends.
+    LessonInEvent {
+        student_id: StudentId,
+        subject: String,
+        event: String,
+        slot: usize,
+    },
+    /// A lesson breaks one of `TimetableInfo::constraints`.
+    ConstraintBroken {
+        student_id: StudentId,
+        subject: String,
+        slot: usize,
+    },
+    NoRoom {
+        subject: String,
+        slot: usize,
+    },
+    UnknownRoom(String),
+    /// A subject is in a room of a different type to the one it needs.
+    WrongRoomType {
+        room_id: String,
+        subject: String,
+        slot: usize,
+    },
+    /// A subject is in a room at a different site to the one it is taught
+    /// at.
+    WrongRoomSite {
+        room_id: String,
+        subject: String,
+        slot: usize,
+    },
+    RoomDoubleBooked {
+        room_id: String,
+        slot: usize,
+    },
+    /// A group is taught in rooms at more than one site.
+    GroupAcrossSites {
+        subject: String,
+        group_idx: usize,
+    },
+    /// A student has lessons in `slot` and `other_slot` in rooms at sites too
+    /// far apart to travel between.
+    StudentCannotTravel {
+        student_id: StudentId,
+        slot: usize,
+        other_slot: usize,
+    },
+    NoTeacher {
+        subject: String,
+        slot: usize,
+    },
+    UnknownTeacher(String),
+    /// A teacher was given a subject they can't teach.
+    CannotTeach {
+        teacher_id: String,
+        subject: String,
+    },
+    TeacherDoubleBooked {
+        teacher_id: String,
+        slot: usize,
+    },
+    /// A teacher has a lesson in `slot`, which is on one of their days off.
+    TeacherOnDayOff {
+        teacher_id: String,
+        slot: usize,
+    },
+    /// A group is taught by more than one teacher.
+    TeacherChanges {
+        subject: String,
+        group_idx: usize,
+    },
+    TeacherOverloaded {
+        teacher_id: String,
+        week: usize,
+        lessons: usize,
+        max: u8,
+    },
+    /// A teacher has too many lessons back to back, in the run ending at
+    /// `slot`.
+    TooManyConsecutiveLessons {
+        teacher_id: String,
+        slot: usize,
+        lessons: usize,
+        max: u8,
+    },
+    /// A teacher has lessons in `slot` and `other_slot` at sites too far apart
+    /// to travel between.
+    TeacherCannotTravel {
+        teacher_id: String,
+        slot: usize,
+        other_slot: usize,
+    },
+    /// A student has fewer lessons on the day starting at `slot` than
+    /// `DailyLessonBounds::hard_min` allows.
+    TooFewDailyLessons {
+        student_id: StudentId,
+        slot: usize,
+        lessons: usize,
+        min: u8,
+    },
+    /// A student has more lessons on the day starting at `slot` than
+    /// `DailyLessonBounds::hard_max` allows.
+    TooManyDailyLessons {
+        student_id: StudentId,
+        slot: usize,
+        lessons: usize,
+        max: u8,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::NotSolved => write!(f, "There is no timetable to check"),
+            Violation::WrongSlotCount {
+                student_id: Some(student_id),
+                slots,
+                expected,
+            } => write!(
+                f,
+                "{} has {} slots rather than {}",
+                student_id, slots, expected
+            ),
+            Violation::WrongSlotCount {
+                student_id: None,
+                slots,
+                expected,
+            } => write!(
+                f,
+                "The timetable has {} slots rather than {}",
+                slots, expected
+            ),
+            Violation::MissingStudent(id) => write!(f, "{} has no timetable", id),
+            Violation::UnknownStudent(id) => {
+                write!(f, "{} has a timetable but isn't a student", id)
+            }
+            Violation::WrongLessonCount {
+                student_id,
+                subject,
+                week,
+                lessons,
+                expected,
+            } => write!(
+                f,
+                "{} has {} lessons of {} in week {} rather than {}",
+                student_id,
+                lessons,
+                subject,
+                week + 1,
+                expected
+            ),
+            Violation::GroupSlotsDiffer { subject, group_idx } => write!(
+                f,
+                "The students of {} don't all have its lessons at the same times",
+                super::group_name(subject, *group_idx)
+            ),
+            Violation::UnlistedLesson { subject, slot } => write!(
+                f,
+                "{} is taught in slot {} but isn't listed in it",
+                subject, slot
+            ),
+            Violation::SameSubjectClash { subject, slot } => write!(
+                f,
+                "More than one group of {} is taught in slot {}",
+                subject, slot
+            ),
+            Violation::TooManyGroups {
+                subject,
+                groups,
+                max,
+            } => write!(
+                f,
+                "{} has {} groups but may only have {}",
+                subject, groups, max
+            ),
+            Violation::TooManyGroupsInSlot { slot, groups, max } => write!(
+                f,
+                "Slot {} has {} groups but may only have {}",
+                slot, groups, max
+            ),
+            Violation::GroupTooBig {
+                subject,
+                group_idx,
+                students,
+                max,
+            } => write!(
+                f,
+                "{} has {} students but may only have {}",
+                super::group_name(subject, *group_idx),
+                students,
+                max
+            ),
+            Violation::LessonInEvent {
+                student_id,
+                subject,
+                event,
+                slot,
+            } => write!(
+                f,
+                "{}'s lesson of {} in slot {} is during {}",
+                student_id, subject, slot, event
+            ),
+            Violation::ConstraintBroken {
+                student_id,
+                subject,
+                slot,
+            } => write!(
+                f,
+                "{}'s lesson of {} in slot {} breaks a constraint",
+                student_id, subject, slot
+            ),
+            Violation::NoRoom { subject, slot } => {
+                write!(f, "{} has no room in slot {}", subject, slot)
+            }
+            Violation::UnknownRoom(id) => write!(f, "There is no room called {}", id),
+            Violation::WrongRoomType {
+                room_id,
+                subject,
+                slot,
+            } => write!(
+                f,
+                "{} is taught in {} in slot {}, which is the wrong type of room",
+                subject, room_id, slot
+            ),
+            Violation::WrongRoomSite {
+                room_id,
+                subject,
+                slot,
+            } => write!(
+                f,
+                "{} is taught in {} in slot {}, which is at a different site",
+                subject, room_id, slot
+            ),
+            Violation::RoomDoubleBooked { room_id, slot } => {
+                write!(f, "{} is used twice in slot {}", room_id, slot)
+            }
+            Violation::GroupAcrossSites { subject, group_idx } => write!(
+                f,
+                "{} is taught at more than one site",
+                super::group_name(subject, *group_idx)
+            ),
+            Violation::StudentCannotTravel {
+                student_id,
+                slot,
+                other_slot,
+            } => write!(
+                f,
+                "{} can't get between sites for slots {} and {}",
+                student_id, slot, other_slot
+            ),
+            Violation::NoTeacher { subject, slot } => {
+                write!(f, "{} has no teacher in slot {}", subject, slot)
+            }
+            Violation::UnknownTeacher(id) => write!(f, "There is no teacher called {}", id),
+            Violation::CannotTeach {
+                teacher_id,
+                subject,
+            } => write!(f, "{} can't teach {}", teacher_id, subject),
+            Violation::TeacherDoubleBooked { teacher_id, slot } => {
+                write!(f, "{} teaches twice in slot {}", teacher_id, slot)
+            }
+            Violation::TeacherOnDayOff { teacher_id, slot } => {
+                write!(f, "{} teaches in slot {} on a day off", teacher_id, slot)
+            }
+            Violation::TeacherChanges { subject, group_idx } => write!(
+                f,
+                "{} is taught by more than one teacher",
+                super::group_name(subject, *group_idx)
+            ),
+            Violation::TeacherOverloaded {
+                teacher_id,
+                week,
+                lessons,
+                max,
+            } => write!(
+                f,
+                "{} has {} lessons in week {} but may only have {}",
+                teacher_id,
+                lessons,
+                week + 1,
+                max
+            ),
+            Violation::TooManyConsecutiveLessons {
+                teacher_id,
+                slot,
+                lessons,
+                max,
+            } => write!(
+                f,
+                "{} has {} lessons in a row up to slot {} but may only have {}",
+                teacher_id, lessons, slot, max
+            ),
+            Violation::TeacherCannotTravel {
+                teacher_id,
+                slot,
+                other_slot,
+            } => write!(
+                f,
+                "{} can't get between sites for slots {} and {}",
+                teacher_id, slot, other_slot
+            ),
+            Violation::TooFewDailyLessons {
+                student_id,
+                slot,
+                lessons,
+                min,
+            } => write!(
+                f,
+                "{} only has {} lessons on the day starting at slot {} but must have {}",
+                student_id, lessons, slot, min
+            ),
+            Violation::TooManyDailyLessons {
+                student_id,
+                slot,
+                lessons,
+                max,
+            } => write!(
+                f,
+                "{} has {} lessons on the day starting at slot {} but may only have {}",
+                student_id, lessons, slot, max
+            ),
+        }
+    }
+}
+
+impl Violation {
+    /// The slot the violation happens in, for those which happen in a
+    /// particular slot.
+    pub fn slot(&self) -> Option<usize> {
+        match self {
+            Violation::UnlistedLesson { slot, .. }
+            | Violation::SameSubjectClash { slot, .. }
+            | Violation::TooManyGroupsInSlot { slot, .. }
+            | Violation::LessonInEvent { slot, .. }
+            | Violation::ConstraintBroken { slot, .. }
+            | Violation::NoRoom { slot, .. }
+            | Violation::WrongRoomType { slot, .. }
+            | Violation::WrongRoomSite { slot, .. }
+            | Violation::RoomDoubleBooked { slot, .. }
+            | Violation::StudentCannotTravel { slot, .. }
+            | Violation::NoTeacher { slot, .. }
+            | Violation::TeacherDoubleBooked { slot, .. }
+            | Violation::TeacherOnDayOff { slot, .. }
+            | Violation::TooManyConsecutiveLessons { slot, .. }
+            | Violation::TeacherCannotTravel { slot, .. }
+            | Violation::TooFewDailyLessons { slot, .. }
+            | Violation::TooManyDailyLessons { slot, .. } => Some(*slot),
+            _ => None,
+        }
+    }
+}
+
+/// Checks a timetable, which may have been made by hand or by another tool,
+/// against every rule of `timetable_info`, listing everything it breaks. The
+/// timetable is given in the same form the solver returns.
+pub fn verify(timetable_info: &TimetableInfo, result: &TimetableResult) -> Vec<Violation> {
+    let (subjects, slots_by_student_id, rooms_by_slot, teachers_by_slot) = match result {
+        TimetableResult::Solved {
+            subjects,
+            slots_by_student_id,
+            rooms_by_slot,
+            teachers_by_slot,
+            ..
+        } => (
+            subjects,
+            slots_by_student_id,
+            rooms_by_slot,
+            teachers_by_slot,
+        ),
+        TimetableResult::Unsolved(_) => return vec![Violation::NotSolved],
+    };
+
+    let mut violations = Vec::new();
+    let slots_per_year = timetable_info.slots_per_year();
+    if subjects.len() != slots_per_year {
+        violations.push(Violation::WrongSlotCount {
+            student_id: None,
+            slots: subjects.len(),
+            expected: slots_per_year,
+        });
+    }
+
+    for student in &timetable_info.students {
+        if !slots_by_student_id.contains_key(&student.id) {
+            violations.push(Violation::MissingStudent(student.id.clone()));
+        }
+    }
+
+    let name = |subject: SubjectId| timetable_info.subjects.name(subject).to_string();
+    let slots_per_week = timetable_info.slots_per_week();
+    let daily_lesson_capacity = usize::from(timetable_info.daily_lesson_capacity);
+    // The slots of each group, as seen by each of its students.
+    let mut slots_by_group: BTreeMap<(SubjectId, usize), Vec<BTreeSet<usize>>> = BTreeMap::new();
+    for (student_id, slots) in slots_by_student_id {
+        let student_info = match timetable_info
+            .students
+            .iter()
+            .find(|student| student.id == *student_id)
+        {
+            Some(student_info) => student_info,
+            None => {
+                violations.push(Violation::UnknownStudent(student_id.clone()));
+                continue;
+            }
+        };
+        if slots.len() != slots_per_year {
+            violations.push(Violation::WrongSlotCount {
+                student_id: Some(student_id.clone()),
+                slots: slots.len(),
+                expected: slots_per_year,
+            });
+            continue;
+        }
+
+        let mut group_slots: BTreeMap<(SubjectId, usize), BTreeSet<usize>> = BTreeMap::new();
+        for (slot, lesson) in slots.iter().enumerate() {
+            if let Some((subject, group_idx)) = *lesson {
+                group_slots
+                    .entry((subject, group_idx))
+                    .or_default()
+                    .insert(slot);
+                if subjects
+                    .get(slot)
+                    .map_or(false, |listed| !listed.contains(&subject))
+                {
+                    violations.push(Violation::UnlistedLesson {
+                        subject: name(subject),
+                        slot,
+                    });
+                }
+            }
+        }
+        for (group, slots) in group_slots {
+            slots_by_group.entry(group).or_default().push(slots);
+        }
+
+        for (week, week_slots) in slots.chunks(slots_per_week).enumerate() {
+            let term = week * slots_per_week / timetable_info.total_slots();
+            let week_of_cycle = week % timetable_info.cycle.weeks();
+            let mut lessons: BTreeMap<SubjectId, usize> = BTreeMap::new();
+            for &subject in &student_info.subjects {
+                lessons.entry(subject).or_insert(0);
+            }
+            for &(subject, _) in week_slots.iter().flatten() {
+                *lessons.entry(subject).or_insert(0) += 1;
+            }
+            for (subject, lessons) in lessons {
+                let expected = if student_info.subjects.contains(&subject)
+                    && timetable_info.terms_of(subject).contains(&term)
+                {
+                    usize::from(timetable_info.lessons_in_week(subject, week_of_cycle))
+                } else {
+                    0
+                };
+                if lessons != expected {
+                    violations.push(Violation::WrongLessonCount {
+                        student_id: student_id.clone(),
+                        subject: name(subject),
+                        week,
+                        lessons,
+                        expected,
+                    });
+                }
+            }
+        }
+
+        if let Some((slot, lessons)) = first_short_day(slots, student_info.cohort(), timetable_info)
+        {
+            violations.push(Violation::TooFewDailyLessons {
+                student_id: student_id.clone(),
+                slot,
+                lessons,
+                min: timetable_info.daily_lessons.hard_min,
+            });
+        }
+        if let Some(max) = timetable_info.daily_lessons.hard_max {
+            for (day, day_slots) in slots.chunks(daily_lesson_capacity).enumerate() {
+                let lessons = day_slots.iter().flatten().count();
+                if lessons > usize::from(max) {
+                    violations.push(Violation::TooManyDailyLessons {
+                        student_id: student_id.clone(),
+                        slot: day * daily_lesson_capacity,
+                        lessons,
+                        max,
+                    });
+                }
+            }
+        }
+
+        // The built-in rules are checked here and below rather than with the
+        // constraints the solver places lessons with, so a mistake in one of
+        // those doesn't hide the lessons which break its rule. Only the
+        // caller's own constraints are asked, with the student's other
+        // lessons in place.
+        let mut trial = slots.clone();
+        for (slot, lesson) in slots.iter().enumerate() {
+            if let Some((subject, _)) = *lesson {
+                let event = timetable_info.fixed_events.iter().find(|event| {
+                    event.slots.contains(&(slot % timetable_info.total_slots()))
+                        && event.is_attended_by(student_info.cohort())
+                });
+                if let Some(event) = event {
+                    violations.push(Violation::LessonInEvent {
+                        student_id: student_id.clone(),
+                        subject: name(subject),
+                        event: event.name.clone(),
+                        slot,
+                    });
+                }
+
+                let taken = trial[slot].take();
+                let student = StudentContext {
+                    slots: &trial,
+                    cohort: student_info.cohort(),
+                };
+                let placement = timetable_info.placement(subject, slot, Some(student), None);
+                if !timetable_info
+                    .constraints
+                    .iter()
+                    .all(|constraint| constraint.check(&placement))
+                {
+                    violations.push(Violation::ConstraintBroken {
+                        student_id: student_id.clone(),
+                        subject: name(subject),
+                        slot,
+                    });
+                }
+                trial[slot] = taken;
+            }
+        }
+    }
+
+    // Every student of a group should see it in the same slots, so the group
+    // is taught in all of them.
+    let mut groups_by_slot: Vec<BTreeSet<(SubjectId, usize)>> =
+        vec![BTreeSet::new(); slots_per_year];
+    let mut group_counts: BTreeMap<SubjectId, usize> = BTreeMap::new();
+    for (&(subject, group_idx), student_slots) in &slots_by_group {
+        if student_slots.windows(2).any(|pair| pair[0] != pair[1]) {
+            violations.push(Violation::GroupSlotsDiffer {
+                subject: name(subject),
+                group_idx,
+            });
+        }
+        if let Some(max) = timetable_info.max_group_size_for(subject) {
+            if student_slots.len() > max {
+                violations.push(Violation::GroupTooBig {
+                    subject: name(subject),
+                    group_idx,
+                    students: student_slots.len(),
+                    max,
+                });
+            }
+        }
+        for &slot in student_slots.iter().flatten() {
+            groups_by_slot[slot].insert((subject, group_idx));
+        }
+        *group_counts.entry(subject).or_insert(0) += 1;
+    }
+    for (subject, groups) in group_counts {
+        let max = timetable_info.max_groups_for(subject);
+        if groups > usize::from(max) {
+            violations.push(Violation::TooManyGroups {
+                subject: name(subject),
+                groups,
+                max,
+            });
+        }
+    }
+    for (slot, groups) in groups_by_slot.iter().enumerate() {
+        let mut seen = BTreeSet::new();
+        let mut clashes = BTreeSet::new();
+        for &(subject, _) in groups {
+            if !seen.insert(subject) {
+                clashes.insert(subject);
+            }
+        }
+        for subject in clashes {
+            violations.push(Violation::SameSubjectClash {
+                subject: name(subject),
+                slot,
+            });
+        }
+        if let Some(max) = timetable_info.max_groups_per_slot {
+            if groups.len() > usize::from(max) {
+                violations.push(Violation::TooManyGroupsInSlot {
+                    slot,
+                    groups: groups.len(),
+                    max,
+                });
+            }
+        }
+    }
+
+    if !timetable_info.rooms.is_empty() {
+        verify_rooms(
+            timetable_info,
+            &groups_by_slot,
+            rooms_by_slot,
+            &mut violations,
+        );
+    }
+    verify_student_travel(
+        timetable_info,
+        slots_by_student_id,
+        rooms_by_slot,
+        &mut violations,
+    );
+    if !timetable_info.teachers.is_empty() {
+        verify_teachers(
+            timetable_info,
+            &groups_by_slot,
+            rooms_by_slot,
+            teachers_by_slot,
+            &mut violations,
+        );
+    }
+
+    violations
+}
+
+fn verify_rooms(
+    timetable_info: &TimetableInfo,
+    groups_by_slot: &[BTreeSet<(SubjectId, usize)>],
+    rooms_by_slot: &[BTreeMap<SubjectId, String>],
+    violations: &mut Vec<Violation>,
+) {
+    let name = |subject: SubjectId| timetable_info.subjects.name(subject).to_string();
+    let mut unknown_rooms = BTreeSet::new();
+    let mut sites_by_group: BTreeMap<(SubjectId, usize), BTreeSet<Option<&str>>> = BTreeMap::new();
+    for (slot, groups) in groups_by_slot.iter().enumerate() {
+        let rooms = rooms_by_slot.get(slot);
+        let mut used = BTreeSet::new();
+        for &(subject, group_idx) in groups {
+            let room_id = match rooms.and_then(|rooms| rooms.get(&subject)) {
+                Some(room_id) => room_id,
+                None => {
+                    violations.push(Violation::NoRoom {
+                        subject: name(subject),
+                        slot,
+                    });
+                    continue;
+                }
+            };
+            if !used.insert(room_id) {
+                violations.push(Violation::RoomDoubleBooked {
+                    room_id: room_id.clone(),
+                    slot,
+                });
+            }
+            match timetable_info.rooms.iter().find(|room| room.id == *room_id) {
+                Some(room) => {
+                    sites_by_group
+                        .entry((subject, group_idx))
+                        .or_default()
+                        .insert(room.site.as_deref());
+                    let required_type = timetable_info.subjects[subject].room_type.as_ref();
+                    if required_type.is_some() && room.room_type.as_ref() != required_type {
+                        violations.push(Violation::WrongRoomType {
+                            room_id: room_id.clone(),
+                            subject: name(subject),
+                            slot,
+                        });
+                    }
+                    let site = timetable_info.site_of(subject);
+                    if site.is_some() && room.site.as_deref() != site {
+                        violations.push(Violation::WrongRoomSite {
+                            room_id: room_id.clone(),
+                            subject: name(subject),
+                            slot,
+                        });
+                    }
+                }
+                None => {
+                    if unknown_rooms.insert(room_id) {
+                        violations.push(Violation::UnknownRoom(room_id.clone()));
+                    }
+                }
+            }
+        }
+    }
+    for ((subject, group_idx), sites) in sites_by_group {
+        if sites.len() > 1 {
+            violations.push(Violation::GroupAcrossSites {
+                subject: name(subject),
+                group_idx,
+            });
+        }
+    }
+}
+
+/// The site of the lesson of `subject` in `slot`, from the room it is in.
+fn lesson_site<'a>(
+    timetable_info: &'a TimetableInfo,
+    rooms_by_slot: &'a [BTreeMap<SubjectId, String>],
+    subject: SubjectId,
+    slot: usize,
+) -> Option<&'a str> {
+    let room_id = rooms_by_slot
+        .get(slot)
+        .and_then(|rooms| rooms.get(&subject));
+    timetable_info.site_of_lesson(subject, room_id.map(String::as_str))
+}
+
+/// Checks students can get between the sites of their lessons, which are
+/// those of the rooms they are in or else of their subjects.
+fn verify_student_travel(
+    timetable_info: &TimetableInfo,
+    slots_by_student_id: &BTreeMap<StudentId, Vec<Option<(SubjectId, usize)>>>,
+    rooms_by_slot: &[BTreeMap<SubjectId, String>],
+    violations: &mut Vec<Violation>,
+) {
+    let daily_lesson_capacity = usize::from(timetable_info.daily_lesson_capacity);
+    for (student_id, slots) in slots_by_student_id {
+        for (slot, lesson) in slots.iter().enumerate() {
+            let subject = match *lesson {
+                Some((subject, _)) => subject,
+                None => continue,
+            };
+            let day_end = slot - slot % daily_lesson_capacity + daily_lesson_capacity;
+            for (other_slot, other) in slots.iter().enumerate().take(day_end).skip(slot + 1) {
+                if let Some((other, _)) = *other {
+                    if !timetable_info.can_travel_between_sites(
+                        (
+                            lesson_site(timetable_info, rooms_by_slot, subject, slot),
+                            slot,
+                        ),
+                        (
+                            lesson_site(timetable_info, rooms_by_slot, other, other_slot),
+                            other_slot,
+                        ),
+                    ) {
+                        violations.push(Violation::StudentCannotTravel {
+                            student_id: student_id.clone(),
+                            slot,
+                            other_slot,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn verify_teachers(
+    timetable_info: &TimetableInfo,
+    groups_by_slot: &[BTreeSet<(SubjectId, usize)>],
+    rooms_by_slot: &[BTreeMap<SubjectId, String>],
+    teachers_by_slot: &[BTreeMap<SubjectId, String>],
+    violations: &mut Vec<Violation>,
+) {
+    let name = |subject: SubjectId| timetable_info.subjects.name(subject).to_string();
+    let teachers = &timetable_info.teachers;
+    let mut unknown_teachers = BTreeSet::new();
+    let mut reported_subjects = BTreeSet::new();
+    let mut teachers_by_group: BTreeMap<(SubjectId, usize), BTreeSet<&String>> = BTreeMap::new();
+    // What each teacher is teaching in each slot.
+    let mut taught: Vec<Vec<Option<SubjectId>>> =
+        vec![vec![None; groups_by_slot.len()]; teachers.len()];
+    for (slot, groups) in groups_by_slot.iter().enumerate() {
+        let slot_teachers = teachers_by_slot.get(slot);
+        for &(subject, group_idx) in groups {
+            let teacher_id = match slot_teachers.and_then(|teachers| teachers.get(&subject)) {
+                Some(teacher_id) => teacher_id,
+                None => {
+                    violations.push(Violation::NoTeacher {
+                        subject: name(subject),
+                        slot,
+                    });
+                    continue;
+                }
+            };
+            teachers_by_group
+                .entry((subject, group_idx))
+                .or_default()
+                .insert(teacher_id);
+            let teacher_idx = match teachers
+                .iter()
+                .position(|teacher| teacher.id == *teacher_id)
+            {
+                Some(teacher_idx) => teacher_idx,
+                None => {
+                    if unknown_teachers.insert(teacher_id) {
+                        violations.push(Violation::UnknownTeacher(teacher_id.clone()));
+                    }
+                    continue;
+                }
+            };
+            if !teachers[teacher_idx].can_teach(subject)
+                && reported_subjects.insert((teacher_id, subject))
+            {
+                violations.push(Violation::CannotTeach {
+                    teacher_id: teacher_id.clone(),
+                    subject: name(subject),
+                });
+            }
+            if taught[teacher_idx][slot].is_some() {
+                violations.push(Violation::TeacherDoubleBooked {
+                    teacher_id: teacher_id.clone(),
+                    slot,
+                });
+            }
+            if teachers[teacher_idx].is_off(slot, timetable_info.daily_lesson_capacity) {
+                violations.push(Violation::TeacherOnDayOff {
+                    teacher_id: teacher_id.clone(),
+                    slot,
+                });
+            }
+            taught[teacher_idx][slot] = Some(subject);
+        }
+    }
+
+    for ((subject, group_idx), group_teachers) in teachers_by_group {
+        if group_teachers.len() > 1 {
+            violations.push(Violation::TeacherChanges {
+                subject: name(subject),
+                group_idx,
+            });
+        }
+    }
+
+    let daily_lesson_capacity = usize::from(timetable_info.daily_lesson_capacity);
+    for (teacher, taught) in teachers.iter().zip(taught) {
+        let is_busy: Vec<bool> = taught.iter().map(Option::is_some).collect();
+        if let Some(max) = teacher.max_weekly_lessons {
+            for (week, week_slots) in is_busy.chunks(timetable_info.slots_per_week()).enumerate() {
+                let lessons = week_slots.iter().filter(|&&is_busy| is_busy).count();
+                if lessons > usize::from(max) {
+                    violations.push(Violation::TeacherOverloaded {
+                        teacher_id: teacher.id.clone(),
+                        week,
+                        lessons,
+                        max,
+                    });
+                }
+            }
+        }
+        if let Some(max) = teacher
+            .max_consecutive_lessons
+            .or(timetable_info.max_consecutive_lessons)
+        {
+            for (day, day_slots) in is_busy.chunks(daily_lesson_capacity).enumerate() {
+                let mut run = 0;
+                for (period, &is_busy) in day_slots.iter().enumerate() {
+                    run = if is_busy { run + 1 } else { 0 };
+                    // Each run is reported once, where it ends.
+                    let run_ends = !day_slots.get(period + 1).copied().unwrap_or(false);
+                    if run > usize::from(max) && run_ends {
+                        violations.push(Violation::TooManyConsecutiveLessons {
+                            teacher_id: teacher.id.clone(),
+                            slot: day * daily_lesson_capacity + period,
+                            lessons: run,
+                            max,
+                        });
+                    }
+                }
+            }
+        }
+
+        // Each pair of lessons on the same day is checked once.
+        for (slot, &subject) in taught.iter().enumerate() {
+            let subject = match subject {
+                Some(subject) => subject,
+                None => continue,
+            };
+            let day_end = slot - slot % daily_lesson_capacity + daily_lesson_capacity;
+            for (other_slot, &other) in taught.iter().enumerate().take(day_end).skip(slot + 1) {
+                if let Some(other) = other {
+                    if !timetable_info.can_travel_between_sites(
+                        (
+                            lesson_site(timetable_info, rooms_by_slot, subject, slot),
+                            slot,
+                        ),
+                        (
+                            lesson_site(timetable_info, rooms_by_slot, other, other_slot),
+                            other_slot,
+                        ),
+                    ) {
+                        violations.push(Violation::TeacherCannotTravel {
+                            teacher_id: teacher.id.clone(),
+                            slot,
+                            other_slot,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+
+    use super::{verify, Violation};
+    use crate::timetabler::{
+        group_name, solve_timetable, Constraint, FixedEvent, Metrics, Placement, RoomInfo,
+        SiteTravel, SolveStats, StudentId, TeacherInfo, TimetableInfo, TimetableResult,
+    };
+
+    /// Ann takes Maths and Art and Bob takes Maths, with two lessons a day.
+    fn school() -> TimetableInfo {
+        let mut info = TimetableInfo::new(1, 2);
+        info.add_student("Ann", &["Maths", "Art"]);
+        info.add_student("Bob", &["Maths"]);
+        info
+    }
+
+    /// Maths on Monday morning and Art on Tuesday morning, which keeps to
+    /// every rule of `school`.
+    const LESSONS: [(&str, usize, &str, usize); 3] = [
+        ("Ann", 0, "Maths", 0),
+        ("Ann", 2, "Art", 0),
+        ("Bob", 0, "Maths", 0),
+    ];
+
+    /// A timetable giving each student the lessons in `lessons`, as
+    /// (student, slot, subject, group), without rooms or teachers.
+    fn timetable(info: &TimetableInfo, lessons: &[(&str, usize, &str, usize)]) -> TimetableResult {
+        let slots_per_year = info.slots_per_year();
+        let mut subjects = vec![Vec::new(); slots_per_year];
+        let mut slots_by_student_id = BTreeMap::new();
+        let mut group_names = BTreeMap::new();
+        for student in &info.students {
+            slots_by_student_id.insert(student.id.clone(), vec![None; slots_per_year]);
+        }
+        for &(student_id, slot, name, group_idx) in lessons {
+            let subject = info.subjects.id(name).unwrap();
+            let slots = slots_by_student_id
+                .entry(StudentId::from(student_id))
+                .or_insert_with(|| vec![None; slots_per_year]);
+            slots[slot] = Some((subject, group_idx));
+            if !subjects[slot].contains(&subject) {
+                subjects[slot].push(subject);
+            }
+            let names: &mut Vec<String> = group_names.entry(subject).or_default();
+            while names.len() <= group_idx {
+                names.push(group_name(name, names.len()));
+            }
+        }
+        TimetableResult::Solved {
+            subjects,
+            slots_by_student_id,
+            group_names,
+            rooms_by_slot: Vec::new(),
+            slots_by_room: BTreeMap::new(),
+            teachers_by_slot: Vec::new(),
+            slots_by_teacher_id: BTreeMap::new(),
+            metrics: Metrics::default(),
+            stats: SolveStats::default(),
+        }
+    }
+
+    #[test]
+    fn a_timetable_keeping_to_the_rules_has_no_violations() {
+        let info = school();
+        assert!(verify(&info, &timetable(&info, &LESSONS)).is_empty());
+    }
+
+    #[test]
+    fn a_solved_timetable_passes_until_it_is_changed() {
+        let mut info = school();
+        let subjects = info.subjects.ids().collect();
+        info.rooms = vec![RoomInfo::new("R1", None)];
+        info.teachers = vec![TeacherInfo::new("T1", subjects)];
+        let mut result = solve_timetable(&info);
+        assert!(verify(&info, &result).is_empty());
+
+        // Bob loses his lessons, and a lesson moves to a room which doesn't
+        // exist.
+        if let TimetableResult::Solved {
+            slots_by_student_id,
+            rooms_by_slot,
+            ..
+        } = &mut result
+        {
+            for lesson in slots_by_student_id
+                .get_mut(&StudentId::from("Bob"))
+                .unwrap()
+            {
+                *lesson = None;
+            }
+            let rooms = rooms_by_slot.iter_mut().find(|rooms| !rooms.is_empty());
+            for room_id in rooms.unwrap().values_mut() {
+                *room_id = "R9".to_string();
+            }
+        }
+        assert_eq!(
+            verify(&info, &result),
+            [
+                Violation::WrongLessonCount {
+                    student_id: StudentId::from("Bob"),
+                    subject: "Maths".to_string(),
+                    week: 0,
+                    lessons: 0,
+                    expected: 1,
+                },
+                Violation::UnknownRoom("R9".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_student_given_two_lessons_at_once_misses_one() {
+        let info = school();
+        // Ann's Art is put in with Maths, which both go on being taught.
+        let lessons = [
+            ("Ann", 0, "Maths", 0),
+            ("Ann", 0, "Art", 0),
+            ("Bob", 0, "Maths", 0),
+        ];
+        assert_eq!(
+            verify(&info, &timetable(&info, &lessons)),
+            [Violation::WrongLessonCount {
+                student_id: StudentId::from("Ann"),
+                subject: "Maths".to_string(),
+                week: 0,
+                lessons: 0,
+                expected: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn missing_subjects_and_students_are_found() {
+        let info = school();
+        assert_eq!(
+            verify(&info, &timetable(&info, &LESSONS[..2])),
+            [Violation::WrongLessonCount {
+                student_id: StudentId::from("Bob"),
+                subject: "Maths".to_string(),
+                week: 0,
+                lessons: 0,
+                expected: 1,
+            }]
+        );
+
+        let mut result = timetable(&info, &LESSONS);
+        if let TimetableResult::Solved {
+            slots_by_student_id,
+            ..
+        } = &mut result
+        {
+            slots_by_student_id.remove(&StudentId::from("Bob"));
+        }
+        assert_eq!(
+            verify(&info, &result),
+            [Violation::MissingStudent(StudentId::from("Bob"))]
+        );
+    }
+
+    #[test]
+    fn students_of_a_group_must_share_its_slots() {
+        let info = school();
+        let mut lessons = LESSONS;
+        lessons[2].1 = 1;
+        assert_eq!(
+            verify(&info, &timetable(&info, &lessons)),
+            [Violation::GroupSlotsDiffer {
+                subject: "Maths".to_string(),
+                group_idx: 0,
+            }]
+        );
+    }
+
+    #[test]
+    fn subjects_may_not_have_too_many_groups() {
+        let mut info = school();
+        let maths = info.subjects.id("Maths").unwrap();
+        info.subjects[maths].max_groups = Some(1);
+        let mut lessons = LESSONS;
+        lessons[2] = ("Bob", 1, "Maths", 1);
+        assert_eq!(
+            verify(&info, &timetable(&info, &lessons)),
+            [Violation::TooManyGroups {
+                subject: "Maths".to_string(),
+                groups: 2,
+                max: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn a_room_may_only_hold_one_group_at_once() {
+        let mut info = TimetableInfo::new(1, 2);
+        info.add_student("Ann", &["Maths"]);
+        info.add_student("Bob", &["Art"]);
+        info.rooms = vec![RoomInfo::new("R1", None), RoomInfo::new("R2", None)];
+        let mut result = timetable(&info, &[("Ann", 0, "Maths", 0), ("Bob", 0, "Art", 0)]);
+        if let TimetableResult::Solved {
+            rooms_by_slot,
+            subjects,
+            ..
+        } = &mut result
+        {
+            *rooms_by_slot = vec![BTreeMap::new(); subjects.len()];
+            for &subject in &subjects[0] {
+                rooms_by_slot[0].insert(subject, "R1".to_string());
+            }
+        }
+        assert_eq!(
+            verify(&info, &result),
+            [Violation::RoomDoubleBooked {
+                room_id: "R1".to_string(),
+                slot: 0,
+            }]
+        );
+    }
+
+    #[test]
+    fn lessons_keep_out_of_events_and_under_the_daily_maximum() {
+        let mut info = school();
+        info.fixed_events = vec![FixedEvent::new("Assembly", vec![2])];
+        info.daily_lessons.hard_max = Some(1);
+        let mut lessons = LESSONS;
+        assert_eq!(
+            verify(&info, &timetable(&info, &lessons)),
+            [Violation::LessonInEvent {
+                student_id: StudentId::from("Ann"),
+                subject: "Art".to_string(),
+                event: "Assembly".to_string(),
+                slot: 2,
+            }]
+        );
+        // Art moves out of the assembly to the same day as Maths.
+        lessons[1].1 = 1;
+        assert_eq!(
+            verify(&info, &timetable(&info, &lessons)),
+            [Violation::TooManyDailyLessons {
+                student_id: StudentId::from("Ann"),
+                slot: 0,
+                lessons: 2,
+                max: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn students_must_be_able_to_get_between_sites() {
+        let mut info = school();
+        let (maths, art) = (
+            info.subjects.id("Maths").unwrap(),
+            info.subjects.id("Art").unwrap(),
+        );
+        info.subjects[maths].site = Some("North".to_string());
+        info.subjects[art].site = Some("South".to_string());
+        info.site_travel = SiteTravel::OneSitePerDay;
+        let mut lessons = LESSONS;
+        lessons[1].1 = 1;
+        assert_eq!(
+            verify(&info, &timetable(&info, &lessons)),
+            [Violation::StudentCannotTravel {
+                student_id: StudentId::from("Ann"),
+                slot: 0,
+                other_slot: 1,
+            }]
+        );
+    }
+
+    /// Keeps every lesson off Mondays.
+    #[derive(Debug)]
+    struct NoMondays;
+
+    impl Constraint for NoMondays {
+        fn check(&self, placement: &Placement<'_>) -> bool {
+            placement.position.day != 0
+        }
+    }
+
+    #[test]
+    fn custom_constraints_are_checked() {
+        let mut info = school();
+        info.constraints.push(Arc::new(NoMondays));
+        let broken = |student_id: &str| Violation::ConstraintBroken {
+            student_id: StudentId::from(student_id),
+            subject: "Maths".to_string(),
+            slot: 0,
+        };
+        assert_eq!(
+            verify(&info, &timetable(&info, &LESSONS)),
+            [broken("Ann"), broken("Bob")]
+        );
+    }
+}
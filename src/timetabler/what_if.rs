@@ -0,0 +1,144 @@
+use super::{
+    diff, solve_timetable, StudentId, StudentInfo, SubjectId, TimetableDiff, TimetableInfo,
+    TimetableResult,
+};
+
+/// A change to a timetable's inputs which hasn't been made yet.
+#[derive(Debug, Clone)]
+pub enum Change {
+    AddStudent(StudentInfo),
+    DropSubject {
+        student_id: StudentId,
+        subject: SubjectId,
+    },
+    /// The teacher with the ID is away, so can't be given any lessons.
+    RemoveTeacher(String),
+}
+
+impl Change {
+    pub fn apply(&self, timetable_info: &mut TimetableInfo) {
+        match self {
+            Change::AddStudent(student) => timetable_info.students.push(student.clone()),
+            Change::DropSubject {
+                student_id,
+                subject,
+            } => timetable_info.drop_subject(student_id, *subject),
+            Change::RemoveTeacher(teacher_id) => timetable_info
+                .teachers
+                .retain(|teacher| teacher.id != *teacher_id),
+        }
+    }
+}
+
+/// What making a change would do to a timetable.
+#[derive(Debug)]
+pub struct Impact {
+    /// The timetable solved with the change made.
+    pub result: TimetableResult,
+    pub diff: TimetableDiff,
+    /// The number of students in both timetables whose lessons differ.
+    pub students_moved: usize,
+    /// How much the score changes by, where both timetables were solved,
+    /// including the weighted cost of the students moved. Negative numbers
+    /// are improvements.
+    pub score_delta: Option<i64>,
+}
+
+impl Impact {
+    /// Whether the timetable can still be solved with the change made.
+    pub fn is_feasible(&self) -> bool {
+        matches!(self.result, TimetableResult::Solved { .. })
+    }
+}
+
+/// Solves `timetable_info` with `change` made to a copy of it, comparing the
+/// result with `result`, the timetable solved without the change. Nothing
+/// given is modified.
+pub fn what_if(
+    timetable_info: &TimetableInfo,
+    result: &TimetableResult,
+    change: &Change,
+) -> Impact {
+    let mut changed_info = timetable_info.clone();
+    change.apply(&mut changed_info);
+    let new_result = solve_timetable(&changed_info);
+
+    let diff = diff(result, &new_result);
+    let students_moved = diff
+        .changed_students
+        .iter()
+        .filter(|&student_id| {
+            // Students added or removed by the change don't count as moving.
+            let is_in = |info: &TimetableInfo| {
+                info.students
+                    .iter()
+                    .any(|student| student.id == *student_id)
+            };
+            is_in(timetable_info) && is_in(&changed_info)
+        })
+        .count();
+    let score_delta = match (result, &new_result) {
+        (
+            TimetableResult::Solved { metrics: old, .. },
+            TimetableResult::Solved { metrics: new, .. },
+        ) => {
+            let weights = &timetable_info.weights;
+            let disruption = students_moved as i64 * i64::from(weights.disruption);
+            Some(new.score(weights) as i64 - old.score(weights) as i64 + disruption)
+        }
+        _ => None,
+    };
+
+    Impact {
+        result: new_result,
+        diff,
+        students_moved,
+        score_delta,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{what_if, Change};
+    use crate::timetabler::{solve_timetable, StudentInfo, TeacherInfo, TimetableInfo};
+
+    /// Ann and Bob taking Maths and Art, each taught by their own teacher.
+    fn school() -> TimetableInfo {
+        let mut info = TimetableInfo::new(1, 2);
+        info.add_student("Ann", &["Maths", "Art"]);
+        info.add_student("Bob", &["Maths"]);
+        let maths = info.subjects.id("Maths").unwrap();
+        let art = info.subjects.id("Art").unwrap();
+        info.teachers = vec![
+            TeacherInfo::new("T1", vec![maths]),
+            TeacherInfo::new("T2", vec![art]),
+        ];
+        info
+    }
+
+    #[test]
+    fn removing_the_only_teacher_of_a_subject_makes_it_unsolvable() {
+        let info = school();
+        let result = solve_timetable(&info);
+        let impact = what_if(&info, &result, &Change::RemoveTeacher("T1".to_string()));
+
+        assert!(!impact.is_feasible());
+        assert_eq!(impact.score_delta, None);
+        // The change is only made to a copy.
+        assert_eq!(info.teachers.len(), 2);
+    }
+
+    #[test]
+    fn an_added_student_is_timetabled_without_moving_anyone() {
+        let info = school();
+        let result = solve_timetable(&info);
+        let art = info.subjects.id("Art").unwrap();
+        let change = Change::AddStudent(StudentInfo::new("Cat", vec![art]));
+        let impact = what_if(&info, &result, &change);
+
+        assert!(impact.is_feasible());
+        assert_eq!(impact.students_moved, 0);
+        assert!(impact.score_delta.is_some());
+        assert_eq!(info.students.len(), 2);
+    }
+}
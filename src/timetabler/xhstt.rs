@@ -0,0 +1,1399 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::io;
+use std::path::Path;
+
+use thiserror::Error;
+
+use super::{
+    diff::{slots_by_group, GroupKey},
+    group_name, RoomInfo, StudentId, StudentInfo, SubjectId, SubjectInfo, SubjectRegistry,
+    TeacherInfo, TimetableInfo, TimetableResult, WeekCycle, DAYS_PER_WEEK,
+};
+
+/// The ID of the single instance in an exported archive.
+const INSTANCE_ID: &str = "Timetable";
+
+/// Escapes the characters which can't appear as they are in XML text or
+/// attribute values.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Writes indented XML, one element per line.
+struct XmlWriter {
+    xml: String,
+    depth: usize,
+}
+
+impl XmlWriter {
+    fn start_line(&mut self) {
+        for _ in 0..self.depth {
+            self.xml.push_str("  ");
+        }
+    }
+
+    fn tag(&mut self, tag: &str, attributes: &[(&str, &str)]) {
+        self.start_line();
+        self.xml.push('<');
+        self.xml.push_str(tag);
+        for (name, value) in attributes {
+            // Writing to a `String` never fails.
+            write!(self.xml, " {}=\"{}\"", name, escape(value)).unwrap();
+        }
+    }
+
+    fn open(&mut self, tag: &str, attributes: &[(&str, &str)]) {
+        self.tag(tag, attributes);
+        self.xml.push_str(">\n");
+        self.depth += 1;
+    }
+
+    fn close(&mut self, tag: &str) {
+        self.depth -= 1;
+        self.start_line();
+        writeln!(self.xml, "</{}>", tag).unwrap();
+    }
+
+    fn empty(&mut self, tag: &str, attributes: &[(&str, &str)]) {
+        self.tag(tag, attributes);
+        self.xml.push_str("/>\n");
+    }
+
+    fn text(&mut self, tag: &str, text: &str) {
+        self.start_line();
+        writeln!(self.xml, "<{}>{}</{}>", tag, escape(text), tag).unwrap();
+    }
+
+    fn reference(&mut self, tag: &str, id: &str) {
+        self.empty(tag, &[("Reference", id)]);
+    }
+
+    /// Writes the elements every constraint starts with.
+    fn constraint_header(&mut self, name: &str, required: bool, weight: u32) {
+        self.text("Name", name);
+        self.text("Required", if required { "true" } else { "false" });
+        self.text("Weight", &weight.to_string());
+        self.text("CostFunction", "Linear");
+    }
+}
+
+fn time_id(slot: usize) -> String {
+    format!("T{}", slot)
+}
+
+fn day_id(day_of_year: usize) -> String {
+    format!("Day{}", day_of_year)
+}
+
+fn week_id(week_of_year: usize) -> String {
+    format!("Week{}", week_of_year)
+}
+
+fn student_id(id: &StudentId) -> String {
+    format!("Student-{}", id)
+}
+
+fn room_id(id: &str) -> String {
+    format!("Room-{}", id)
+}
+
+fn teacher_id(id: &str) -> String {
+    format!("Teacher-{}", id)
+}
+
+fn course_id(subject: &str) -> String {
+    format!("Course-{}", subject)
+}
+
+fn room_type_group_id(room_type: &str) -> String {
+    format!("RoomType-{}", room_type)
+}
+
+fn teachers_of_group_id(subject: &str) -> String {
+    format!("TeachersOf-{}", subject)
+}
+
+/// The students of a group, and its slots if it has been solved.
+struct ExportedGroup {
+    students: Vec<StudentId>,
+    slots: Option<BTreeSet<usize>>,
+}
+
+/// One lesson of a group, which becomes an XHSTT event.
+struct Lesson {
+    subject: SubjectId,
+    group_idx: usize,
+    /// The week of the year the lesson is in.
+    week_of_year: usize,
+    /// The slot the lesson was given, when exporting a solution.
+    slot: Option<usize>,
+}
+
+/// Writes `timetable_info` in XHSTT, the XML format for high school
+/// timetabling used by academic solvers and benchmark suites. It is written
+/// as an archive with a single instance, along with `result` as a solution of
+/// it when it is solved.
+///
+/// XHSTT has no way to split a subject's students into groups, so the groups
+/// are taken from `result`. Without a solved result, each subject is taught
+/// to all of its students as one group. Limits on consecutive lessons, on
+/// groups per slot and on groups per subject can't be written in XHSTT and
+/// are left out.
+pub fn to_xhstt(timetable_info: &TimetableInfo, result: Option<&TimetableResult>) -> String {
+    let slots_per_week = timetable_info.slots_per_week();
+    let daily_lesson_capacity = usize::from(timetable_info.daily_lesson_capacity);
+    let weeks = timetable_info.cycle.weeks();
+    let slots_per_year = timetable_info.slots_per_year();
+
+    let mut groups: BTreeMap<GroupKey, ExportedGroup> = BTreeMap::new();
+    let solution = match result {
+        Some(result @ TimetableResult::Solved { .. }) => Some(result),
+        _ => None,
+    };
+    match solution {
+        Some(result) => {
+            let mut slots_by_group = slots_by_group(result);
+            for (subject, rosters) in result.rosters() {
+                for (group_idx, roster) in rosters.into_iter().enumerate() {
+                    let key = (subject, group_idx);
+                    let slots = slots_by_group.remove(&key).unwrap_or_default();
+                    groups.insert(
+                        key,
+                        ExportedGroup {
+                            students: roster,
+                            slots: Some(slots),
+                        },
+                    );
+                }
+            }
+        }
+        None => {
+            for student in &timetable_info.students {
+                for &subject in &student.subjects {
+                    groups
+                        .entry((subject, 0))
+                        .or_insert_with(|| ExportedGroup {
+                            students: Vec::new(),
+                            slots: None,
+                        })
+                        .students
+                        .push(student.id.clone());
+                }
+            }
+        }
+    }
+
+    let mut lessons = Vec::new();
+    for (&(subject, group_idx), group) in &groups {
+        match &group.slots {
+            Some(slots) => lessons.extend(slots.iter().map(|&slot| Lesson {
+                subject,
+                group_idx,
+                week_of_year: slot / slots_per_week,
+                slot: Some(slot),
+            })),
+            None => {
+                for term in timetable_info.terms_of(subject) {
+                    for week in 0..weeks {
+                        for _ in 0..timetable_info.lessons_in_week(subject, week) {
+                            lessons.push(Lesson {
+                                subject,
+                                group_idx,
+                                week_of_year: term * weeks + week,
+                                slot: None,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+    let event_id = |lesson_idx: usize| format!("E{}", lesson_idx);
+    let subjects: BTreeSet<SubjectId> = groups.keys().map(|&(subject, _)| subject).collect();
+    let name = |subject: SubjectId| timetable_info.subjects.name(subject);
+    let has_rooms = !timetable_info.rooms.is_empty();
+    let has_teachers = !timetable_info.teachers.is_empty();
+
+    let mut xml = XmlWriter {
+        xml: String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"),
+        depth: 0,
+    };
+    xml.open("HighSchoolTimetableArchive", &[("Id", "Timetabler")]);
+    xml.open("Instances", &[]);
+    xml.open("Instance", &[("Id", INSTANCE_ID)]);
+    xml.open("MetaData", &[]);
+    xml.text("Name", INSTANCE_ID);
+    xml.text("Contributor", "Timetabler");
+    xml.text("Date", "");
+    xml.text("Country", "");
+    xml.text(
+        "Description",
+        "Exported from Timetabler. Times run through every term of the year.",
+    );
+    xml.close("MetaData");
+
+    // Times
+    xml.open("Times", &[]);
+    xml.open("TimeGroups", &[]);
+    for week_of_year in 0..slots_per_year / slots_per_week {
+        xml.open("Week", &[("Id", &week_id(week_of_year))]);
+        xml.text("Name", &format!("Week {}", week_of_year + 1));
+        xml.close("Week");
+    }
+    for day_of_year in 0..slots_per_year / daily_lesson_capacity {
+        xml.open("Day", &[("Id", &day_id(day_of_year))]);
+        xml.text("Name", &format!("Day {}", day_of_year + 1));
+        xml.close("Day");
+    }
+    xml.close("TimeGroups");
+    for slot in 0..slots_per_year {
+        xml.open("Time", &[("Id", &time_id(slot))]);
+        xml.text(
+            "Name",
+            &format!(
+                "Day {} period {}",
+                slot / daily_lesson_capacity + 1,
+                slot % daily_lesson_capacity + 1
+            ),
+        );
+        xml.reference("Week", &week_id(slot / slots_per_week));
+        xml.reference("Day", &day_id(slot / daily_lesson_capacity));
+        xml.close("Time");
+    }
+    xml.close("Times");
+
+    // Resources
+    xml.open("Resources", &[]);
+    xml.open("ResourceTypes", &[]);
+    let mut resource_types = vec!["Student"];
+    if has_rooms {
+        resource_types.push("Room");
+    }
+    if has_teachers {
+        resource_types.push("Teacher");
+    }
+    for resource_type in &resource_types {
+        xml.open("ResourceType", &[("Id", resource_type)]);
+        xml.text("Name", resource_type);
+        xml.close("ResourceType");
+    }
+    xml.close("ResourceTypes");
+
+    let room_types: BTreeSet<&str> = timetable_info
+        .rooms
+        .iter()
+        .filter_map(|room| room.room_type.as_deref())
+        .collect();
+    xml.open("ResourceGroups", &[]);
+    let mut resource_groups = vec![("Students".to_string(), "Student")];
+    if has_rooms {
+        resource_groups.push(("Rooms".to_string(), "Room"));
+        for room_type in &room_types {
+            resource_groups.push((room_type_group_id(room_type), "Room"));
+        }
+    }
+    if has_teachers {
+        resource_groups.push(("Teachers".to_string(), "Teacher"));
+        for &subject in &subjects {
+            resource_groups.push((teachers_of_group_id(name(subject)), "Teacher"));
+        }
+    }
+    for (id, resource_type) in &resource_groups {
+        xml.open("ResourceGroup", &[("Id", id)]);
+        xml.text("Name", id);
+        xml.reference("ResourceType", resource_type);
+        xml.close("ResourceGroup");
+    }
+    xml.close("ResourceGroups");
+
+    for student in &timetable_info.students {
+        xml.open("Resource", &[("Id", &student_id(&student.id))]);
+        xml.text("Name", student.id.as_str());
+        xml.reference("ResourceType", "Student");
+        xml.open("ResourceGroups", &[]);
+        xml.reference("ResourceGroup", "Students");
+        xml.close("ResourceGroups");
+        xml.close("Resource");
+    }
+    for room in &timetable_info.rooms {
+        xml.open("Resource", &[("Id", &room_id(&room.id))]);
+        xml.text("Name", &room.id);
+        xml.reference("ResourceType", "Room");
+        xml.open("ResourceGroups", &[]);
+        xml.reference("ResourceGroup", "Rooms");
+        if let Some(room_type) = &room.room_type {
+            xml.reference("ResourceGroup", &room_type_group_id(room_type));
+        }
+        xml.close("ResourceGroups");
+        xml.close("Resource");
+    }
+    for teacher in &timetable_info.teachers {
+        xml.open("Resource", &[("Id", &teacher_id(&teacher.id))]);
+        xml.text("Name", &teacher.id);
+        xml.reference("ResourceType", "Teacher");
+        xml.open("ResourceGroups", &[]);
+        xml.reference("ResourceGroup", "Teachers");
+        for &subject in &teacher.subjects {
+            // Teachers may list subjects nobody takes, which have no group.
+            if subjects.contains(&subject) {
+                xml.reference("ResourceGroup", &teachers_of_group_id(name(subject)));
+            }
+        }
+        xml.close("ResourceGroups");
+        xml.close("Resource");
+    }
+    xml.close("Resources");
+
+    // Events
+    xml.open("Events", &[]);
+    xml.open("EventGroups", &[]);
+    for &subject in &subjects {
+        xml.open("Course", &[("Id", &course_id(name(subject)))]);
+        xml.text("Name", name(subject));
+        xml.close("Course");
+    }
+    xml.open("EventGroup", &[("Id", "AllEvents")]);
+    xml.text("Name", "All events");
+    xml.close("EventGroup");
+    xml.close("EventGroups");
+    for (lesson_idx, lesson) in lessons.iter().enumerate() {
+        xml.open("Event", &[("Id", &event_id(lesson_idx))]);
+        xml.text("Name", &group_name(name(lesson.subject), lesson.group_idx));
+        xml.text("Duration", "1");
+        xml.reference("Course", &course_id(name(lesson.subject)));
+        xml.open("Resources", &[]);
+        let group = &groups[&(lesson.subject, lesson.group_idx)];
+        for student in &group.students {
+            xml.reference("Resource", &student_id(student));
+        }
+        if has_rooms {
+            xml.open("Resource", &[]);
+            xml.text("Role", "Room");
+            xml.reference("ResourceType", "Room");
+            xml.close("Resource");
+        }
+        if has_teachers {
+            xml.open("Resource", &[]);
+            xml.text("Role", "Teacher");
+            xml.reference("ResourceType", "Teacher");
+            xml.close("Resource");
+        }
+        xml.close("Resources");
+        xml.open("EventGroups", &[]);
+        xml.reference("EventGroup", "AllEvents");
+        xml.close("EventGroups");
+        xml.close("Event");
+    }
+    xml.close("Events");
+
+    // Constraints
+    let weights = &timetable_info.weights;
+    let mut constraint_idx = 0;
+    let mut next_constraint_id = || {
+        constraint_idx += 1;
+        format!("C{}", constraint_idx)
+    };
+    xml.open("Constraints", &[]);
+
+    xml.open("AssignTimeConstraint", &[("Id", &next_constraint_id())]);
+    xml.constraint_header("Every lesson has a time", true, 1);
+    xml.open("AppliesTo", &[]);
+    xml.open("EventGroups", &[]);
+    xml.reference("EventGroup", "AllEvents");
+    xml.close("EventGroups");
+    xml.close("AppliesTo");
+    xml.close("AssignTimeConstraint");
+
+    xml.open("AvoidClashesConstraint", &[("Id", &next_constraint_id())]);
+    xml.constraint_header("Nobody has two lessons at once", true, 1);
+    xml.open("AppliesTo", &[]);
+    xml.open("ResourceGroups", &[]);
+    xml.reference("ResourceGroup", "Students");
+    if has_rooms {
+        xml.reference("ResourceGroup", "Rooms");
+    }
+    if has_teachers {
+        xml.reference("ResourceGroup", "Teachers");
+    }
+    xml.close("ResourceGroups");
+    xml.close("AppliesTo");
+    xml.close("AvoidClashesConstraint");
+
+    for role in resource_types.iter().skip(1) {
+        xml.open("AssignResourceConstraint", &[("Id", &next_constraint_id())]);
+        xml.constraint_header(&format!("Every lesson has a {}", role), true, 1);
+        xml.open("AppliesTo", &[]);
+        xml.open("EventGroups", &[]);
+        xml.reference("EventGroup", "AllEvents");
+        xml.close("EventGroups");
+        xml.close("AppliesTo");
+        xml.text("Role", role);
+        xml.close("AssignResourceConstraint");
+    }
+
+    for &subject in &subjects {
+        let room_group = match timetable_info.subjects[subject].room_type.as_deref() {
+            Some(room_type) if has_rooms => Some(("Room", room_type_group_id(room_type))),
+            _ => None,
+        };
+        let teacher_group = if has_teachers {
+            Some(("Teacher", teachers_of_group_id(name(subject))))
+        } else {
+            None
+        };
+        for (role, group_id) in room_group.into_iter().chain(teacher_group) {
+            xml.open(
+                "PreferResourcesConstraint",
+                &[("Id", &next_constraint_id())],
+            );
+            xml.constraint_header(
+                &format!("{} has a suitable {}", name(subject), role),
+                true,
+                1,
+            );
+            xml.open("AppliesTo", &[]);
+            xml.open("EventGroups", &[]);
+            xml.reference("EventGroup", &course_id(name(subject)));
+            xml.close("EventGroups");
+            xml.close("AppliesTo");
+            xml.open("ResourceGroups", &[]);
+            xml.reference("ResourceGroup", &group_id);
+            xml.close("ResourceGroups");
+            xml.text("Role", role);
+            xml.close("PreferResourcesConstraint");
+        }
+    }
+
+    // Each lesson must stay in its week, in a slot the hard constraints
+    // allow, and should be in a slot without a soft constraint penalty.
+    let mut lesson_idxs_by_week: BTreeMap<(SubjectId, usize), Vec<usize>> = BTreeMap::new();
+    for (lesson_idx, lesson) in lessons.iter().enumerate() {
+        lesson_idxs_by_week
+            .entry((lesson.subject, lesson.week_of_year))
+            .or_default()
+            .push(lesson_idx);
+    }
+    for (&(subject, week_of_year), lesson_idxs) in &lesson_idxs_by_week {
+        let week_slots = week_of_year * slots_per_week..(week_of_year + 1) * slots_per_week;
+        let allowed: Vec<usize> = week_slots
+            .filter(|&slot| timetable_info.allows(subject, slot, None, None))
+            .collect();
+        let preferred: Vec<usize> = allowed
+            .iter()
+            .copied()
+            .filter(|&slot| timetable_info.slot_penalty(subject, slot, None) == 0)
+            .collect();
+        let mut preferences = vec![(
+            format!("{} is taught in week {}", name(subject), week_of_year + 1),
+            true,
+            1,
+            &allowed,
+        )];
+        if preferred.len() < allowed.len() {
+            preferences.push((
+                format!("{} is taught at preferred times", name(subject)),
+                false,
+                weights.slot_preference,
+                &preferred,
+            ));
+        }
+        for (name, required, weight, slots) in preferences {
+            xml.open("PreferTimesConstraint", &[("Id", &next_constraint_id())]);
+            xml.constraint_header(&name, required, weight);
+            xml.open("AppliesTo", &[]);
+            xml.open("Events", &[]);
+            for &lesson_idx in lesson_idxs {
+                xml.reference("Event", &event_id(lesson_idx));
+            }
+            xml.close("Events");
+            xml.close("AppliesTo");
+            xml.open("Times", &[]);
+            for &slot in slots {
+                xml.reference("Time", &time_id(slot));
+            }
+            xml.close("Times");
+            xml.close("PreferTimesConstraint");
+        }
+    }
+
+    xml.open("LimitIdleTimesConstraint", &[("Id", &next_constraint_id())]);
+    xml.constraint_header("Students have no gaps", false, weights.gap);
+    xml.open("AppliesTo", &[]);
+    xml.open("ResourceGroups", &[]);
+    xml.reference("ResourceGroup", "Students");
+    xml.close("ResourceGroups");
+    xml.close("AppliesTo");
+    xml.open("TimeGroups", &[]);
+    for day_of_year in 0..slots_per_year / daily_lesson_capacity {
+        xml.reference("TimeGroup", &day_id(day_of_year));
+    }
+    xml.close("TimeGroups");
+    xml.text("Minimum", "0");
+    xml.text("Maximum", "0");
+    xml.close("LimitIdleTimesConstraint");
+
+    for teacher in &timetable_info.teachers {
+        if let Some(max_weekly_lessons) = teacher.max_weekly_lessons {
+            xml.open("LimitBusyTimesConstraint", &[("Id", &next_constraint_id())]);
+            xml.constraint_header(
+                &format!(
+                    "{} teaches at most {} lessons a week",
+                    teacher.id, max_weekly_lessons
+                ),
+                true,
+                1,
+            );
+            xml.open("AppliesTo", &[]);
+            xml.open("Resources", &[]);
+            xml.reference("Resource", &teacher_id(&teacher.id));
+            xml.close("Resources");
+            xml.close("AppliesTo");
+            xml.open("TimeGroups", &[]);
+            for week_of_year in 0..slots_per_year / slots_per_week {
+                xml.reference("TimeGroup", &week_id(week_of_year));
+            }
+            xml.close("TimeGroups");
+            xml.text("Minimum", "0");
+            xml.text("Maximum", &max_weekly_lessons.to_string());
+            xml.close("LimitBusyTimesConstraint");
+        }
+    }
+    xml.close("Constraints");
+    xml.close("Instance");
+    xml.close("Instances");
+
+    if let Some(TimetableResult::Solved {
+        rooms_by_slot,
+        teachers_by_slot,
+        ..
+    }) = solution
+    {
+        xml.open("SolutionGroups", &[]);
+        xml.open("SolutionGroup", &[("Id", "Timetabler")]);
+        xml.open("MetaData", &[]);
+        xml.text("Contributor", "Timetabler");
+        xml.text("Date", "");
+        xml.text("Description", "Solved by Timetabler.");
+        xml.close("MetaData");
+        xml.open("Solution", &[("Reference", INSTANCE_ID)]);
+        xml.open("Events", &[]);
+        for (lesson_idx, lesson) in lessons.iter().enumerate() {
+            // Every lesson of a solved result has a slot.
+            let slot = lesson.slot.unwrap();
+            xml.open("Event", &[("Reference", &event_id(lesson_idx))]);
+            xml.text("Duration", "1");
+            xml.reference("Time", &time_id(slot));
+            let room = rooms_by_slot
+                .get(slot)
+                .and_then(|rooms| rooms.get(&lesson.subject))
+                .map(|id| ("Room", room_id(id)));
+            let teacher = teachers_by_slot
+                .get(slot)
+                .and_then(|teachers| teachers.get(&lesson.subject))
+                .map(|id| ("Teacher", teacher_id(id)));
+            let resources: Vec<(&str, String)> = room.into_iter().chain(teacher).collect();
+            if !resources.is_empty() {
+                xml.open("Resources", &[]);
+                for (role, id) in resources {
+                    xml.open("Resource", &[("Reference", &id)]);
+                    xml.text("Role", role);
+                    xml.close("Resource");
+                }
+                xml.close("Resources");
+            }
+            xml.close("Event");
+        }
+        xml.close("Events");
+        xml.close("Solution");
+        xml.close("SolutionGroup");
+        xml.close("SolutionGroups");
+    }
+    xml.close("HighSchoolTimetableArchive");
+
+    xml.xml
+}
+
+/// Why an XHSTT file couldn't be read.
+#[derive(Debug, Error)]
+pub enum XhsttError {
+    #[error("couldn't read the XHSTT file: {0}")]
+    Io(#[from] io::Error),
+    /// The file isn't XML, or uses parts of XML which aren't read.
+    #[error("{message} on line {line}")]
+    Syntax { message: String, line: usize },
+    #[error("there is no instance")]
+    NoInstance,
+    /// The instance lacks one of its `Times`, `Resources` or `Events`.
+    #[error("the instance has no {0}")]
+    MissingSection(&'static str),
+    /// An element which can be referred to has no `Id`.
+    #[error("a {0} has no Id")]
+    MissingId(String),
+    #[error("the times aren't grouped into days")]
+    NoDays,
+    #[error(
+        "there are {0} days, but only one or two weeks of {} days are supported",
+        DAYS_PER_WEEK
+    )]
+    TooManyDays(usize),
+    /// A day has more times than fit in a `u8`.
+    #[error("a day has {0} times")]
+    TooManyTimes(usize),
+    #[error("resource {0} has an unknown type")]
+    UnknownResourceType(String),
+    #[error("event {event} uses unknown resource {resource}")]
+    UnknownResource { event: String, resource: String },
+    #[error("event {0} has an invalid duration")]
+    InvalidDuration(String),
+    /// A subject has more lessons than fit in a `u8`.
+    #[error("{subject} has {lessons} lessons")]
+    TooManyLessons { subject: String, lessons: usize },
+    /// A subject has more groups than fit in a `u8`.
+    #[error("{subject} has {groups} groups")]
+    TooManyGroups { subject: String, groups: usize },
+}
+
+/// An XML element with everything inside it.
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Element>,
+    text: String,
+}
+
+impl Element {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(attribute, _)| attribute == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    fn child_text(&self, name: &str) -> Option<&str> {
+        self.child(name).map(|child| child.text.trim())
+    }
+
+    /// The `Reference` of the child named `name`.
+    fn reference(&self, name: &str) -> Option<&str> {
+        self.child(name)
+            .and_then(|child| child.attribute("Reference"))
+    }
+
+    /// The `Reference`s of the children named `name` of the child named
+    /// `list`, e.g. every `<ResourceGroup Reference="..."/>` in
+    /// `<ResourceGroups>`.
+    fn references<'a>(&'a self, list: &str, name: &'a str) -> Vec<&'a str> {
+        self.child(list)
+            .map(|list| {
+                list.children(name)
+                    .filter_map(|child| child.attribute("Reference"))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The `Id`, which XHSTT gives everything that can be referred to.
+    fn id(&self) -> Result<&str, XhsttError> {
+        self.attribute("Id")
+            .ok_or_else(|| XhsttError::MissingId(self.name.clone()))
+    }
+}
+
+/// Reads just enough XML for XHSTT files: elements, attributes, text, CDATA
+/// and character references. Comments, processing instructions and document
+/// types are skipped.
+struct Parser<'a> {
+    xml: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.xml[self.pos..]
+    }
+
+    fn error(&self, message: &str) -> XhsttError {
+        XhsttError::Syntax {
+            message: message.to_string(),
+            line: self.xml[..self.pos].matches('\n').count() + 1,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Moves past the next `end`, returning what came before it.
+    fn take_until(&mut self, end: &str) -> Result<&'a str, XhsttError> {
+        match self.rest().find(end) {
+            Some(idx) => {
+                let taken = &self.rest()[..idx];
+                self.pos += idx + end.len();
+                Ok(taken)
+            }
+            None => Err(self.error(&format!("expected {}", end))),
+        }
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), XhsttError> {
+        if self.rest().starts_with(expected) {
+            self.pos += expected.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected {}", expected)))
+        }
+    }
+
+    /// Skips a comment, processing instruction or document type if one is
+    /// next, returning whether there was one.
+    fn skip_markup(&mut self) -> Result<bool, XhsttError> {
+        let rest = self.rest();
+        if rest.starts_with("<!--") {
+            self.take_until("-->")?;
+        } else if rest.starts_with("<?") {
+            self.take_until("?>")?;
+        } else if rest.starts_with("<!DOCTYPE") {
+            // The internal subset, if any, is in square brackets.
+            let end = rest.find('>').unwrap_or(rest.len());
+            if rest[..end].contains('[') {
+                self.take_until("]")?;
+            }
+            self.take_until(">")?;
+        } else {
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    fn name(&mut self) -> Result<&'a str, XhsttError> {
+        let rest = self.rest();
+        let end = rest
+            .find(|c: char| c.is_whitespace() || "/>=".contains(c))
+            .unwrap_or(rest.len());
+        if end == 0 {
+            return Err(self.error("expected a name"));
+        }
+        self.pos += end;
+        Ok(&rest[..end])
+    }
+
+    fn unescape(&self, text: &str) -> Result<String, XhsttError> {
+        let mut unescaped = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find('&') {
+            unescaped.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find(';')
+                .ok_or_else(|| self.error("an entity is missing its ;"))?;
+            let entity = &rest[start + 1..start + end];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ => {
+                    let code = match entity.strip_prefix("#x") {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                        None => entity
+                            .strip_prefix('#')
+                            .and_then(|decimal| decimal.parse().ok()),
+                    };
+                    code.and_then(char::from_u32)
+                }
+            };
+            match c {
+                Some(c) => unescaped.push(c),
+                None => return Err(self.error(&format!("unknown entity &{};", entity))),
+            }
+            rest = &rest[start + end + 1..];
+        }
+        unescaped.push_str(rest);
+        Ok(unescaped)
+    }
+
+    fn document(&mut self) -> Result<Element, XhsttError> {
+        loop {
+            self.skip_whitespace();
+            if !self.skip_markup()? {
+                break;
+            }
+        }
+        self.element()
+    }
+
+    fn element(&mut self) -> Result<Element, XhsttError> {
+        self.expect("<")?;
+        let mut element = Element {
+            name: self.name()?.to_string(),
+            attributes: Vec::new(),
+            children: Vec::new(),
+            text: String::new(),
+        };
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("/>") {
+                self.pos += 2;
+                return Ok(element);
+            }
+            if self.rest().starts_with('>') {
+                self.pos += 1;
+                break;
+            }
+            let name = self.name()?.to_string();
+            self.skip_whitespace();
+            self.expect("=")?;
+            self.skip_whitespace();
+            let quote = if self.rest().starts_with('\'') {
+                "'"
+            } else {
+                "\""
+            };
+            self.expect(quote)?;
+            let value = self.take_until(quote)?;
+            let value = self.unescape(value)?;
+            element.attributes.push((name, value));
+        }
+
+        loop {
+            let rest = self.rest();
+            if rest.starts_with("</") {
+                self.pos += 2;
+                if self.name()? != element.name {
+                    return Err(self.error(&format!("expected </{}>", element.name)));
+                }
+                self.skip_whitespace();
+                self.expect(">")?;
+                return Ok(element);
+            } else if rest.starts_with("<![CDATA[") {
+                self.pos += "<![CDATA[".len();
+                element.text.push_str(self.take_until("]]>")?);
+            } else if self.skip_markup()? {
+                continue;
+            } else if rest.starts_with('<') {
+                element.children.push(self.element()?);
+            } else if rest.is_empty() {
+                return Err(self.error(&format!("expected </{}>", element.name)));
+            } else {
+                let text = &rest[..rest.find('<').unwrap_or(rest.len())];
+                self.pos += text.len();
+                let text = self.unescape(text)?;
+                element.text.push_str(&text);
+            }
+        }
+    }
+}
+
+/// What a kind of resource is used as.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ResourceKind {
+    Student,
+    Room,
+    Teacher,
+}
+
+/// Reads the first instance of an XHSTT archive, or a lone instance, into a
+/// `TimetableInfo`. Solutions in the archive are ignored.
+///
+/// Resources whose type is named like a room or teacher become rooms and
+/// teachers, and every other resource, such as a class, becomes a student.
+/// Resources are known by their names, or their IDs if they have none.
+/// Each course, or each event outside of a course, becomes a subject with as
+/// many lessons as its events last, and with a group for each different set
+/// of students its events are taught to.
+/// Teachers are given the subjects they are preassigned to, or may be
+/// assigned to under a required prefer resources constraint. The times must
+/// be grouped into days, with one or two weeks of them. Other constraints
+/// aren't read.
+pub fn from_xhstt(xml: &str) -> Result<TimetableInfo, XhsttError> {
+    let root = Parser { xml, pos: 0 }.document()?;
+    let instance = if root.name == "Instance" {
+        &root
+    } else {
+        root.child("Instances")
+            .and_then(|instances| instances.child("Instance"))
+            .ok_or(XhsttError::NoInstance)?
+    };
+
+    // Times
+    let times = instance
+        .child("Times")
+        .ok_or(XhsttError::MissingSection("Times"))?;
+    let mut times_by_day: BTreeMap<&str, usize> = BTreeMap::new();
+    if let Some(time_groups) = times.child("TimeGroups") {
+        for day in time_groups.children("Day") {
+            times_by_day.insert(day.id()?, 0);
+        }
+    }
+    for time in times.children("Time") {
+        if let Some(day) = time.reference("Day") {
+            if let Some(count) = times_by_day.get_mut(day) {
+                *count += 1;
+            }
+        }
+    }
+    times_by_day.retain(|_, count| *count > 0);
+    if times_by_day.is_empty() {
+        return Err(XhsttError::NoDays);
+    }
+    let cycle = match (times_by_day.len() + DAYS_PER_WEEK - 1) / DAYS_PER_WEEK {
+        1 => WeekCycle::Weekly,
+        2 => WeekCycle::Fortnightly,
+        _ => return Err(XhsttError::TooManyDays(times_by_day.len())),
+    };
+    let most_times = times_by_day.values().copied().max().unwrap_or_default();
+    let daily_lesson_capacity =
+        u8::try_from(most_times).map_err(|_| XhsttError::TooManyTimes(most_times))?;
+
+    // Resources
+    let resources = instance
+        .child("Resources")
+        .ok_or(XhsttError::MissingSection("Resources"))?;
+    let mut kinds_by_type: BTreeMap<&str, ResourceKind> = BTreeMap::new();
+    if let Some(resource_types) = resources.child("ResourceTypes") {
+        for resource_type in resource_types.children("ResourceType") {
+            let id = resource_type.id()?;
+            let name = format!(
+                "{} {}",
+                id,
+                resource_type.child_text("Name").unwrap_or_default()
+            )
+            .to_lowercase();
+            let kind = if name.contains("room") {
+                ResourceKind::Room
+            } else if name.contains("teacher") {
+                ResourceKind::Teacher
+            } else {
+                ResourceKind::Student
+            };
+            kinds_by_type.insert(id, kind);
+        }
+    }
+    let mut kinds: BTreeMap<&str, ResourceKind> = BTreeMap::new();
+    let mut names: BTreeMap<&str, &str> = BTreeMap::new();
+    let mut members_by_group: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    // Resources are kept in the order they are given in.
+    let mut resource_ids = Vec::new();
+    for resource in resources.children("Resource") {
+        let id = resource.id()?;
+        let kind = resource
+            .reference("ResourceType")
+            .and_then(|resource_type| kinds_by_type.get(resource_type))
+            .copied()
+            .ok_or_else(|| XhsttError::UnknownResourceType(id.to_string()))?;
+        kinds.insert(id, kind);
+        names.insert(id, resource.child_text("Name").unwrap_or(id));
+        resource_ids.push(id);
+        for group in resource.references("ResourceGroups", "ResourceGroup") {
+            members_by_group.entry(group).or_default().push(id);
+        }
+    }
+
+    // Events
+    let events = instance
+        .child("Events")
+        .ok_or(XhsttError::MissingSection("Events"))?;
+    let mut course_names: BTreeMap<&str, &str> = BTreeMap::new();
+    if let Some(event_groups) = events.child("EventGroups") {
+        for course in event_groups.children("Course") {
+            let id = course.id()?;
+            course_names.insert(id, course.child_text("Name").unwrap_or(id));
+        }
+    }
+    let mut event_ids_by_group: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for event in events.children("Event") {
+        let id = event.id()?;
+        for group in event
+            .references("EventGroups", "EventGroup")
+            .into_iter()
+            .chain(event.reference("Course"))
+        {
+            event_ids_by_group.entry(group).or_default().push(id);
+        }
+    }
+
+    // The resources each role of each event may be given, from the required
+    // prefer resources constraints.
+    let mut allowed_by_role: BTreeMap<(&str, &str), BTreeSet<&str>> = BTreeMap::new();
+    if let Some(constraints) = instance.child("Constraints") {
+        for constraint in constraints.children("PreferResourcesConstraint") {
+            if constraint.child_text("Required") != Some("true") {
+                continue;
+            }
+            let (role, applies_to) =
+                match (constraint.child_text("Role"), constraint.child("AppliesTo")) {
+                    (Some(role), Some(applies_to)) => (role, applies_to),
+                    _ => continue,
+                };
+            let allowed: BTreeSet<&str> = constraint
+                .references("Resources", "Resource")
+                .into_iter()
+                .chain(
+                    constraint
+                        .references("ResourceGroups", "ResourceGroup")
+                        .into_iter()
+                        .flat_map(|group| members_by_group.get(group).cloned().unwrap_or_default()),
+                )
+                .collect();
+            let event_ids = applies_to.references("Events", "Event").into_iter().chain(
+                applies_to
+                    .references("EventGroups", "EventGroup")
+                    .into_iter()
+                    .flat_map(|group| event_ids_by_group.get(group).cloned().unwrap_or_default()),
+            );
+            for event_id in event_ids {
+                // Every constraint must be kept, so only resources all of
+                // them allow may be given.
+                let entry = allowed_by_role
+                    .entry((event_id, role))
+                    .or_insert_with(|| allowed.clone());
+                entry.retain(|resource| allowed.contains(resource));
+            }
+        }
+    }
+
+    let all_teachers: BTreeSet<&str> = resource_ids
+        .iter()
+        .copied()
+        .filter(|id| kinds[id] == ResourceKind::Teacher)
+        .collect();
+    // How long each student spends in each subject, and who may teach it.
+    let mut lessons_by_subject: BTreeMap<&str, BTreeMap<&str, usize>> = BTreeMap::new();
+    // The students of an event are taught together, so each different set of
+    // students taking a subject is one of its groups.
+    let mut groups_by_subject: BTreeMap<&str, BTreeSet<Vec<&str>>> = BTreeMap::new();
+    let mut teachers_by_subject: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for event in events.children("Event") {
+        let id = event.id()?;
+        let subject = match event.reference("Course") {
+            Some(course) => course_names.get(course).copied().unwrap_or(course),
+            None => event.child_text("Name").unwrap_or(id),
+        };
+        let duration: usize = match event.child_text("Duration") {
+            Some(duration) => duration
+                .parse()
+                .map_err(|_| XhsttError::InvalidDuration(id.to_string()))?,
+            None => 1,
+        };
+
+        let mut students = Vec::new();
+        let mut teachers = BTreeSet::new();
+        if let Some(event_resources) = event.child("Resources") {
+            for resource in event_resources.children("Resource") {
+                match resource.attribute("Reference") {
+                    Some(resource_id) => match kinds.get(resource_id) {
+                        Some(ResourceKind::Student) => students.push(resource_id),
+                        Some(ResourceKind::Teacher) => {
+                            teachers.insert(resource_id);
+                        }
+                        Some(ResourceKind::Room) => {}
+                        None => {
+                            return Err(XhsttError::UnknownResource {
+                                event: id.to_string(),
+                                resource: resource_id.to_string(),
+                            })
+                        }
+                    },
+                    // A resource still to be assigned.
+                    None => {
+                        let is_teacher = resource
+                            .reference("ResourceType")
+                            .and_then(|resource_type| kinds_by_type.get(resource_type))
+                            == Some(&ResourceKind::Teacher);
+                        if is_teacher {
+                            let role = resource.child_text("Role").unwrap_or_default();
+                            match allowed_by_role.get(&(id, role)) {
+                                Some(allowed) => teachers.extend(allowed),
+                                None => teachers.extend(&all_teachers),
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        // Lessons without students, such as meetings, aren't timetabled.
+        if students.is_empty() {
+            continue;
+        }
+        let lessons = lessons_by_subject.entry(subject).or_default();
+        for &student in &students {
+            *lessons.entry(student).or_insert(0) += duration;
+        }
+        students.sort_unstable();
+        groups_by_subject
+            .entry(subject)
+            .or_default()
+            .insert(students);
+        teachers_by_subject
+            .entry(subject)
+            .or_default()
+            .extend(teachers);
+    }
+
+    let mut subjects_by_student: BTreeMap<&str, Vec<SubjectId>> = BTreeMap::new();
+    let mut registry = SubjectRegistry::new();
+    let mut max_groups = 1;
+    for (subject, lessons) in &lessons_by_subject {
+        let most_lessons = lessons.values().copied().max().unwrap_or_default();
+        let lessons = u8::try_from(most_lessons).map_err(|_| XhsttError::TooManyLessons {
+            subject: subject.to_string(),
+            lessons: most_lessons,
+        })?;
+        let groups = groups_by_subject[subject].len();
+        let groups = u8::try_from(groups).map_err(|_| XhsttError::TooManyGroups {
+            subject: subject.to_string(),
+            groups,
+        })?;
+        max_groups = max_groups.max(groups);
+        let mut subject_info = SubjectInfo::new(*subject);
+        subject_info.max_groups = Some(groups);
+        subject_info.lessons_per_week = match cycle {
+            WeekCycle::Weekly => [lessons, 0],
+            // Week A takes the odd lesson out.
+            WeekCycle::Fortnightly => [lessons - lessons / 2, lessons / 2],
+        };
+        let subject_id = registry.insert(subject_info);
+        for student in lessons_by_subject[subject].keys() {
+            subjects_by_student
+                .entry(student)
+                .or_default()
+                .push(subject_id);
+        }
+    }
+
+    let mut timetable_info = TimetableInfo::new(max_groups, daily_lesson_capacity);
+    timetable_info.cycle = cycle;
+    for id in resource_ids {
+        match kinds[id] {
+            ResourceKind::Student => timetable_info.students.push(StudentInfo::new(
+                names[id].to_string(),
+                subjects_by_student.remove(id).unwrap_or_default(),
+            )),
+            ResourceKind::Room => timetable_info.rooms.push(RoomInfo::new(names[id], None)),
+            ResourceKind::Teacher => {
+                let subjects = teachers_by_subject
+                    .iter()
+                    .filter(|(_, teachers)| teachers.contains(id))
+                    .map(|(subject, _)| registry.register(subject))
+                    .collect();
+                timetable_info
+                    .teachers
+                    .push(TeacherInfo::new(names[id], subjects));
+            }
+        }
+    }
+    timetable_info.subjects = registry;
+    Ok(timetable_info)
+}
+
+/// Reads an XHSTT file, as `from_xhstt` does.
+pub fn from_xhstt_file(path: impl AsRef<Path>) -> Result<TimetableInfo, XhsttError> {
+    let xml = std::fs::read_to_string(path)?;
+    from_xhstt(&xml)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_xhstt, to_xhstt, XhsttError};
+    use crate::timetabler::{
+        solve_timetable, StudentInfo, SubjectId, TimetableInfo, TimetableResult, WeekCycle,
+    };
+
+    /// Two days of two times, with Maths taught to two classes, one of them
+    /// by a preassigned teacher, and a staff meeting with no students.
+    const INSTANCE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<HighSchoolTimetableArchive Id="Test">
+  <Instances>
+    <Instance Id="School">
+      <Times>
+        <TimeGroups>
+          <Day Id="Mon"><Name>Monday</Name></Day>
+          <Day Id="Tue"><Name>Tuesday</Name></Day>
+        </TimeGroups>
+        <Time Id="Mon1"><Day Reference="Mon"/></Time>
+        <Time Id="Mon2"><Day Reference="Mon"/></Time>
+        <Time Id="Tue1"><Day Reference="Tue"/></Time>
+        <Time Id="Tue2"><Day Reference="Tue"/></Time>
+      </Times>
+      <Resources>
+        <ResourceTypes>
+          <ResourceType Id="Class"><Name>Class</Name></ResourceType>
+          <ResourceType Id="Room"><Name>Room</Name></ResourceType>
+          <ResourceType Id="Teacher"><Name>Teacher</Name></ResourceType>
+        </ResourceTypes>
+        <Resource Id="C1"><Name>Class 1</Name><ResourceType Reference="Class"/></Resource>
+        <Resource Id="C2"><Name>Class 2 &amp; friends</Name><ResourceType Reference="Class"/></Resource>
+        <Resource Id="C3"><ResourceType Reference="Class"/></Resource>
+        <Resource Id="R1"><Name>Room 1</Name><ResourceType Reference="Room"/></Resource>
+        <Resource Id="T1"><Name>Teacher 1</Name><ResourceType Reference="Teacher"/></Resource>
+      </Resources>
+      <Events>
+        <EventGroups>
+          <Course Id="M"><Name>Maths</Name></Course>
+        </EventGroups>
+        <!-- Classes 1 and 2 are taught together. -->
+        <Event Id="E1">
+          <Duration>2</Duration>
+          <Course Reference="M"/>
+          <Resources>
+            <Resource Reference="C1"/>
+            <Resource Reference="C2"/>
+            <Resource Reference="T1"/>
+          </Resources>
+        </Event>
+        <Event Id="E2">
+          <Course Reference="M"/>
+          <Resources><Resource Reference="C3"/></Resources>
+        </Event>
+        <Event Id="Meeting">
+          <Resources><Resource Reference="T1"/></Resources>
+        </Event>
+      </Events>
+    </Instance>
+  </Instances>
+</HighSchoolTimetableArchive>
+"#;
+
+    #[test]
+    fn reads_an_instance() {
+        let info = from_xhstt(INSTANCE).unwrap();
+        assert_eq!(info.daily_lesson_capacity, 2);
+        assert_eq!(info.cycle, WeekCycle::Weekly);
+        let students: Vec<(&str, &[SubjectId])> = info
+            .students
+            .iter()
+            .map(|student| (student.id().as_str(), student.subjects()))
+            .collect();
+        let maths = [info.subjects.id("Maths").unwrap()];
+        assert_eq!(
+            students,
+            [
+                ("Class 1", &maths[..]),
+                ("Class 2 & friends", &maths[..]),
+                ("C3", &maths[..]),
+            ]
+        );
+
+        // Each class is taught for as long as its events last, and the two
+        // events are two groups.
+        assert_eq!(info.subjects.len(), 1);
+        assert_eq!(info.subjects[maths[0]].lessons_per_week, [2, 0]);
+        assert_eq!(info.subjects[maths[0]].max_groups, Some(2));
+        assert_eq!(info.max_groups, 2);
+
+        let rooms: Vec<&str> = info.rooms.iter().map(|room| room.id.as_str()).collect();
+        assert_eq!(rooms, ["Room 1"]);
+        assert_eq!(info.teachers.len(), 1);
+        assert_eq!(info.teachers[0].id, "Teacher 1");
+        assert_eq!(info.teachers[0].subjects, maths);
+    }
+
+    #[test]
+    fn reads_a_lone_instance() {
+        let start = INSTANCE.find("<Instance ").unwrap();
+        let end = INSTANCE.find("</Instances>").unwrap();
+        let info = from_xhstt(&INSTANCE[start..end]).unwrap();
+        assert_eq!(info.students.len(), 3);
+    }
+
+    #[test]
+    fn rejects_what_it_cant_read() {
+        assert!(matches!(
+            from_xhstt("<Instances></Instances>"),
+            Err(XhsttError::NoInstance)
+        ));
+        assert!(matches!(
+            from_xhstt("<Instance Id=\"School\"><Times>"),
+            Err(XhsttError::Syntax { line: 1, .. })
+        ));
+        // Without days, the times can't be split into lessons a day.
+        let no_days = INSTANCE.replace("<Day Reference=\"Mon\"/>", "");
+        let no_days = no_days.replace("<Day Reference=\"Tue\"/>", "");
+        assert!(matches!(from_xhstt(&no_days), Err(XhsttError::NoDays)));
+        let unknown_resource = INSTANCE.replace("Reference=\"C3\"", "Reference=\"C4\"");
+        assert!(matches!(
+            from_xhstt(&unknown_resource),
+            Err(XhsttError::UnknownResource { resource, .. }) if resource == "C4"
+        ));
+    }
+
+    /// Three students in a fortnightly week with two lessons a day, where
+    /// Maths has too many students for one group.
+    fn school() -> TimetableInfo {
+        let mut info = TimetableInfo::new(2, 2);
+        info.add_student("A", &["Maths", "Art"]);
+        info.add_student("B", &["Maths"]);
+        info.add_student("C", &["Maths", "Art"]);
+        info.cycle = WeekCycle::Fortnightly;
+        info.max_group_size = Some(2);
+        info
+    }
+
+    #[test]
+    fn round_trips_an_unsolved_timetable() {
+        let info = school();
+        let read = from_xhstt(&to_xhstt(&info, None)).unwrap();
+        assert_eq!(read.daily_lesson_capacity, info.daily_lesson_capacity);
+        assert_eq!(read.cycle, info.cycle);
+        // Subjects are numbered afresh in the order they are read.
+        let names = |info: &TimetableInfo, student: &StudentInfo| {
+            let mut names: Vec<String> = student
+                .subjects()
+                .iter()
+                .map(|&subject| info.subjects.name(subject).to_string())
+                .collect();
+            names.sort();
+            names
+        };
+        for (read_student, student) in read.students.iter().zip(&info.students) {
+            assert_eq!(read_student.id(), student.id());
+            assert_eq!(names(&read, read_student), names(&info, student));
+        }
+        for name in ["Maths", "Art"] {
+            let subject = info.subjects.id(name).unwrap();
+            let read_subject = read.subjects.get(name).unwrap();
+            let lessons = [
+                info.lessons_in_week(subject, 0),
+                info.lessons_in_week(subject, 1),
+            ];
+            assert_eq!(read_subject.lessons_per_week, lessons);
+            // Without a result, each subject is written as one group.
+            assert_eq!(read_subject.max_groups, Some(1));
+        }
+    }
+
+    #[test]
+    fn round_trips_the_groups_of_a_solved_timetable() {
+        let info = school();
+        let result = solve_timetable(&info);
+        assert!(matches!(result, TimetableResult::Solved { .. }));
+        let read = from_xhstt(&to_xhstt(&info, Some(&result))).unwrap();
+        for (subject, rosters) in result.rosters() {
+            let groups = u8::try_from(rosters.len()).unwrap();
+            let name = info.subjects.name(subject);
+            assert_eq!(read.subjects.get(name).unwrap().max_groups, Some(groups));
+        }
+        assert_eq!(read.subjects.get("Maths").unwrap().max_groups, Some(2));
+    }
+}
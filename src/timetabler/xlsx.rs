@@ -0,0 +1,142 @@
+use std::collections::BTreeSet;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use simple_excel_writer::{Row, SheetWriter, Workbook};
+use thiserror::Error;
+
+use super::{school_grids, student_grids, TimetableGrid, TimetableInfo, TimetableResult};
+
+/// The longest name Excel allows for a sheet.
+const MAX_SHEET_NAME_CHARS: usize = 31;
+
+/// Why the workbook couldn't be made or written.
+#[derive(Debug, Error)]
+pub enum XlsxError {
+    #[error("there is no timetable, as it wasn't solved")]
+    Unsolved,
+    /// The workbook writer only takes paths which are valid UTF-8.
+    #[error("{} isn't a path the workbook can be written to", .0.display())]
+    InvalidPath(PathBuf),
+    #[error("couldn't write the workbook: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// A name for a sheet which Excel accepts and no other sheet in the workbook
+/// has, keeping as much of `name` as possible.
+fn sheet_name(name: &str, used: &mut BTreeSet<String>) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| match c {
+            '[' | ']' | ':' | '*' | '?' | '/' | '\\' => '_',
+            c => c,
+        })
+        .collect();
+    let cleaned = cleaned.trim_matches('\'');
+    let cleaned = if cleaned.is_empty() { "Sheet" } else { cleaned };
+
+    // Excel compares sheet names ignoring case.
+    let mut candidate: String = cleaned.chars().take(MAX_SHEET_NAME_CHARS).collect();
+    let mut copy = 1;
+    while used.contains(&candidate.to_lowercase()) {
+        copy += 1;
+        let suffix = format!(" ({})", copy);
+        candidate = cleaned
+            .chars()
+            .take(MAX_SHEET_NAME_CHARS - suffix.len())
+            .chain(suffix.chars())
+            .collect();
+    }
+    used.insert(candidate.to_lowercase());
+    candidate
+}
+
+/// Writes grids one under another, each with its title above it.
+fn write_grids(sheet_writer: &mut SheetWriter, grids: &[TimetableGrid]) -> std::io::Result<()> {
+    for (grid_idx, grid) in grids.iter().enumerate() {
+        if grid_idx > 0 {
+            sheet_writer.append_blank_rows(1);
+        }
+        let mut title = Row::new();
+        title.add_cell(grid.title.as_str());
+        sheet_writer.append_row(title)?;
+
+        let mut headings = Row::new();
+        headings.add_cell("");
+        for day in &grid.days {
+            headings.add_cell(day.as_str());
+        }
+        sheet_writer.append_row(headings)?;
+
+        for (period, cells) in grid.periods.iter().enumerate() {
+            let mut row = Row::new();
+            row.add_cell(format!("Period {}", period + 1));
+            for cell in cells {
+                row.add_cell(cell.as_str());
+            }
+            sheet_writer.append_row(row)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the solved timetable to an Excel workbook at `path`: the school's
+/// timetable on the first sheet, then a sheet for every student and lastly
+/// the students in every group.
+pub fn write_xlsx(
+    timetable_info: &TimetableInfo,
+    result: &TimetableResult,
+    path: impl AsRef<Path>,
+) -> Result<(), XlsxError> {
+    let (slots_by_student_id, group_names) = match result {
+        TimetableResult::Solved {
+            slots_by_student_id,
+            group_names,
+            ..
+        } => (slots_by_student_id, group_names),
+        TimetableResult::Unsolved(_) => return Err(XlsxError::Unsolved),
+    };
+    let path = path.as_ref();
+    let path = path
+        .to_str()
+        .ok_or_else(|| XlsxError::InvalidPath(path.to_path_buf()))?;
+
+    let mut workbook = Workbook::create(path);
+    let mut used_names = BTreeSet::new();
+
+    let mut sheet = workbook.create_sheet(&sheet_name("Timetable", &mut used_names));
+    let grids = school_grids(timetable_info, result);
+    workbook.write_sheet(&mut sheet, |sheet_writer| write_grids(sheet_writer, &grids))?;
+
+    for student_id in slots_by_student_id.keys() {
+        let mut sheet = workbook.create_sheet(&sheet_name(student_id.as_str(), &mut used_names));
+        let grids = student_grids(timetable_info, result, student_id);
+        workbook.write_sheet(&mut sheet, |sheet_writer| write_grids(sheet_writer, &grids))?;
+    }
+
+    // A row for each group, with its students across the columns after it.
+    let mut sheet = workbook.create_sheet(&sheet_name("Groups", &mut used_names));
+    let rosters = result.rosters();
+    workbook.write_sheet(&mut sheet, |sheet_writer| {
+        let mut headings = Row::new();
+        headings.add_cell("Subject");
+        headings.add_cell("Group");
+        headings.add_cell("Students");
+        sheet_writer.append_row(headings)?;
+        for (subject, groups) in &rosters {
+            for (group_idx, student_ids) in groups.iter().enumerate() {
+                let mut row = Row::new();
+                row.add_cell(timetable_info.subjects.name(*subject));
+                row.add_cell(group_names[subject][group_idx].as_str());
+                for student_id in student_ids {
+                    row.add_cell(student_id.as_str());
+                }
+                sheet_writer.append_row(row)?;
+            }
+        }
+        Ok(())
+    })?;
+
+    workbook.close()?;
+    Ok(())
+}